@@ -3,8 +3,10 @@
  * Simplified AST for the MVP implementation
  */
 
+use std::fmt;
 use serde::{Deserialize, Serialize};
 use crate::error::Span;
+use crate::pratt::LValue;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Program {
@@ -105,9 +107,14 @@ pub enum Statement {
     Assignment(Assignment),
     Expression(Expression),
     When(WhenStatement),
+    If(IfStatement),
+    For(ForStatement),
+    While(WhileStatement),
+    Break(Span),
+    Continue(Span),
     Phase(PhaseStatement),
-    Return(Option<Expression>),
-    Broadcast(Expression),
+    Return(Option<Expression>, Span),
+    Broadcast(BroadcastStatement),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -119,7 +126,7 @@ pub struct LetStatement {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Assignment {
-    pub target: String,
+    pub target: LValue,
     pub op: AssignmentOp,
     pub value: Expression,
     pub span: Span,
@@ -129,35 +136,100 @@ pub struct Assignment {
 pub enum AssignmentOp {
     Assign,     // =
     Merge,      // <#>
+    AddAssign,  // +=
+    SubAssign,  // -=
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WhenStatement {
     pub condition: Expression,
     pub body: Block,
+    pub else_body: Option<Block>,
     pub span: Span,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IfStatement {
+    pub condition: Expression,
+    pub then_body: Block,
+    /// `else if` is stored as an else block holding a single `If`
+    pub else_body: Option<Block>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForStatement {
+    /// `for x in` binds elements (or map keys); `for (i, x) in` binds index/key and element/value
+    pub bindings: Vec<LoopVariable>,
+    pub iterable: Expression,
+    pub body: Block,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoopVariable {
+    pub name: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WhileStatement {
+    pub condition: Expression,
+    pub body: Block,
+    pub span: Span,
+}
+
+/// `broadcast(Event(a, b))` or `broadcast(Event { x: a, y: b })`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BroadcastStatement {
+    pub event: String,
+    pub args: EventArgs,
+    pub span: Span,
+}
+
+/// Event payload, bound to the handler's parameters by position or by name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum EventArgs {
+    Positional(Vec<Expression>),
+    Named(Vec<ObjectField>),
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PhaseStatement {
     pub name: String,
+    /// `timeout` and `participants`, written in parentheses after the name
+    pub options: Vec<ObjectField>,
     pub body: Block,
+    /// Runs instead of continuing when the phase aborts on any participant
+    pub abort: Option<Block>,
     pub span: Span,
 }
 
+impl PhaseStatement {
+    pub fn option(&self, name: &str) -> Option<&Expression> {
+        self.options.iter().find(|field| field.name == name).map(|field| &field.value)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Expression {
-    Literal(Literal),
-    Identifier(String),
+    Literal(Literal, Span),
+    Identifier(String, Span),
     Binary(BinaryExpression),
     Unary(UnaryExpression),
     Call(CallExpression),
+    MethodCall(MethodCallExpression),
     Member(MemberExpression),
     Index(IndexExpression),
     Proposal(ProposalExpression),
     Vote(VoteExpression),
-    Array(Vec<Expression>),
-    Object(Vec<ObjectField>),
+    Broadcast(DistributedExpression),  // <~>
+    Gossip(DistributedExpression),     // <@>
+    Sync(DistributedExpression),       // <=>
+    Partition(DistributedExpression),  // <|>
+    Quorum(DistributedExpression),     // <*>
+    Array(Vec<Expression>, Span),
+    Object(Vec<ObjectField>, Span),
     Assignment(AssignmentExpression),
 }
 
@@ -207,7 +279,7 @@ pub struct AssignmentExpression {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum BinaryOp {
-    Add, Sub, Mul, Div,
+    Add, Sub, Mul, Div, Mod,
     Eq, Ne, Lt, Le, Gt, Ge,
     And, Or,
 }
@@ -219,10 +291,20 @@ pub struct CallExpression {
     pub span: Span,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MethodCallExpression {
+    pub receiver: Box<Expression>,
+    pub method: String,
+    pub args: Vec<Expression>,
+    pub span: Span,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProposalExpression {
     pub value: Box<Expression>,
     pub config: ConsensusConfig,
+    /// Map of config fields computed at runtime, when the operand is not a `{ .. }` block
+    pub config_value: Option<Box<Expression>>,
     pub span: Span,
 }
 
@@ -230,15 +312,33 @@ pub struct ProposalExpression {
 pub struct VoteExpression {
     pub value: Box<Expression>,
     pub config: ConsensusConfig,
+    /// Map of config fields computed at runtime, when the operand is not a `{ .. }` block
+    pub config_value: Option<Box<Expression>>,
     pub span: Span,
 }
 
+/// Operand and options of `<~>`, `<@>`, `<=>`, `<|>` and `<*>`
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DistributedExpression {
+    pub value: Box<Expression>,
+    pub options: Vec<ObjectField>,
+    pub span: Span,
+}
+
+impl DistributedExpression {
+    pub fn option(&self, name: &str) -> Option<&Expression> {
+        self.options.iter().find(|field| field.name == name).map(|field| &field.value)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ConsensusConfig {
     pub validators: Option<u32>,
     pub timeout: Option<u64>, // milliseconds
     pub algorithm: Option<ConsensusAlgorithm>,
     pub quorum: Option<u32>,
+    /// Ballot cast by `<?>`; accept when omitted
+    pub vote: Option<VoteChoice>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -255,9 +355,10 @@ pub enum Literal {
     Float(f64),
     String(String),
     Boolean(bool),
+    Duration(u64), // milliseconds
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Type {
     U64,
     I64,
@@ -265,11 +366,14 @@ pub enum Type {
     Bool,
     String,
     Bytes,
+    Duration,
     Vec(Box<Type>),
     Set(Box<Type>),
     Map(Box<Type>, Box<Type>),
     Option(Box<Type>),
     Result(Box<Type>, Box<Type>),
+    Tuple(Vec<Type>),
+    Unit,
     Custom(String),
 }
 
@@ -278,6 +382,14 @@ pub enum ConsensusAlgorithm {
     Raft,
     PBFT,
     Tendermint,
+    HotStuff,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VoteChoice {
+    Accept,
+    Reject,
+    Abstain,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -303,14 +415,85 @@ impl Program {
     }
 }
 
+impl Item {
+    pub fn span(&self) -> Span {
+        match self {
+            Item::Node(node) => node.span.clone(),
+            Item::Cluster(cluster) => cluster.span.clone(),
+            Item::Function(func) => func.span.clone(),
+        }
+    }
+}
+
+impl Statement {
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::Let(stmt) => stmt.span.clone(),
+            Statement::Assignment(stmt) => stmt.span.clone(),
+            Statement::Expression(expr) => expr.span(),
+            Statement::When(stmt) => stmt.span.clone(),
+            Statement::If(stmt) => stmt.span.clone(),
+            Statement::For(stmt) => stmt.span.clone(),
+            Statement::While(stmt) => stmt.span.clone(),
+            Statement::Break(span) => span.clone(),
+            Statement::Continue(span) => span.clone(),
+            Statement::Phase(stmt) => stmt.span.clone(),
+            Statement::Return(_, span) => span.clone(),
+            Statement::Broadcast(stmt) => stmt.span.clone(),
+        }
+    }
+}
+
 impl Expression {
     pub fn span(&self) -> Span {
         match self {
+            Expression::Literal(_, span) => span.clone(),
+            Expression::Identifier(_, span) => span.clone(),
             Expression::Binary(expr) => expr.span.clone(),
+            Expression::Unary(expr) => expr.span.clone(),
             Expression::Call(expr) => expr.span.clone(),
+            Expression::MethodCall(expr) => expr.span.clone(),
+            Expression::Member(expr) => expr.span.clone(),
+            Expression::Index(expr) => expr.span.clone(),
             Expression::Proposal(expr) => expr.span.clone(),
             Expression::Vote(expr) => expr.span.clone(),
-            _ => Span::unknown(),
+            Expression::Broadcast(expr)
+            | Expression::Gossip(expr)
+            | Expression::Sync(expr)
+            | Expression::Partition(expr)
+            | Expression::Quorum(expr) => expr.span.clone(),
+            Expression::Array(_, span) => span.clone(),
+            Expression::Object(_, span) => span.clone(),
+            Expression::Assignment(expr) => expr.span.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::U64 => write!(f, "u64"),
+            Type::I64 => write!(f, "i64"),
+            Type::F64 => write!(f, "f64"),
+            Type::Bool => write!(f, "bool"),
+            Type::String => write!(f, "String"),
+            Type::Bytes => write!(f, "Bytes"),
+            Type::Duration => write!(f, "Duration"),
+            Type::Vec(inner) => write!(f, "Vec<{}>", inner),
+            Type::Set(inner) => write!(f, "Set<{}>", inner),
+            Type::Map(key, value) => write!(f, "Map<{}, {}>", key, value),
+            Type::Option(inner) => write!(f, "Option<{}>", inner),
+            Type::Result(ok, err) => write!(f, "Result<{}, {}>", ok, err),
+            Type::Tuple(elements) => {
+                let elements: Vec<String> = elements.iter().map(|t| t.to_string()).collect();
+                if elements.len() == 1 {
+                    write!(f, "({},)", elements[0])
+                } else {
+                    write!(f, "({})", elements.join(", "))
+                }
+            }
+            Type::Unit => write!(f, "()"),
+            Type::Custom(name) => write!(f, "{}", name),
         }
     }
 }
@@ -45,6 +45,7 @@ pub enum ErrorKind {
     // Semantic errors
     UndeclaredVariable(String),
     DuplicateDefinition(String),
+    ShadowedDefinition(String),
     TypeMismatch { expected: String, found: String },
     InvalidConsensusConfig(String),
     
@@ -12,6 +12,7 @@ pub struct Lexer<'a> {
     logos_lexer: logos::Lexer<'a, Token>,
     current_line: u32,
     line_start: usize,
+    scanned: usize,
 }
 
 impl<'a> Lexer<'a> {
@@ -21,6 +22,7 @@ impl<'a> Lexer<'a> {
             logos_lexer: Token::lexer(source),
             current_line: 1,
             line_start: 0,
+            scanned: 0,
         }
     }
     
@@ -30,6 +32,9 @@ impl<'a> Lexer<'a> {
         
         while let Some(token_result) = self.logos_lexer.next() {
             let span_range = self.logos_lexer.span();
+            
+            // Account for newlines in skipped whitespace and comments before this token
+            self.update_line_tracking(span_range.start);
             let span = self.make_span(span_range.start, span_range.end);
             
             match token_result {
@@ -47,9 +52,6 @@ impl<'a> Lexer<'a> {
                     }
                 }
             }
-            
-            // Update line tracking
-            self.update_line_tracking(span_range.end);
         }
         
         if diagnostics.is_empty() {
@@ -65,13 +67,14 @@ impl<'a> Lexer<'a> {
     }
     
     fn update_line_tracking(&mut self, pos: usize) {
-        let text_slice = &self.source[self.line_start..pos];
-        for (i, ch) in text_slice.char_indices() {
+        let scan_start = self.scanned;
+        for (i, ch) in self.source[scan_start..pos].char_indices() {
             if ch == '\n' {
                 self.current_line += 1;
-                self.line_start = self.line_start + i + 1;
+                self.line_start = scan_start + i + 1;
             }
         }
+        self.scanned = pos;
     }
 }
 
@@ -88,7 +91,7 @@ mod tests {
     #[test]
     fn test_consensus_operators() {
         let source = "value <!> consensus <?> vote <#> commit";
-        let tokens = tokenize(source).unwrap();
+        let tokens: Vec<Token> = tokenize(source).unwrap().into_iter().map(|(token, _)| token).collect();
         
         assert!(tokens.contains(&Token::Propose));
         assert!(tokens.contains(&Token::Vote));
@@ -98,7 +101,7 @@ mod tests {
     #[test]
     fn test_distributed_keywords() {
         let source = "consensus cluster replicated byzantine atomic";
-        let tokens = tokenize(source).unwrap();
+        let tokens: Vec<Token> = tokenize(source).unwrap().into_iter().map(|(token, _)| token).collect();
         
         assert!(tokens.contains(&Token::Consensus));
         assert!(tokens.contains(&Token::Cluster));
@@ -110,7 +113,7 @@ mod tests {
     #[test]
     fn test_time_literals() {
         let source = "timeout: 3000ms heartbeat: 5s";
-        let tokens = tokenize(source).unwrap();
+        let tokens: Vec<Token> = tokenize(source).unwrap().into_iter().map(|(token, _)| token).collect();
         
         assert!(tokens.contains(&Token::Milliseconds(3000)));
         assert!(tokens.contains(&Token::Seconds(5)));
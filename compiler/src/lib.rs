@@ -9,9 +9,12 @@ pub mod parser;
 pub mod ast;
 pub mod error;
 pub mod pratt;
+pub mod resolver;
+pub mod typechecker;
 
 use anyhow::Result;
 
+#[derive(Default)]
 pub struct Compiler {
     // Compiler state
 }
@@ -22,9 +25,15 @@ impl Compiler {
     }
     
     pub fn compile(&self, source: &str) -> Result<Vec<u8>> {
-        let tokens = lexer::tokenize(source)?;
-        let ast = parser::parse(tokens)?;
+        let tokens = lexer::tokenize(source).map_err(diagnostics_error)?;
+        let _ast = parser::parse(tokens).map_err(diagnostics_error)?;
         // TODO: Generate distributed runtime code
         Ok(vec![])
     }
-}
\ No newline at end of file
+}
+
+/// One error carrying every diagnostic, a line each
+pub fn diagnostics_error(diagnostics: Vec<error::Diagnostic>) -> anyhow::Error {
+    let lines: Vec<String> = diagnostics.iter().map(|d| d.to_string()).collect();
+    anyhow::anyhow!(lines.join("\n"))
+}
@@ -6,11 +6,15 @@
 use crate::ast::*;
 use crate::token::Token;
 use crate::error::{Diagnostic, ErrorKind, Span, CompilerResult, DiagnosticCollector};
+use crate::pratt::{LValue, PrattParser, Precedence, SpanTracker, WithSpan};
 
 pub struct Parser {
     tokens: Vec<(Token, Span)>,
     current: usize,
     diagnostics: DiagnosticCollector,
+    pratt: PrattParser,
+    // Number of enclosing loops, for validating `break`/`continue`
+    loop_depth: usize,
 }
 
 impl Parser {
@@ -19,10 +23,13 @@ impl Parser {
             tokens,
             current: 0,
             diagnostics: DiagnosticCollector::new(),
+            pratt: PrattParser::new(),
+            loop_depth: 0,
         }
     }
     
     pub fn parse(&mut self) -> CompilerResult<Program> {
+        let tracker = self.start_span();
         let mut items = Vec::new();
         
         while !self.is_at_end() {
@@ -40,29 +47,33 @@ impl Parser {
         if self.diagnostics.has_errors() {
             Err(self.diagnostics.diagnostics.clone())
         } else {
-            Ok(Program::new(items))
+            Ok(Program {
+                items,
+                span: tracker.end(self.previous_span()),
+            })
         }
     }
     
     fn parse_item(&mut self) -> CompilerResult<Item> {
+        let tracker = self.start_span();
         let annotations = self.parse_annotations()?;
         
         match self.peek_token() {
             Some(Token::Node) => {
                 self.advance();
-                Ok(Item::Node(self.parse_node(annotations)?))
+                Ok(Item::Node(self.parse_node(annotations, tracker)?))
             }
             Some(Token::Consensus) => {
                 self.advance();
                 if self.match_token(&Token::Cluster) {
-                    Ok(Item::Cluster(self.parse_cluster()?))
+                    Ok(Item::Cluster(self.parse_cluster(tracker)?))
                 } else {
                     Err(vec![self.error("Expected 'cluster' after 'consensus'")])
                 }
             }
             Some(Token::Function) => {
                 self.advance();
-                Ok(Item::Function(self.parse_function(annotations)?))
+                Ok(Item::Function(self.parse_function(annotations, tracker)?))
             }
             _ => Err(vec![self.error("Expected node, consensus cluster, or function")])
         }
@@ -71,57 +82,76 @@ impl Parser {
     fn parse_annotations(&mut self) -> CompilerResult<Vec<Annotation>> {
         let mut annotations = Vec::new();
         
-        while self.match_token(&Token::At) {
-            let name = self.expect_identifier()?;
-            self.expect_token(&Token::LeftParen, "Expected '(' after annotation name")?;
+        while self.check(&Token::At) {
+            let tracker = self.start_span();
+            self.advance();
+            let name = self.expect_annotation_name()?;
             
+            // Parameters are optional: `@replicated`, `@network(port: 8080)`
             let mut params = Vec::new();
-            if !self.check(&Token::RightParen) {
-                loop {
-                    let param_name = self.expect_identifier()?;
-                    self.expect_token(&Token::Colon, "Expected ':' after parameter name")?;
-                    let value = self.parse_expression()?;
-                    params.push(AnnotationParam { name: param_name, value });
-                    
-                    if !self.match_token(&Token::Comma) {
-                        break;
+            if self.match_token(&Token::LeftParen) {
+                if !self.check(&Token::RightParen) {
+                    loop {
+                        let param_name = self.expect_identifier()?;
+                        self.expect_token(&Token::Colon, "Expected ':' after parameter name")?;
+                        let value = self.parse_expression()?;
+                        params.push(AnnotationParam { name: param_name, value });
+                        
+                        if !self.match_token(&Token::Comma) {
+                            break;
+                        }
                     }
                 }
+                
+                self.expect_token(&Token::RightParen, "Expected ')' after annotation parameters")?;
             }
             
-            self.expect_token(&Token::RightParen, "Expected ')' after annotation parameters")?;
-            
             annotations.push(Annotation {
                 name,
                 params,
-                span: self.previous_span(),
+                span: tracker.end(self.previous_span()),
             });
         }
         
         Ok(annotations)
     }
     
-    fn parse_node(&mut self, annotations: Vec<Annotation>) -> CompilerResult<NodeDefinition> {
+    /// Annotation names are identifiers or one of the distributed keywords
+    fn expect_annotation_name(&mut self) -> CompilerResult<String> {
+        let name = match self.peek_token() {
+            Some(Token::Replicated) => "replicated",
+            Some(Token::Byzantine) => "byzantine",
+            Some(Token::Atomic) => "atomic",
+            Some(Token::Network) => "network",
+            Some(Token::Distributed) => "distributed",
+            _ => return self.expect_identifier(),
+        };
+        self.advance();
+        Ok(name.to_string())
+    }
+    
+    fn parse_node(&mut self, annotations: Vec<Annotation>, tracker: SpanTracker) -> CompilerResult<NodeDefinition> {
         let name = self.expect_identifier()?;
         self.expect_token(&Token::LeftBrace, "Expected '{' after node name")?;
         
         let mut items = Vec::new();
         
         while !self.check(&Token::RightBrace) && !self.is_at_end() {
+            let item_tracker = self.start_span();
             let item_annotations = self.parse_annotations()?;
             
             match self.peek_token() {
                 Some(Token::State) => {
                     self.advance();
-                    items.push(NodeItem::State(self.parse_state(item_annotations)?));
+                    items.push(NodeItem::State(self.parse_state(item_annotations, item_tracker)?));
                 }
                 Some(Token::Function) => {
                     self.advance();
-                    items.push(NodeItem::Function(self.parse_function(item_annotations)?));
+                    items.push(NodeItem::Function(self.parse_function(item_annotations, item_tracker)?));
                 }
                 Some(Token::On) => {
                     self.advance();
-                    items.push(NodeItem::EventHandler(self.parse_event_handler()?));
+                    items.push(NodeItem::EventHandler(self.parse_event_handler(item_tracker)?));
                 }
                 _ => return Err(vec![self.error("Expected state, function, or event handler")])
             }
@@ -133,22 +163,25 @@ impl Parser {
             name,
             annotations,
             items,
-            span: self.previous_span(),
+            span: tracker.end(self.previous_span()),
         })
     }
     
-    fn parse_cluster(&mut self) -> CompilerResult<ConsensusCluster> {
+    fn parse_cluster(&mut self, tracker: SpanTracker) -> CompilerResult<ConsensusCluster> {
         let name = self.expect_identifier()?;
         self.expect_token(&Token::LeftBrace, "Expected '{' after cluster name")?;
         
         let mut replicas = 3; // default
         let mut consensus = ConsensusAlgorithm::Raft; // default
-        let mut zones = None;
+        let zones = None;
         let mut items = Vec::new();
         
         while !self.check(&Token::RightBrace) && !self.is_at_end() {
+            let item_tracker = self.start_span();
+            
             match self.peek_token() {
-                Some(Token::Replicas) => {
+                // `replicas` is not a keyword, so it stays usable as a name elsewhere
+                Some(Token::Identifier(name)) if name == "replicas" => {
                     self.advance();
                     self.expect_token(&Token::Colon, "Expected ':' after 'replicas'")?;
                     replicas = self.expect_number()? as u32;
@@ -161,11 +194,11 @@ impl Parser {
                 Some(Token::State) => {
                     self.advance();
                     let annotations = self.parse_annotations()?;
-                    items.push(ClusterItem::State(self.parse_state(annotations)?));
+                    items.push(ClusterItem::State(self.parse_state(annotations, item_tracker)?));
                 }
                 Some(Token::Service) => {
                     self.advance();
-                    items.push(ClusterItem::Service(self.parse_service()?));
+                    items.push(ClusterItem::Service(self.parse_service(item_tracker)?));
                 }
                 _ => {
                     self.advance(); // skip unknown tokens
@@ -181,11 +214,11 @@ impl Parser {
             consensus,
             zones,
             items,
-            span: self.previous_span(),
+            span: tracker.end(self.previous_span()),
         })
     }
     
-    fn parse_state(&mut self, annotations: Vec<Annotation>) -> CompilerResult<StateVariable> {
+    fn parse_state(&mut self, annotations: Vec<Annotation>, tracker: SpanTracker) -> CompilerResult<StateVariable> {
         let name = self.expect_identifier()?;
         self.expect_token(&Token::Colon, "Expected ':' after state variable name")?;
         let type_ = self.parse_type()?;
@@ -203,11 +236,11 @@ impl Parser {
             name,
             type_,
             initial_value,
-            span: self.previous_span(),
+            span: tracker.end(self.previous_span()),
         })
     }
     
-    fn parse_function(&mut self, annotations: Vec<Annotation>) -> CompilerResult<Function> {
+    fn parse_function(&mut self, annotations: Vec<Annotation>, tracker: SpanTracker) -> CompilerResult<Function> {
         let name = self.expect_identifier()?;
         self.expect_token(&Token::LeftParen, "Expected '(' after function name")?;
         
@@ -228,11 +261,11 @@ impl Parser {
             params,
             return_type,
             body,
-            span: self.previous_span(),
+            span: tracker.end(self.previous_span()),
         })
     }
     
-    fn parse_service(&mut self) -> CompilerResult<Service> {
+    fn parse_service(&mut self, tracker: SpanTracker) -> CompilerResult<Service> {
         let name = self.expect_identifier()?;
         self.expect_token(&Token::LeftParen, "Expected '(' after service name")?;
         
@@ -252,16 +285,20 @@ impl Parser {
             params,
             return_type,
             body,
-            span: self.previous_span(),
+            span: tracker.end(self.previous_span()),
         })
     }
     
-    fn parse_event_handler(&mut self) -> CompilerResult<EventHandler> {
+    fn parse_event_handler(&mut self, tracker: SpanTracker) -> CompilerResult<EventHandler> {
         let event_name = self.expect_identifier()?;
-        self.expect_token(&Token::LeftParen, "Expected '(' after event name")?;
-        
-        let params = self.parse_parameters()?;
-        self.expect_token(&Token::RightParen, "Expected ')' after parameters")?;
+        // `on Event { ... }` takes no parameters
+        let params = if self.match_token(&Token::LeftParen) {
+            let params = self.parse_parameters()?;
+            self.expect_token(&Token::RightParen, "Expected ')' after parameters")?;
+            params
+        } else {
+            Vec::new()
+        };
         
         let body = self.parse_block()?;
         
@@ -269,7 +306,7 @@ impl Parser {
             event_name,
             params,
             body,
-            span: self.previous_span(),
+            span: tracker.end(self.previous_span()),
         })
     }
     
@@ -278,15 +315,14 @@ impl Parser {
         
         if !self.check(&Token::RightParen) {
             loop {
-                let name = self.expect_identifier()?;
-                self.expect_token(&Token::Colon, "Expected ':' after parameter name")?;
-                let type_ = self.parse_type()?;
+                let ((name, type_), span) = self.with_span(|parser| {
+                    let name = parser.expect_identifier()?;
+                    parser.expect_token(&Token::Colon, "Expected ':' after parameter name")?;
+                    let type_ = parser.parse_type()?;
+                    Ok((name, type_))
+                })?;
                 
-                params.push(Parameter {
-                    name,
-                    type_,
-                    span: self.previous_span(),
-                });
+                params.push(Parameter { name, type_, span });
                 
                 if !self.match_token(&Token::Comma) {
                     break;
@@ -298,6 +334,7 @@ impl Parser {
     }
     
     fn parse_block(&mut self) -> CompilerResult<Block> {
+        let tracker = self.start_span();
         self.expect_token(&Token::LeftBrace, "Expected '{'")?;
         
         let mut statements = Vec::new();
@@ -310,11 +347,13 @@ impl Parser {
         
         Ok(Block {
             statements,
-            span: self.previous_span(),
+            span: tracker.end(self.previous_span()),
         })
     }
     
     fn parse_statement(&mut self) -> CompilerResult<Statement> {
+        let tracker = self.start_span();
+        
         match self.peek_token() {
             Some(Token::Let) => {
                 self.advance();
@@ -326,18 +365,96 @@ impl Parser {
                 Ok(Statement::Let(LetStatement {
                     name,
                     value,
-                    span: self.previous_span(),
+                    span: tracker.end(self.previous_span()),
                 }))
             }
             Some(Token::When) => {
                 self.advance();
                 let condition = self.parse_expression()?;
                 let body = self.parse_block()?;
+                let else_body = if self.match_token(&Token::Else) {
+                    Some(self.parse_block()?)
+                } else {
+                    None
+                };
                 
                 Ok(Statement::When(WhenStatement {
                     condition,
                     body,
-                    span: self.previous_span(),
+                    else_body,
+                    span: tracker.end(self.previous_span()),
+                }))
+            }
+            Some(Token::If) => {
+                self.advance();
+                Ok(Statement::If(self.parse_if(tracker)?))
+            }
+            Some(Token::While) => {
+                self.advance();
+                let condition = self.parse_expression()?;
+                let body = self.parse_loop_body()?;
+                
+                Ok(Statement::While(WhileStatement {
+                    condition,
+                    body,
+                    span: tracker.end(self.previous_span()),
+                }))
+            }
+            Some(Token::For) => {
+                self.advance();
+                let mut bindings = Vec::new();
+                if self.match_token(&Token::LeftParen) {
+                    bindings.push(self.parse_loop_variable()?);
+                    self.expect_token(&Token::Comma, "Expected ',' between loop variables")?;
+                    bindings.push(self.parse_loop_variable()?);
+                    self.expect_token(&Token::RightParen, "Expected ')' after loop variables")?;
+                } else {
+                    bindings.push(self.parse_loop_variable()?);
+                }
+                self.expect_token(&Token::In, "Expected 'in' after loop variable")?;
+                let iterable = self.parse_expression()?;
+                let body = self.parse_loop_body()?;
+                
+                Ok(Statement::For(ForStatement {
+                    bindings,
+                    iterable,
+                    body,
+                    span: tracker.end(self.previous_span()),
+                }))
+            }
+            Some(Token::Break) | Some(Token::Continue) => {
+                let is_break = self.check(&Token::Break);
+                if self.loop_depth == 0 {
+                    let keyword = if is_break { "break" } else { "continue" };
+                    return Err(vec![self.error(&format!("'{}' outside of a loop", keyword))]);
+                }
+                self.advance();
+                self.expect_token(&Token::Semicolon, "Expected ';' after loop control")?;
+                
+                let span = tracker.end(self.previous_span());
+                Ok(if is_break { Statement::Break(span) } else { Statement::Continue(span) })
+            }
+            Some(Token::Phase) => {
+                self.advance();
+                let name = self.expect_identifier()?;
+                let options = if self.match_token(&Token::LeftParen) {
+                    self.parse_options(&Token::RightParen, "Expected ')' after phase options")?
+                } else {
+                    Vec::new()
+                };
+                let body = self.parse_block()?;
+                let abort = if self.match_token(&Token::Abort) {
+                    Some(self.parse_block()?)
+                } else {
+                    None
+                };
+                
+                Ok(Statement::Phase(PhaseStatement {
+                    name,
+                    options,
+                    body,
+                    abort,
+                    span: tracker.end(self.previous_span()),
                 }))
             }
             Some(Token::Return) => {
@@ -349,41 +466,56 @@ impl Parser {
                 };
                 self.expect_token(&Token::Semicolon, "Expected ';' after return")?;
                 
-                Ok(Statement::Return(value))
+                Ok(Statement::Return(value, tracker.end(self.previous_span())))
             }
-            Some(Token::Broadcast) => {
+            Some(Token::BroadcastKw) => {
                 self.advance();
                 self.expect_token(&Token::LeftParen, "Expected '(' after broadcast")?;
-                let expr = self.parse_expression()?;
-                self.expect_token(&Token::RightParen, "Expected ')' after broadcast expression")?;
+                let event = self.expect_identifier()?;
+                let args = if self.match_token(&Token::LeftParen) {
+                    EventArgs::Positional(self.parse_arguments()?)
+                } else if self.check(&Token::LeftBrace) {
+                    EventArgs::Named(self.parse_object_fields()?)
+                } else {
+                    EventArgs::Positional(Vec::new())
+                };
+                self.expect_token(&Token::RightParen, "Expected ')' after broadcast event")?;
                 self.expect_token(&Token::Semicolon, "Expected ';' after broadcast")?;
                 
-                Ok(Statement::Broadcast(expr))
+                Ok(Statement::Broadcast(BroadcastStatement {
+                    event,
+                    args,
+                    span: tracker.end(self.previous_span()),
+                }))
             }
             _ => {
                 let expr = self.parse_expression()?;
                 
                 // Check for assignment
-                if self.match_token(&Token::Assign) || self.match_token(&Token::Commit) {
-                    let op = if self.previous_token() == Some(&Token::Assign) {
-                        AssignmentOp::Assign
-                    } else {
-                        AssignmentOp::Merge
+                let op = match self.peek_token() {
+                    Some(Token::Assign) => Some(AssignmentOp::Assign),
+                    Some(Token::Commit) => Some(AssignmentOp::Merge),
+                    Some(Token::AddAssign) => Some(AssignmentOp::AddAssign),
+                    Some(Token::SubAssign) => Some(AssignmentOp::SubAssign),
+                    _ => None,
+                };
+                
+                if let Some(op) = op {
+                    let target = match LValue::from_expression(&expr) {
+                        Some(target) => target,
+                        None => return Err(vec![self.error("Invalid assignment target")]),
                     };
+                    self.advance();
                     
-                    if let Expression::Identifier(target) = expr {
-                        let value = self.parse_expression()?;
-                        self.expect_token(&Token::Semicolon, "Expected ';' after assignment")?;
-                        
-                        Ok(Statement::Assignment(Assignment {
-                            target,
-                            op,
-                            value,
-                            span: self.previous_span(),
-                        }))
-                    } else {
-                        Err(vec![self.error("Invalid assignment target")])
-                    }
+                    let value = self.parse_expression()?;
+                    self.expect_token(&Token::Semicolon, "Expected ';' after assignment")?;
+                    
+                    Ok(Statement::Assignment(Assignment {
+                        target,
+                        op,
+                        value,
+                        span: tracker.end(self.previous_span()),
+                    }))
                 } else {
                     self.expect_token(&Token::Semicolon, "Expected ';' after expression")?;
                     Ok(Statement::Expression(expr))
@@ -393,82 +525,219 @@ impl Parser {
     }
     
     fn parse_expression(&mut self) -> CompilerResult<Expression> {
-        self.parse_binary()
+        // Assignment-level operators are handled by parse_statement
+        self.parse_binary(Precedence::Consensus)
     }
     
-    fn parse_binary(&mut self) -> CompilerResult<Expression> {
-        let mut expr = self.parse_primary()?;
+    /// Precedence climbing driven by the Pratt precedence table
+    fn parse_binary(&mut self, min_precedence: Precedence) -> CompilerResult<Expression> {
+        let tracker = self.start_span();
+        let mut expr = self.parse_unary()?;
         
-        while let Some(op) = self.match_binary_op() {
-            let right = self.parse_primary()?;
-            expr = Expression::Binary(BinaryExpression {
-                left: Box::new(expr),
-                op,
-                right: Box::new(right),
-                span: self.previous_span(),
-            });
+        while let Some(token) = self.peek_token().cloned() {
+            let precedence = self.pratt.get_precedence(&token);
+            if precedence == Precedence::Lowest || precedence < min_precedence {
+                break;
+            }
+            
+            match token {
+                Token::Propose => {
+                    self.advance();
+                    let (config, config_value) = self.parse_consensus_operand(&token, precedence)?;
+                    expr = Expression::Proposal(ProposalExpression {
+                        value: Box::new(expr),
+                        config,
+                        config_value,
+                        span: tracker.end(self.previous_span()),
+                    });
+                }
+                Token::Vote => {
+                    self.advance();
+                    let (config, config_value) = self.parse_consensus_operand(&token, precedence)?;
+                    expr = Expression::Vote(VoteExpression {
+                        value: Box::new(expr),
+                        config,
+                        config_value,
+                        span: tracker.end(self.previous_span()),
+                    });
+                }
+                Token::Broadcast | Token::Gossip | Token::Sync | Token::Partition | Token::Quorum => {
+                    self.advance();
+                    let options = self.parse_operator_options()?;
+                    let distributed = DistributedExpression {
+                        value: Box::new(expr),
+                        options,
+                        span: tracker.end(self.previous_span()),
+                    };
+                    expr = match token {
+                        Token::Broadcast => Expression::Broadcast(distributed),
+                        Token::Gossip => Expression::Gossip(distributed),
+                        Token::Sync => Expression::Sync(distributed),
+                        Token::Partition => Expression::Partition(distributed),
+                        _ => Expression::Quorum(distributed),
+                    };
+                }
+                _ => {
+                    let op = match Self::binary_op(&token) {
+                        Some(op) => op,
+                        None => break,
+                    };
+                    self.advance();
+                    
+                    let next_precedence = if self.pratt.is_right_associative(&token) {
+                        precedence
+                    } else {
+                        precedence.next()
+                    };
+                    let right = self.parse_binary(next_precedence)?;
+                    
+                    expr = Expression::Binary(BinaryExpression {
+                        left: Box::new(expr),
+                        op,
+                        right: Box::new(right),
+                        span: tracker.end(self.previous_span()),
+                    });
+                }
+            }
         }
         
-        // Check for consensus operators
-        if self.match_token(&Token::Propose) {
-            let config = self.parse_consensus_config()?;
-            expr = Expression::Proposal(ProposalExpression {
-                value: Box::new(expr),
-                config,
-                span: self.previous_span(),
-            });
+        Ok(expr)
+    }
+    
+    /// Right-hand side of `<!>` or `<?>`. A `{ .. }` block closes the operator, so in
+    /// `v <!> { .. } <?> { .. }` the vote applies to the proposal. Any other operand is a config
+    /// computed at runtime and extends as far right as the operator's associativity allows,
+    /// so `a <!> b <?> c` reads `a <!> (b <?> c)`.
+    fn parse_consensus_operand(&mut self, token: &Token, precedence: Precedence) -> CompilerResult<(ConsensusConfig, Option<Box<Expression>>)> {
+        if self.check(&Token::LeftBrace) {
+            return Ok((self.parse_consensus_config()?, None));
         }
+        let next_precedence = if self.pratt.is_right_associative(token) {
+            precedence
+        } else {
+            precedence.next()
+        };
+        let config_value = self.parse_binary(next_precedence)?;
+        Ok((ConsensusConfig::default(), Some(Box::new(config_value))))
+    }
+    
+    fn parse_unary(&mut self) -> CompilerResult<Expression> {
+        let tracker = self.start_span();
+        let op = match self.peek_token() {
+            Some(Token::Not) => UnaryOp::Not,
+            Some(Token::Minus) => UnaryOp::Neg,
+            Some(Token::Plus) => UnaryOp::Plus,
+            _ => {
+                let primary = self.parse_primary()?;
+                return self.parse_postfix(primary, &tracker);
+            }
+        };
+        self.advance();
         
-        Ok(expr)
+        let operand = self.parse_binary(Precedence::Unary)?;
+        
+        Ok(Expression::Unary(UnaryExpression {
+            op,
+            operand: Box::new(operand),
+            span: tracker.end(self.previous_span()),
+        }))
+    }
+    
+    /// Calls, indexing and member access, which bind tighter than any prefix operator
+    fn parse_postfix(&mut self, mut expr: Expression, tracker: &SpanTracker) -> CompilerResult<Expression> {
+        loop {
+            if self.match_token(&Token::Dot) {
+                let field = self.expect_identifier()?;
+                expr = Expression::Member(MemberExpression {
+                    object: Box::new(expr),
+                    field,
+                    span: tracker.end(self.previous_span()),
+                });
+            } else if self.match_token(&Token::LeftBracket) {
+                let index = self.parse_expression()?;
+                self.expect_token(&Token::RightBracket, "Expected ']' after index")?;
+                expr = Expression::Index(IndexExpression {
+                    array: Box::new(expr),
+                    index: Box::new(index),
+                    span: tracker.end(self.previous_span()),
+                });
+            } else if self.check(&Token::LeftParen) {
+                expr = match expr {
+                    Expression::Identifier(function, _) => {
+                        self.advance();
+                        let args = self.parse_arguments()?;
+                        Expression::Call(CallExpression {
+                            function,
+                            args,
+                            span: tracker.end(self.previous_span()),
+                        })
+                    }
+                    Expression::Member(member) => {
+                        self.advance();
+                        let args = self.parse_arguments()?;
+                        Expression::MethodCall(MethodCallExpression {
+                            receiver: member.object,
+                            method: member.field,
+                            args,
+                            span: tracker.end(self.previous_span()),
+                        })
+                    }
+                    _ => return Err(vec![self.error("Expression is not callable")]),
+                };
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+    
+    fn parse_arguments(&mut self) -> CompilerResult<Vec<Expression>> {
+        let mut args = Vec::new();
+        
+        if !self.check(&Token::RightParen) {
+            loop {
+                args.push(self.parse_expression()?);
+                if !self.match_token(&Token::Comma) {
+                    break;
+                }
+            }
+        }
+        
+        self.expect_token(&Token::RightParen, "Expected ')' after arguments")?;
+        Ok(args)
     }
     
     fn parse_primary(&mut self) -> CompilerResult<Expression> {
+        let span = self.current_span();
+        
         match self.peek_token() {
             Some(Token::Integer(n)) => {
                 let value = *n;
                 self.advance();
-                Ok(Expression::Literal(Literal::Integer(value)))
+                Ok(Expression::Literal(Literal::Integer(value), span))
             }
-            Some(Token::String(s)) => {
+            Some(Token::StringLiteral(s)) => {
                 let value = s.clone();
                 self.advance();
-                Ok(Expression::Literal(Literal::String(value)))
+                Ok(Expression::Literal(Literal::String(value), span))
             }
-            Some(Token::True) => {
+            Some(Token::BoolLiteral(b)) => {
+                let value = *b;
                 self.advance();
-                Ok(Expression::Literal(Literal::Boolean(true)))
+                Ok(Expression::Literal(Literal::Boolean(value), span))
             }
-            Some(Token::False) => {
+            Some(Token::Float(f)) => {
+                let value = *f;
                 self.advance();
-                Ok(Expression::Literal(Literal::Boolean(false)))
+                Ok(Expression::Literal(Literal::Float(value), span))
             }
-            Some(Token::Identifier(name)) => {
-                let name = name.clone();
-                self.advance();
-                
-                // Check for function call
-                if self.match_token(&Token::LeftParen) {
-                    let mut args = Vec::new();
-                    
-                    if !self.check(&Token::RightParen) {
-                        loop {
-                            args.push(self.parse_expression()?);
-                            if !self.match_token(&Token::Comma) {
-                                break;
-                            }
-                        }
-                    }
-                    
-                    self.expect_token(&Token::RightParen, "Expected ')' after arguments")?;
-                    
-                    Ok(Expression::Call(CallExpression {
-                        function: name,
-                        args,
-                        span: self.previous_span(),
-                    }))
-                } else {
-                    Ok(Expression::Identifier(name))
-                }
+            Some(Token::Milliseconds(_)) | Some(Token::Seconds(_)) => {
+                let millis = self.expect_duration()?;
+                Ok(Expression::Literal(Literal::Duration(millis), span))
+            }
+            Some(Token::LeftBracket) => self.parse_array_literal(),
+            Some(Token::LeftBrace) => self.parse_object_literal(),
+            Some(Token::Identifier(_)) | Some(Token::Vec) | Some(Token::Set) | Some(Token::Map) => {
+                self.parse_path()
             }
             Some(Token::LeftParen) => {
                 self.advance();
@@ -480,18 +749,130 @@ impl Parser {
         }
     }
     
+    /// A name, or a `::` path such as `Map::new` kept as a single identifier
+    fn parse_path(&mut self) -> CompilerResult<Expression> {
+        let tracker = self.start_span();
+        let mut name = match self.advance() {
+            Some(Token::Identifier(name)) => name.clone(),
+            Some(Token::Vec) => "Vec".to_string(),
+            Some(Token::Set) => "Set".to_string(),
+            Some(Token::Map) => "Map".to_string(),
+            _ => return Err(vec![self.error("Expected expression")]),
+        };
+        
+        // Collection types only appear in expressions as the head of a path
+        if matches!(self.previous_token(), Some(Token::Vec | Token::Set | Token::Map)) && !self.check(&Token::DoubleColon) {
+            return Err(vec![self.error(&format!("Expected '::' after '{}'", name))]);
+        }
+        while self.match_token(&Token::DoubleColon) {
+            name.push_str("::");
+            name.push_str(&self.expect_identifier()?);
+        }
+        Ok(Expression::Identifier(name, tracker.end(self.previous_span())))
+    }
+    
+    fn parse_if(&mut self, tracker: SpanTracker) -> CompilerResult<IfStatement> {
+        let condition = self.parse_expression()?;
+        let then_body = self.parse_block()?;
+        
+        let else_body = if self.match_token(&Token::Else) {
+            if self.check(&Token::If) {
+                let else_tracker = self.start_span();
+                self.advance();
+                let nested = self.parse_if(else_tracker)?;
+                let span = nested.span.clone();
+                Some(Block {
+                    statements: vec![Statement::If(nested)],
+                    span,
+                })
+            } else {
+                Some(self.parse_block()?)
+            }
+        } else {
+            None
+        };
+        
+        Ok(IfStatement {
+            condition,
+            then_body,
+            else_body,
+            span: tracker.end(self.previous_span()),
+        })
+    }
+    
+    fn parse_loop_body(&mut self) -> CompilerResult<Block> {
+        self.loop_depth += 1;
+        let body = self.parse_block();
+        self.loop_depth -= 1;
+        body
+    }
+    
+    fn parse_loop_variable(&mut self) -> CompilerResult<LoopVariable> {
+        let (name, span) = self.with_span(|parser| parser.expect_identifier())?;
+        Ok(LoopVariable { name, span })
+    }
+    
+    fn parse_array_literal(&mut self) -> CompilerResult<Expression> {
+        let tracker = self.start_span();
+        self.expect_token(&Token::LeftBracket, "Expected '['")?;
+        
+        let mut elements = Vec::new();
+        while !self.check(&Token::RightBracket) && !self.is_at_end() {
+            elements.push(self.parse_expression()?);
+            if !self.match_token(&Token::Comma) {
+                break;
+            }
+        }
+        
+        self.expect_token(&Token::RightBracket, "Expected ']' after array elements")?;
+        Ok(Expression::Array(elements, tracker.end(self.previous_span())))
+    }
+    
+    fn parse_object_literal(&mut self) -> CompilerResult<Expression> {
+        let tracker = self.start_span();
+        let fields = self.parse_object_fields()?;
+        Ok(Expression::Object(fields, tracker.end(self.previous_span())))
+    }
+    
+    /// `{ name: expr, "key": expr }`, braces included
+    fn parse_object_fields(&mut self) -> CompilerResult<Vec<ObjectField>> {
+        self.expect_token(&Token::LeftBrace, "Expected '{'")?;
+        
+        let mut fields = Vec::new();
+        while !self.check(&Token::RightBrace) && !self.is_at_end() {
+            let field_tracker = self.start_span();
+            let name = match self.peek_token() {
+                Some(Token::StringLiteral(key)) => {
+                    let key = key.clone();
+                    self.advance();
+                    key
+                }
+                _ => self.expect_identifier()?,
+            };
+            self.expect_token(&Token::Colon, "Expected ':' after object field name")?;
+            let value = self.parse_expression()?;
+            fields.push(ObjectField {
+                name,
+                value,
+                span: field_tracker.end(self.previous_span()),
+            });
+            
+            if !self.match_token(&Token::Comma) {
+                break;
+            }
+        }
+        
+        self.expect_token(&Token::RightBrace, "Expected '}' after object fields")?;
+        Ok(fields)
+    }
+    
     fn parse_consensus_config(&mut self) -> CompilerResult<ConsensusConfig> {
         self.expect_token(&Token::LeftBrace, "Expected '{' after consensus operator")?;
         
-        let mut config = ConsensusConfig {
-            validators: None,
-            timeout: None,
-            algorithm: None,
-            quorum: None,
-        };
+        let mut config = ConsensusConfig::default();
         
         while !self.check(&Token::RightBrace) && !self.is_at_end() {
-            let key = self.expect_identifier()?;
+            let key = self.expect_option_key()?;
             self.expect_token(&Token::Colon, "Expected ':' after config key")?;
             
             match key.as_str() {
@@ -499,7 +880,7 @@ impl Parser {
                     config.validators = Some(self.expect_number()? as u32);
                 }
                 "timeout" => {
-                    config.timeout = Some(self.expect_number()? as u64);
+                    config.timeout = Some(self.expect_duration()?);
                 }
                 "algorithm" => {
                     config.algorithm = Some(self.parse_consensus_algorithm()?);
@@ -507,6 +888,9 @@ impl Parser {
                 "quorum" => {
                     config.quorum = Some(self.expect_number()? as u32);
                 }
+                "vote" => {
+                    config.vote = Some(self.parse_vote_choice()?);
+                }
                 _ => {
                     // Skip unknown config options
                     self.parse_expression()?;
@@ -522,11 +906,76 @@ impl Parser {
         Ok(config)
     }
     
+    /// Options of `<~>`, `<@>`, `<=>`, `<|>` and `<*>`; values are kept as expressions
+    fn parse_operator_options(&mut self) -> CompilerResult<Vec<ObjectField>> {
+        self.expect_token(&Token::LeftBrace, "Expected '{' after distributed operator")?;
+        self.parse_options(&Token::RightBrace, "Expected '}' after options")
+    }
+    
+    /// `key: expr` pairs up to and including `close`, after the opening token has been consumed
+    fn parse_options(&mut self, close: &Token, message: &str) -> CompilerResult<Vec<ObjectField>> {
+        let mut options = Vec::new();
+        while !self.check(close) && !self.is_at_end() {
+            let tracker = self.start_span();
+            let name = self.expect_option_key()?;
+            self.expect_token(&Token::Colon, "Expected ':' after option name")?;
+            let value = self.parse_expression()?;
+            options.push(ObjectField {
+                name,
+                value,
+                span: tracker.end(self.previous_span()),
+            });
+            
+            if !self.match_token(&Token::Comma) {
+                break;
+            }
+        }
+        
+        self.expect_token(close, message)?;
+        Ok(options)
+    }
+    
+    /// Option keys are identifiers, plus the `quorum` and `consensus` keywords
+    fn expect_option_key(&mut self) -> CompilerResult<String> {
+        if self.match_token(&Token::QuorumKw) {
+            Ok("quorum".to_string())
+        } else if self.match_token(&Token::Consensus) {
+            Ok("consensus".to_string())
+        } else {
+            self.expect_identifier()
+        }
+    }
+    
+    fn parse_vote_choice(&mut self) -> CompilerResult<VoteChoice> {
+        let mut name = self.expect_identifier()?;
+        // Accept both `Reject` and `Vote::Reject`
+        if name == "Vote" && self.match_token(&Token::DoubleColon) {
+            name = self.expect_identifier()?;
+        }
+        
+        match name.as_str() {
+            "Accept" => Ok(VoteChoice::Accept),
+            "Reject" => Ok(VoteChoice::Reject),
+            "Abstain" => Ok(VoteChoice::Abstain),
+            name => {
+                let message = format!("Unknown vote '{}', expected Accept, Reject or Abstain", name);
+                Err(vec![Diagnostic::error(ErrorKind::InvalidSyntax(message.clone()), message, self.previous_span())])
+            }
+        }
+    }
+    
     fn parse_consensus_algorithm(&mut self) -> CompilerResult<ConsensusAlgorithm> {
-        match self.expect_identifier()?.as_str() {
+        let mut name = self.expect_identifier()?;
+        // Accept both `Raft` and `Consensus::Raft`
+        if name == "Consensus" && self.match_token(&Token::DoubleColon) {
+            name = self.expect_identifier()?;
+        }
+        
+        match name.as_str() {
             "Raft" => Ok(ConsensusAlgorithm::Raft),
             "PBFT" => Ok(ConsensusAlgorithm::PBFT),
             "Tendermint" => Ok(ConsensusAlgorithm::Tendermint),
+            "HotStuff" => Ok(ConsensusAlgorithm::HotStuff),
             name => Err(vec![Diagnostic::error(
                 ErrorKind::UnsupportedConsensusAlgorithm(name.to_string()),
                 format!("Unsupported consensus algorithm: {}", name),
@@ -536,30 +985,106 @@ impl Parser {
     }
     
     fn parse_type(&mut self) -> CompilerResult<Type> {
-        match self.expect_identifier()?.as_str() {
-            "u64" => Ok(Type::U64),
-            "i64" => Ok(Type::I64),
-            "f64" => Ok(Type::F64),
-            "bool" => Ok(Type::Bool),
-            "String" => Ok(Type::String),
-            "Bytes" => Ok(Type::Bytes),
-            name => Ok(Type::Custom(name.to_string())),
+        // Unit `()` and tuples `(A, B)`
+        if self.match_token(&Token::LeftParen) {
+            let mut elements = Vec::new();
+            let mut trailing_comma = false;
+            while !self.check(&Token::RightParen) {
+                elements.push(self.parse_type()?);
+                trailing_comma = self.match_token(&Token::Comma);
+                if !trailing_comma {
+                    break;
+                }
+            }
+            self.expect_token(&Token::RightParen, "Expected ')' after tuple type")?;
+            
+            return Ok(match elements.len() {
+                0 => Type::Unit,
+                // `(T)` is just a parenthesized type, `(T,)` a one-element tuple
+                1 if !trailing_comma => elements.remove(0),
+                _ => Type::Tuple(elements),
+            });
+        }
+        
+        let name = match self.peek_token() {
+            Some(Token::U64) => "u64".to_string(),
+            Some(Token::I64) => "i64".to_string(),
+            Some(Token::F64) => "f64".to_string(),
+            Some(Token::U32) => "u32".to_string(),
+            Some(Token::I32) => "i32".to_string(),
+            Some(Token::F32) => "f32".to_string(),
+            Some(Token::Bool) => "bool".to_string(),
+            Some(Token::String) => "String".to_string(),
+            Some(Token::Vec) => "Vec".to_string(),
+            Some(Token::Set) => "Set".to_string(),
+            Some(Token::Map) => "Map".to_string(),
+            _ => return self.parse_named_type(),
+        };
+        self.advance();
+        self.finish_named_type(name)
+    }
+    
+    fn parse_named_type(&mut self) -> CompilerResult<Type> {
+        let name = self.expect_identifier()?;
+        self.finish_named_type(name)
+    }
+    
+    fn finish_named_type(&mut self, name: String) -> CompilerResult<Type> {
+        let mut args = Vec::new();
+        if self.match_token(&Token::LessThan) {
+            loop {
+                args.push(self.parse_type()?);
+                if !self.match_token(&Token::Comma) {
+                    break;
+                }
+            }
+            self.expect_token(&Token::GreaterThan, "Expected '>' after type arguments")?;
+        }
+        
+        let arity = match name.as_str() {
+            "Vec" | "Set" | "Option" => 1,
+            "Map" | "Result" => 2,
+            _ => 0,
+        };
+        if arity > 0 && args.len() != arity {
+            return Err(vec![self.error(&format!("'{}' expects {} type argument(s), found {}", name, arity, args.len()))]);
         }
+        
+        let mut args = args.into_iter();
+        let mut next = || Box::new(args.next().expect("arity checked above"));
+        Ok(match name.as_str() {
+            "u64" => Type::U64,
+            "i64" => Type::I64,
+            "f64" => Type::F64,
+            "bool" => Type::Bool,
+            "String" => Type::String,
+            "Bytes" => Type::Bytes,
+            "Duration" => Type::Duration,
+            "Vec" => Type::Vec(next()),
+            "Set" => Type::Set(next()),
+            "Option" => Type::Option(next()),
+            "Map" => Type::Map(next(), next()),
+            "Result" => Type::Result(next(), next()),
+            _ => Type::Custom(name),
+        })
     }
     
     // Helper methods
-    fn match_binary_op(&mut self) -> Option<BinaryOp> {
-        match self.peek_token() {
-            Some(Token::Plus) => { self.advance(); Some(BinaryOp::Add) }
-            Some(Token::Minus) => { self.advance(); Some(BinaryOp::Sub) }
-            Some(Token::Star) => { self.advance(); Some(BinaryOp::Mul) }
-            Some(Token::Slash) => { self.advance(); Some(BinaryOp::Div) }
-            Some(Token::EqualEqual) => { self.advance(); Some(BinaryOp::Eq) }
-            Some(Token::BangEqual) => { self.advance(); Some(BinaryOp::Ne) }
-            Some(Token::Greater) => { self.advance(); Some(BinaryOp::Gt) }
-            Some(Token::GreaterEqual) => { self.advance(); Some(BinaryOp::Ge) }
-            Some(Token::Less) => { self.advance(); Some(BinaryOp::Lt) }
-            Some(Token::LessEqual) => { self.advance(); Some(BinaryOp::Le) }
+    fn binary_op(token: &Token) -> Option<BinaryOp> {
+        match token {
+            Token::Plus => Some(BinaryOp::Add),
+            Token::Minus => Some(BinaryOp::Sub),
+            Token::Star => Some(BinaryOp::Mul),
+            Token::Slash => Some(BinaryOp::Div),
+            Token::Percent => Some(BinaryOp::Mod),
+            Token::Equal => Some(BinaryOp::Eq),
+            Token::NotEqual => Some(BinaryOp::Ne),
+            Token::GreaterThan => Some(BinaryOp::Gt),
+            Token::GreaterEqual => Some(BinaryOp::Ge),
+            Token::LessThan => Some(BinaryOp::Lt),
+            Token::LessEqual => Some(BinaryOp::Le),
+            Token::And => Some(BinaryOp::And),
+            Token::Or => Some(BinaryOp::Or),
             _ => None
         }
     }
@@ -574,7 +1099,8 @@ impl Parser {
     }
     
     fn check(&self, token: &Token) -> bool {
-        std::mem::discriminant(self.peek_token().unwrap_or(&Token::Eof)) == std::mem::discriminant(token)
+        self.peek_token()
+            .is_some_and(|t| std::mem::discriminant(t) == std::mem::discriminant(token))
     }
     
     fn advance(&mut self) -> Option<&Token> {
@@ -600,12 +1126,11 @@ impl Parser {
         }
     }
     
-    fn previous_span(&self) -> Span {
-        if self.current > 0 {
-            self.tokens.get(self.current - 1).map(|(_, span)| span.clone()).unwrap_or(Span::unknown())
-        } else {
-            Span::unknown()
-        }
+    /// Begin tracking a node whose first token is the current one
+    fn start_span(&self) -> SpanTracker {
+        let mut tracker = SpanTracker::new();
+        tracker.start(self.current_span());
+        tracker
     }
     
     fn expect_token(&mut self, expected: &Token, message: &str) -> CompilerResult<()> {
@@ -637,17 +1162,23 @@ impl Parser {
         }
     }
     
-    fn error(&self, message: &str) -> Diagnostic {
-        let span = if self.current < self.tokens.len() {
-            self.tokens[self.current].1.clone()
-        } else {
-            Span::unknown()
+    /// Duration literal in milliseconds; a bare integer is taken as milliseconds
+    fn expect_duration(&mut self) -> CompilerResult<u64> {
+        let millis = match self.peek_token() {
+            Some(Token::Milliseconds(ms)) => *ms,
+            Some(Token::Seconds(s)) => s.saturating_mul(1000),
+            Some(Token::Integer(n)) if *n >= 0 => *n as u64,
+            _ => return Err(vec![self.error("Expected duration")]),
         };
-        
+        self.advance();
+        Ok(millis)
+    }
+    
+    fn error(&self, message: &str) -> Diagnostic {
         Diagnostic::error(
             ErrorKind::InvalidSyntax(message.to_string()),
             message.to_string(),
-            span,
+            self.current_span(),
         )
     }
     
@@ -670,6 +1201,36 @@ impl Parser {
     }
 }
 
+impl WithSpan for Parser {
+    fn with_span<T, F>(&mut self, f: F) -> CompilerResult<(T, Span)>
+    where
+        F: FnOnce(&mut Self) -> CompilerResult<T>,
+    {
+        let tracker = self.start_span();
+        let value = f(self)?;
+        Ok((value, tracker.end(self.previous_span())))
+    }
+    
+    fn current_span(&self) -> Span {
+        match self.tokens.get(self.current) {
+            Some((_, span)) => span.clone(),
+            // At end of input, point just past the last token
+            None => {
+                let last = self.previous_span();
+                Span::new(last.end, last.end, last.line, last.column)
+            }
+        }
+    }
+    
+    fn previous_span(&self) -> Span {
+        if self.current > 0 {
+            self.tokens.get(self.current - 1).map(|(_, span)| span.clone()).unwrap_or(Span::unknown())
+        } else {
+            Span::unknown()
+        }
+    }
+}
+
 // Convenience function
 pub fn parse(tokens: Vec<(Token, Span)>) -> CompilerResult<Program> {
     let mut parser = Parser::new(tokens);
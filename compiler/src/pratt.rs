@@ -4,8 +4,10 @@
 
 use crate::ast::*;
 use crate::token::Token;
-use crate::error::{Diagnostic, ErrorKind, Span, CompilerResult};
+use crate::error::{Span, CompilerResult};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::mem::{discriminant, Discriminant};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Precedence {
@@ -24,6 +26,27 @@ pub enum Precedence {
     Primary = 12,      // literals, identifiers
 }
 
+impl Precedence {
+    /// The next tighter-binding level, used as the floor for the right
+    /// operand of a left-associative operator
+    pub fn next(self) -> Precedence {
+        match self {
+            Precedence::Lowest => Precedence::Assignment,
+            Precedence::Assignment => Precedence::Consensus,
+            Precedence::Consensus => Precedence::LogicalOr,
+            Precedence::LogicalOr => Precedence::LogicalAnd,
+            Precedence::LogicalAnd => Precedence::Equality,
+            Precedence::Equality => Precedence::Comparison,
+            Precedence::Comparison => Precedence::Addition,
+            Precedence::Addition => Precedence::Multiplication,
+            Precedence::Multiplication => Precedence::Unary,
+            Precedence::Unary => Precedence::Call,
+            Precedence::Call => Precedence::Member,
+            Precedence::Member | Precedence::Primary => Precedence::Primary,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Associativity {
     Left,
@@ -31,7 +54,14 @@ pub enum Associativity {
 }
 
 pub struct PrattParser {
-    precedence_table: HashMap<Token, (Precedence, Associativity)>,
+    // Keyed by discriminant: tokens carry payloads (f64) that rule out Hash/Eq
+    precedence_table: HashMap<Discriminant<Token>, (Precedence, Associativity)>,
+}
+
+impl Default for PrattParser {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl PrattParser {
@@ -39,52 +69,59 @@ impl PrattParser {
         let mut precedence_table = HashMap::new();
         
         // Assignment operators (right-associative)
-        precedence_table.insert(Token::Assign, (Precedence::Assignment, Associativity::Right));
-        precedence_table.insert(Token::Commit, (Precedence::Assignment, Associativity::Right)); // <#>
+        precedence_table.insert(discriminant(&Token::Assign), (Precedence::Assignment, Associativity::Right));
+        precedence_table.insert(discriminant(&Token::Commit), (Precedence::Assignment, Associativity::Right)); // <#>
+        precedence_table.insert(discriminant(&Token::AddAssign), (Precedence::Assignment, Associativity::Right));
+        precedence_table.insert(discriminant(&Token::SubAssign), (Precedence::Assignment, Associativity::Right));
         
         // Consensus operators (right-associative)
-        precedence_table.insert(Token::Propose, (Precedence::Consensus, Associativity::Right)); // <!>
-        precedence_table.insert(Token::Vote, (Precedence::Consensus, Associativity::Right)); // <?>
+        precedence_table.insert(discriminant(&Token::Propose), (Precedence::Consensus, Associativity::Right)); // <!>
+        precedence_table.insert(discriminant(&Token::Vote), (Precedence::Consensus, Associativity::Right)); // <?>
+        precedence_table.insert(discriminant(&Token::Broadcast), (Precedence::Consensus, Associativity::Right)); // <~>
+        precedence_table.insert(discriminant(&Token::Gossip), (Precedence::Consensus, Associativity::Right)); // <@>
+        precedence_table.insert(discriminant(&Token::Sync), (Precedence::Consensus, Associativity::Right)); // <=>
+        precedence_table.insert(discriminant(&Token::Partition), (Precedence::Consensus, Associativity::Right)); // <|>
+        precedence_table.insert(discriminant(&Token::Quorum), (Precedence::Consensus, Associativity::Right)); // <*>
         
         // Logical operators
-        precedence_table.insert(Token::OrOr, (Precedence::LogicalOr, Associativity::Left));
-        precedence_table.insert(Token::AndAnd, (Precedence::LogicalAnd, Associativity::Left));
+        precedence_table.insert(discriminant(&Token::Or), (Precedence::LogicalOr, Associativity::Left));
+        precedence_table.insert(discriminant(&Token::And), (Precedence::LogicalAnd, Associativity::Left));
         
         // Equality operators
-        precedence_table.insert(Token::EqualEqual, (Precedence::Equality, Associativity::Left));
-        precedence_table.insert(Token::BangEqual, (Precedence::Equality, Associativity::Left));
+        precedence_table.insert(discriminant(&Token::Equal), (Precedence::Equality, Associativity::Left));
+        precedence_table.insert(discriminant(&Token::NotEqual), (Precedence::Equality, Associativity::Left));
         
         // Comparison operators
-        precedence_table.insert(Token::Less, (Precedence::Comparison, Associativity::Left));
-        precedence_table.insert(Token::Greater, (Precedence::Comparison, Associativity::Left));
-        precedence_table.insert(Token::LessEqual, (Precedence::Comparison, Associativity::Left));
-        precedence_table.insert(Token::GreaterEqual, (Precedence::Comparison, Associativity::Left));
+        precedence_table.insert(discriminant(&Token::LessThan), (Precedence::Comparison, Associativity::Left));
+        precedence_table.insert(discriminant(&Token::GreaterThan), (Precedence::Comparison, Associativity::Left));
+        precedence_table.insert(discriminant(&Token::LessEqual), (Precedence::Comparison, Associativity::Left));
+        precedence_table.insert(discriminant(&Token::GreaterEqual), (Precedence::Comparison, Associativity::Left));
         
         // Arithmetic operators
-        precedence_table.insert(Token::Plus, (Precedence::Addition, Associativity::Left));
-        precedence_table.insert(Token::Minus, (Precedence::Addition, Associativity::Left));
-        precedence_table.insert(Token::Star, (Precedence::Multiplication, Associativity::Left));
-        precedence_table.insert(Token::Slash, (Precedence::Multiplication, Associativity::Left));
-        precedence_table.insert(Token::Percent, (Precedence::Multiplication, Associativity::Left));
+        precedence_table.insert(discriminant(&Token::Plus), (Precedence::Addition, Associativity::Left));
+        precedence_table.insert(discriminant(&Token::Minus), (Precedence::Addition, Associativity::Left));
+        precedence_table.insert(discriminant(&Token::Star), (Precedence::Multiplication, Associativity::Left));
+        precedence_table.insert(discriminant(&Token::Slash), (Precedence::Multiplication, Associativity::Left));
+        precedence_table.insert(discriminant(&Token::Percent), (Precedence::Multiplication, Associativity::Left));
         
         // Call and member access
-        precedence_table.insert(Token::LeftParen, (Precedence::Call, Associativity::Left));
-        precedence_table.insert(Token::LeftBracket, (Precedence::Call, Associativity::Left));
-        precedence_table.insert(Token::Dot, (Precedence::Member, Associativity::Left));
+        precedence_table.insert(discriminant(&Token::LeftParen), (Precedence::Call, Associativity::Left));
+        precedence_table.insert(discriminant(&Token::LeftBracket), (Precedence::Call, Associativity::Left));
+        precedence_table.insert(discriminant(&Token::Dot), (Precedence::Member, Associativity::Left));
         
         Self { precedence_table }
     }
     
     pub fn get_precedence(&self, token: &Token) -> Precedence {
         self.precedence_table
-            .get(token)
+            .get(&discriminant(token))
             .map(|(prec, _)| *prec)
             .unwrap_or(Precedence::Lowest)
     }
     
     pub fn get_associativity(&self, token: &Token) -> Associativity {
         self.precedence_table
-            .get(token)
+            .get(&discriminant(token))
             .map(|(_, assoc)| *assoc)
             .unwrap_or(Associativity::Left)
     }
@@ -97,7 +134,7 @@ impl PrattParser {
 /// Extended LValue for rich assignment targets
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum LValue {
-    Identifier(String),
+    Identifier(String, Span),
     Member {
         object: Box<LValue>,
         field: String,
@@ -113,7 +150,7 @@ pub enum LValue {
 impl LValue {
     pub fn to_expression(&self) -> Expression {
         match self {
-            LValue::Identifier(name) => Expression::Identifier(name.clone()),
+            LValue::Identifier(name, span) => Expression::Identifier(name.clone(), span.clone()),
             LValue::Member { object, field, span } => {
                 Expression::Member(MemberExpression {
                     object: Box::new(object.to_expression()),
@@ -131,9 +168,26 @@ impl LValue {
         }
     }
     
+    /// Name of the variable the assignment ultimately writes into
+    pub fn root(&self) -> &str {
+        match self {
+            LValue::Identifier(name, _) => name,
+            LValue::Member { object, .. } => object.root(),
+            LValue::Index { array, .. } => array.root(),
+        }
+    }
+    
+    pub fn span(&self) -> Span {
+        match self {
+            LValue::Identifier(_, span) => span.clone(),
+            LValue::Member { span, .. } => span.clone(),
+            LValue::Index { span, .. } => span.clone(),
+        }
+    }
+    
     pub fn from_expression(expr: &Expression) -> Option<LValue> {
         match expr {
-            Expression::Identifier(name) => Some(LValue::Identifier(name.clone())),
+            Expression::Identifier(name, span) => Some(LValue::Identifier(name.clone(), span.clone())),
             Expression::Member(member) => {
                 let object = LValue::from_expression(&member.object)?;
                 Some(LValue::Member {
@@ -156,6 +210,7 @@ impl LValue {
 }
 
 /// Span utilities for accurate source tracking
+#[derive(Default)]
 pub struct SpanTracker {
     start: Option<Span>,
 }
@@ -201,8 +256,8 @@ mod tests {
         let pratt = PrattParser::new();
         
         assert!(pratt.get_precedence(&Token::Star) > pratt.get_precedence(&Token::Plus));
-        assert!(pratt.get_precedence(&Token::Plus) > pratt.get_precedence(&Token::EqualEqual));
-        assert!(pratt.get_precedence(&Token::AndAnd) > pratt.get_precedence(&Token::OrOr));
+        assert!(pratt.get_precedence(&Token::Plus) > pratt.get_precedence(&Token::Equal));
+        assert!(pratt.get_precedence(&Token::And) > pratt.get_precedence(&Token::Or));
         assert!(pratt.get_precedence(&Token::Propose) > pratt.get_precedence(&Token::Assign));
     }
     
@@ -217,8 +272,25 @@ mod tests {
     
     #[test]
     fn test_lvalue_conversion() {
-        let ident = Expression::Identifier("x".to_string());
+        let ident = Expression::Identifier("x".to_string(), Span::unknown());
         let lvalue = LValue::from_expression(&ident);
-        assert!(matches!(lvalue, Some(LValue::Identifier(name)) if name == "x"));
+        assert!(matches!(lvalue, Some(LValue::Identifier(name, _)) if name == "x"));
+    }
+    
+    #[test]
+    fn test_lvalue_nested_root() {
+        let expr = Expression::Index(IndexExpression {
+            array: Box::new(Expression::Member(MemberExpression {
+                object: Box::new(Expression::Identifier("a".to_string(), Span::unknown())),
+                field: "b".to_string(),
+                span: Span::unknown(),
+            })),
+            index: Box::new(Expression::Identifier("c".to_string(), Span::unknown())),
+            span: Span::unknown(),
+        });
+        
+        let lvalue = LValue::from_expression(&expr).expect("Index of member is assignable");
+        assert_eq!(lvalue.root(), "a");
+        assert!(LValue::from_expression(&Expression::Literal(Literal::Integer(1), Span::unknown())).is_none());
     }
 }
\ No newline at end of file
@@ -0,0 +1,433 @@
+/*!
+ * OMNIX Name Resolver v0.1 MVP
+ * Builds nested scopes over the AST and binds identifiers to their declarations
+ */
+
+use std::collections::HashMap;
+use crate::ast::*;
+use crate::error::{Diagnostic, DiagnosticCollector, ErrorKind, Severity, Span};
+use crate::pratt::LValue;
+
+/// Functions provided by the runtime standard library that need no declaration.
+/// Must match the lowercase functions in `omnix_runtime::runtime::stdlib`, which `tests/stdlib_test.rs`
+/// checks; capitalized ones such as `Map::new` are skipped anyway.
+pub const BUILTIN_FUNCTIONS: &[&str] = &[
+    "print", "println", "format", "to_string", "now", "generate_id", "hash",
+];
+
+/// Values the runtime injects into every node
+pub const BUILTIN_VALUES: &[&str] = &["node_id", "None"];
+
+pub type SymbolId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Node,
+    Cluster,
+    Function,
+    Service,
+    State,
+    Parameter,
+    Local,
+}
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub type_: Option<Type>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeKind {
+    Program,
+    Node,
+    Cluster,
+    Function,
+    Block,
+    Phase,
+}
+
+#[derive(Debug)]
+struct Scope {
+    kind: ScopeKind,
+    names: HashMap<String, SymbolId>,
+}
+
+/// Output of name resolution: every declared symbol, and which symbol each use refers to
+#[derive(Debug, Default)]
+pub struct Resolution {
+    pub symbols: Vec<Symbol>,
+    pub diagnostics: Vec<Diagnostic>,
+    // Keyed by the start offset of the identifier use
+    bindings: HashMap<usize, SymbolId>,
+}
+
+impl Resolution {
+    /// Symbol referenced by the identifier, call or assignment root starting at `span`
+    pub fn binding(&self, span: &Span) -> Option<&Symbol> {
+        self.bindings.get(&span.start).map(|id| &self.symbols[*id])
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(|d| d.severity == Severity::Error)
+    }
+}
+
+pub struct Resolver {
+    scopes: Vec<Scope>,
+    symbols: Vec<Symbol>,
+    bindings: HashMap<usize, SymbolId>,
+    diagnostics: DiagnosticCollector,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self {
+            scopes: Vec::new(),
+            symbols: Vec::new(),
+            bindings: HashMap::new(),
+            diagnostics: DiagnosticCollector::new(),
+        }
+    }
+
+    pub fn resolve(mut self, program: &Program) -> Resolution {
+        self.push_scope(ScopeKind::Program);
+
+        // Top-level items are visible everywhere, regardless of order
+        for item in &program.items {
+            match item {
+                Item::Node(node) => self.declare(&node.name, SymbolKind::Node, None, &node.span),
+                Item::Cluster(cluster) => self.declare(&cluster.name, SymbolKind::Cluster, None, &cluster.span),
+                Item::Function(func) => self.declare(&func.name, SymbolKind::Function, func.return_type.clone(), &func.span),
+            }
+        }
+
+        for item in &program.items {
+            match item {
+                Item::Node(node) => self.resolve_node(node),
+                Item::Cluster(cluster) => self.resolve_cluster(cluster),
+                Item::Function(func) => self.resolve_function(func),
+            }
+        }
+
+        self.pop_scope();
+
+        Resolution {
+            symbols: self.symbols,
+            diagnostics: self.diagnostics.diagnostics,
+            bindings: self.bindings,
+        }
+    }
+
+    fn resolve_node(&mut self, node: &NodeDefinition) {
+        self.push_scope(ScopeKind::Node);
+
+        for item in &node.items {
+            match item {
+                NodeItem::State(state) => self.declare_state(state),
+                NodeItem::Function(func) => self.declare(&func.name, SymbolKind::Function, func.return_type.clone(), &func.span),
+                NodeItem::EventHandler(_) => {}
+            }
+        }
+
+        for item in &node.items {
+            match item {
+                NodeItem::State(state) => self.resolve_state_initializer(state),
+                NodeItem::Function(func) => self.resolve_function(func),
+                NodeItem::EventHandler(handler) => self.resolve_callable(&handler.params, &handler.body),
+            }
+        }
+
+        self.pop_scope();
+    }
+
+    fn resolve_cluster(&mut self, cluster: &ConsensusCluster) {
+        self.push_scope(ScopeKind::Cluster);
+
+        for item in &cluster.items {
+            match item {
+                ClusterItem::State(state) => self.declare_state(state),
+                ClusterItem::Service(service) => self.declare(&service.name, SymbolKind::Service, service.return_type.clone(), &service.span),
+            }
+        }
+
+        for item in &cluster.items {
+            match item {
+                ClusterItem::State(state) => self.resolve_state_initializer(state),
+                ClusterItem::Service(service) => self.resolve_callable(&service.params, &service.body),
+            }
+        }
+
+        self.pop_scope();
+    }
+
+    fn declare_state(&mut self, state: &StateVariable) {
+        self.declare(&state.name, SymbolKind::State, Some(state.type_.clone()), &state.span);
+    }
+
+    fn resolve_state_initializer(&mut self, state: &StateVariable) {
+        if let Some(value) = &state.initial_value {
+            self.resolve_expression(value);
+        }
+    }
+
+    fn resolve_function(&mut self, func: &Function) {
+        self.resolve_callable(&func.params, &func.body);
+    }
+
+    fn resolve_callable(&mut self, params: &[Parameter], body: &Block) {
+        self.push_scope(ScopeKind::Function);
+        for param in params {
+            self.declare(&param.name, SymbolKind::Parameter, Some(param.type_.clone()), &param.span);
+        }
+        self.resolve_block(body, ScopeKind::Block);
+        self.pop_scope();
+    }
+
+    fn resolve_block(&mut self, block: &Block, kind: ScopeKind) {
+        self.push_scope(kind);
+        for statement in &block.statements {
+            self.resolve_statement(statement);
+        }
+        self.pop_scope();
+    }
+
+    fn resolve_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Let(let_stmt) => {
+                // The initializer cannot see the binding it introduces
+                self.resolve_expression(&let_stmt.value);
+                self.declare(&let_stmt.name, SymbolKind::Local, None, &let_stmt.span);
+            }
+            Statement::Assignment(assignment) => {
+                self.resolve_lvalue(&assignment.target);
+                self.resolve_expression(&assignment.value);
+            }
+            Statement::Expression(expr) => self.resolve_expression(expr),
+            Statement::When(when_stmt) => {
+                self.resolve_expression(&when_stmt.condition);
+                self.resolve_block(&when_stmt.body, ScopeKind::Block);
+                if let Some(else_body) = &when_stmt.else_body {
+                    self.resolve_block(else_body, ScopeKind::Block);
+                }
+            }
+            Statement::If(if_stmt) => {
+                self.resolve_expression(&if_stmt.condition);
+                self.resolve_block(&if_stmt.then_body, ScopeKind::Block);
+                if let Some(else_body) = &if_stmt.else_body {
+                    self.resolve_block(else_body, ScopeKind::Block);
+                }
+            }
+            Statement::For(for_stmt) => {
+                self.resolve_expression(&for_stmt.iterable);
+                // Loop variables live in their own scope around the body
+                self.push_scope(ScopeKind::Block);
+                for binding in &for_stmt.bindings {
+                    self.declare(&binding.name, SymbolKind::Local, None, &binding.span);
+                }
+                self.resolve_block(&for_stmt.body, ScopeKind::Block);
+                self.pop_scope();
+            }
+            Statement::While(while_stmt) => {
+                self.resolve_expression(&while_stmt.condition);
+                self.resolve_block(&while_stmt.body, ScopeKind::Block);
+            }
+            Statement::Break(_) | Statement::Continue(_) => {}
+            Statement::Phase(phase) => {
+                for option in &phase.options {
+                    self.resolve_expression(&option.value);
+                }
+                self.resolve_block(&phase.body, ScopeKind::Phase);
+                if let Some(abort) = &phase.abort {
+                    self.resolve_block(abort, ScopeKind::Phase);
+                }
+            }
+            Statement::Return(expr, _) => {
+                if let Some(expr) = expr {
+                    self.resolve_expression(expr);
+                }
+            }
+            // Event names are message constructors and need no declaration
+            Statement::Broadcast(broadcast) => match &broadcast.args {
+                EventArgs::Positional(args) => {
+                    for arg in args {
+                        self.resolve_expression(arg);
+                    }
+                }
+                EventArgs::Named(fields) => {
+                    for field in fields {
+                        self.resolve_expression(&field.value);
+                    }
+                }
+            },
+        }
+    }
+
+    fn resolve_lvalue(&mut self, target: &LValue) {
+        match target {
+            LValue::Identifier(name, span) => self.resolve_name(name, span),
+            LValue::Member { object, .. } => self.resolve_lvalue(object),
+            LValue::Index { array, index, .. } => {
+                self.resolve_lvalue(array);
+                self.resolve_expression(index);
+            }
+        }
+    }
+
+    fn resolve_expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Literal(..) => {}
+            Expression::Identifier(name, span) => {
+                // `Type::item` paths name runtime items rather than declarations
+                if !BUILTIN_VALUES.contains(&name.as_str()) && !name.contains("::") {
+                    self.resolve_name(name, span);
+                }
+            }
+            Expression::Binary(binary) => {
+                self.resolve_expression(&binary.left);
+                self.resolve_expression(&binary.right);
+            }
+            Expression::Unary(unary) => self.resolve_expression(&unary.operand),
+            Expression::Call(call) => {
+                // Capitalized callees construct messages and custom types, which have no declarations
+                let is_constructor = call.function.starts_with(|c: char| c.is_ascii_uppercase());
+                if !is_constructor && !BUILTIN_FUNCTIONS.contains(&call.function.as_str()) {
+                    self.resolve_name(&call.function, &call.span);
+                }
+                for arg in &call.args {
+                    self.resolve_expression(arg);
+                }
+            }
+            Expression::MethodCall(call) => {
+                self.resolve_expression(&call.receiver);
+                for arg in &call.args {
+                    self.resolve_expression(arg);
+                }
+            }
+            Expression::Member(member) => self.resolve_expression(&member.object),
+            Expression::Index(index) => {
+                self.resolve_expression(&index.array);
+                self.resolve_expression(&index.index);
+            }
+            Expression::Proposal(proposal) => {
+                self.resolve_expression(&proposal.value);
+                if let Some(config) = &proposal.config_value {
+                    self.resolve_expression(config);
+                }
+            }
+            Expression::Vote(vote) => {
+                self.resolve_expression(&vote.value);
+                if let Some(config) = &vote.config_value {
+                    self.resolve_expression(config);
+                }
+            }
+            Expression::Broadcast(distributed)
+            | Expression::Gossip(distributed)
+            | Expression::Sync(distributed)
+            | Expression::Partition(distributed)
+            | Expression::Quorum(distributed) => {
+                self.resolve_expression(&distributed.value);
+                for option in &distributed.options {
+                    // Capitalized bare names such as `Quorum` are symbolic option values
+                    match &option.value {
+                        Expression::Identifier(name, _) if name.starts_with(|c: char| c.is_ascii_uppercase()) => {}
+                        value => self.resolve_expression(value),
+                    }
+                }
+            }
+            Expression::Array(elements, _) => {
+                for element in elements {
+                    self.resolve_expression(element);
+                }
+            }
+            Expression::Object(fields, _) => {
+                for field in fields {
+                    self.resolve_expression(&field.value);
+                }
+            }
+            Expression::Assignment(assignment) => {
+                self.resolve_expression(&assignment.target);
+                self.resolve_expression(&assignment.value);
+            }
+        }
+    }
+
+    fn resolve_name(&mut self, name: &str, span: &Span) {
+        match self.lookup(name) {
+            Some(id) => {
+                self.bindings.insert(span.start, id);
+            }
+            None => {
+                self.diagnostics.error(
+                    ErrorKind::UndeclaredVariable(name.to_string()),
+                    format!("Cannot find '{}' in this scope", name),
+                    span.clone(),
+                );
+            }
+        }
+    }
+
+    fn declare(&mut self, name: &str, kind: SymbolKind, type_: Option<Type>, span: &Span) {
+        let scope = self.scopes.last().expect("declaration outside of any scope");
+
+        if let Some(&existing) = scope.names.get(name) {
+            let previous = &self.symbols[existing];
+            let diagnostic = Diagnostic::error(
+                ErrorKind::DuplicateDefinition(name.to_string()),
+                format!("'{}' is defined more than once in this {:?} scope", name, scope.kind),
+                span.clone(),
+            ).with_help(format!("previous definition at line {}, column {}", previous.span.line, previous.span.column));
+            self.diagnostics.diagnostics.push(diagnostic);
+            return;
+        }
+
+        if let Some(shadowed) = self.lookup(name) {
+            let previous = &self.symbols[shadowed];
+            let diagnostic = Diagnostic::warning(
+                ErrorKind::ShadowedDefinition(name.to_string()),
+                format!("'{}' shadows an outer {:?}", name, previous.kind),
+                span.clone(),
+            ).with_help(format!("outer definition at line {}, column {}", previous.span.line, previous.span.column));
+            self.diagnostics.diagnostics.push(diagnostic);
+        }
+
+        let id = self.symbols.len();
+        self.symbols.push(Symbol {
+            name: name.to_string(),
+            kind,
+            type_,
+            span: span.clone(),
+        });
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.names.insert(name.to_string(), id);
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<SymbolId> {
+        self.scopes.iter().rev().find_map(|scope| scope.names.get(name).copied())
+    }
+
+    fn push_scope(&mut self, kind: ScopeKind) {
+        self.scopes.push(Scope {
+            kind,
+            names: HashMap::new(),
+        });
+    }
+
+    fn pop_scope(&mut self) {
+        self.scopes.pop();
+    }
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Convenience function
+pub fn resolve(program: &Program) -> Resolution {
+    Resolver::new().resolve(program)
+}
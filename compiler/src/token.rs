@@ -7,6 +7,10 @@ use logos::Logos;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Logos)]
+// Comments and whitespace
+#[logos(skip r"//[^\n]*")]
+#[logos(skip r"/\*([^*]|\*[^/])*\*/")]
+#[logos(skip r"[ \t\n\r]+")]
 pub enum Token {
     // Consensus Operators
     #[token("<!>")]
@@ -61,6 +65,9 @@ pub enum Token {
     #[token("phase")]
     Phase,
     
+    #[token("abort")]
+    Abort,
+    
     #[token("view")]
     View,
     
@@ -128,6 +135,15 @@ pub enum Token {
     #[token("while")]
     While,
     
+    #[token("in")]
+    In,
+    
+    #[token("break")]
+    Break,
+    
+    #[token("continue")]
+    Continue,
+    
     #[token("return")]
     Return,
     
@@ -287,10 +303,10 @@ pub enum Token {
     #[regex(r"[a-zA-Z_][a-zA-Z0-9_]*", |lex| lex.slice().to_string())]
     Identifier(String),
     
-    #[regex(r"\d+", |lex| lex.slice().parse())]
+    #[regex(r"\d+", |lex| lex.slice().parse().ok())]
     Integer(i64),
     
-    #[regex(r"\d+\.\d+", |lex| lex.slice().parse())]
+    #[regex(r"\d+\.\d+", |lex| lex.slice().parse().ok())]
     Float(f64),
     
     #[regex(r#""([^"\\]|\\.)*""#, |lex| lex.slice()[1..lex.slice().len()-1].to_string())]
@@ -300,18 +316,11 @@ pub enum Token {
     BoolLiteral(bool),
     
     // Time literals
-    #[regex(r"\d+ms", |lex| lex.slice()[..lex.slice().len()-2].parse())]
+    #[regex(r"\d+ms", |lex| lex.slice()[..lex.slice().len()-2].parse().ok())]
     Milliseconds(u64),
     
-    #[regex(r"\d+s", |lex| lex.slice()[..lex.slice().len()-1].parse())]
+    #[regex(r"\d+s", |lex| lex.slice()[..lex.slice().len()-1].parse().ok())]
     Seconds(u64),
-    
-    // Comments and whitespace
-    #[regex(r"//[^\n]*", logos::skip)]
-    #[regex(r"/\*([^*]|\*[^/])*\*/", logos::skip)]
-    #[regex(r"[ \t\n\r]+", logos::skip)]
-    #[error]
-    Error,
 }
 
 impl Token {
@@ -327,7 +336,7 @@ impl Token {
         matches!(self,
             Token::Consensus | Token::Replicated | Token::Distributed |
             Token::Node | Token::Cluster | Token::Byzantine |
-            Token::Atomic | Token::Transaction | Token::Phase |
+            Token::Atomic | Token::Transaction | Token::Phase | Token::Abort |
             Token::View | Token::Leader | Token::Follower |
             Token::Candidate | Token::Network | Token::Peer |
             Token::CRDT
@@ -0,0 +1,523 @@
+/*!
+ * OMNIX Type Checker v0.1 MVP
+ * Infers expression types and checks them against declared state, parameter and return types
+ */
+
+use std::collections::HashMap;
+use std::fmt;
+use crate::ast::*;
+use crate::error::{CompilerResult, DiagnosticCollector, ErrorKind, Span};
+use crate::resolver::{Resolution, SymbolKind};
+
+/// Type of an expression as far as the checker can tell
+#[derive(Debug, Clone, PartialEq)]
+enum Ty {
+    Known(Type),
+    /// Unsuffixed integer literal, usable as any integer type
+    Integer,
+    /// Not inferable in the MVP (consensus results, method calls, custom types)
+    Unknown,
+}
+
+impl Ty {
+    fn is_numeric(&self) -> bool {
+        matches!(self, Ty::Integer | Ty::Known(Type::U64 | Type::I64 | Type::F64))
+    }
+}
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ty::Known(type_) => write!(f, "{}", type_),
+            Ty::Integer => write!(f, "integer"),
+            Ty::Unknown => write!(f, "_"),
+        }
+    }
+}
+
+impl From<&Type> for Ty {
+    fn from(type_: &Type) -> Self {
+        match type_ {
+            // Custom types have no declarations yet, so they check as anything
+            Type::Custom(_) => Ty::Unknown,
+            other => Ty::Known(other.clone()),
+        }
+    }
+}
+
+struct Signature<'a> {
+    params: &'a [Parameter],
+    return_type: Option<&'a Type>,
+}
+
+pub struct TypeChecker<'a> {
+    resolution: &'a Resolution,
+    // Function and service signatures, keyed by declaration span start
+    signatures: HashMap<usize, Signature<'a>>,
+    // Inferred `let` types, keyed by declaration span start
+    locals: HashMap<usize, Ty>,
+    return_type: Option<&'a Type>,
+    diagnostics: DiagnosticCollector,
+}
+
+impl<'a> TypeChecker<'a> {
+    pub fn new(resolution: &'a Resolution) -> Self {
+        Self {
+            resolution,
+            signatures: HashMap::new(),
+            locals: HashMap::new(),
+            return_type: None,
+            diagnostics: DiagnosticCollector::new(),
+        }
+    }
+
+    pub fn check(mut self, program: &'a Program) -> CompilerResult<()> {
+        self.collect_signatures(program);
+
+        for item in &program.items {
+            match item {
+                Item::Node(node) => {
+                    for node_item in &node.items {
+                        match node_item {
+                            NodeItem::State(state) => self.check_state(state),
+                            NodeItem::Function(func) => self.check_body(func.return_type.as_ref(), &func.body),
+                            NodeItem::EventHandler(handler) => self.check_body(None, &handler.body),
+                        }
+                    }
+                }
+                Item::Cluster(cluster) => {
+                    for cluster_item in &cluster.items {
+                        match cluster_item {
+                            ClusterItem::State(state) => self.check_state(state),
+                            ClusterItem::Service(service) => self.check_body(service.return_type.as_ref(), &service.body),
+                        }
+                    }
+                }
+                Item::Function(func) => self.check_body(func.return_type.as_ref(), &func.body),
+            }
+        }
+
+        self.diagnostics.into_result(())
+    }
+
+    fn collect_signatures(&mut self, program: &'a Program) {
+        for item in &program.items {
+            match item {
+                Item::Node(node) => {
+                    for node_item in &node.items {
+                        if let NodeItem::Function(func) = node_item {
+                            self.add_signature(&func.span, &func.params, func.return_type.as_ref());
+                        }
+                    }
+                }
+                Item::Cluster(cluster) => {
+                    for cluster_item in &cluster.items {
+                        if let ClusterItem::Service(service) = cluster_item {
+                            self.add_signature(&service.span, &service.params, service.return_type.as_ref());
+                        }
+                    }
+                }
+                Item::Function(func) => self.add_signature(&func.span, &func.params, func.return_type.as_ref()),
+            }
+        }
+    }
+
+    fn add_signature(&mut self, span: &Span, params: &'a [Parameter], return_type: Option<&'a Type>) {
+        self.signatures.insert(span.start, Signature { params, return_type });
+    }
+
+    fn check_state(&mut self, state: &StateVariable) {
+        if let Some(value) = &state.initial_value {
+            let found = self.infer(value);
+            self.expect(&state.type_, &found, &value.span());
+        }
+    }
+
+    fn check_body(&mut self, return_type: Option<&'a Type>, body: &Block) {
+        self.return_type = return_type;
+        self.check_block(body);
+        self.return_type = None;
+    }
+
+    fn check_block(&mut self, block: &Block) {
+        for statement in &block.statements {
+            self.check_statement(statement);
+        }
+    }
+
+    fn check_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Let(let_stmt) => {
+                let ty = self.infer(&let_stmt.value);
+                self.locals.insert(let_stmt.span.start, ty);
+            }
+            Statement::Assignment(assignment) => {
+                let target = self.infer(&assignment.target.to_expression());
+                let value = self.infer(&assignment.value);
+                self.check_assignment(&assignment.op, &target, &value, &assignment.value.span());
+            }
+            Statement::Expression(expr) => {
+                self.infer(expr);
+            }
+            Statement::When(when_stmt) => {
+                self.check_condition(&when_stmt.condition);
+                self.check_block(&when_stmt.body);
+                if let Some(else_body) = &when_stmt.else_body {
+                    self.check_block(else_body);
+                }
+            }
+            Statement::If(if_stmt) => {
+                self.check_condition(&if_stmt.condition);
+                self.check_block(&if_stmt.then_body);
+                if let Some(else_body) = &if_stmt.else_body {
+                    self.check_block(else_body);
+                }
+            }
+            Statement::For(for_stmt) => {
+                let element_types = self.iteration_types(&for_stmt.iterable, for_stmt.bindings.len());
+                for (binding, ty) in for_stmt.bindings.iter().zip(element_types) {
+                    self.locals.insert(binding.span.start, ty);
+                }
+                self.check_block(&for_stmt.body);
+            }
+            Statement::While(while_stmt) => {
+                self.check_condition(&while_stmt.condition);
+                self.check_block(&while_stmt.body);
+            }
+            Statement::Break(_) | Statement::Continue(_) => {}
+            Statement::Phase(phase) => {
+                for option in &phase.options {
+                    self.infer(&option.value);
+                }
+                self.check_block(&phase.body);
+                if let Some(abort) = &phase.abort {
+                    self.check_block(abort);
+                }
+            }
+            Statement::Return(expr, span) => self.check_return(expr.as_ref(), span),
+            Statement::Broadcast(broadcast) => match &broadcast.args {
+                EventArgs::Positional(args) => {
+                    for arg in args {
+                        self.infer(arg);
+                    }
+                }
+                EventArgs::Named(fields) => {
+                    for field in fields {
+                        self.infer(&field.value);
+                    }
+                }
+            },
+        }
+    }
+
+    fn check_condition(&mut self, condition: &Expression) {
+        let found = self.infer(condition);
+        self.expect(&Type::Bool, &found, &condition.span());
+    }
+
+    /// Types bound by a `for` loop with `count` variables over `iterable`
+    fn iteration_types(&mut self, iterable: &Expression, count: usize) -> Vec<Ty> {
+        // A single variable binds list elements, but map keys
+        match self.infer(iterable) {
+            Ty::Known(Type::Vec(element)) | Ty::Known(Type::Set(element)) => {
+                let element = Ty::from(element.as_ref());
+                if count == 1 { vec![element] } else { vec![Ty::Known(Type::U64), element] }
+            }
+            Ty::Known(Type::Map(key, value)) => vec![Ty::from(key.as_ref()), Ty::from(value.as_ref())],
+            Ty::Unknown => vec![Ty::Unknown; count],
+            other => {
+                self.diagnostics.error(
+                    ErrorKind::TypeMismatch { expected: "Vec, Set or Map".to_string(), found: other.to_string() },
+                    format!("Cannot iterate over a value of type {}", other),
+                    iterable.span(),
+                );
+                vec![Ty::Unknown; count]
+            }
+        }
+    }
+
+    fn check_return(&mut self, expr: Option<&Expression>, span: &Span) {
+        match (self.return_type, expr) {
+            (Some(expected), Some(expr)) => {
+                let found = self.infer(expr);
+                self.expect(expected, &found, &expr.span());
+            }
+            (Some(Type::Unit), None) => {}
+            (Some(expected), None) => {
+                self.mismatch(expected.to_string(), "()".to_string(), span);
+            }
+            // Functions without a declared return type are not checked
+            (None, Some(expr)) => {
+                self.infer(expr);
+            }
+            (None, None) => {}
+        }
+    }
+
+    fn check_assignment(&mut self, op: &AssignmentOp, target: &Ty, value: &Ty, span: &Span) {
+        match op {
+            // A merge replicates a value of the target's own type
+            AssignmentOp::Assign | AssignmentOp::Merge => {
+                if Self::unify(target, value).is_none() {
+                    self.mismatch(target.to_string(), value.to_string(), span);
+                }
+            }
+            AssignmentOp::AddAssign | AssignmentOp::SubAssign => {
+                let binary_op = if matches!(op, AssignmentOp::AddAssign) { BinaryOp::Add } else { BinaryOp::Sub };
+                self.binary_result(&binary_op, target, value, span);
+            }
+        }
+    }
+
+    fn infer(&mut self, expr: &Expression) -> Ty {
+        match expr {
+            Expression::Literal(literal, _) => match literal {
+                Literal::Integer(_) => Ty::Integer,
+                Literal::UInteger(_) => Ty::Known(Type::U64),
+                Literal::Float(_) => Ty::Known(Type::F64),
+                Literal::String(_) => Ty::Known(Type::String),
+                Literal::Boolean(_) => Ty::Known(Type::Bool),
+                Literal::Duration(_) => Ty::Known(Type::Duration),
+            },
+            Expression::Identifier(_, span) => self.symbol_type(span),
+            Expression::Binary(binary) => {
+                let left = self.infer(&binary.left);
+                let right = self.infer(&binary.right);
+                self.binary_result(&binary.op, &left, &right, &binary.span)
+            }
+            Expression::Unary(unary) => {
+                let operand = self.infer(&unary.operand);
+                self.unary_result(&unary.op, &operand, &unary.span)
+            }
+            Expression::Call(call) => self.check_call(call),
+            Expression::MethodCall(call) => {
+                self.infer(&call.receiver);
+                for arg in &call.args {
+                    self.infer(arg);
+                }
+                Ty::Unknown
+            }
+            Expression::Member(member) => {
+                self.infer(&member.object);
+                Ty::Unknown
+            }
+            Expression::Index(index) => {
+                let container = self.infer(&index.array);
+                let key = self.infer(&index.index);
+                match container {
+                    Ty::Known(Type::Vec(element)) => {
+                        self.expect(&Type::U64, &key, &index.index.span());
+                        Ty::from(element.as_ref())
+                    }
+                    Ty::Known(Type::Map(key_type, value_type)) => {
+                        self.expect(&key_type, &key, &index.index.span());
+                        Ty::from(value_type.as_ref())
+                    }
+                    Ty::Known(other) => {
+                        self.diagnostics.error(
+                            ErrorKind::TypeMismatch { expected: "Vec or Map".to_string(), found: other.to_string() },
+                            format!("Cannot index into a value of type {}", other),
+                            index.array.span(),
+                        );
+                        Ty::Unknown
+                    }
+                    _ => Ty::Unknown,
+                }
+            }
+            Expression::Proposal(proposal) => {
+                self.infer(&proposal.value);
+                if let Some(config) = &proposal.config_value {
+                    self.infer(config);
+                }
+                Ty::Unknown
+            }
+            Expression::Vote(vote) => {
+                self.infer(&vote.value);
+                if let Some(config) = &vote.config_value {
+                    self.infer(config);
+                }
+                Ty::Unknown
+            }
+            // These evaluate to their operand; `<|>` may substitute its fallback
+            Expression::Broadcast(distributed)
+            | Expression::Gossip(distributed)
+            | Expression::Sync(distributed)
+            | Expression::Quorum(distributed) => self.infer(&distributed.value),
+            Expression::Partition(distributed) => {
+                self.infer(&distributed.value);
+                Ty::Unknown
+            }
+            Expression::Array(elements, _) => {
+                let mut element_ty = Ty::Unknown;
+                for (i, element) in elements.iter().enumerate() {
+                    let ty = self.infer(element);
+                    if i == 0 {
+                        element_ty = ty;
+                    } else if let Some(unified) = Self::unify(&element_ty, &ty) {
+                        element_ty = unified;
+                    } else {
+                        self.mismatch(element_ty.to_string(), ty.to_string(), &element.span());
+                    }
+                }
+                match element_ty {
+                    Ty::Known(element) => Ty::Known(Type::Vec(Box::new(element))),
+                    Ty::Integer => Ty::Known(Type::Vec(Box::new(Type::I64))),
+                    Ty::Unknown => Ty::Unknown,
+                }
+            }
+            Expression::Object(fields, _) => {
+                for field in fields {
+                    self.infer(&field.value);
+                }
+                Ty::Unknown
+            }
+            Expression::Assignment(assignment) => {
+                let target = self.infer(&assignment.target);
+                let value = self.infer(&assignment.value);
+                self.check_assignment(&assignment.op, &target, &value, &assignment.value.span());
+                target
+            }
+        }
+    }
+
+    fn check_call(&mut self, call: &CallExpression) -> Ty {
+        let args: Vec<Ty> = call.args.iter().map(|arg| self.infer(arg)).collect();
+
+        let signature = self.resolution.binding(&call.span)
+            .filter(|symbol| matches!(symbol.kind, SymbolKind::Function | SymbolKind::Service))
+            .and_then(|symbol| self.signatures.get(&symbol.span.start));
+        let Some(signature) = signature else {
+            // Built-ins and constructors are checked by the runtime
+            return Ty::Unknown;
+        };
+        let params = signature.params;
+        let return_type = signature.return_type;
+
+        if params.len() != args.len() {
+            self.diagnostics.error(
+                ErrorKind::TypeMismatch {
+                    expected: format!("{} arguments", params.len()),
+                    found: format!("{} arguments", args.len()),
+                },
+                format!("'{}' takes {} arguments but {} were supplied", call.function, params.len(), args.len()),
+                call.span.clone(),
+            );
+        }
+        for ((param, arg), expr) in params.iter().zip(&args).zip(&call.args) {
+            self.expect(&param.type_, arg, &expr.span());
+        }
+
+        return_type.map(Ty::from).unwrap_or(Ty::Unknown)
+    }
+
+    fn symbol_type(&self, span: &Span) -> Ty {
+        match self.resolution.binding(span) {
+            Some(symbol) => match symbol.kind {
+                SymbolKind::State | SymbolKind::Parameter => {
+                    symbol.type_.as_ref().map(Ty::from).unwrap_or(Ty::Unknown)
+                }
+                SymbolKind::Local => self.locals.get(&symbol.span.start).cloned().unwrap_or(Ty::Unknown),
+                _ => Ty::Unknown,
+            },
+            None => Ty::Unknown,
+        }
+    }
+
+    fn binary_result(&mut self, op: &BinaryOp, left: &Ty, right: &Ty, span: &Span) -> Ty {
+        if *left == Ty::Unknown || *right == Ty::Unknown {
+            return match op {
+                BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod => Ty::Unknown,
+                _ => Ty::Known(Type::Bool),
+            };
+        }
+
+        let ok = match op {
+            // Concatenation formats the other operand
+            BinaryOp::Add if *left == Ty::Known(Type::String) || *right == Ty::Known(Type::String) => {
+                return Ty::Known(Type::String);
+            }
+            BinaryOp::Add | BinaryOp::Sub if *left == Ty::Known(Type::Duration) && *right == Ty::Known(Type::Duration) => {
+                return Ty::Known(Type::Duration);
+            }
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod => {
+                if left.is_numeric() && right.is_numeric() {
+                    if let Some(unified) = Self::unify(left, right) {
+                        return unified;
+                    }
+                }
+                false
+            }
+            BinaryOp::Eq | BinaryOp::Ne => Self::unify(left, right).is_some(),
+            BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+                let ordered = left.is_numeric() || matches!(left, Ty::Known(Type::String | Type::Duration));
+                ordered && Self::unify(left, right).is_some()
+            }
+            BinaryOp::And | BinaryOp::Or => {
+                *left == Ty::Known(Type::Bool) && *right == Ty::Known(Type::Bool)
+            }
+        };
+
+        if !ok {
+            self.diagnostics.error(
+                ErrorKind::TypeMismatch { expected: left.to_string(), found: right.to_string() },
+                format!("Operator {:?} cannot be applied to {} and {}", op, left, right),
+                span.clone(),
+            );
+        }
+
+        match op {
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod => Ty::Unknown,
+            _ => Ty::Known(Type::Bool),
+        }
+    }
+
+    fn unary_result(&mut self, op: &UnaryOp, operand: &Ty, span: &Span) -> Ty {
+        match (op, operand) {
+            (_, Ty::Unknown) => Ty::Unknown,
+            (UnaryOp::Not, Ty::Known(Type::Bool)) => Ty::Known(Type::Bool),
+            // Negating an unsigned value yields a signed one, as in the runtime
+            (UnaryOp::Neg, Ty::Known(Type::U64)) => Ty::Known(Type::I64),
+            // An integer literal takes the type of the u64 or i64 operand it meets, but the
+            // runtime has no negative u64, so a negated one is always signed
+            (UnaryOp::Neg, Ty::Integer) => Ty::Known(Type::I64),
+            (UnaryOp::Neg | UnaryOp::Plus, ty) if ty.is_numeric() => ty.clone(),
+            (UnaryOp::Not, ty) => {
+                self.mismatch("bool".to_string(), ty.to_string(), span);
+                Ty::Known(Type::Bool)
+            }
+            (_, ty) => {
+                self.mismatch("a number".to_string(), ty.to_string(), span);
+                Ty::Unknown
+            }
+        }
+    }
+
+    /// Common type of two operands, if they are compatible
+    fn unify(left: &Ty, right: &Ty) -> Option<Ty> {
+        match (left, right) {
+            (Ty::Unknown, _) | (_, Ty::Unknown) => Some(Ty::Unknown),
+            (Ty::Integer, Ty::Integer) => Some(Ty::Integer),
+            (Ty::Integer, Ty::Known(t)) | (Ty::Known(t), Ty::Integer) => {
+                matches!(t, Type::U64 | Type::I64).then(|| Ty::Known(t.clone()))
+            }
+            (Ty::Known(a), Ty::Known(b)) => (a == b).then(|| left.clone()),
+        }
+    }
+
+    fn expect(&mut self, expected: &Type, found: &Ty, span: &Span) {
+        let expected_ty = Ty::from(expected);
+        if Self::unify(&expected_ty, found).is_none() {
+            self.mismatch(expected.to_string(), found.to_string(), span);
+        }
+    }
+
+    fn mismatch(&mut self, expected: String, found: String, span: &Span) {
+        let message = format!("Expected {}, found {}", expected, found);
+        self.diagnostics.error(ErrorKind::TypeMismatch { expected, found }, message, span.clone());
+    }
+}
+
+// Convenience function
+pub fn check(program: &Program, resolution: &Resolution) -> CompilerResult<()> {
+    TypeChecker::new(resolution).check(program)
+}
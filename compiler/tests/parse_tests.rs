@@ -5,6 +5,7 @@
 use omnix_compiler::lexer::tokenize;
 use omnix_compiler::parser::parse;
 use omnix_compiler::ast::*;
+use omnix_compiler::pratt::LValue;
 
 #[test]
 fn test_parse_simple_node() {
@@ -184,4 +185,668 @@ node TestNode {
         }
         _ => panic!("Expected node definition")
     }
-}
\ No newline at end of file
+}
+
+fn parse_let_value(source: &str) -> Expression {
+    let tokens = tokenize(source).expect("Tokenization should succeed");
+    let program = parse(tokens).expect("Parsing should succeed");
+    
+    match &program.items[0] {
+        Item::Function(func) => match &func.body.statements[0] {
+            Statement::Let(let_stmt) => let_stmt.value.clone(),
+            _ => panic!("Expected let statement")
+        },
+        _ => panic!("Expected function definition")
+    }
+}
+
+#[test]
+fn test_parse_operator_precedence() {
+    let value = parse_let_value("function test() { let x = a + b * c % d; }");
+    
+    match value {
+        Expression::Binary(add) => {
+            assert!(matches!(add.op, BinaryOp::Add));
+            assert!(matches!(*add.left, Expression::Identifier(ref name, _) if name == "a"));
+            match *add.right {
+                Expression::Binary(rem) => {
+                    assert!(matches!(rem.op, BinaryOp::Mod));
+                    assert!(matches!(*rem.left, Expression::Binary(ref mul) if matches!(mul.op, BinaryOp::Mul)));
+                }
+                _ => panic!("Expected multiplicative expression on the right")
+            }
+        }
+        _ => panic!("Expected binary expression")
+    }
+}
+
+#[test]
+fn test_parse_logical_precedence() {
+    let value = parse_let_value("function test() { let x = a || x == y && !z; }");
+    
+    match value {
+        Expression::Binary(or) => {
+            assert!(matches!(or.op, BinaryOp::Or));
+            match *or.right {
+                Expression::Binary(and) => {
+                    assert!(matches!(and.op, BinaryOp::And));
+                    assert!(matches!(*and.left, Expression::Binary(ref eq) if matches!(eq.op, BinaryOp::Eq)));
+                    assert!(matches!(*and.right, Expression::Unary(ref not) if matches!(not.op, UnaryOp::Not)));
+                }
+                _ => panic!("Expected && on the right of ||")
+            }
+        }
+        _ => panic!("Expected binary expression")
+    }
+}
+
+#[test]
+fn test_parse_left_associativity() {
+    let value = parse_let_value("function test() { let x = a - b - c; }");
+    
+    match value {
+        Expression::Binary(outer) => {
+            assert!(matches!(outer.op, BinaryOp::Sub));
+            assert!(matches!(*outer.left, Expression::Binary(_)));
+            assert!(matches!(*outer.right, Expression::Identifier(ref name, _) if name == "c"));
+        }
+        _ => panic!("Expected binary expression")
+    }
+}
+
+#[test]
+fn test_parse_unary_and_postfix() {
+    let value = parse_let_value("function test() { let x = -store.items[i] * +y; }");
+    
+    match value {
+        Expression::Binary(mul) => {
+            assert!(matches!(mul.op, BinaryOp::Mul));
+            match *mul.left {
+                Expression::Unary(neg) => {
+                    assert!(matches!(neg.op, UnaryOp::Neg));
+                    match *neg.operand {
+                        Expression::Index(index) => {
+                            assert!(matches!(*index.array, Expression::Member(ref m) if m.field == "items"));
+                        }
+                        _ => panic!("Expected index expression under negation")
+                    }
+                }
+                _ => panic!("Expected unary expression")
+            }
+            assert!(matches!(*mul.right, Expression::Unary(ref plus) if matches!(plus.op, UnaryOp::Plus)));
+        }
+        _ => panic!("Expected binary expression")
+    }
+}
+
+#[test]
+fn test_parse_method_call() {
+    let value = parse_let_value("function test() { let x = result.accepted() && ready(1, 2); }");
+    
+    match value {
+        Expression::Binary(and) => {
+            match *and.left {
+                Expression::MethodCall(call) => {
+                    assert_eq!(call.method, "accepted");
+                    assert!(call.args.is_empty());
+                }
+                _ => panic!("Expected method call")
+            }
+            assert!(matches!(*and.right, Expression::Call(ref call) if call.args.len() == 2));
+        }
+        _ => panic!("Expected binary expression")
+    }
+}
+
+#[test]
+fn test_parse_consensus_operator_binds_loosest() {
+    let value = parse_let_value("function test() { let x = a + 1 <!> { validators: 3 } <?> { timeout: 500 }; }");
+    
+    match value {
+        Expression::Vote(vote) => {
+            assert_eq!(vote.config.timeout, Some(500));
+            match *vote.value {
+                Expression::Proposal(proposal) => {
+                    assert!(matches!(*proposal.value, Expression::Binary(ref add) if matches!(add.op, BinaryOp::Add)));
+                }
+                _ => panic!("Expected proposal under vote")
+            }
+        }
+        _ => panic!("Expected vote expression")
+    }
+}
+
+#[test]
+fn test_parse_consensus_operators_are_right_associative() {
+    let value = parse_let_value("function test() { let x = a <!> b <?> c; }");
+    
+    match value {
+        Expression::Proposal(proposal) => {
+            assert!(matches!(*proposal.value, Expression::Identifier(ref name, _) if name == "a"));
+            match proposal.config_value.as_deref() {
+                Some(Expression::Vote(vote)) => {
+                    assert!(matches!(*vote.value, Expression::Identifier(ref name, _) if name == "b"));
+                    assert!(matches!(vote.config_value.as_deref(), Some(Expression::Identifier(name, _)) if name == "c"));
+                }
+                _ => panic!("Expected vote as the proposal's operand")
+            }
+        }
+        _ => panic!("Expected proposal expression")
+    }
+}
+
+#[test]
+fn test_parse_rich_assignment_targets() {
+    let source = r#"
+function test() {
+    a.b[c] = d;
+    store[key] <#> v;
+    count += 1;
+    count -= 2;
+}
+"#;
+
+    let tokens = tokenize(source).expect("Tokenization should succeed");
+    let program = parse(tokens).expect("Parsing should succeed");
+    
+    match &program.items[0] {
+        Item::Function(func) => {
+            let assignments: Vec<&Assignment> = func.body.statements.iter()
+                .map(|stmt| match stmt {
+                    Statement::Assignment(assignment) => assignment,
+                    _ => panic!("Expected assignment statement")
+                })
+                .collect();
+            
+            match &assignments[0].target {
+                LValue::Index { array, .. } => {
+                    assert!(matches!(**array, LValue::Member { ref field, .. } if field == "b"));
+                }
+                _ => panic!("Expected index target")
+            }
+            assert_eq!(assignments[0].target.root(), "a");
+            
+            assert!(matches!(assignments[1].target, LValue::Index { .. }));
+            assert!(matches!(assignments[1].op, AssignmentOp::Merge));
+            assert!(matches!(assignments[2].op, AssignmentOp::AddAssign));
+            assert!(matches!(assignments[3].op, AssignmentOp::SubAssign));
+        }
+        _ => panic!("Expected function definition")
+    }
+}
+
+#[test]
+fn test_parse_invalid_assignment_target() {
+    let source = r#"
+function test() {
+    compute() = 1;
+}
+"#;
+
+    let tokens = tokenize(source).expect("Tokenization should succeed");
+    let errors = parse(tokens).unwrap_err();
+    
+    assert!(errors.iter().any(|e| e.message == "Invalid assignment target"));
+}
+
+#[test]
+fn test_parse_spans_cover_whole_nodes() {
+    let source = "function test() {\n    let total = a + b * c;\n    return total;\n}\n";
+    
+    let tokens = tokenize(source).expect("Tokenization should succeed");
+    let program = parse(tokens).expect("Parsing should succeed");
+    let text = |span: &omnix_compiler::error::Span| &source[span.start..span.end];
+    
+    let func = match &program.items[0] {
+        Item::Function(func) => func,
+        _ => panic!("Expected function definition")
+    };
+    assert_eq!(text(&func.span), source.trim_end());
+    assert_eq!(text(&program.items[0].span()), source.trim_end());
+    
+    let let_stmt = &func.body.statements[0];
+    assert_eq!(text(&let_stmt.span()), "let total = a + b * c;");
+    assert_eq!(let_stmt.span().line, 2);
+    assert_eq!(let_stmt.span().column, 4);
+    
+    match let_stmt {
+        Statement::Let(stmt) => {
+            assert_eq!(text(&stmt.value.span()), "a + b * c");
+            match &stmt.value {
+                Expression::Binary(add) => {
+                    assert_eq!(text(&add.left.span()), "a");
+                    assert_eq!(text(&add.right.span()), "b * c");
+                }
+                _ => panic!("Expected binary expression")
+            }
+        }
+        _ => panic!("Expected let statement")
+    }
+    
+    assert_eq!(text(&func.body.statements[1].span()), "return total;");
+    assert_eq!(func.body.statements[1].span().line, 3);
+}
+
+#[test]
+fn test_parse_postfix_spans() {
+    let source = "function test() { let x = -(store.items[i]).len(); }";
+    
+    let tokens = tokenize(source).expect("Tokenization should succeed");
+    let program = parse(tokens).expect("Parsing should succeed");
+    let text = |span: omnix_compiler::error::Span| &source[span.start..span.end];
+    
+    match &program.items[0] {
+        Item::Function(func) => match &func.body.statements[0] {
+            Statement::Let(stmt) => {
+                assert_eq!(text(stmt.value.span()), "-(store.items[i]).len()");
+                match &stmt.value {
+                    Expression::Unary(neg) => match &*neg.operand {
+                        Expression::MethodCall(call) => {
+                            assert_eq!(text(call.span.clone()), "(store.items[i]).len()");
+                            assert_eq!(text(call.receiver.span()), "store.items[i]");
+                        }
+                        _ => panic!("Expected method call")
+                    },
+                    _ => panic!("Expected unary expression")
+                }
+            }
+            _ => panic!("Expected let statement")
+        },
+        _ => panic!("Expected function definition")
+    }
+}
+
+#[test]
+fn test_parse_generic_and_tuple_types() {
+    let source = r#"
+consensus cluster Store {
+    replicas: 3,
+    consensus: Raft,
+    state data: Map<String, Vec<Bytes>>;
+    state seen: Set<u64>;
+
+    service put(entries: Vec<(String, Bytes)>, limit: u64) -> Result<(), Error> {
+        return;
+    }
+
+    service get(key: String) -> Option<Bytes> {
+        return;
+    }
+}
+"#;
+
+    let tokens = tokenize(source).expect("Tokenization should succeed");
+    let program = parse(tokens).expect("Parsing should succeed");
+    let Item::Cluster(cluster) = &program.items[0] else { panic!("Expected cluster") };
+
+    let ClusterItem::State(data) = &cluster.items[0] else { panic!("Expected state") };
+    assert_eq!(data.type_, Type::Map(Box::new(Type::String), Box::new(Type::Vec(Box::new(Type::Bytes)))));
+    let ClusterItem::State(seen) = &cluster.items[1] else { panic!("Expected state") };
+    assert_eq!(seen.type_, Type::Set(Box::new(Type::U64)));
+
+    let ClusterItem::Service(put) = &cluster.items[2] else { panic!("Expected service") };
+    assert_eq!(put.params[0].type_, Type::Vec(Box::new(Type::Tuple(vec![Type::String, Type::Bytes]))));
+    assert_eq!(put.params[1].type_, Type::U64);
+    assert_eq!(put.return_type, Some(Type::Result(Box::new(Type::Unit), Box::new(Type::Custom("Error".to_string())))));
+    assert_eq!(put.return_type.as_ref().unwrap().to_string(), "Result<(), Error>");
+
+    let ClusterItem::Service(get) = &cluster.items[3] else { panic!("Expected service") };
+    assert_eq!(get.return_type, Some(Type::Option(Box::new(Type::Bytes))));
+}
+
+#[test]
+fn test_parse_generic_arity_error() {
+    let source = r#"
+function f(values: Map<String>) {
+}
+"#;
+
+    let tokens = tokenize(source).expect("Tokenization should succeed");
+    let errors = parse(tokens).expect_err("Parsing should fail");
+    assert!(errors[0].message.contains("'Map' expects 2 type argument(s), found 1"));
+}
+
+#[test]
+fn test_parse_float_and_duration_literals() {
+    let value = parse_let_value("function test() { let x = 1.5; }");
+    assert!(matches!(value, Expression::Literal(Literal::Float(f), _) if f == 1.5));
+    
+    let value = parse_let_value("function test() { let x = 250ms; }");
+    assert!(matches!(value, Expression::Literal(Literal::Duration(250), _)));
+    
+    let value = parse_let_value("function test() { let x = 3s; }");
+    assert!(matches!(value, Expression::Literal(Literal::Duration(3000), _)));
+}
+
+#[test]
+fn test_parse_collection_literals() {
+    let value = parse_let_value("function test() { let x = [1, [2, 3], ]; }");
+    match value {
+        Expression::Array(elements, span) => {
+            assert_eq!(elements.len(), 2);
+            assert!(matches!(&elements[1], Expression::Array(inner, _) if inner.len() == 2));
+            assert_eq!(span.start, 26);
+        }
+        other => panic!("Expected array literal, got {:?}", other)
+    }
+    
+    let value = parse_let_value(r#"function test() { let x = { op: "put", key: key, "raw key": [] }; }"#);
+    match value {
+        Expression::Object(fields, _) => {
+            let names: Vec<_> = fields.iter().map(|f| f.name.as_str()).collect();
+            assert_eq!(names, vec!["op", "key", "raw key"]);
+            assert!(matches!(&fields[1].value, Expression::Identifier(name, _) if name == "key"));
+        }
+        other => panic!("Expected object literal, got {:?}", other)
+    }
+    
+    let value = parse_let_value("function test() { let x = {}.len(); }");
+    assert!(matches!(value, Expression::MethodCall(_)));
+}
+
+#[test]
+fn test_parse_consensus_timeout_duration() {
+    let value = parse_let_value("function test() { let x = v <!> { timeout: 2s }; }");
+    match value {
+        Expression::Proposal(proposal) => assert_eq!(proposal.config.timeout, Some(2000)),
+        other => panic!("Expected proposal, got {:?}", other)
+    }
+}
+
+#[test]
+fn test_parse_vote_choice() {
+    let value = parse_let_value("function test() { let t = result <?> { vote: Vote::Reject, quorum: 2 }; }");
+    match value {
+        Expression::Vote(vote) => {
+            assert_eq!(vote.config.vote, Some(VoteChoice::Reject));
+            assert_eq!(vote.config.quorum, Some(2));
+        }
+        other => panic!("Expected vote, got {:?}", other)
+    }
+    
+    let tokens = tokenize("function test() { let t = result <?> { vote: Maybe }; }").expect("Tokenization should succeed");
+    let errors = parse(tokens).expect_err("Parsing should fail");
+    assert_eq!(errors[0].message, "Unknown vote 'Maybe', expected Accept, Reject or Abstain");
+}
+
+#[test]
+fn test_parse_consensus_config_keys() {
+    let value = parse_let_value("function test() { let x = v <!> { validators: 3, quorum: 2, algorithm: Consensus::Raft }; }");
+    match value {
+        Expression::Proposal(proposal) => {
+            assert_eq!(proposal.config.validators, Some(3));
+            assert_eq!(proposal.config.quorum, Some(2));
+            assert!(matches!(proposal.config.algorithm, Some(ConsensusAlgorithm::Raft)));
+        }
+        other => panic!("Expected proposal, got {:?}", other)
+    }
+}
+
+#[test]
+fn test_parse_hotstuff_algorithm() {
+    let value = parse_let_value("function test() { let x = v <!> { algorithm: Consensus::HotStuff }; }");
+    match value {
+        Expression::Proposal(proposal) => {
+            assert!(matches!(proposal.config.algorithm, Some(ConsensusAlgorithm::HotStuff)));
+        }
+        other => panic!("Expected proposal, got {:?}", other)
+    }
+
+    let tokens = tokenize("consensus cluster Bridge { replicas: 4 consensus: HotStuff }").expect("Tokenization should succeed");
+    let program = parse(tokens).expect("Parsing should succeed");
+    match &program.items[0] {
+        Item::Cluster(cluster) => assert!(matches!(cluster.consensus, ConsensusAlgorithm::HotStuff)),
+        other => panic!("Expected cluster, got {:?}", other)
+    }
+}
+
+fn parse_body(source: &str) -> Vec<Statement> {
+    let tokens = tokenize(source).expect("Tokenization should succeed");
+    let program = parse(tokens).expect("Parsing should succeed");
+    
+    match &program.items[0] {
+        Item::Function(func) => func.body.statements.clone(),
+        _ => panic!("Expected function definition")
+    }
+}
+
+#[test]
+fn test_parse_if_else_chain() {
+    let statements = parse_body(r#"
+function test() {
+    if a { x = 1; } else if b { x = 2; } else { x = 3; }
+    when ready { go(); } else { wait(); }
+}
+"#);
+    
+    let Statement::If(if_stmt) = &statements[0] else { panic!("Expected if") };
+    let else_body = if_stmt.else_body.as_ref().expect("Expected else branch");
+    let Statement::If(nested) = &else_body.statements[0] else { panic!("Expected else if") };
+    assert_eq!(else_body.span, nested.span);
+    assert!(matches!(&nested.condition, Expression::Identifier(name, _) if name == "b"));
+    assert_eq!(nested.else_body.as_ref().unwrap().statements.len(), 1);
+    
+    let Statement::When(when_stmt) = &statements[1] else { panic!("Expected when") };
+    assert_eq!(when_stmt.else_body.as_ref().unwrap().statements.len(), 1);
+}
+
+#[test]
+fn test_parse_loops() {
+    let statements = parse_body(r#"
+function test() {
+    for item in items {
+        if item == 0 { continue; }
+        total += item;
+    }
+    for (key, value) in entries { }
+    while total < 10 {
+        total += 1;
+        break;
+    }
+}
+"#);
+    
+    let Statement::For(single) = &statements[0] else { panic!("Expected for") };
+    assert_eq!(single.bindings.len(), 1);
+    assert_eq!(single.bindings[0].name, "item");
+    assert_eq!(single.body.statements.len(), 2);
+    
+    let Statement::For(pair) = &statements[1] else { panic!("Expected for") };
+    let names: Vec<_> = pair.bindings.iter().map(|b| b.name.as_str()).collect();
+    assert_eq!(names, vec!["key", "value"]);
+    
+    let Statement::While(while_stmt) = &statements[2] else { panic!("Expected while") };
+    assert!(matches!(while_stmt.body.statements[1], Statement::Break(_)));
+}
+
+#[test]
+fn test_parse_break_outside_loop() {
+    let tokens = tokenize("function test() { if done { break; } }").expect("Tokenization should succeed");
+    let errors = parse(tokens).expect_err("Parsing should fail");
+    assert_eq!(errors[0].message, "'break' outside of a loop");
+}
+
+#[test]
+fn test_parse_paths() {
+    let statements = parse_body(r#"
+function test() {
+    let cache = Map::new();
+    cache <#> Map::insert("k", Some(1));
+    let label = cache.get("k").unwrap_or(None);
+}
+"#);
+    
+    let Statement::Let(let_stmt) = &statements[0] else { panic!("Expected let") };
+    let Expression::Call(call) = &let_stmt.value else { panic!("Expected call") };
+    assert_eq!(call.function, "Map::new");
+    assert!(call.args.is_empty());
+    
+    let Statement::Assignment(assignment) = &statements[1] else { panic!("Expected assignment") };
+    let Expression::Call(insert) = &assignment.value else { panic!("Expected call") };
+    assert_eq!(insert.function, "Map::insert");
+    assert_eq!(insert.args.len(), 2);
+    
+    let Statement::Let(let_stmt) = &statements[2] else { panic!("Expected let") };
+    let Expression::MethodCall(unwrap) = &let_stmt.value else { panic!("Expected method call") };
+    assert_eq!(unwrap.method, "unwrap_or");
+}
+
+#[test]
+fn test_parse_distributed_operators() {
+    let statements = parse_body(r#"
+function test() {
+    let a = update <~> { };
+    let b = query_replicas(key) <@> { nodes: 3, timeout: 1000ms, consistency: Quorum };
+    let c = counter <=> { timeout: 1s };
+    let d = counter <|> { fallback: 0 };
+    let e = counter + 1 <*> { quorum: 2 };
+}
+"#);
+    
+    let values: Vec<&Expression> = statements.iter()
+        .map(|statement| match statement {
+            Statement::Let(let_stmt) => &let_stmt.value,
+            other => panic!("Expected let, got {:?}", other)
+        })
+        .collect();
+    
+    assert!(matches!(values[0], Expression::Broadcast(d) if d.options.is_empty()));
+    match values[1] {
+        Expression::Gossip(gossip) => {
+            let names: Vec<_> = gossip.options.iter().map(|o| o.name.as_str()).collect();
+            assert_eq!(names, vec!["nodes", "timeout", "consistency"]);
+            assert!(matches!(gossip.option("consistency"), Some(Expression::Identifier(name, _)) if name == "Quorum"));
+            assert!(matches!(*gossip.value, Expression::Call(_)));
+        }
+        other => panic!("Expected gossip, got {:?}", other)
+    }
+    assert!(matches!(values[2], Expression::Sync(_)));
+    assert!(matches!(values[3], Expression::Partition(d) if d.option("fallback").is_some()));
+    match values[4] {
+        Expression::Quorum(quorum) => {
+            assert!(quorum.option("quorum").is_some());
+            assert!(matches!(*quorum.value, Expression::Binary(_)));
+        }
+        other => panic!("Expected quorum read, got {:?}", other)
+    }
+}
+
+#[test]
+fn test_parse_phases() {
+    let statements = parse_body(r#"
+function test() {
+    phase prepare {
+        let x = 1;
+    }
+    phase commit(timeout: 500ms, participants: 3) {
+        counter <#> 1;
+    } abort {
+        return false;
+    }
+}
+"#);
+    
+    match &statements[0] {
+        Statement::Phase(phase) => {
+            assert_eq!(phase.name, "prepare");
+            assert!(phase.options.is_empty());
+            assert_eq!(phase.body.statements.len(), 1);
+            assert!(phase.abort.is_none());
+        }
+        other => panic!("Expected phase, got {:?}", other)
+    }
+    match &statements[1] {
+        Statement::Phase(phase) => {
+            assert_eq!(phase.name, "commit");
+            assert!(matches!(phase.option("timeout"), Some(Expression::Literal(Literal::Duration(500), _))));
+            assert!(phase.option("participants").is_some());
+            assert!(matches!(phase.abort.as_ref().map(|abort| &abort.statements[..]), Some([Statement::Return(..)])));
+        }
+        other => panic!("Expected phase, got {:?}", other)
+    }
+}
+
+#[test]
+fn test_parse_abort_is_reserved() {
+    let source = r#"
+function test() {
+    phase prepare {
+        let x = 1;
+    }
+    let abort = 2;
+}
+"#;
+
+    let tokens = tokenize(source).expect("Tokenization should succeed");
+    let errors = parse(tokens).unwrap_err();
+    
+    assert!(errors.iter().any(|e| e.message == "Expected identifier"));
+}
+
+#[test]
+fn test_parse_broadcast_events() {
+    let statements = parse_body(r#"
+function test() {
+    broadcast(CounterUpdate(counter, node_id));
+    broadcast(KVUpdate { key: key, version: version + 1 });
+    broadcast(Shutdown);
+}
+"#);
+    
+    match &statements[0] {
+        Statement::Broadcast(broadcast) => {
+            assert_eq!(broadcast.event, "CounterUpdate");
+            assert!(matches!(&broadcast.args, EventArgs::Positional(args) if args.len() == 2));
+        }
+        other => panic!("Expected broadcast, got {:?}", other)
+    }
+    match &statements[1] {
+        Statement::Broadcast(broadcast) => {
+            assert_eq!(broadcast.event, "KVUpdate");
+            match &broadcast.args {
+                EventArgs::Named(fields) => {
+                    let names: Vec<_> = fields.iter().map(|f| f.name.as_str()).collect();
+                    assert_eq!(names, vec!["key", "version"]);
+                    assert!(matches!(fields[1].value, Expression::Binary(_)));
+                }
+                other => panic!("Expected named arguments, got {:?}", other)
+            }
+        }
+        other => panic!("Expected broadcast, got {:?}", other)
+    }
+    assert!(matches!(&statements[2], Statement::Broadcast(b) if matches!(&b.args, EventArgs::Positional(args) if args.is_empty())));
+}
+
+#[test]
+fn test_parse_event_handlers() {
+    let source = r#"
+node Replica {
+    on CounterUpdate(value: u64, origin: String) {
+        let v = value;
+    }
+    on partition_detected {
+        let x = 1;
+    }
+}
+"#;
+
+    let tokens = tokenize(source).expect("Tokenization should succeed");
+    let program = parse(tokens).expect("Parsing should succeed");
+    
+    match &program.items[0] {
+        Item::Node(node) => {
+            let handlers: Vec<_> = node.items.iter()
+                .filter_map(|item| match item {
+                    NodeItem::EventHandler(handler) => Some(handler),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(handlers.len(), 2);
+            assert_eq!(handlers[0].event_name, "CounterUpdate");
+            assert_eq!(handlers[0].params.len(), 2);
+            assert_eq!(handlers[1].event_name, "partition_detected");
+            assert!(handlers[1].params.is_empty());
+        }
+        _ => panic!("Expected node definition")
+    }
+}
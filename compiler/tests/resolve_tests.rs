@@ -0,0 +1,178 @@
+/*!
+ * Name resolution tests for OMNIX compiler
+ */
+
+use omnix_compiler::lexer::tokenize;
+use omnix_compiler::parser::parse;
+use omnix_compiler::resolver::{resolve, Resolution, SymbolKind};
+use omnix_compiler::error::{ErrorKind, Severity};
+use omnix_compiler::ast::*;
+
+fn resolve_source(source: &str) -> (Program, Resolution) {
+    let tokens = tokenize(source).expect("Tokenization should succeed");
+    let program = parse(tokens).expect("Parsing should succeed");
+    let resolution = resolve(&program);
+    (program, resolution)
+}
+
+#[test]
+fn test_resolve_binds_state_params_and_locals() {
+    let source = r#"
+node Counter {
+    state counter: String = "";
+
+    function add(amount: String) {
+        let next = counter + amount;
+        counter = next;
+    }
+}
+"#;
+
+    let (program, resolution) = resolve_source(source);
+    assert!(resolution.diagnostics.is_empty(), "{:?}", resolution.diagnostics);
+
+    let Item::Node(node) = &program.items[0] else { panic!("Expected node") };
+    let NodeItem::Function(func) = &node.items[1] else { panic!("Expected function") };
+
+    let Statement::Let(let_stmt) = &func.body.statements[0] else { panic!("Expected let") };
+    let Expression::Binary(sum) = &let_stmt.value else { panic!("Expected binary") };
+    let Expression::Identifier(_, counter_span) = sum.left.as_ref() else { panic!("Expected identifier") };
+    let Expression::Identifier(_, amount_span) = sum.right.as_ref() else { panic!("Expected identifier") };
+    assert_eq!(resolution.binding(counter_span).unwrap().kind, SymbolKind::State);
+    assert_eq!(resolution.binding(amount_span).unwrap().kind, SymbolKind::Parameter);
+
+    let Statement::Assignment(assignment) = &func.body.statements[1] else { panic!("Expected assignment") };
+    let Expression::Identifier(_, next_span) = &assignment.value else { panic!("Expected identifier") };
+    assert_eq!(resolution.binding(next_span).unwrap().kind, SymbolKind::Local);
+    assert_eq!(resolution.binding(&assignment.target.span()).unwrap().name, "counter");
+}
+
+#[test]
+fn test_resolve_reports_undeclared_names() {
+    let source = r#"
+function main() {
+    let a = missing + 1;
+    undefined_fn(a);
+    ghost = 2;
+    println(a);
+}
+"#;
+
+    let (_, resolution) = resolve_source(source);
+    let undeclared: Vec<_> = resolution.diagnostics.iter()
+        .filter_map(|d| match &d.kind {
+            ErrorKind::UndeclaredVariable(name) => Some(name.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(undeclared, vec!["missing", "undefined_fn", "ghost"]);
+    assert!(resolution.has_errors());
+}
+
+#[test]
+fn test_resolve_let_is_scoped_to_its_block() {
+    let source = r#"
+function main() {
+    when true {
+        let inner = 1;
+    }
+    return inner;
+}
+"#;
+
+    let (_, resolution) = resolve_source(source);
+    assert_eq!(resolution.diagnostics.len(), 1);
+    assert_eq!(resolution.diagnostics[0].kind, ErrorKind::UndeclaredVariable("inner".to_string()));
+    assert_eq!(resolution.diagnostics[0].span.line, 6);
+}
+
+#[test]
+fn test_resolve_duplicates_and_shadowing() {
+    let source = r#"
+node Store {
+    state value: String = "a";
+    state value: String = "b";
+
+    function update(value: String) {
+        let copy = value;
+        let copy = 2;
+    }
+}
+"#;
+
+    let (_, resolution) = resolve_source(source);
+    let kinds: Vec<_> = resolution.diagnostics.iter()
+        .map(|d| (d.severity, d.kind.clone()))
+        .collect();
+    assert_eq!(kinds, vec![
+        (Severity::Error, ErrorKind::DuplicateDefinition("value".to_string())),
+        (Severity::Warning, ErrorKind::ShadowedDefinition("value".to_string())),
+        (Severity::Error, ErrorKind::DuplicateDefinition("copy".to_string())),
+    ]);
+}
+
+#[test]
+fn test_resolve_items_are_visible_before_definition() {
+    let source = r#"
+function main() {
+    helper();
+}
+
+function helper() {
+    return 1;
+}
+"#;
+
+    let (_, resolution) = resolve_source(source);
+    assert!(resolution.diagnostics.is_empty(), "{:?}", resolution.diagnostics);
+}
+
+#[test]
+fn test_resolve_accepts_standard_library() {
+    let source = r#"
+function main() {
+    let counts = Map::new();
+    counts <#> Map::insert(generate_id(), now());
+    println(format("{} entries", counts.len()), hash("x"), None);
+}
+"#;
+
+    let (_, resolution) = resolve_source(source);
+    assert!(resolution.diagnostics.is_empty(), "{:?}", resolution.diagnostics);
+}
+
+#[test]
+fn test_resolve_distributed_operator_options() {
+    let source = r#"
+function main() {
+    let counter = 1;
+    let read = counter <@> { consistency: Quorum, fallback: missing };
+}
+"#;
+
+    let (_, resolution) = resolve_source(source);
+    let kinds: Vec<_> = resolution.diagnostics.iter().map(|d| d.kind.clone()).collect();
+    assert_eq!(kinds, vec![ErrorKind::UndeclaredVariable("missing".to_string())]);
+}
+
+#[test]
+fn test_resolve_broadcast_and_handlers() {
+    let source = r#"
+node Replica {
+    state counter: u64 = 0;
+
+    function bump() {
+        broadcast(CounterUpdate(counter));
+        broadcast(KVUpdate { key: missing });
+    }
+
+    on CounterUpdate(value: u64) {
+        counter = value;
+    }
+}
+"#;
+
+    let (_, resolution) = resolve_source(source);
+    let kinds: Vec<_> = resolution.diagnostics.iter().map(|d| d.kind.clone()).collect();
+    assert_eq!(kinds, vec![ErrorKind::UndeclaredVariable("missing".to_string())]);
+}
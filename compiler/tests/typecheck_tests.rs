@@ -0,0 +1,220 @@
+/*!
+ * Type checker tests for OMNIX compiler
+ */
+
+use omnix_compiler::lexer::tokenize;
+use omnix_compiler::parser::parse;
+use omnix_compiler::resolver::resolve;
+use omnix_compiler::typechecker::check;
+use omnix_compiler::error::{Diagnostic, ErrorKind};
+
+fn check_source(source: &str) -> Result<(), Vec<Diagnostic>> {
+    let tokens = tokenize(source).expect("Tokenization should succeed");
+    let program = parse(tokens).expect("Parsing should succeed");
+    let resolution = resolve(&program);
+    assert!(!resolution.has_errors(), "{:?}", resolution.diagnostics);
+    check(&program, &resolution)
+}
+
+fn mismatches(source: &str) -> Vec<(String, String)> {
+    check_source(source)
+        .expect_err("Type checking should fail")
+        .into_iter()
+        .map(|d| match d.kind {
+            ErrorKind::TypeMismatch { expected, found } => (expected, found),
+            other => panic!("Unexpected diagnostic {:?}", other),
+        })
+        .collect()
+}
+
+#[test]
+fn test_typecheck_accepts_consistent_program() {
+    let source = r#"
+node Greeter {
+    state greeting: String = "hello";
+
+    function greet(name: String) -> String {
+        let count = 1 + 2 * 3;
+        when count > 2 && !false {
+            greeting = greeting + " again";
+        }
+        return greeting + name;
+    }
+}
+"#;
+
+    check_source(source).expect("Type checking should succeed");
+}
+
+#[test]
+fn test_typecheck_state_initializer() {
+    let source = r#"
+node Store {
+    state label: String = 42;
+}
+"#;
+
+    assert_eq!(mismatches(source), vec![("String".to_string(), "integer".to_string())]);
+}
+
+#[test]
+fn test_typecheck_assignment_and_merge() {
+    let source = r#"
+node Store {
+    state label: String = "a";
+
+    function update() {
+        let count = 1;
+        label = true;
+        label <#> count;
+        count = "x";
+    }
+}
+"#;
+
+    assert_eq!(mismatches(source), vec![
+        ("String".to_string(), "bool".to_string()),
+        ("String".to_string(), "integer".to_string()),
+        ("integer".to_string(), "String".to_string()),
+    ]);
+}
+
+#[test]
+fn test_typecheck_operators_and_conditions() {
+    let source = r#"
+function main() {
+    let a = true && 1;
+    let b = -"text";
+    when 5 {
+        return;
+    }
+}
+"#;
+
+    let errors = check_source(source).expect_err("Type checking should fail");
+    assert_eq!(errors.len(), 3);
+    assert_eq!(errors[0].span.line, 3);
+    assert_eq!(errors[1].span.line, 4);
+    assert_eq!(errors[2].span.line, 5);
+}
+
+#[test]
+fn test_typecheck_return_type() {
+    let source = r#"
+function name() -> String {
+    return 1 == 1;
+}
+
+function missing() -> String {
+    return;
+}
+"#;
+
+    assert_eq!(mismatches(source), vec![
+        ("String".to_string(), "bool".to_string()),
+        ("String".to_string(), "()".to_string()),
+    ]);
+}
+
+#[test]
+fn test_typecheck_call_arguments() {
+    let source = r#"
+function greet(name: String, suffix: String) -> String {
+    return name + suffix;
+}
+
+function main() {
+    let ok = greet("a", "b") + "!";
+    greet(1, "b");
+    greet("a");
+}
+"#;
+
+    let errors = check_source(source).expect_err("Type checking should fail");
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].kind, ErrorKind::TypeMismatch { expected: "String".to_string(), found: "integer".to_string() });
+    assert!(errors[1].message.contains("takes 2 arguments but 1 were supplied"));
+}
+
+#[test]
+fn test_typecheck_numeric_and_collection_types() {
+    let source = r#"
+node Ledger {
+    state total: u64 = 0;
+    state balances: Map<String, u64>;
+    state history: Vec<i64>;
+
+    function credit(account: String, amount: u64) -> u64 {
+        total += amount;
+        balances[account] = balances[account] + amount;
+        history[0] = -amount;
+        return balances[1];
+    }
+
+    function ratio(value: f64) -> f64 {
+        return value + 1;
+    }
+}
+"#;
+
+    assert_eq!(mismatches(source), vec![
+        ("String".to_string(), "integer".to_string()),
+        ("f64".to_string(), "integer".to_string()),
+    ]);
+}
+
+#[test]
+fn test_typecheck_integer_literals_match_runtime() {
+    let source = r#"
+node Counter {
+    state count: u64;
+    state offset: i64 = -1;
+
+    function step() {
+        count += 1;
+        count = count * 2 - 1;
+        offset = offset + -2;
+        count = count + -1;
+    }
+}
+"#;
+
+    assert_eq!(mismatches(source), vec![
+        ("u64".to_string(), "i64".to_string()),
+    ]);
+}
+
+#[test]
+fn test_typecheck_control_flow() {
+    let source = r#"
+node Ledger {
+    state names: Vec<String>;
+    state balances: Map<String, u64>;
+
+    function audit() {
+        for name in names {
+            let upper = name + "!";
+        }
+        for (account, balance) in balances {
+            if balance > 100 {
+                let label = account + ": rich";
+            } else if balance {
+                continue;
+            }
+        }
+        for (i, name) in names {
+            while i {
+                break;
+            }
+        }
+        for x in 5 { }
+    }
+}
+"#;
+
+    assert_eq!(mismatches(source), vec![
+        ("bool".to_string(), "u64".to_string()),
+        ("bool".to_string(), "u64".to_string()),
+        ("Vec, Set or Map".to_string(), "integer".to_string()),
+    ]);
+}
@@ -1,245 +1,31 @@
 /*!
  * Consensus algorithms for OMNIX MVP
- * Currently only implements Raft consensus
+ * Picks the engine for the configured algorithm; Raft, PBFT, Tendermint and HotStuff live in their own modules
  */
 
-use crate::{ConsensusEngine, ConsensusConfig, ConsensusAlgorithm, ProposalId, Vote, NodeId};
+use crate::{ConsensusEngine, ConsensusConfig, ConsensusAlgorithm, NodeId};
+use crate::hotstuff::HotStuffNode;
+use crate::pbft::PbftNode;
 use crate::raft::RaftNode;
-use async_trait::async_trait;
+use crate::tendermint::TendermintNode;
 
 pub fn create_engine(config: ConsensusConfig, node_id: NodeId) -> anyhow::Result<Box<dyn ConsensusEngine>> {
     match config.algorithm {
         ConsensusAlgorithm::Raft => {
-            let raft_node = RaftNode::new(node_id);
+            let raft_node = RaftNode::with_config(node_id, config.raft)?;
             Ok(Box::new(raft_node))
         }
         ConsensusAlgorithm::PBFT => {
-            // For MVP, PBFT is not implemented
-            Err(anyhow::anyhow!("PBFT not implemented in MVP"))
+            let pbft_node = PbftNode::with_config(node_id, config.max_faulty, config.pbft)?;
+            Ok(Box::new(pbft_node))
         }
         ConsensusAlgorithm::Tendermint => {
-            // For MVP, Tendermint is not implemented
-            Err(anyhow::anyhow!("Tendermint not implemented in MVP"))
+            let tendermint_node = TendermintNode::with_config(node_id, config.timeout_ms, config.tendermint)?;
+            Ok(Box::new(tendermint_node))
         }
-    }
-}
-
-/// Raft consensus implementation
-pub struct RaftConsensus {
-    config: ConsensusConfig,
-    state: Arc<RwLock<RaftState>>,
-}
-
-struct RaftState {
-    term: u64,
-    voted_for: Option<String>,
-    log: Vec<LogEntry>,
-    role: RaftRole,
-}
-
-#[derive(Debug, Clone)]
-enum RaftRole {
-    Leader,
-    Follower,
-    Candidate,
-}
-
-#[derive(Debug, Clone)]
-struct LogEntry {
-    term: u64,
-    value: Vec<u8>,
-}
-
-impl RaftConsensus {
-    pub fn new(config: ConsensusConfig) -> anyhow::Result<Self> {
-        let state = Arc::new(RwLock::new(RaftState {
-            term: 0,
-            voted_for: None,
-            log: Vec::new(),
-            role: RaftRole::Follower,
-        }));
-        
-        Ok(Self { config, state })
-    }
-}
-
-#[async_trait]
-impl ConsensusEngine for RaftConsensus {
-    async fn start(&mut self) -> anyhow::Result<()> {
-        // Start Raft consensus protocol
-        Ok(())
-    }
-    
-    async fn propose(&self, value: Vec<u8>) -> anyhow::Result<ProposalId> {
-        let mut state = self.state.write().await;
-        
-        // Only leader can propose
-        match state.role {
-            RaftRole::Leader => {
-                let entry = LogEntry {
-                    term: state.term,
-                    value,
-                };
-                state.log.push(entry);
-                
-                let id = ProposalId(format!("raft_{}", uuid::Uuid::new_v4()));
-                Ok(id)
-            }
-            _ => Err(anyhow::anyhow!("Not the leader")),
-        }
-    }
-    
-    async fn vote(&self, _proposal_id: ProposalId, _vote: Vote) -> anyhow::Result<()> {
-        // Raft voting logic
-        Ok(())
-    }
-    
-    async fn on_commit(&self, _value: Vec<u8>) -> anyhow::Result<()> {
-        // Apply committed value to state machine
-        Ok(())
-    }
-}
-
-/// PBFT consensus implementation
-pub struct PBFTConsensus {
-    config: ConsensusConfig,
-    state: Arc<RwLock<PBFTState>>,
-}
-
-struct PBFTState {
-    view: u64,
-    phase: PBFTPhase,
-    proposals: HashMap<ProposalId, ProposalState>,
-}
-
-#[derive(Debug, Clone)]
-enum PBFTPhase {
-    PrePrepare,
-    Prepare,
-    Commit,
-}
-
-struct ProposalState {
-    value: Vec<u8>,
-    prepares: HashMap<String, Vote>,
-    commits: HashMap<String, Vote>,
-}
-
-impl PBFTConsensus {
-    pub fn new(config: ConsensusConfig) -> anyhow::Result<Self> {
-        let state = Arc::new(RwLock::new(PBFTState {
-            view: 0,
-            phase: PBFTPhase::PrePrepare,
-            proposals: HashMap::new(),
-        }));
-        
-        Ok(Self { config, state })
-    }
-}
-
-#[async_trait]
-impl ConsensusEngine for PBFTConsensus {
-    async fn start(&mut self) -> anyhow::Result<()> {
-        // Start PBFT consensus protocol
-        Ok(())
-    }
-    
-    async fn propose(&self, value: Vec<u8>) -> anyhow::Result<ProposalId> {
-        let mut state = self.state.write().await;
-        
-        let id = ProposalId(format!("pbft_{}", uuid::Uuid::new_v4()));
-        
-        state.proposals.insert(id.clone(), ProposalState {
-            value,
-            prepares: HashMap::new(),
-            commits: HashMap::new(),
-        });
-        
-        Ok(id)
-    }
-    
-    async fn vote(&self, proposal_id: ProposalId, vote: Vote) -> anyhow::Result<()> {
-        let mut state = self.state.write().await;
-        
-        if let Some(proposal) = state.proposals.get_mut(&proposal_id) {
-            match state.phase {
-                PBFTPhase::Prepare => {
-                    proposal.prepares.insert("node".to_string(), vote);
-                }
-                PBFTPhase::Commit => {
-                    proposal.commits.insert("node".to_string(), vote);
-                }
-                _ => {}
-            }
+        ConsensusAlgorithm::HotStuff => {
+            let hotstuff_node = HotStuffNode::with_config(node_id, config.max_faulty, config.hotstuff)?;
+            Ok(Box::new(hotstuff_node))
         }
-        
-        Ok(())
-    }
-    
-    async fn on_commit(&self, _value: Vec<u8>) -> anyhow::Result<()> {
-        // Apply committed value
-        Ok(())
     }
 }
-
-/// Tendermint consensus implementation
-pub struct TendermintConsensus {
-    config: ConsensusConfig,
-    state: Arc<RwLock<TendermintState>>,
-}
-
-struct TendermintState {
-    height: u64,
-    round: u32,
-    step: TendermintStep,
-}
-
-#[derive(Debug, Clone)]
-enum TendermintStep {
-    Propose,
-    Prevote,
-    Precommit,
-    Commit,
-}
-
-impl TendermintConsensus {
-    pub fn new(config: ConsensusConfig) -> anyhow::Result<Self> {
-        let state = Arc::new(RwLock::new(TendermintState {
-            height: 0,
-            round: 0,
-            step: TendermintStep::Propose,
-        }));
-        
-        Ok(Self { config, state })
-    }
-}
-
-#[async_trait]
-impl ConsensusEngine for TendermintConsensus {
-    async fn start(&mut self) -> anyhow::Result<()> {
-        // Start Tendermint consensus protocol
-        Ok(())
-    }
-    
-    async fn propose(&self, value: Vec<u8>) -> anyhow::Result<ProposalId> {
-        let state = self.state.read().await;
-        
-        match state.step {
-            TendermintStep::Propose => {
-                let id = ProposalId(format!("tendermint_{}", uuid::Uuid::new_v4()));
-                Ok(id)
-            }
-            _ => Err(anyhow::anyhow!("Not in propose step")),
-        }
-    }
-    
-    async fn vote(&self, _proposal_id: ProposalId, _vote: Vote) -> anyhow::Result<()> {
-        // Tendermint voting logic
-        Ok(())
-    }
-    
-    async fn on_commit(&self, _value: Vec<u8>) -> anyhow::Result<()> {
-        // Apply committed value
-        Ok(())
-    }
-}
\ No newline at end of file
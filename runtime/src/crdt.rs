@@ -159,7 +159,7 @@ impl<T: Clone + Eq + std::hash::Hash> ORSet<T> {
         
         self.elements
             .entry(element)
-            .or_insert_with(HashSet::new)
+            .or_default()
             .insert(tag);
     }
     
@@ -175,7 +175,7 @@ impl<T: Clone + Eq + std::hash::Hash> ORSet<T> {
         for (element, tags) in &other.elements {
             self.elements
                 .entry(element.clone())
-                .or_insert_with(HashSet::new)
+                .or_default()
                 .extend(tags.clone());
         }
     }
@@ -0,0 +1,833 @@
+/*!
+ * HotStuff consensus for OMNIX
+ * Chained BFT agreement with quorum certificates, votes sent only to the next leader and a
+ * pacemaker that keeps replicas in step across views
+ */
+
+use crate::{ConsensusEngine, CommittedProposal, HotStuffConfig, NodeId, ProposalId, ReadConsistency, StateMachine, Vote, VoteTally};
+use crate::pbft::Request;
+use crate::raft::Membership;
+use crate::transport::{ConsensusTransport, TcpTransport};
+use async_trait::async_trait;
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch, Mutex, RwLock};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration, Instant};
+
+/// SHA-256 of a block
+pub type BlockHash = [u8; 32];
+
+/// Most requests a leader puts in one block
+const MAX_BLOCK_REQUESTS: usize = 1024;
+
+/// Most messages held while the blocks they build on are fetched
+const MAX_ORPHANS: usize = 1024;
+
+/// Views each leader holds in a row. A block executes once the three views from it onwards
+/// are certified, so a correct leader holding four views finishes blocks without relying on
+/// the leaders either side of it.
+pub const VIEWS_PER_LEADER: u64 = 4;
+
+/// Votes from a quorum for one block, proving a quorum accepted it in `view`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuorumCert {
+    pub view: u64,
+    pub block: BlockHash,
+    /// Each signer's signature over its `Vote`
+    pub votes: Vec<(NodeId, Vec<u8>)>,
+}
+
+/// One link of the chain. A block extends the block its `justify` certifies, so every
+/// proposal also carries the certificate for the one before it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Block {
+    pub view: u64,
+    pub parent: BlockHash,
+    pub justify: QuorumCert,
+    /// Signed `Request` messages, in execution order
+    pub requests: Vec<HotStuffMessage>,
+}
+
+impl Block {
+    /// The block every chain starts from, certified by definition
+    pub fn genesis() -> Self {
+        Self {
+            view: 0,
+            parent: [0; 32],
+            justify: QuorumCert {
+                view: 0,
+                block: [0; 32],
+                votes: Vec::new(),
+            },
+            requests: Vec::new(),
+        }
+    }
+
+    pub fn hash(&self) -> BlockHash {
+        Sha256::digest(bincode::serialize(self).expect("HotStuff blocks always serialize")).into()
+    }
+}
+
+/// A signed message. Requests inside blocks keep their original signatures, so any replica
+/// can check them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HotStuffMessage {
+    pub from: NodeId,
+    pub payload: HotStuffPayload,
+    pub signature: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum HotStuffPayload {
+    /// Sent by the origin to every replica, so whichever leads next includes it
+    Request(Request),
+    /// The leader of `block.view` extends the chain
+    Proposal {
+        block: Block,
+    },
+    /// Sent only to the next view's leader, which combines a quorum of them into a certificate
+    Vote {
+        view: u64,
+        block: BlockHash,
+    },
+    /// Sent to the leader of `view` by a replica that gave up on the view before it, with
+    /// the highest certificate the replica knows
+    NewView {
+        view: u64,
+        high_qc: QuorumCert,
+    },
+    /// Asks for a block this replica is missing
+    FetchBlock {
+        hash: BlockHash,
+    },
+    BlockData {
+        block: Block,
+    },
+}
+
+/// Bytes a message's signature covers
+fn signed_bytes(from: &NodeId, payload: &HotStuffPayload) -> Vec<u8> {
+    bincode::serialize(&(from, payload)).expect("HotStuff payloads always serialize")
+}
+
+#[derive(Debug)]
+struct HotStuffState {
+    /// View the pacemaker is in
+    view: u64,
+    /// Highest view this replica has voted in; it votes at most once per view
+    voted_view: u64,
+    /// Certificate for the block this replica is locked on. It only votes for blocks that
+    /// extend it, or that carry a certificate from a later view.
+    locked_qc: QuorumCert,
+    /// Highest certificate known; the next proposal extends the block it certifies
+    high_qc: QuorumCert,
+    executed_block: BlockHash,
+    executed_view: u64,
+    /// Every block received, kept so replicas that fall behind can fetch them
+    blocks: HashMap<BlockHash, Block>,
+    /// Votes received as the leader of the view after theirs, by block
+    votes: HashMap<BlockHash, (u64, HashMap<NodeId, Vec<u8>>)>,
+    /// Replicas that sent this one, as leader, a new-view message for each view
+    new_views: BTreeMap<u64, HashSet<NodeId>>,
+    /// View this replica may propose in as leader, once it holds a certificate for the view
+    /// before it or a quorum has moved to it
+    ready: Option<u64>,
+    proposed_view: u64,
+    /// When this replica gives up on the current view, while the chain has work to finish
+    timer: Option<Instant>,
+    /// Views in a row that timed out; each waits twice as long as the last
+    failed_views: u32,
+    /// Requests received but not executed yet, in arrival order
+    pending: Vec<(String, HotStuffMessage)>,
+    executed: HashMap<String, CommittedProposal>,
+    last_executed: u64,
+    /// Size of the certificate for each executed block, by view
+    acks: HashMap<u64, usize>,
+    /// Messages waiting on a missing block, by its hash
+    orphans: HashMap<BlockHash, Vec<HotStuffMessage>>,
+}
+
+/// One HotStuff replica. Every replica accepts proposals and broadcasts them; the leader of
+/// each view extends the chain, and a block executes once three blocks from consecutive
+/// views are certified on top of one another starting from it.
+#[derive(Clone)]
+pub struct HotStuffNode {
+    pub node_id: NodeId,
+    /// Every replica, this one included, in the order leadership rotates
+    pub replicas: Vec<NodeId>,
+    /// Byzantine replicas tolerated
+    pub max_faulty: usize,
+    pub config: HotStuffConfig,
+    signing_key: Arc<SigningKey>,
+    public_keys: Arc<HashMap<NodeId, VerifyingKey>>,
+    // Changed as a whole by each message, so a single lock keeps it consistent
+    state: Arc<Mutex<HotStuffState>>,
+    // Signalled whenever blocks execute
+    pub progress: Arc<watch::Sender<u64>>,
+    // Application-level votes from `<?>`, one per replica and proposal
+    pub votes: Arc<RwLock<HashMap<String, HashMap<NodeId, Vote>>>>,
+    pub state_machine: Arc<RwLock<Option<Arc<dyn StateMachine>>>>,
+    pub transport: Arc<RwLock<Option<Arc<dyn ConsensusTransport<HotStuffMessage>>>>>,
+    pub tasks: Arc<Mutex<Vec<JoinHandle<()>>>>,
+    inbox: mpsc::UnboundedSender<HotStuffMessage>,
+    message_rx: Arc<Mutex<Option<mpsc::UnboundedReceiver<HotStuffMessage>>>>,
+    pub view_timeout: Duration,
+}
+
+impl HotStuffNode {
+    pub fn with_config(node_id: NodeId, max_faulty: u32, config: HotStuffConfig) -> anyhow::Result<Self> {
+        let max_faulty = max_faulty as usize;
+        let replica_count = config.peers.len() + 1;
+        if replica_count < 3 * max_faulty + 1 {
+            return Err(anyhow::anyhow!(
+                "HotStuff tolerating {} faulty replicas needs at least {}, but {} are configured",
+                max_faulty, 3 * max_faulty + 1, replica_count
+            ));
+        }
+
+        let signing_key = SigningKey::from_bytes(&config.secret_key.unwrap_or_else(rand::random));
+        let mut public_keys = HashMap::new();
+        for (peer, entry) in &config.peers {
+            let key = VerifyingKey::from_bytes(&entry.public_key)
+                .map_err(|e| anyhow::anyhow!("Invalid public key for HotStuff replica {}: {}", peer, e))?;
+            public_keys.insert(peer.clone(), key);
+        }
+        public_keys.insert(node_id.clone(), signing_key.verifying_key());
+
+        let mut replicas: Vec<NodeId> = public_keys.keys().cloned().collect();
+        replicas.sort();
+        let (inbox, message_rx) = mpsc::unbounded_channel();
+
+        let genesis = Block::genesis();
+        let genesis_hash = genesis.hash();
+        let genesis_qc = QuorumCert {
+            view: 0,
+            block: genesis_hash,
+            votes: Vec::new(),
+        };
+        let state = HotStuffState {
+            view: 1,
+            voted_view: 0,
+            locked_qc: genesis_qc.clone(),
+            high_qc: genesis_qc,
+            executed_block: genesis_hash,
+            executed_view: 0,
+            blocks: HashMap::from([(genesis_hash, genesis)]),
+            votes: HashMap::new(),
+            new_views: BTreeMap::new(),
+            // The genesis certificate lets the first leader start at once
+            ready: Some(1),
+            proposed_view: 0,
+            timer: None,
+            failed_views: 0,
+            pending: Vec::new(),
+            executed: HashMap::new(),
+            last_executed: 0,
+            acks: HashMap::new(),
+            orphans: HashMap::new(),
+        };
+
+        Ok(Self {
+            node_id,
+            replicas,
+            max_faulty,
+            signing_key: Arc::new(signing_key),
+            public_keys: Arc::new(public_keys),
+            state: Arc::new(Mutex::new(state)),
+            progress: Arc::new(watch::channel(0).0),
+            votes: Arc::new(RwLock::new(HashMap::new())),
+            state_machine: Arc::new(RwLock::new(None)),
+            transport: Arc::new(RwLock::new(None)),
+            tasks: Arc::new(Mutex::new(Vec::new())),
+            inbox,
+            message_rx: Arc::new(Mutex::new(Some(message_rx))),
+            view_timeout: Duration::from_millis(config.view_timeout_ms),
+            config,
+        })
+    }
+
+    /// Sender for messages addressed to this replica; transports deliver into it
+    pub fn inbox(&self) -> mpsc::UnboundedSender<HotStuffMessage> {
+        self.inbox.clone()
+    }
+
+    /// Route outgoing messages through `transport`
+    pub async fn set_transport(&self, transport: Arc<dyn ConsensusTransport<HotStuffMessage>>) {
+        *self.transport.write().await = Some(transport);
+    }
+
+    /// The replica that proposes in `view`
+    pub fn leader(&self, view: u64) -> &NodeId {
+        &self.replicas[(view / VIEWS_PER_LEADER % self.replicas.len() as u64) as usize]
+    }
+
+    /// Replicas a certificate needs: 2f + 1 when there are 3f + 1 replicas. Any two
+    /// quorums share at least one correct replica.
+    pub fn quorum(&self) -> usize {
+        (self.replicas.len() + self.max_faulty) / 2 + 1
+    }
+
+    pub async fn view(&self) -> u64 {
+        self.state.lock().await.view
+    }
+
+    /// Index of the last request executed here
+    pub async fn last_executed(&self) -> u64 {
+        self.state.lock().await.last_executed
+    }
+
+    pub async fn start_consensus_loop(&self) -> anyhow::Result<()> {
+        let rx = self.message_rx.lock().await.take()
+            .ok_or_else(|| anyhow::anyhow!("HotStuff replica {} already started", self.node_id))?;
+        let mut tasks = self.tasks.lock().await;
+        {
+            let hotstuff = self.clone();
+            tasks.push(tokio::spawn(async move {
+                hotstuff.message_handler_loop(rx).await;
+            }));
+        }
+        {
+            let hotstuff = self.clone();
+            tasks.push(tokio::spawn(async move {
+                hotstuff.pacemaker_loop().await;
+            }));
+        }
+        Ok(())
+    }
+
+    fn sign(&self, payload: HotStuffPayload) -> HotStuffMessage {
+        let signature = self.signing_key.sign(&signed_bytes(&self.node_id, &payload));
+        HotStuffMessage {
+            from: self.node_id.clone(),
+            payload,
+            signature: signature.to_bytes().to_vec(),
+        }
+    }
+
+    /// Whether `signature` is `from`'s signature over `payload`
+    fn verify_signature(&self, from: &NodeId, payload: &HotStuffPayload, signature: &[u8]) -> bool {
+        let Some(key) = self.public_keys.get(from) else {
+            return false;
+        };
+        let Ok(signature) = Signature::from_slice(signature) else {
+            return false;
+        };
+        key.verify_strict(&signed_bytes(from, payload), &signature).is_ok()
+    }
+
+    /// Whether `message` was signed by the replica it claims to come from
+    fn verify(&self, message: &HotStuffMessage) -> bool {
+        self.verify_signature(&message.from, &message.payload, &message.signature)
+    }
+
+    /// Whether a quorum of distinct replicas signed the certificate's vote; the genesis
+    /// certificate needs no signatures
+    fn verify_qc(&self, qc: &QuorumCert) -> bool {
+        if qc.view == 0 {
+            return qc.block == Block::genesis().hash() && qc.votes.is_empty();
+        }
+        let vote = HotStuffPayload::Vote { view: qc.view, block: qc.block };
+        let signers: HashSet<&NodeId> = qc.votes.iter()
+            .filter(|(signer, signature)| self.verify_signature(signer, &vote, signature))
+            .map(|(signer, _)| signer)
+            .collect();
+        signers.len() == qc.votes.len() && signers.len() >= self.quorum()
+    }
+
+    /// Sign `payload` and send it to every replica, this one included
+    async fn broadcast(&self, payload: HotStuffPayload) {
+        let message = self.sign(payload);
+        for peer in self.replicas.iter().filter(|replica| **replica != self.node_id) {
+            self.send(peer, message.clone()).await;
+        }
+        let _ = self.inbox.send(message);
+    }
+
+    /// Send to one replica, which may be this one
+    async fn deliver(&self, peer: &NodeId, message: HotStuffMessage) {
+        if *peer == self.node_id {
+            let _ = self.inbox.send(message);
+        } else {
+            self.send(peer, message).await;
+        }
+    }
+
+    /// Hand a message to the transport without waiting for delivery
+    async fn send(&self, peer: &NodeId, message: HotStuffMessage) {
+        let Some(transport) = self.transport.read().await.clone() else {
+            return;
+        };
+        let peer = peer.clone();
+        tokio::spawn(async move {
+            if let Err(e) = transport.send(&peer, message).await {
+                tracing::debug!("Failed to send HotStuff message to {}: {}", peer, e);
+            }
+        });
+    }
+
+    async fn message_handler_loop(&self, mut rx: mpsc::UnboundedReceiver<HotStuffMessage>) {
+
+        while let Some(message) = rx.recv().await {
+            self.handle_message(message).await;
+        }
+    }
+
+    /// Hand leadership to the next replica whenever the current view runs out of time, while
+    /// the chain has work to finish
+    async fn pacemaker_loop(&self) {
+        loop {
+            sleep(self.view_timeout / 10).await;
+
+            let mut state = self.state.lock().await;
+            if state.timer.is_some_and(|timer| Instant::now() >= timer) {
+                tracing::info!("{} gave up on view {}", self.node_id, state.view);
+                // A leader that let a view lapse loses the rest of its turn
+                state.view = (state.view / VIEWS_PER_LEADER + 1) * VIEWS_PER_LEADER;
+                state.failed_views += 1;
+                state.timer = Some(Instant::now() + self.view_timeout * 2u32.pow(state.failed_views.min(6)));
+                let new_view = self.sign(HotStuffPayload::NewView {
+                    view: state.view,
+                    high_qc: state.high_qc.clone(),
+                });
+                self.deliver(self.leader(state.view), new_view).await;
+            }
+        }
+    }
+
+    async fn handle_message(&self, message: HotStuffMessage) {
+        if !self.verify(&message) {
+            tracing::warn!("{} dropped a HotStuff message with a bad signature claiming to be from {}", self.node_id, message.from);
+            return;
+        }
+
+        let mut state = self.state.lock().await;
+        match &message.payload {
+            HotStuffPayload::Request(request) => self.handle_request(&mut state, &message, request).await,
+            HotStuffPayload::Proposal { block } => self.handle_proposal(&mut state, &message, block).await,
+            HotStuffPayload::Vote { view, block } => self.handle_vote(&mut state, &message, *view, *block).await,
+            HotStuffPayload::NewView { view, high_qc } => self.handle_new_view(&mut state, &message.from, *view, high_qc).await,
+            HotStuffPayload::FetchBlock { hash } => {
+                if let Some(block) = state.blocks.get(hash) {
+                    let reply = self.sign(HotStuffPayload::BlockData { block: block.clone() });
+                    self.send(&message.from, reply).await;
+                }
+            }
+            HotStuffPayload::BlockData { block } => {
+                // Only blocks this replica asked for, so the hash alone proves them genuine
+                let hash = block.hash();
+                if let Some(waiting) = state.orphans.remove(&hash) {
+                    state.blocks.insert(hash, block.clone());
+                    for message in waiting {
+                        let _ = self.inbox.send(message);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The request inside a signed request message, if its origin really sent it
+    fn valid_request(&self, message: &HotStuffMessage) -> Option<Request> {
+        match &message.payload {
+            HotStuffPayload::Request(request) if request.origin == message.from && self.verify(message) => Some(request.clone()),
+            _ => None,
+        }
+    }
+
+    async fn handle_request(&self, state: &mut HotStuffState, message: &HotStuffMessage, request: &Request) {
+        if request.origin != message.from {
+            return;
+        }
+        let key = request.key();
+        if state.executed.contains_key(&key) || state.pending.iter().any(|(pending, _)| *pending == key) {
+            return;
+        }
+        state.pending.push((key, message.clone()));
+        if state.timer.is_none() {
+            state.timer = Some(Instant::now() + self.view_timeout);
+        }
+        self.try_propose(state).await;
+    }
+
+    /// Blocks from `from` back to the last executed one, newest first; `None` if one of
+    /// them has not arrived
+    fn uncommitted<'a>(&self, state: &'a HotStuffState, from: BlockHash) -> Option<Vec<&'a Block>> {
+        let mut chain = Vec::new();
+        let mut current = from;
+        while current != state.executed_block {
+            let block = state.blocks.get(&current)?;
+            if block.view <= state.executed_view {
+                break;
+            }
+            chain.push(block);
+            current = block.parent;
+        }
+        Some(chain)
+    }
+
+    /// The first block missing between `from` and the last executed one
+    fn missing_ancestor(&self, state: &HotStuffState, from: BlockHash) -> Option<BlockHash> {
+        let mut current = from;
+        while current != state.executed_block {
+            let Some(block) = state.blocks.get(&current) else {
+                return Some(current);
+            };
+            if block.view <= state.executed_view {
+                return None;
+            }
+            current = block.parent;
+        }
+        None
+    }
+
+    /// Whether requests are waiting, or blocks with requests still need descendants to execute
+    fn needs_progress(&self, state: &HotStuffState) -> bool {
+        !state.pending.is_empty()
+            || self.uncommitted(state, state.high_qc.block)
+                .is_none_or(|chain| chain.iter().any(|block| !block.requests.is_empty()))
+    }
+
+    /// Give the current view a full timeout if the chain has work to finish
+    fn reset_timer(&self, state: &mut HotStuffState) {
+        state.failed_views = 0;
+        state.timer = self.needs_progress(state).then(|| Instant::now() + self.view_timeout);
+    }
+
+    fn update_high_qc(&self, state: &mut HotStuffState, qc: &QuorumCert) {
+        if qc.view > state.high_qc.view {
+            state.high_qc = qc.clone();
+        }
+    }
+
+    /// As leader of the current view, extend the highest certified block
+    async fn try_propose(&self, state: &mut HotStuffState) {
+        let view = state.view;
+        if *self.leader(view) != self.node_id || state.ready != Some(view) || state.proposed_view >= view || !self.needs_progress(state) {
+            return;
+        }
+        state.proposed_view = view;
+
+        let parent = state.high_qc.block;
+        let in_chain: HashSet<String> = self.uncommitted(state, parent).unwrap_or_default().iter()
+            .flat_map(|block| &block.requests)
+            .filter_map(|message| match &message.payload {
+                HotStuffPayload::Request(request) => Some(request.key()),
+                _ => None,
+            })
+            .collect();
+        let requests = state.pending.iter()
+            .filter(|(key, _)| !in_chain.contains(key))
+            .take(MAX_BLOCK_REQUESTS)
+            .map(|(_, message)| message.clone())
+            .collect();
+        tracing::debug!("{} proposing in view {}", self.node_id, view);
+        self.broadcast(HotStuffPayload::Proposal {
+            block: Block {
+                view,
+                parent,
+                justify: state.high_qc.clone(),
+                requests,
+            },
+        }).await;
+    }
+
+    async fn handle_proposal(&self, state: &mut HotStuffState, message: &HotStuffMessage, block: &Block) {
+        if message.from != *self.leader(block.view) || block.parent != block.justify.block || block.view <= block.justify.view {
+            return;
+        }
+        if block.requests.len() > MAX_BLOCK_REQUESTS || !block.requests.iter().all(|request| self.valid_request(request).is_some()) {
+            return;
+        }
+        if !self.verify_qc(&block.justify) {
+            tracing::warn!("{} ignored a proposal with an invalid certificate from {}", self.node_id, message.from);
+            return;
+        }
+        if let Some(missing) = self.missing_ancestor(state, block.parent) {
+            let held: usize = state.orphans.values().map(Vec::len).sum();
+            if held < MAX_ORPHANS {
+                let waiting = state.orphans.entry(missing).or_default();
+                if waiting.is_empty() {
+                    let request = self.sign(HotStuffPayload::FetchBlock { hash: missing });
+                    self.send(&message.from, request).await;
+                }
+                waiting.push(message.clone());
+            }
+            return;
+        }
+
+        let hash = block.hash();
+        state.blocks.insert(hash, block.clone());
+        self.update_high_qc(state, &block.justify);
+
+        // Vote once per view, for a block that extends the lock or carries a newer certificate
+        let extends_lock = self.uncommitted(state, hash).is_some_and(|chain| {
+            chain.iter().any(|ancestor| ancestor.hash() == state.locked_qc.block)
+        }) || state.locked_qc.block == state.executed_block;
+        let safe = extends_lock || block.justify.view > state.locked_qc.view;
+        if block.view > state.voted_view && block.view >= state.view && safe {
+            state.voted_view = block.view;
+            state.view = block.view + 1;
+            let vote = self.sign(HotStuffPayload::Vote { view: block.view, block: hash });
+            self.deliver(self.leader(block.view + 1), vote).await;
+        }
+
+        self.update(state, block).await;
+        self.reset_timer(state);
+        self.try_propose(state).await;
+    }
+
+    /// Follow the certificates back from a new block: two certified blocks in consecutive
+    /// views lock the first, and three execute the first and everything before it
+    async fn update(&self, state: &mut HotStuffState, block: &Block) {
+        let Some(certified) = state.blocks.get(&block.justify.block).cloned() else {
+            return;
+        };
+        let Some(parent) = state.blocks.get(&certified.justify.block).cloned() else {
+            return;
+        };
+        if certified.view != parent.view + 1 || parent.view == 0 {
+            return;
+        }
+        if certified.justify.view > state.locked_qc.view {
+            state.locked_qc = certified.justify.clone();
+        }
+
+        let Some(grandparent) = state.blocks.get(&parent.justify.block) else {
+            return;
+        };
+        if parent.view == grandparent.view + 1 && grandparent.view > state.executed_view {
+            let hash = parent.justify.block;
+            self.execute(state, hash, parent.justify.votes.len()).await;
+        }
+    }
+
+    /// Execute `hash` and every block before it that has not executed yet. `acks` is the
+    /// size of the certificate for `hash`. Stops at the first request the state machine
+    /// fails on; that block executes again from there once a later block commits.
+    async fn execute(&self, state: &mut HotStuffState, hash: BlockHash, acks: usize) {
+        let Some(chain) = self.uncommitted(state, hash) else {
+            return;
+        };
+        // Each block's certificate travels in the block after it
+        let mut blocks: Vec<(Block, usize)> = Vec::new();
+        let mut certificate = acks;
+        for block in chain {
+            blocks.push((block.clone(), certificate));
+            certificate = block.justify.votes.len();
+        }
+
+        let machine = self.state_machine.read().await.clone();
+        for (block, acks) in blocks.into_iter().rev() {
+            for message in &block.requests {
+                let HotStuffPayload::Request(request) = &message.payload else {
+                    continue;
+                };
+                let key = request.key();
+                // A request can be in two blocks if the first was abandoned by a view change
+                // and later turned out to be certified after all
+                if !state.executed.contains_key(&key) {
+                    let committed = CommittedProposal {
+                        term: block.view,
+                        index: state.last_executed + 1,
+                        value: request.value.clone(),
+                    };
+                    if let Some(machine) = &machine {
+                        // Skipping the request would leave this replica's state behind the others'
+                        if let Err(e) = machine.apply(&committed).await {
+                            tracing::error!("Failed to apply request {}, halting execution at view {}: {}", committed.index, block.view, e);
+                            self.progress.send_modify(|_| {});
+                            return;
+                        }
+                    }
+                    state.last_executed = committed.index;
+                    state.executed.insert(key.clone(), committed);
+                }
+                state.pending.retain(|(pending, _)| *pending != key);
+            }
+            state.acks.insert(block.view, acks);
+            state.executed_view = block.view;
+            state.executed_block = block.hash();
+        }
+        tracing::debug!("{} executed up to view {}", self.node_id, state.executed_view);
+        self.progress.send_modify(|_| {});
+    }
+
+    /// As leader of the view after `view`, combine a quorum of votes into a certificate
+    async fn handle_vote(&self, state: &mut HotStuffState, message: &HotStuffMessage, view: u64, block: BlockHash) {
+        if *self.leader(view + 1) != self.node_id || view + 1 < state.view {
+            return;
+        }
+        let (voted_view, votes) = state.votes.entry(block).or_insert_with(|| (view, HashMap::new()));
+        if *voted_view != view {
+            return;
+        }
+        votes.insert(message.from.clone(), message.signature.clone());
+        if votes.len() != self.quorum() {
+            return;
+        }
+
+        let qc = QuorumCert {
+            view,
+            block,
+            votes: votes.iter().map(|(voter, signature)| (voter.clone(), signature.clone())).collect(),
+        };
+        self.update_high_qc(state, &qc);
+        state.votes.retain(|_, (voted_view, _)| *voted_view > view);
+        if state.view <= view + 1 {
+            state.view = view + 1;
+            state.ready = Some(view + 1);
+        }
+        self.try_propose(state).await;
+    }
+
+    /// As leader of `view`, start it once a quorum has given up on the views before it
+    async fn handle_new_view(&self, state: &mut HotStuffState, from: &NodeId, view: u64, high_qc: &QuorumCert) {
+        if *self.leader(view) != self.node_id || view < state.view || !self.verify_qc(high_qc) {
+            return;
+        }
+        self.update_high_qc(state, high_qc);
+        let senders = state.new_views.entry(view).or_default();
+        senders.insert(from.clone());
+        if senders.len() >= self.quorum() && state.ready != Some(view) {
+            tracing::info!("{} leading view {} after a view change", self.node_id, view);
+            state.view = view;
+            state.ready = Some(view);
+            state.new_views = state.new_views.split_off(&(view + 1));
+            self.try_propose(state).await;
+        }
+    }
+
+    /// Record `voter`'s ballot on a proposal, replacing any earlier one
+    pub async fn record_vote(&self, proposal_id: &ProposalId, voter: NodeId, vote: Vote) {
+        self.votes.write().await
+            .entry(proposal_id.0.clone())
+            .or_default()
+            .insert(voter, vote);
+    }
+}
+
+#[async_trait]
+impl ConsensusEngine for HotStuffNode {
+    async fn start(&mut self) -> anyhow::Result<()> {
+        if let Some(addr) = self.config.listen_addr {
+            let peers = self.config.peers.iter()
+                .filter_map(|(peer, entry)| entry.addr.map(|addr| (peer.clone(), addr)))
+                .collect();
+            let transport = TcpTransport::bind(addr, peers, self.inbox()).await?;
+            self.set_transport(Arc::new(transport)).await;
+        }
+        self.start_consensus_loop().await
+    }
+
+    /// Any replica accepts proposals. The request goes to every replica, so whichever leads
+    /// next can include it.
+    async fn propose(&self, value: Vec<u8>) -> anyhow::Result<ProposalId> {
+        let request = Request {
+            origin: self.node_id.clone(),
+            id: uuid::Uuid::new_v4().to_string(),
+            value,
+        };
+        let proposal_id = ProposalId(request.key());
+        self.broadcast(HotStuffPayload::Request(request)).await;
+        Ok(proposal_id)
+    }
+
+    async fn wait_for_commit(&self, proposal_id: &ProposalId, required_acks: usize) -> anyhow::Result<CommittedProposal> {
+        if required_acks > self.replicas.len() {
+            return Err(anyhow::anyhow!("Proposal needs {} acknowledgements but the cluster has {} replicas", required_acks, self.replicas.len()));
+        }
+
+        // Subscribe before checking so an execution between the check and the wait is not missed
+        let mut progress = self.progress.subscribe();
+        loop {
+            {
+                let state = self.state.lock().await;
+                if let Some(committed) = state.executed.get(&proposal_id.0) {
+                    // A certificate never grows once formed
+                    let acks = state.acks.get(&committed.term).copied().unwrap_or(0);
+                    if acks >= required_acks {
+                        return Ok(committed.clone());
+                    }
+                    return Err(anyhow::anyhow!("Proposal was certified by {} replicas, fewer than the {} required", acks, required_acks));
+                }
+            }
+            progress.changed().await?;
+        }
+    }
+
+    async fn vote(&self, proposal_id: ProposalId, vote: Vote) -> anyhow::Result<()> {
+        self.record_vote(&proposal_id, self.node_id.clone(), vote).await;
+        Ok(())
+    }
+
+    async fn receive_vote(&self, proposal_id: ProposalId, voter: NodeId, vote: Vote) -> anyhow::Result<()> {
+        self.record_vote(&proposal_id, voter, vote).await;
+        Ok(())
+    }
+
+    async fn tally(&self, proposal_id: &ProposalId) -> anyhow::Result<VoteTally> {
+        let mut tally = VoteTally {
+            voters: self.replicas.len(),
+            ..VoteTally::default()
+        };
+        if let Some(ballots) = self.votes.read().await.get(&proposal_id.0) {
+            for vote in ballots.values() {
+                match vote {
+                    Vote::Accept => tally.accept += 1,
+                    Vote::Reject => tally.reject += 1,
+                    Vote::Abstain => tally.abstain += 1,
+                }
+            }
+        }
+        Ok(tally)
+    }
+
+    async fn on_commit(&self, _value: Vec<u8>) -> anyhow::Result<()> {
+        // Blocks reach the state machine as they execute
+        Ok(())
+    }
+
+    async fn set_state_machine(&self, machine: Arc<dyn StateMachine>) -> anyhow::Result<()> {
+        *self.state_machine.write().await = Some(machine);
+        Ok(())
+    }
+
+    async fn add_learner(&self, _node: NodeId, _addr: Option<SocketAddr>) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!("HotStuff replicas are fixed by configuration"))
+    }
+
+    async fn add_voter(&self, _node: NodeId, _addr: Option<SocketAddr>) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!("HotStuff replicas are fixed by configuration"))
+    }
+
+    async fn remove_server(&self, _node: NodeId) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!("HotStuff replicas are fixed by configuration"))
+    }
+
+    async fn membership(&self) -> anyhow::Result<Membership> {
+        Ok(Membership {
+            voters: self.replicas.iter().cloned().collect(),
+            ..Membership::default()
+        })
+    }
+
+    async fn transfer_leadership(&self, _target: NodeId) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!("HotStuff leaders rotate with every view"))
+    }
+
+    async fn read_index(&self, consistency: ReadConsistency) -> anyhow::Result<u64> {
+        match consistency {
+            ReadConsistency::Stale => Ok(self.state.lock().await.last_executed),
+            // No replica leads for longer than a view, so every consistent read is ordered
+            // like a write and reflects everything executed before it
+            ReadConsistency::Linearizable | ReadConsistency::Lease => {
+                let proposal_id = self.propose(Vec::new()).await?;
+                Ok(self.wait_for_commit(&proposal_id, 0).await?.index)
+            }
+        }
+    }
+}
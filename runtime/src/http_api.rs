@@ -3,7 +3,8 @@
  * Provides REST endpoints for interacting with the distributed system
  */
 
-use crate::runtime::Executor;
+use crate::runtime::{PhaseStatus, RuntimeValue, StateHandle};
+use crate::ReadConsistency;
 use axum::{
     extract::{Query, State},
     http::StatusCode,
@@ -11,16 +12,21 @@ use axum::{
     routing::{get, post},
     Router,
 };
+use omnix_compiler::ast::AssignmentOp;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 use tracing::{info, error};
 
 /// Shared application state
 pub struct AppState {
-    pub executor: Arc<RwLock<Executor>>,
+    /// The program's state, usable while the executor is busy running the program
+    pub state: StateHandle,
     pub node_id: String,
-    pub counter: Arc<RwLock<u64>>,  // Simple counter for demo
+    /// The `@replicated` state variable the counter endpoints read and write
+    pub counter: String,
+    pub phase: Arc<RwLock<Option<PhaseStatus>>>,
 }
 
 /// Response for status endpoint
@@ -30,6 +36,8 @@ pub struct StatusResponse {
     pub status: String,
     pub peers: usize,
     pub counter: u64,
+    /// Most recent protocol phase, if the program has entered one
+    pub phase: Option<PhaseStatus>,
 }
 
 /// Response for increment operation
@@ -41,6 +49,13 @@ pub struct IncrementResponse {
     pub message: String,
 }
 
+/// Query for reads, e.g. `/value?consistency=stale`
+#[derive(Debug, Deserialize)]
+pub struct ReadQuery {
+    /// Defaults to `linearizable`
+    pub consistency: Option<ReadConsistency>,
+}
+
 /// Request for setting value
 #[derive(Debug, Deserialize)]
 pub struct SetValueRequest {
@@ -71,60 +86,83 @@ async fn health() -> StatusCode {
     StatusCode::OK
 }
 
+/// The counter as a `u64`; 0 if the program has not declared it
+fn counter_value(value: Option<&RuntimeValue>) -> Result<u64, StatusCode> {
+    match value {
+        None => Ok(0),
+        Some(RuntimeValue::UInteger(n)) => Ok(*n),
+        Some(RuntimeValue::Integer(n)) => u64::try_from(*n).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR),
+        Some(other) => {
+            error!("Counter holds {:?}, not a count", other);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Write the counter through consensus and report its value once applied on this node
+async fn update_counter(state: &AppState, op: AssignmentOp, value: u64, action: &str) -> Result<Json<IncrementResponse>, StatusCode> {
+    let updated = state.state.update(&state.counter, op, RuntimeValue::UInteger(value)).await.map_err(|e| {
+        error!("Counter update failed on node {}: {}", state.node_id, e);
+        StatusCode::SERVICE_UNAVAILABLE
+    })?;
+    let new_value = counter_value(Some(&updated))?;
+    
+    info!("Counter {} to {} on node {}", action, new_value, state.node_id);
+    
+    Ok(Json(IncrementResponse {
+        success: true,
+        new_value,
+        node_id: state.node_id.clone(),
+        message: format!("Counter {} to {}", action, new_value),
+    }))
+}
+
 /// Status endpoint
-async fn status(State(state): State<Arc<AppState>>) -> Json<StatusResponse> {
-    let counter = *state.counter.read().await;
+async fn status(State(state): State<Arc<AppState>>) -> Result<Json<StatusResponse>, StatusCode> {
+    let counter = counter_value(state.state.get(&state.counter).await.as_ref())?;
+    let phase = state.phase.read().await.clone();
     
-    Json(StatusResponse {
+    Ok(Json(StatusResponse {
         node_id: state.node_id.clone(),
         status: "running".to_string(),
         peers: 2, // TODO: Get actual peer count
         counter,
-    })
+        phase,
+    }))
 }
 
-/// Get current counter value
-async fn get_value(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
-    let counter = *state.counter.read().await;
-    
-    Json(serde_json::json!({
+/// Get the counter as applied from the log, at the requested consistency
+async fn get_value(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ReadQuery>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    let consistency = query.consistency.unwrap_or(ReadConsistency::Linearizable);
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    let counter = state.state.read(&state.counter, consistency, deadline).await.map_err(|e| {
+        error!("{:?} read failed on node {}: {}", consistency, state.node_id, e);
+        StatusCode::SERVICE_UNAVAILABLE
+    })?;
+    let counter = counter_value(Some(&counter.ok_or(StatusCode::NOT_FOUND)?))?;
+    
+    Ok(Json(serde_json::json!({
         "value": counter,
         "node_id": state.node_id,
-    }))
+        "consistency": consistency,
+    })))
 }
 
 /// Increment counter through consensus
 async fn increment(State(state): State<Arc<AppState>>) -> Result<Json<IncrementResponse>, StatusCode> {
     info!("Increment request received on node {}", state.node_id);
-    
-    // Get current value
-    let current = *state.counter.read().await;
-    let new_value = current + 1;
-    
-    // In a real implementation, this would go through consensus
-    // For MVP, we simulate consensus success
-    {
-        let mut counter = state.counter.write().await;
-        *counter = new_value;
-    }
-    
-    info!("Counter incremented to {} on node {}", new_value, state.node_id);
-    
-    Ok(Json(IncrementResponse {
-        success: true,
-        new_value,
-        node_id: state.node_id.clone(),
-        message: format!("Counter incremented to {}", new_value),
-    }))
+    update_counter(&state, AssignmentOp::AddAssign, 1, "incremented").await
 }
 
 /// Decrement counter through consensus
 async fn decrement(State(state): State<Arc<AppState>>) -> Result<Json<IncrementResponse>, StatusCode> {
     info!("Decrement request received on node {}", state.node_id);
     
-    // Get current value
-    let current = *state.counter.read().await;
-    if current == 0 {
+    // Unsigned subtraction stops at 0 when applied; refuse up front so callers are told
+    if counter_value(state.state.get(&state.counter).await.as_ref())? == 0 {
         return Ok(Json(IncrementResponse {
             success: false,
             new_value: 0,
@@ -133,23 +171,7 @@ async fn decrement(State(state): State<Arc<AppState>>) -> Result<Json<IncrementR
         }));
     }
     
-    let new_value = current - 1;
-    
-    // In a real implementation, this would go through consensus
-    // For MVP, we simulate consensus success
-    {
-        let mut counter = state.counter.write().await;
-        *counter = new_value;
-    }
-    
-    info!("Counter decremented to {} on node {}", new_value, state.node_id);
-    
-    Ok(Json(IncrementResponse {
-        success: true,
-        new_value,
-        node_id: state.node_id.clone(),
-        message: format!("Counter decremented to {}", new_value),
-    }))
+    update_counter(&state, AssignmentOp::SubAssign, 1, "decremented").await
 }
 
 /// Set counter value (admin operation)
@@ -160,24 +182,14 @@ async fn set_value(
     info!("Set value to {} requested on node {}", req.value, state.node_id);
     
     // In production, this should require authentication
-    {
-        let mut counter = state.counter.write().await;
-        *counter = req.value;
-    }
-    
-    Ok(Json(IncrementResponse {
-        success: true,
-        new_value: req.value,
-        node_id: state.node_id.clone(),
-        message: format!("Counter set to {}", req.value),
-    }))
+    update_counter(&state, AssignmentOp::Assign, req.value, "set").await
 }
 
 /// Metrics endpoint (Prometheus format)
-async fn metrics(State(state): State<Arc<AppState>>) -> String {
-    let counter = *state.counter.read().await;
+async fn metrics(State(state): State<Arc<AppState>>) -> Result<String, StatusCode> {
+    let counter = counter_value(state.state.get(&state.counter).await.as_ref())?;
     
-    format!(
+    Ok(format!(
         r#"# HELP omnix_counter Current counter value
 # TYPE omnix_counter gauge
 omnix_counter{{node_id="{}"}} {}
@@ -187,7 +199,7 @@ omnix_counter{{node_id="{}"}} {}
 omnix_node_info{{node_id="{}",version="0.1.0"}} 1
 "#,
         state.node_id, counter, state.node_id
-    )
+    ))
 }
 
 /// Start the HTTP server
@@ -5,49 +5,53 @@
 
 pub mod consensus;
 pub mod network;
-pub mod network_impl;
 pub mod state;
 pub mod crdt;
+pub mod hotstuff;
+pub mod pbft;
+pub mod tendermint;
 pub mod raft;
+pub mod raft_storage;
+pub mod transport;
 pub mod runtime;
 pub mod http_api;
 
 use async_trait::async_trait;
+use raft::Membership;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{mpsc, RwLock};
 
 /// Node ID type
 pub type NodeId = String;
 
 /// The main OMNIX runtime
+#[derive(Clone)]
 pub struct Runtime {
     node_id: NodeId,
-    consensus: Arc<RwLock<dyn ConsensusEngine>>,
-    network: Arc<RwLock<dyn NetworkLayer>>,
+    consensus: Arc<RwLock<Box<dyn ConsensusEngine>>>,
+    network: Arc<RwLock<Box<dyn NetworkLayer>>>,
     state: Arc<RwLock<StateManager>>,
 }
 
 impl Runtime {
     pub async fn new(node_id: NodeId, config: RuntimeConfig) -> anyhow::Result<Self> {
-        let consensus = Arc::new(RwLock::new(
-            consensus::create_engine(config.consensus, node_id.clone())?
-        ));
-        
-        let network = Arc::new(RwLock::new(
-            network::create_layer(config.network)?
-        ));
-        
-        let state = Arc::new(RwLock::new(
-            StateManager::new(config.state)?
-        ));
-        
-        Ok(Self {
+        let consensus = consensus::create_engine(config.consensus, node_id.clone())?;
+        let network = network::create_layer(node_id.clone(), config.network)?;
+        Ok(Self::with_parts(node_id, consensus, network))
+    }
+    
+    /// A runtime over an engine and network built by the caller, e.g. in-process ones for tests
+    pub fn with_parts(node_id: NodeId, consensus: Box<dyn ConsensusEngine>, network: Box<dyn NetworkLayer>) -> Self {
+        Self {
             node_id,
-            consensus,
-            network,
-            state,
-        })
+            consensus: Arc::new(RwLock::new(consensus)),
+            network: Arc::new(RwLock::new(network)),
+            state: Arc::new(RwLock::new(StateManager::default())),
+        }
     }
     
     pub async fn start(&self) -> anyhow::Result<()> {
@@ -64,12 +68,174 @@ impl Runtime {
         self.consensus.read().await.propose(value).await
     }
     
+    /// Propose a value, retrying until the deadline while no leader is known
+    pub async fn propose_until(&self, value: Vec<u8>, deadline: tokio::time::Instant) -> anyhow::Result<ProposalId> {
+        loop {
+            match self.propose(value.clone()).await {
+                Ok(id) => return Ok(id),
+                Err(e) if tokio::time::Instant::now() < deadline => {
+                    tracing::debug!("Proposal not accepted yet: {}", e);
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    
+    /// Wait until the proposal commits on `required_acks` replicas, or the deadline passes
+    pub async fn wait_for_commit(&self, proposal_id: &ProposalId, required_acks: usize, deadline: tokio::time::Instant) -> anyhow::Result<CommittedProposal> {
+        let committed = async {
+            self.consensus.read().await.wait_for_commit(proposal_id, required_acks).await
+        };
+        tokio::time::timeout_at(deadline, committed).await
+            .map_err(|_| anyhow::anyhow!("Proposal {} timed out before committing", proposal_id.0))?
+    }
+    
+    /// Wait until local state can serve a read at `consistency`, on the leader or a follower
+    pub async fn read_index(&self, consistency: ReadConsistency, deadline: tokio::time::Instant) -> anyhow::Result<u64> {
+        let ready = async {
+            self.consensus.read().await.read_index(consistency).await
+        };
+        tokio::time::timeout_at(deadline, ready).await
+            .map_err(|_| anyhow::anyhow!("{:?} read timed out", consistency))?
+    }
+    
+    /// Read a committed value at `consistency`
+    pub async fn get(&self, key: &str, consistency: ReadConsistency, deadline: tokio::time::Instant) -> anyhow::Result<Option<Vec<u8>>> {
+        self.read_index(consistency, deadline).await?;
+        self.state.read().await.get(key).await
+    }
+    
+    /// Commit an empty entry so everything committed before this call is known locally.
+    /// Fails if `required_acks` replicas cannot be reached before the deadline.
+    pub async fn read_barrier(&self, required_acks: usize, deadline: tokio::time::Instant) -> anyhow::Result<CommittedProposal> {
+        let proposal_id = self.propose_until(Vec::new(), deadline).await?;
+        self.wait_for_commit(&proposal_id, required_acks, deadline).await
+    }
+    
+    pub async fn broadcast(&self, message: Message) -> anyhow::Result<()> {
+        self.network.read().await.broadcast(message).await
+    }
+    
+    pub async fn gossip(&self, data: Vec<u8>, fanout: u32) -> anyhow::Result<()> {
+        self.network.read().await.gossip(data, fanout).await
+    }
+    
     pub async fn vote(&self, proposal_id: ProposalId, vote: Vote) -> anyhow::Result<()> {
         self.consensus.read().await.vote(proposal_id, vote).await
     }
     
-    pub async fn commit(&self, value: Vec<u8>) -> anyhow::Result<()> {
-        self.state.write().await.commit(value).await
+    /// Cast this node's vote, announce it to peers, and wait until `required` accepts are in,
+    /// the outcome can no longer change, or the timeout expires. Returns the tally at that point.
+    pub async fn vote_and_tally(&self, proposal_id: ProposalId, vote: Vote, required: Option<usize>, timeout: std::time::Duration) -> anyhow::Result<VoteTally> {
+        self.cast_vote(proposal_id.clone(), vote).await?;
+        
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let tally = self.consensus.read().await.tally(&proposal_id).await?;
+            if tally.is_decided(required) || tokio::time::Instant::now() >= deadline {
+                return Ok(tally);
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+    }
+    
+    /// Record this node's vote and announce it to peers; a failed announcement is only logged
+    pub async fn cast_vote(&self, proposal_id: ProposalId, vote: Vote) -> anyhow::Result<()> {
+        self.vote(proposal_id.clone(), vote.clone()).await?;
+        let announcement = Message::Vote { id: proposal_id.clone(), voter: self.node_id.clone(), vote };
+        if let Err(e) = self.network.read().await.broadcast(announcement).await {
+            tracing::warn!("Failed to announce vote on {}: {}", proposal_id.0, e);
+        }
+        Ok(())
+    }
+    
+    /// Announce that this node finished the barrier `id` and wait until `participants` nodes
+    /// (every voter that has not abstained by default) have too. Fails as soon as any participant
+    /// rejects the barrier, when too many abstain to reach `participants`, or when the deadline passes.
+    pub async fn phase_barrier(&self, id: ProposalId, participants: Option<usize>, deadline: tokio::time::Instant) -> anyhow::Result<VoteTally> {
+        self.cast_vote(id.clone(), Vote::Accept).await?;
+        
+        loop {
+            let tally = self.consensus.read().await.tally(&id).await?;
+            // An abstaining participant left the phase without aborting it, so nobody waits for it
+            let remaining = tally.voters.saturating_sub(tally.abstain);
+            let required = participants.unwrap_or(remaining);
+            if required > tally.voters {
+                return Err(anyhow::anyhow!("{} participants required but the cluster has {} voters", required, tally.voters));
+            }
+            if tally.reject > 0 {
+                return Err(anyhow::anyhow!("aborted by {} participant(s)", tally.reject));
+            }
+            if required > remaining {
+                return Err(anyhow::anyhow!("{} participant(s) left, {} of {} required remain", tally.abstain, remaining, required));
+            }
+            if tally.has_quorum(Some(required)) {
+                return Ok(tally);
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Err(anyhow::anyhow!("timed out with {} of {} participants ready", tally.accept, required));
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+    }
+    
+    /// Add `node` as a learner, which receives the log but does not vote.
+    /// Only the leader can change membership.
+    pub async fn add_learner(&self, node: NodeId, addr: Option<SocketAddr>, deadline: tokio::time::Instant) -> anyhow::Result<()> {
+        let change = async {
+            self.consensus.read().await.add_learner(node.clone(), addr).await
+        };
+        tokio::time::timeout_at(deadline, change).await
+            .map_err(|_| anyhow::anyhow!("Adding learner {} timed out", node))?
+    }
+    
+    /// Add `node` as a voter. It catches up as a learner first; if the deadline passes while
+    /// it is still catching up, it stays a learner.
+    pub async fn add_voter(&self, node: NodeId, addr: Option<SocketAddr>, deadline: tokio::time::Instant) -> anyhow::Result<()> {
+        let change = async {
+            self.consensus.read().await.add_voter(node.clone(), addr).await
+        };
+        tokio::time::timeout_at(deadline, change).await
+            .map_err(|_| anyhow::anyhow!("Adding voter {} timed out", node))?
+    }
+    
+    /// Remove a voter or learner. A leader that removes itself steps down once the change commits.
+    pub async fn remove_server(&self, node: NodeId, deadline: tokio::time::Instant) -> anyhow::Result<()> {
+        let change = async {
+            self.consensus.read().await.remove_server(node.clone()).await
+        };
+        tokio::time::timeout_at(deadline, change).await
+            .map_err(|_| anyhow::anyhow!("Removing {} timed out", node))?
+    }
+    
+    /// Make `target` the leader, e.g. before taking this node down for maintenance. The leader
+    /// refuses proposals during the handover and resumes if it does not complete within an
+    /// election timeout.
+    pub async fn transfer_leadership(&self, target: NodeId) -> anyhow::Result<()> {
+        self.consensus.read().await.transfer_leadership(target).await
+    }
+    
+    /// Voters and learners in the configuration this node is using
+    pub async fn membership(&self) -> anyhow::Result<Membership> {
+        self.consensus.read().await.membership().await
+    }
+    
+    /// Have the consensus engine feed committed entries to `machine`, and serve reads from it
+    pub async fn set_state_machine(&self, machine: Arc<dyn StateMachine>) -> anyhow::Result<()> {
+        self.consensus.read().await.set_state_machine(machine.clone()).await?;
+        self.state.write().await.set_machine(machine);
+        Ok(())
+    }
+    
+    /// Record a vote announced by a peer
+    pub async fn receive_vote(&self, proposal_id: ProposalId, voter: NodeId, vote: Vote) -> anyhow::Result<()> {
+        self.consensus.read().await.receive_vote(proposal_id, voter, vote).await
+    }
+    
+    /// Messages from peers; only the first caller gets the receiver
+    pub async fn take_incoming(&self) -> Option<mpsc::UnboundedReceiver<Message>> {
+        self.network.write().await.take_incoming()
     }
 }
 
@@ -85,6 +251,178 @@ pub struct ConsensusConfig {
     pub algorithm: ConsensusAlgorithm,
     pub timeout_ms: u64,
     pub max_faulty: u32,
+    #[serde(default)]
+    pub raft: RaftConfig,
+    #[serde(default)]
+    pub pbft: PbftConfig,
+    #[serde(default)]
+    pub tendermint: TendermintConfig,
+    #[serde(default)]
+    pub hotstuff: HotStuffConfig,
+}
+
+/// Raft cluster members and transport. Without a `listen_addr` the node runs as a single-node cluster.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RaftConfig {
+    /// Address the Raft TCP transport listens on
+    pub listen_addr: Option<SocketAddr>,
+    /// The other voting members and their Raft addresses
+    pub peers: HashMap<NodeId, SocketAddr>,
+    /// Where the Raft log, term and vote are kept; without one they are lost on restart
+    pub data_dir: Option<PathBuf>,
+    /// Join a running cluster instead of founding one with `peers`: the node waits for a leader
+    /// to add it, and `peers` only says where to reach the existing members
+    pub join_existing: bool,
+    /// Entries applied since the last snapshot that trigger a new one; 0 disables snapshots
+    pub snapshot_threshold: u64,
+    /// Entries kept behind a snapshot, so followers that are only slightly behind can catch
+    /// up from the log instead of receiving the whole snapshot
+    pub snapshot_retain: u64,
+    /// Ask for votes without changing term first, so a node that was cut off cannot force
+    /// a healthy leader out when it returns
+    pub pre_vote: bool,
+    /// Have a leader step down once it has not heard from a quorum for an election timeout
+    pub check_quorum: bool,
+    /// Allow handing leadership to another voter with `transfer_leadership`
+    pub leadership_transfer: bool,
+    /// How long after a quorum answers a heartbeat the leader serves lease reads without
+    /// another round. Must stay below the minimum election timeout to allow for clock drift;
+    /// 0 disables leases, so lease reads confirm leadership like linearizable ones.
+    pub read_lease_ms: u64,
+}
+
+impl Default for RaftConfig {
+    fn default() -> Self {
+        Self {
+            listen_addr: None,
+            peers: HashMap::new(),
+            data_dir: None,
+            join_existing: false,
+            snapshot_threshold: 1024,
+            snapshot_retain: 128,
+            pre_vote: true,
+            check_quorum: true,
+            leadership_transfer: true,
+            read_lease_ms: 100,
+        }
+    }
+}
+
+/// PBFT replicas, their keys and transport. `max_faulty` Byzantine replicas are tolerated
+/// out of at least `3 * max_faulty + 1`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PbftConfig {
+    /// Address the PBFT TCP transport listens on
+    pub listen_addr: Option<SocketAddr>,
+    /// The other replicas
+    pub peers: HashMap<NodeId, PbftPeer>,
+    /// This replica's ed25519 secret key. A random one is generated if omitted, which only
+    /// suits a single replica since no peer can know its public key.
+    pub secret_key: Option<[u8; 32]>,
+    /// Requests executed between checkpoints. Once a quorum agrees on a checkpoint, the
+    /// messages behind it are discarded; the primary assigns at most twice this many
+    /// sequence numbers past it.
+    pub checkpoint_interval: u64,
+    /// How long a replica waits for a request to execute before voting to replace the
+    /// primary. Doubles with each view change that fails to complete.
+    pub view_change_timeout_ms: u64,
+}
+
+impl Default for PbftConfig {
+    fn default() -> Self {
+        Self {
+            listen_addr: None,
+            peers: HashMap::new(),
+            secret_key: None,
+            checkpoint_interval: 128,
+            view_change_timeout_ms: 500,
+        }
+    }
+}
+
+/// Another PBFT replica
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PbftPeer {
+    /// Where its PBFT transport listens; not needed in process
+    pub addr: Option<SocketAddr>,
+    /// The ed25519 public key every message from it must be signed with
+    pub public_key: [u8; 32],
+}
+
+/// Tendermint validators, their keys, voting power and transport. Decisions need more than
+/// two thirds of the total power; round timeouts start at `ConsensusConfig::timeout_ms`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TendermintConfig {
+    /// Address the Tendermint TCP transport listens on
+    pub listen_addr: Option<SocketAddr>,
+    /// The other validators
+    pub peers: HashMap<NodeId, TendermintPeer>,
+    /// This validator's ed25519 secret key. A random one is generated if omitted, which only
+    /// suits a single validator since no peer can know its public key.
+    pub secret_key: Option<[u8; 32]>,
+    /// This validator's voting power, which also sets how often it proposes
+    pub power: u64,
+}
+
+impl Default for TendermintConfig {
+    fn default() -> Self {
+        Self {
+            listen_addr: None,
+            peers: HashMap::new(),
+            secret_key: None,
+            power: 1,
+        }
+    }
+}
+
+/// Another Tendermint validator
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TendermintPeer {
+    /// Where its Tendermint transport listens; not needed in process
+    pub addr: Option<SocketAddr>,
+    /// The ed25519 public key every message from it must be signed with
+    pub public_key: [u8; 32],
+    pub power: u64,
+}
+
+/// HotStuff replicas, their keys and transport. `max_faulty` Byzantine replicas are tolerated
+/// out of at least `3 * max_faulty + 1`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HotStuffConfig {
+    /// Address the HotStuff TCP transport listens on
+    pub listen_addr: Option<SocketAddr>,
+    /// The other replicas
+    pub peers: HashMap<NodeId, HotStuffPeer>,
+    /// This replica's ed25519 secret key. A random one is generated if omitted, which only
+    /// suits a single replica since no peer can know its public key.
+    pub secret_key: Option<[u8; 32]>,
+    /// How long a replica waits in a view for the chain to grow before moving to the next
+    /// view. Doubles with each view in a row that times out.
+    pub view_timeout_ms: u64,
+}
+
+impl Default for HotStuffConfig {
+    fn default() -> Self {
+        Self {
+            listen_addr: None,
+            peers: HashMap::new(),
+            secret_key: None,
+            view_timeout_ms: 500,
+        }
+    }
+}
+
+/// Another HotStuff replica
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HotStuffPeer {
+    /// Where its HotStuff transport listens; not needed in process
+    pub addr: Option<SocketAddr>,
+    /// The ed25519 public key every message from it must be signed with
+    pub public_key: [u8; 32],
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -92,6 +430,7 @@ pub enum ConsensusAlgorithm {
     Raft,
     PBFT,
     Tendermint,
+    HotStuff,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -119,13 +458,67 @@ pub enum ConsistencyLevel {
     Causal,
 }
 
+/// How up to date a read must be, chosen per request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadConsistency {
+    /// Reflects every write committed before the read began; the leader confirms it still
+    /// leads with a heartbeat round (ReadIndex)
+    Linearizable,
+    /// Like `Linearizable`, but the leader skips the heartbeat round while a quorum's recent
+    /// answers rule out a new leader. Relies on bounded clock drift.
+    Lease,
+    /// Whatever this node has applied; may be behind the cluster
+    Stale,
+}
+
 /// Consensus engine trait
 #[async_trait]
 pub trait ConsensusEngine: Send + Sync {
     async fn start(&mut self) -> anyhow::Result<()>;
     async fn propose(&self, value: Vec<u8>) -> anyhow::Result<ProposalId>;
+    /// Resolve once the proposal is committed, applied locally and stored on at least
+    /// `required_acks` replicas. Never times out on its own; callers bound the wait.
+    async fn wait_for_commit(&self, proposal_id: &ProposalId, required_acks: usize) -> anyhow::Result<CommittedProposal>;
     async fn vote(&self, proposal_id: ProposalId, vote: Vote) -> anyhow::Result<()>;
+    /// Record a vote cast by another node
+    async fn receive_vote(&self, proposal_id: ProposalId, voter: NodeId, vote: Vote) -> anyhow::Result<()>;
+    /// Votes recorded so far for a proposal
+    async fn tally(&self, proposal_id: &ProposalId) -> anyhow::Result<VoteTally>;
     async fn on_commit(&self, value: Vec<u8>) -> anyhow::Result<()>;
+    /// Register the state machine that committed entries are applied to
+    async fn set_state_machine(&self, machine: Arc<dyn StateMachine>) -> anyhow::Result<()>;
+    /// Add `node` as a learner, which receives the log but does not vote.
+    /// Resolves once the new configuration has committed.
+    async fn add_learner(&self, node: NodeId, addr: Option<SocketAddr>) -> anyhow::Result<()>;
+    /// Add `node` as a voter, first as a learner until it has caught up with the log
+    async fn add_voter(&self, node: NodeId, addr: Option<SocketAddr>) -> anyhow::Result<()>;
+    /// Remove a voter or learner from the cluster
+    async fn remove_server(&self, node: NodeId) -> anyhow::Result<()>;
+    /// The configuration this node is currently using
+    async fn membership(&self) -> anyhow::Result<Membership>;
+    /// Hand leadership to the voter `target`, resolving once it has taken over
+    async fn transfer_leadership(&self, target: NodeId) -> anyhow::Result<()>;
+    /// Wait until this node's state machine can serve a read at `consistency`, returning the
+    /// applied index the read reflects
+    async fn read_index(&self, consistency: ReadConsistency) -> anyhow::Result<u64>;
+}
+
+/// Application state built from the committed log
+#[async_trait]
+pub trait StateMachine: Send + Sync {
+    /// Called for every committed entry, in log order and exactly once per index. Must be
+    /// deterministic, so replicas that apply the same entries end in the same state. An error
+    /// halts application at that entry: it is retried, never skipped.
+    async fn apply(&self, entry: &CommittedProposal) -> anyhow::Result<()>;
+    /// Serialize the state built from every entry applied so far
+    async fn snapshot(&self) -> anyhow::Result<Vec<u8>>;
+    /// Replace the state with one produced by `snapshot`
+    async fn restore(&self, snapshot: &[u8]) -> anyhow::Result<()>;
+    /// The applied value stored under `key`, for machines that serve reads
+    async fn get(&self, _key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        Ok(None)
+    }
 }
 
 /// Network layer trait
@@ -135,42 +528,68 @@ pub trait NetworkLayer: Send + Sync {
     async fn broadcast(&self, message: Message) -> anyhow::Result<()>;
     async fn send_to(&self, node: NodeId, message: Message) -> anyhow::Result<()>;
     async fn gossip(&self, data: Vec<u8>, fanout: u32) -> anyhow::Result<()>;
+    /// Messages received from peers; `None` once the receiver has been taken
+    fn take_incoming(&mut self) -> Option<mpsc::UnboundedReceiver<Message>>;
 }
 
-/// State manager
+/// State manager: serves reads from the state machine the committed log is applied to
+#[derive(Default)]
 pub struct StateManager {
-    store: sled::Db,
-    replicas: Vec<NodeId>,
+    machine: Option<Arc<dyn StateMachine>>,
 }
 
 impl StateManager {
-    pub fn new(config: StateConfig) -> anyhow::Result<Self> {
-        let store = sled::open("omnix_state")?;
-        
-        Ok(Self {
-            store,
-            replicas: Vec::new(),
-        })
-    }
-    
-    pub async fn commit(&mut self, value: Vec<u8>) -> anyhow::Result<()> {
-        // Store value locally
-        let key = format!("value_{}", uuid::Uuid::new_v4());
-        self.store.insert(key, value)?;
-        
-        // TODO: Replicate to other nodes
-        
-        Ok(())
+    pub fn set_machine(&mut self, machine: Arc<dyn StateMachine>) {
+        self.machine = Some(machine);
     }
     
+    /// The value applied under `key`; `None` until a state machine is registered
     pub async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
-        Ok(self.store.get(key)?.map(|v| v.to_vec()))
+        match &self.machine {
+            Some(machine) => machine.get(key).await,
+            None => Ok(None),
+        }
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProposalId(pub String);
 
+/// A proposal's position in the replicated log once committed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommittedProposal {
+    pub term: u64,
+    pub index: u64,
+    pub value: Vec<u8>,
+}
+
+/// Application-level votes counted for one proposal
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VoteTally {
+    pub accept: usize,
+    pub reject: usize,
+    pub abstain: usize,
+    /// Members entitled to vote
+    pub voters: usize,
+}
+
+impl VoteTally {
+    /// Accepts needed for a quorum: `required`, or a majority of voters
+    pub fn required(&self, required: Option<usize>) -> usize {
+        required.unwrap_or(self.voters / 2 + 1)
+    }
+    
+    pub fn has_quorum(&self, required: Option<usize>) -> bool {
+        self.accept >= self.required(required)
+    }
+    
+    /// Whether further votes can no longer change the quorum outcome
+    pub fn is_decided(&self, required: Option<usize>) -> bool {
+        let outstanding = self.voters.saturating_sub(self.accept + self.reject + self.abstain);
+        self.has_quorum(required) || self.accept + outstanding < self.required(required)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Vote {
     Accept,
@@ -181,9 +600,13 @@ pub enum Vote {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Message {
     Propose { id: ProposalId, value: Vec<u8> },
-    Vote { id: ProposalId, vote: Vote },
+    Vote { id: ProposalId, voter: NodeId, vote: Vote },
     Commit { value: Vec<u8> },
+    StateSync { name: String, value: Vec<u8> },
     Gossip { data: Vec<u8> },
+    /// A `broadcast` event. `sequence` counts up from 1 per sender incarnation,
+    /// so receivers can run handlers in the order the sender broadcast them.
+    Event { origin: NodeId, incarnation: String, sequence: u64, name: String, payload: Vec<u8> },
     Heartbeat,
 }
 
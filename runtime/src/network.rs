@@ -4,35 +4,47 @@
 
 use crate::{NetworkLayer, NetworkConfig, DiscoveryMethod, NodeId, Message};
 use async_trait::async_trait;
+use futures::StreamExt;
 use libp2p::{
-    identity,
-    PeerId,
+    gossipsub::{self, IdentTopic, MessageAuthenticity},
+    mdns,
+    noise,
+    swarm::{behaviour::toggle::Toggle, NetworkBehaviour, SwarmEvent},
+    tcp,
+    yamux,
+    Multiaddr,
     Swarm,
-    swarm::SwarmEvent,
-    gossipsub::{self, Gossipsub, GossipsubEvent, MessageAuthenticity},
-    mdns::{self, Mdns, MdnsEvent},
-    NetworkBehaviour,
+    SwarmBuilder,
 };
-use std::collections::HashSet;
-use tokio::sync::mpsc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, RwLock};
 
-pub fn create_layer(config: NetworkConfig) -> anyhow::Result<Box<dyn NetworkLayer>> {
-    Ok(Box::new(P2PNetwork::new(config)?))
+/// Gossipsub topic every OMNIX node publishes on and subscribes to
+const TOPIC: &str = "omnix";
+
+pub fn create_layer(node_id: NodeId, config: NetworkConfig) -> anyhow::Result<Box<dyn NetworkLayer>> {
+    Ok(Box::new(P2PNetwork::new(node_id, config)?))
 }
 
 /// P2P network implementation using libp2p
 pub struct P2PNetwork {
+    node_id: NodeId,
     config: NetworkConfig,
-    swarm: Option<Swarm<OmnixBehaviour>>,
-    peers: HashSet<PeerId>,
     tx: mpsc::Sender<NetworkCommand>,
-    rx: mpsc::Receiver<NetworkCommand>,
+    // Taken by the event loop once the network starts
+    rx: Option<mpsc::Receiver<NetworkCommand>>,
+    incoming_tx: mpsc::UnboundedSender<Message>,
+    incoming_rx: Option<mpsc::UnboundedReceiver<Message>>,
 }
 
 #[derive(NetworkBehaviour)]
 struct OmnixBehaviour {
-    gossipsub: Gossipsub,
-    mdns: Mdns,
+    gossipsub: gossipsub::Behaviour,
+    // Only enabled with `DiscoveryMethod::MDNS`
+    mdns: Toggle<mdns::tokio::Behaviour>,
 }
 
 enum NetworkCommand {
@@ -41,64 +53,148 @@ enum NetworkCommand {
     Gossip(Vec<u8>, u32),
 }
 
+/// What goes on the wire: every node receives every envelope and keeps those addressed to it
+#[derive(Debug, Serialize, Deserialize)]
+struct Envelope {
+    /// `None` for broadcasts
+    to: Option<NodeId>,
+    message: Message,
+}
+
 impl P2PNetwork {
-    pub fn new(config: NetworkConfig) -> anyhow::Result<Self> {
+    pub fn new(node_id: NodeId, config: NetworkConfig) -> anyhow::Result<Self> {
         let (tx, rx) = mpsc::channel(100);
+        let (incoming_tx, incoming_rx) = mpsc::unbounded_channel();
         
         Ok(Self {
+            node_id,
             config,
-            swarm: None,
-            peers: HashSet::new(),
             tx,
-            rx,
+            rx: Some(rx),
+            incoming_tx,
+            incoming_rx: Some(incoming_rx),
         })
     }
     
-    fn create_swarm(&mut self) -> anyhow::Result<Swarm<OmnixBehaviour>> {
-        // Generate peer identity
-        let local_key = identity::Keypair::generate_ed25519();
-        let local_peer_id = PeerId::from(local_key.public());
-        
-        // Create Gossipsub
-        let gossipsub_config = gossipsub::GossipsubConfigBuilder::default()
-            .build()
-            .map_err(|e| anyhow::anyhow!("Failed to build gossipsub config: {}", e))?;
-        
-        let gossipsub = Gossipsub::new(
-            MessageAuthenticity::Signed(local_key.clone()),
-            gossipsub_config,
-        )?;
-        
-        // Create mDNS for peer discovery
-        let mdns = Mdns::new(mdns::MdnsConfig::default())?;
-        
-        // Create network behaviour
-        let behaviour = OmnixBehaviour { gossipsub, mdns };
-        
-        // Create swarm
-        let transport = libp2p::tcp::TcpConfig::new();
-        let swarm = Swarm::new(transport, behaviour, local_peer_id);
+    fn create_swarm(&self) -> anyhow::Result<Swarm<OmnixBehaviour>> {
+        let use_mdns = matches!(self.config.discovery, DiscoveryMethod::MDNS);
+        let swarm = SwarmBuilder::with_new_identity()
+            .with_tokio()
+            .with_tcp(tcp::Config::default(), noise::Config::new, yamux::Config::default)?
+            .with_behaviour(|key| {
+                let gossipsub_config = gossipsub::ConfigBuilder::default()
+                    .heartbeat_interval(Duration::from_secs(1))
+                    .build()?;
+                let gossipsub = gossipsub::Behaviour::new(MessageAuthenticity::Signed(key.clone()), gossipsub_config)?;
+                
+                let mdns = if use_mdns {
+                    Some(mdns::tokio::Behaviour::new(mdns::Config::default(), key.public().to_peer_id())?)
+                } else {
+                    None
+                };
+                
+                Ok(OmnixBehaviour { gossipsub, mdns: Toggle::from(mdns) })
+            })
+            .map_err(|e| anyhow::anyhow!("Failed to create network behaviour: {}", e))?
+            .with_swarm_config(|config| config.with_idle_connection_timeout(Duration::from_secs(60)))
+            .build();
         
         Ok(swarm)
     }
 }
 
+/// A static peer as a multiaddr, or as `host:port` for TCP
+fn peer_address(peer: &str) -> anyhow::Result<Multiaddr> {
+    if peer.starts_with('/') {
+        return Ok(peer.parse()?);
+    }
+    let addr: std::net::SocketAddr = peer.parse()?;
+    let ip = match addr.ip() {
+        std::net::IpAddr::V4(ip) => format!("/ip4/{}", ip),
+        std::net::IpAddr::V6(ip) => format!("/ip6/{}", ip),
+    };
+    Ok(format!("{}/tcp/{}", ip, addr.port()).parse()?)
+}
+
+/// Publish commands on the topic and pass messages from peers to `incoming`, until the
+/// network is dropped
+async fn event_loop(
+    mut swarm: Swarm<OmnixBehaviour>,
+    mut commands: mpsc::Receiver<NetworkCommand>,
+    incoming: mpsc::UnboundedSender<Message>,
+    node_id: NodeId,
+    topic: IdentTopic,
+) {
+    loop {
+        tokio::select! {
+            command = commands.recv() => {
+                let envelope = match command {
+                    Some(NetworkCommand::Broadcast(message)) => Envelope { to: None, message },
+                    Some(NetworkCommand::SendTo(node, message)) => Envelope { to: Some(node), message },
+                    // Gossipsub picks the peers to forward to, so the fanout is not used
+                    Some(NetworkCommand::Gossip(data, _fanout)) => Envelope { to: None, message: Message::Gossip { data } },
+                    None => break,
+                };
+                let published = bincode::serialize(&envelope)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|data| Ok(swarm.behaviour_mut().gossipsub.publish(topic.clone(), data)?));
+                if let Err(e) = published {
+                    tracing::warn!("Failed to publish message: {}", e);
+                }
+            }
+            event = swarm.select_next_some() => match event {
+                SwarmEvent::Behaviour(OmnixBehaviourEvent::Gossipsub(gossipsub::Event::Message { message, .. })) => {
+                    match bincode::deserialize::<Envelope>(&message.data) {
+                        Ok(envelope) if envelope.to.as_ref().is_none_or(|to| *to == node_id) => {
+                            let _ = incoming.send(envelope.message);
+                        }
+                        Ok(_) => {}
+                        Err(e) => tracing::warn!("Dropping undecodable message: {}", e),
+                    }
+                }
+                SwarmEvent::Behaviour(OmnixBehaviourEvent::Mdns(mdns::Event::Discovered(peers))) => {
+                    for (peer_id, _) in peers {
+                        swarm.behaviour_mut().gossipsub.add_explicit_peer(&peer_id);
+                    }
+                }
+                SwarmEvent::Behaviour(OmnixBehaviourEvent::Mdns(mdns::Event::Expired(peers))) => {
+                    for (peer_id, _) in peers {
+                        swarm.behaviour_mut().gossipsub.remove_explicit_peer(&peer_id);
+                    }
+                }
+                SwarmEvent::NewListenAddr { address, .. } => {
+                    tracing::info!("Node {} listening on {}", node_id, address);
+                }
+                SwarmEvent::ConnectionEstablished { peer_id, .. } => {
+                    tracing::debug!("Connected to {}", peer_id);
+                }
+                _ => {}
+            },
+        }
+    }
+}
+
 #[async_trait]
 impl NetworkLayer for P2PNetwork {
     async fn start(&mut self) -> anyhow::Result<()> {
+        let commands = self.rx.take()
+            .ok_or_else(|| anyhow::anyhow!("Network of node {} already started", self.node_id))?;
         let mut swarm = self.create_swarm()?;
         
+        let topic = IdentTopic::new(TOPIC);
+        swarm.behaviour_mut().gossipsub.subscribe(&topic)?;
+        
         // Listen on specified port
         let addr = format!("/ip4/0.0.0.0/tcp/{}", self.config.port);
         swarm.listen_on(addr.parse()?)?;
         
-        self.swarm = Some(swarm);
+        if let DiscoveryMethod::Static(peers) = &self.config.discovery {
+            for peer in peers {
+                swarm.dial(peer_address(peer)?)?;
+            }
+        }
         
-        // Start event loop
-        tokio::spawn(async move {
-            // Handle swarm events
-            // This would be the main network event loop
-        });
+        tokio::spawn(event_loop(swarm, commands, self.incoming_tx.clone(), self.node_id.clone(), topic));
         
         Ok(())
     }
@@ -117,4 +213,69 @@ impl NetworkLayer for P2PNetwork {
         self.tx.send(NetworkCommand::Gossip(data, fanout)).await?;
         Ok(())
     }
-}
\ No newline at end of file
+    
+    fn take_incoming(&mut self) -> Option<mpsc::UnboundedReceiver<Message>> {
+        self.incoming_rx.take()
+    }
+}
+
+/// Connects network layers in the same process, e.g. the executors of a test cluster
+#[derive(Clone, Default)]
+pub struct InProcessHub {
+    inboxes: Arc<RwLock<HashMap<NodeId, mpsc::UnboundedSender<Message>>>>,
+}
+
+impl InProcessHub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// A network layer for `node_id` that reaches every other layer joined to this hub
+    pub async fn join(&self, node_id: &NodeId) -> InProcessLayer {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.inboxes.write().await.insert(node_id.clone(), tx);
+        InProcessLayer {
+            hub: self.clone(),
+            node_id: node_id.clone(),
+            incoming: Some(rx),
+        }
+    }
+}
+
+/// A node's connection to an `InProcessHub`
+pub struct InProcessLayer {
+    hub: InProcessHub,
+    node_id: NodeId,
+    incoming: Option<mpsc::UnboundedReceiver<Message>>,
+}
+
+#[async_trait]
+impl NetworkLayer for InProcessLayer {
+    async fn start(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+    
+    async fn broadcast(&self, message: Message) -> anyhow::Result<()> {
+        for (node, inbox) in self.hub.inboxes.read().await.iter() {
+            if *node != self.node_id {
+                let _ = inbox.send(message.clone());
+            }
+        }
+        Ok(())
+    }
+    
+    async fn send_to(&self, node: NodeId, message: Message) -> anyhow::Result<()> {
+        let inboxes = self.hub.inboxes.read().await;
+        let inbox = inboxes.get(&node).ok_or_else(|| anyhow::anyhow!("Unknown node: {}", node))?;
+        let _ = inbox.send(message);
+        Ok(())
+    }
+    
+    async fn gossip(&self, data: Vec<u8>, _fanout: u32) -> anyhow::Result<()> {
+        self.broadcast(Message::Gossip { data }).await
+    }
+    
+    fn take_incoming(&mut self) -> Option<mpsc::UnboundedReceiver<Message>> {
+        self.incoming.take()
+    }
+}
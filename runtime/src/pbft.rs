@@ -0,0 +1,1091 @@
+/*!
+ * PBFT consensus for OMNIX
+ * Byzantine fault tolerant ordering with ed25519-signed messages, checkpoints and view changes
+ */
+
+use crate::{ConsensusEngine, CommittedProposal, NodeId, PbftConfig, ProposalId, ReadConsistency, StateMachine, Vote, VoteTally};
+use crate::raft::Membership;
+use crate::transport::{ConsensusTransport, TcpTransport};
+use async_trait::async_trait;
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch, Mutex, RwLock};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration, Instant};
+
+/// SHA-256 of a request or of the state at a checkpoint
+pub type Digest = [u8; 32];
+
+/// Digest of the null requests a new primary fills gaps in the sequence with
+const NULL_DIGEST: Digest = [0; 32];
+
+/// Sequence number, digest and request a new primary assigns again in its view
+type Reassignment = (u64, Digest, Option<Box<PbftMessage>>);
+
+/// Most pre-prepares held for a view this replica has not entered yet
+const MAX_FUTURE_MESSAGES: usize = 1024;
+
+/// Public key for an ed25519 secret key, as listed in the other replicas' `PbftPeer`
+pub fn public_key(secret_key: &[u8; 32]) -> [u8; 32] {
+    SigningKey::from_bytes(secret_key).verifying_key().to_bytes()
+}
+
+/// A value submitted through `propose`. The origin signs it, so a faulty primary can order
+/// it but not forge or alter it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Request {
+    pub origin: NodeId,
+    pub id: String,
+    pub value: Vec<u8>,
+}
+
+impl Request {
+    /// Identifies the request across views; a request executes at most once
+    pub fn key(&self) -> String {
+        format!("{}:{}", self.origin, self.id)
+    }
+
+    pub fn digest(&self) -> Digest {
+        // Strings, integers and byte vectors always serialize
+        Sha256::digest(bincode::serialize(self).expect("PBFT requests always serialize")).into()
+    }
+}
+
+/// A signed message. Messages quoted as proof keep their original signatures, so any replica
+/// can check them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PbftMessage {
+    pub from: NodeId,
+    pub payload: PbftPayload,
+    pub signature: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PbftPayload {
+    /// Sent by the origin to every replica, so backups notice a primary that ignores it
+    Request(Request),
+    /// The primary assigns `sequence` to a request, or to a null request without one
+    PrePrepare {
+        view: u64,
+        sequence: u64,
+        digest: Digest,
+        request: Option<Box<PbftMessage>>,
+    },
+    Prepare {
+        view: u64,
+        sequence: u64,
+        digest: Digest,
+    },
+    Commit {
+        view: u64,
+        sequence: u64,
+        digest: Digest,
+    },
+    /// Digest of the state after executing everything up to `sequence`
+    Checkpoint {
+        sequence: u64,
+        state_digest: Digest,
+    },
+    /// Vote to move to `view`, carrying the latest stable checkpoint and every request
+    /// prepared after it, with the messages that prove both
+    ViewChange {
+        view: u64,
+        checkpoint: u64,
+        checkpoint_proof: Vec<PbftMessage>,
+        prepared: Vec<PreparedProof>,
+    },
+    /// The new primary's proof that a quorum moved to `view`, with the pre-prepares that
+    /// carry requests prepared in earlier views into it
+    NewView {
+        view: u64,
+        view_changes: Vec<PbftMessage>,
+        pre_prepares: Vec<PbftMessage>,
+    },
+    /// Asks for the state at a stable checkpoint this replica has not reached
+    FetchState {
+        sequence: u64,
+    },
+    State {
+        sequence: u64,
+        data: Vec<u8>,
+    },
+}
+
+/// A pre-prepare and the matching prepares that, with it, make up a quorum
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreparedProof {
+    pub pre_prepare: PbftMessage,
+    pub prepares: Vec<PbftMessage>,
+}
+
+/// Bytes a message's signature covers
+fn signed_bytes(from: &NodeId, payload: &PbftPayload) -> Vec<u8> {
+    bincode::serialize(&(from, payload)).expect("PBFT payloads always serialize")
+}
+
+/// View, sequence number and digest a prepare or commit votes for
+fn phase_vote(message: &PbftMessage) -> Option<(u64, u64, Digest)> {
+    match message.payload {
+        PbftPayload::Prepare { view, sequence, digest } | PbftPayload::Commit { view, sequence, digest } => Some((view, sequence, digest)),
+        _ => None,
+    }
+}
+
+/// The pre-prepare a replica accepted for a sequence number
+#[derive(Debug)]
+struct Accepted {
+    view: u64,
+    digest: Digest,
+    /// `None` for a null request
+    request: Option<Request>,
+    message: PbftMessage,
+}
+
+/// One sequence number's progress through pre-prepare, prepare and commit
+#[derive(Debug, Default)]
+struct Slot {
+    accepted: Option<Accepted>,
+    /// Latest prepare and commit from each replica. They may arrive before the pre-prepare,
+    /// so only those matching the accepted view and digest count.
+    prepares: HashMap<NodeId, PbftMessage>,
+    commits: HashMap<NodeId, PbftMessage>,
+    /// Set once prepared in the accepted view, when this replica sends its commit
+    prepared: bool,
+}
+
+impl Slot {
+    fn matching<'a>(&'a self, votes: &'a HashMap<NodeId, PbftMessage>, sequence: u64) -> impl Iterator<Item = &'a PbftMessage> {
+        let accepted = self.accepted.as_ref().map(|accepted| (accepted.view, sequence, accepted.digest));
+        votes.values().filter(move |vote| accepted.is_some() && phase_vote(vote) == accepted)
+    }
+
+    fn commit_count(&self, sequence: u64) -> usize {
+        self.matching(&self.commits, sequence).count()
+    }
+}
+
+#[derive(Debug, Default)]
+struct PbftState {
+    view: u64,
+    /// Set from sending a view change for `view` until its new-view message arrives
+    view_changing: bool,
+    /// View changes in a row that have not completed; each waits twice as long as the last
+    failed_view_changes: u32,
+    /// When this replica gives up on the primary, while a request it knows of is outstanding
+    timer: Option<Instant>,
+    /// Highest sequence number assigned by this replica as primary
+    last_assigned: u64,
+    slots: BTreeMap<u64, Slot>,
+    /// Latest prepared certificate for each sequence number after the stable checkpoint
+    prepared: BTreeMap<u64, PreparedProof>,
+    last_executed: u64,
+    stable_checkpoint: u64,
+    stable_proof: Vec<PbftMessage>,
+    /// Checkpoint messages after the stable checkpoint, by sequence number and sender
+    checkpoints: BTreeMap<u64, HashMap<NodeId, PbftMessage>>,
+    /// This replica's state at recent checkpoints, served to replicas that fall behind
+    snapshots: BTreeMap<u64, Vec<u8>>,
+    /// Stable checkpoint being fetched from another replica
+    fetching: Option<u64>,
+    /// Requests received but not executed yet, in arrival order
+    pending: Vec<(String, PbftMessage)>,
+    /// Requests this replica has assigned as primary of the current view
+    assigned: HashSet<String>,
+    executed: HashMap<String, CommittedProposal>,
+    view_changes: BTreeMap<u64, HashMap<NodeId, PbftMessage>>,
+    new_view_sent: Option<u64>,
+    /// Pre-prepares for a view this replica has not entered yet
+    future: Vec<PbftMessage>,
+}
+
+/// One PBFT replica. Every replica accepts proposals and broadcasts them; the primary of the
+/// current view orders them, and the others replace it if it stalls.
+#[derive(Clone)]
+pub struct PbftNode {
+    pub node_id: NodeId,
+    /// Every replica, this one included, in the order the primary role rotates
+    pub replicas: Vec<NodeId>,
+    /// Byzantine replicas tolerated
+    pub max_faulty: usize,
+    pub config: PbftConfig,
+    signing_key: Arc<SigningKey>,
+    public_keys: Arc<HashMap<NodeId, VerifyingKey>>,
+    // Changed as a whole by each message, so a single lock keeps it consistent
+    state: Arc<Mutex<PbftState>>,
+    // Signalled whenever a request executes or a commit arrives
+    pub progress: Arc<watch::Sender<u64>>,
+    // Application-level votes from `<?>`, one per replica and proposal
+    pub votes: Arc<RwLock<HashMap<String, HashMap<NodeId, Vote>>>>,
+    pub state_machine: Arc<RwLock<Option<Arc<dyn StateMachine>>>>,
+    pub transport: Arc<RwLock<Option<Arc<dyn ConsensusTransport<PbftMessage>>>>>,
+    pub tasks: Arc<Mutex<Vec<JoinHandle<()>>>>,
+    inbox: mpsc::UnboundedSender<PbftMessage>,
+    message_rx: Arc<Mutex<Option<mpsc::UnboundedReceiver<PbftMessage>>>>,
+    pub view_change_timeout: Duration,
+}
+
+impl PbftNode {
+    pub fn with_config(node_id: NodeId, max_faulty: u32, config: PbftConfig) -> anyhow::Result<Self> {
+        let max_faulty = max_faulty as usize;
+        let replica_count = config.peers.len() + 1;
+        if replica_count < 3 * max_faulty + 1 {
+            return Err(anyhow::anyhow!(
+                "PBFT tolerating {} faulty replicas needs at least {}, but {} are configured",
+                max_faulty, 3 * max_faulty + 1, replica_count
+            ));
+        }
+        if config.checkpoint_interval == 0 {
+            return Err(anyhow::anyhow!("PBFT checkpoint_interval must be at least 1"));
+        }
+
+        let signing_key = SigningKey::from_bytes(&config.secret_key.unwrap_or_else(rand::random));
+        let mut public_keys = HashMap::new();
+        for (peer, entry) in &config.peers {
+            let key = VerifyingKey::from_bytes(&entry.public_key)
+                .map_err(|e| anyhow::anyhow!("Invalid public key for PBFT replica {}: {}", peer, e))?;
+            public_keys.insert(peer.clone(), key);
+        }
+        public_keys.insert(node_id.clone(), signing_key.verifying_key());
+
+        let mut replicas: Vec<NodeId> = public_keys.keys().cloned().collect();
+        replicas.sort();
+        let (inbox, message_rx) = mpsc::unbounded_channel();
+
+        Ok(Self {
+            node_id,
+            replicas,
+            max_faulty,
+            signing_key: Arc::new(signing_key),
+            public_keys: Arc::new(public_keys),
+            state: Arc::new(Mutex::new(PbftState::default())),
+            progress: Arc::new(watch::channel(0).0),
+            votes: Arc::new(RwLock::new(HashMap::new())),
+            state_machine: Arc::new(RwLock::new(None)),
+            transport: Arc::new(RwLock::new(None)),
+            tasks: Arc::new(Mutex::new(Vec::new())),
+            inbox,
+            message_rx: Arc::new(Mutex::new(Some(message_rx))),
+            view_change_timeout: Duration::from_millis(config.view_change_timeout_ms),
+            config,
+        })
+    }
+
+    /// Sender for messages addressed to this replica; transports deliver into it
+    pub fn inbox(&self) -> mpsc::UnboundedSender<PbftMessage> {
+        self.inbox.clone()
+    }
+
+    /// Route outgoing messages through `transport`
+    pub async fn set_transport(&self, transport: Arc<dyn ConsensusTransport<PbftMessage>>) {
+        *self.transport.write().await = Some(transport);
+    }
+
+    /// The replica that orders requests in `view`
+    pub fn primary(&self, view: u64) -> &NodeId {
+        &self.replicas[(view % self.replicas.len() as u64) as usize]
+    }
+
+    /// Replicas that must agree at each step: 2f + 1 when there are 3f + 1 replicas. Any two
+    /// quorums share at least one correct replica.
+    pub fn quorum(&self) -> usize {
+        (self.replicas.len() + self.max_faulty) / 2 + 1
+    }
+
+    pub async fn view(&self) -> u64 {
+        self.state.lock().await.view
+    }
+
+    /// Sequence number of the last request executed here
+    pub async fn last_executed(&self) -> u64 {
+        self.state.lock().await.last_executed
+    }
+
+    pub async fn stable_checkpoint(&self) -> u64 {
+        self.state.lock().await.stable_checkpoint
+    }
+
+    pub async fn start_consensus_loop(&self) -> anyhow::Result<()> {
+        let rx = self.message_rx.lock().await.take()
+            .ok_or_else(|| anyhow::anyhow!("PBFT replica {} already started", self.node_id))?;
+        let mut tasks = self.tasks.lock().await;
+        {
+            let pbft = self.clone();
+            tasks.push(tokio::spawn(async move {
+                pbft.message_handler_loop(rx).await;
+            }));
+        }
+        {
+            let pbft = self.clone();
+            tasks.push(tokio::spawn(async move {
+                pbft.view_change_timer_loop().await;
+            }));
+        }
+        Ok(())
+    }
+
+    fn sign(&self, payload: PbftPayload) -> PbftMessage {
+        let signature = self.signing_key.sign(&signed_bytes(&self.node_id, &payload));
+        PbftMessage {
+            from: self.node_id.clone(),
+            payload,
+            signature: signature.to_bytes().to_vec(),
+        }
+    }
+
+    /// Whether `message` was signed by the replica it claims to come from
+    fn verify(&self, message: &PbftMessage) -> bool {
+        let Some(key) = self.public_keys.get(&message.from) else {
+            return false;
+        };
+        let Ok(signature) = Signature::from_slice(&message.signature) else {
+            return false;
+        };
+        key.verify_strict(&signed_bytes(&message.from, &message.payload), &signature).is_ok()
+    }
+
+    /// Sign `payload` and send it to every replica, this one included
+    async fn broadcast(&self, payload: PbftPayload) {
+        let message = self.sign(payload);
+        for peer in self.replicas.iter().filter(|replica| **replica != self.node_id) {
+            self.send(peer, message.clone()).await;
+        }
+        let _ = self.inbox.send(message);
+    }
+
+    /// Hand a message to the transport without waiting for delivery
+    async fn send(&self, peer: &NodeId, message: PbftMessage) {
+        let Some(transport) = self.transport.read().await.clone() else {
+            return;
+        };
+        let peer = peer.clone();
+        tokio::spawn(async move {
+            if let Err(e) = transport.send(&peer, message).await {
+                tracing::debug!("Failed to send PBFT message to {}: {}", peer, e);
+            }
+        });
+    }
+
+    async fn message_handler_loop(&self, mut rx: mpsc::UnboundedReceiver<PbftMessage>) {
+
+        while let Some(message) = rx.recv().await {
+            self.handle_message(message).await;
+        }
+    }
+
+    /// Vote for the next view whenever the timer runs out, while a request is outstanding
+    /// or a view change has not completed
+    async fn view_change_timer_loop(&self) {
+        loop {
+            sleep(self.view_change_timeout / 10).await;
+
+            let mut state = self.state.lock().await;
+            if state.timer.is_some_and(|timer| Instant::now() >= timer) {
+                let view = state.view + 1;
+                tracing::info!("{} gave up waiting in view {}", self.node_id, state.view);
+                self.start_view_change(&mut state, view).await;
+            }
+        }
+    }
+
+    async fn handle_message(&self, message: PbftMessage) {
+        if !self.verify(&message) {
+            tracing::warn!("{} dropped a PBFT message with a bad signature claiming to be from {}", self.node_id, message.from);
+            return;
+        }
+
+        let mut state = self.state.lock().await;
+        match &message.payload {
+            PbftPayload::Request(request) => self.handle_request(&mut state, &message, request).await,
+            PbftPayload::PrePrepare { view, .. } => self.handle_pre_prepare(&mut state, &message, *view).await,
+            PbftPayload::Prepare { view, sequence, .. } => self.handle_prepare(&mut state, &message, *view, *sequence).await,
+            PbftPayload::Commit { view, sequence, .. } => self.handle_commit(&mut state, &message, *view, *sequence).await,
+            PbftPayload::Checkpoint { sequence, .. } => self.handle_checkpoint(&mut state, &message, *sequence).await,
+            PbftPayload::ViewChange { view, .. } => self.handle_view_change(&mut state, &message, *view).await,
+            PbftPayload::NewView { view, view_changes, pre_prepares } => {
+                self.handle_new_view(&mut state, &message.from, *view, view_changes, pre_prepares).await;
+            }
+            PbftPayload::FetchState { sequence } => {
+                if let Some(data) = state.snapshots.get(sequence) {
+                    let reply = self.sign(PbftPayload::State { sequence: *sequence, data: data.clone() });
+                    self.send(&message.from, reply).await;
+                }
+            }
+            PbftPayload::State { sequence, data } => self.handle_state(&mut state, *sequence, data).await,
+        }
+    }
+
+    /// The request inside a signed request message, if its origin really sent it
+    fn valid_request(&self, message: &PbftMessage) -> Option<Request> {
+        match &message.payload {
+            PbftPayload::Request(request) if request.origin == message.from && self.verify(message) => Some(request.clone()),
+            _ => None,
+        }
+    }
+
+    /// Whether `sequence` falls between the stable checkpoint and the high watermark
+    fn in_window(&self, state: &PbftState, sequence: u64) -> bool {
+        sequence > state.stable_checkpoint && sequence <= state.stable_checkpoint + 2 * self.config.checkpoint_interval
+    }
+
+    async fn handle_request(&self, state: &mut PbftState, message: &PbftMessage, request: &Request) {
+        if request.origin != message.from {
+            return;
+        }
+        let key = request.key();
+        if state.executed.contains_key(&key) || state.pending.iter().any(|(pending, _)| *pending == key) {
+            return;
+        }
+        state.pending.push((key, message.clone()));
+        // A replica catching up cannot tell whether the primary is stalling
+        if state.timer.is_none() && !state.view_changing && state.fetching.is_none() {
+            state.timer = Some(Instant::now() + self.view_change_timeout);
+        }
+        self.assign_pending(state).await;
+    }
+
+    /// As primary, give pending requests the next sequence numbers the window allows
+    async fn assign_pending(&self, state: &mut PbftState) {
+        if *self.primary(state.view) != self.node_id || state.view_changing {
+            return;
+        }
+        let unassigned: Vec<PbftMessage> = state.pending.iter()
+            .filter(|(key, _)| !state.assigned.contains(key))
+            .map(|(_, message)| message.clone())
+            .collect();
+        for message in unassigned {
+            let sequence = state.last_assigned + 1;
+            if !self.in_window(state, sequence) {
+                break;
+            }
+            let PbftPayload::Request(request) = &message.payload else {
+                continue;
+            };
+            state.last_assigned = sequence;
+            state.assigned.insert(request.key());
+            let digest = request.digest();
+            self.broadcast(PbftPayload::PrePrepare {
+                view: state.view,
+                sequence,
+                digest,
+                request: Some(Box::new(message)),
+            }).await;
+        }
+    }
+
+    async fn handle_pre_prepare(&self, state: &mut PbftState, message: &PbftMessage, view: u64) {
+        // Messages between replicas are not ordered, so the new primary's first pre-prepares
+        // can arrive before its new-view message
+        if view > state.view || (view == state.view && state.view_changing) {
+            if state.future.len() < MAX_FUTURE_MESSAGES {
+                state.future.push(message.clone());
+            }
+            return;
+        }
+        if view < state.view || message.from != *self.primary(view) {
+            return;
+        }
+        self.accept_pre_prepare(state, message).await;
+    }
+
+    /// Accept a pre-prepare from the primary of the current view and prepare it
+    async fn accept_pre_prepare(&self, state: &mut PbftState, message: &PbftMessage) {
+        let PbftPayload::PrePrepare { view, sequence, digest, request } = &message.payload else {
+            return;
+        };
+        if !self.in_window(state, *sequence) {
+            return;
+        }
+        let request = match request {
+            Some(request) => match self.valid_request(request) {
+                Some(request) if request.digest() == *digest => Some(request),
+                _ => return,
+            },
+            None if *digest == NULL_DIGEST => None,
+            None => return,
+        };
+
+        let slot = state.slots.entry(*sequence).or_default();
+        // One pre-prepare per view; a primary that sends two conflicting ones is ignored
+        if slot.accepted.as_ref().is_some_and(|accepted| accepted.view >= *view) {
+            return;
+        }
+        slot.accepted = Some(Accepted {
+            view: *view,
+            digest: *digest,
+            request,
+            message: message.clone(),
+        });
+        slot.prepared = false;
+
+        if *self.primary(*view) != self.node_id {
+            self.broadcast(PbftPayload::Prepare { view: *view, sequence: *sequence, digest: *digest }).await;
+        }
+        self.check_prepared(state, *sequence).await;
+    }
+
+    async fn handle_prepare(&self, state: &mut PbftState, message: &PbftMessage, view: u64, sequence: u64) {
+        // The primary's pre-prepare stands in for its prepare
+        if view < state.view || !self.in_window(state, sequence) || message.from == *self.primary(view) {
+            return;
+        }
+        let slot = state.slots.entry(sequence).or_default();
+        if slot.prepares.get(&message.from).and_then(phase_vote).is_some_and(|(previous, ..)| previous > view) {
+            return;
+        }
+        slot.prepares.insert(message.from.clone(), message.clone());
+        self.check_prepared(state, sequence).await;
+    }
+
+    /// Commit once the pre-prepare and matching prepares make up a quorum
+    async fn check_prepared(&self, state: &mut PbftState, sequence: u64) {
+        let Some(slot) = state.slots.get_mut(&sequence) else {
+            return;
+        };
+        let Some(accepted) = &slot.accepted else {
+            return;
+        };
+        if slot.prepared {
+            return;
+        }
+        let prepares: Vec<PbftMessage> = slot.matching(&slot.prepares, sequence).cloned().collect();
+        if prepares.len() + 1 < self.quorum() {
+            return;
+        }
+
+        let (view, digest) = (accepted.view, accepted.digest);
+        let proof = PreparedProof {
+            pre_prepare: accepted.message.clone(),
+            prepares,
+        };
+        slot.prepared = true;
+        state.prepared.insert(sequence, proof);
+        self.broadcast(PbftPayload::Commit { view, sequence, digest }).await;
+        // Commits from faster replicas may already be in
+        self.try_execute(state).await;
+    }
+
+    async fn handle_commit(&self, state: &mut PbftState, message: &PbftMessage, view: u64, sequence: u64) {
+        if view < state.view || !self.in_window(state, sequence) {
+            return;
+        }
+        let slot = state.slots.entry(sequence).or_default();
+        if slot.commits.get(&message.from).and_then(phase_vote).is_some_and(|(previous, ..)| previous > view) {
+            return;
+        }
+        slot.commits.insert(message.from.clone(), message.clone());
+        self.try_execute(state).await;
+        // Wake proposers waiting for more replicas to commit
+        self.progress.send_modify(|_| {});
+    }
+
+    /// Execute committed requests in sequence order, stopping at the first gap
+    async fn try_execute(&self, state: &mut PbftState) {
+        let machine = self.state_machine.read().await.clone();
+        let mut executed_any = false;
+        loop {
+            let sequence = state.last_executed + 1;
+            let Some(slot) = state.slots.get(&sequence) else {
+                break;
+            };
+            let Some(accepted) = slot.accepted.as_ref().filter(|_| slot.prepared && slot.commit_count(sequence) >= self.quorum()) else {
+                break;
+            };
+
+            // A request reassigned after a view change executes only once
+            if let Some(request) = accepted.request.clone() {
+                let key = request.key();
+                if !state.executed.contains_key(&key) {
+                    let committed = CommittedProposal {
+                        term: accepted.view,
+                        index: sequence,
+                        value: request.value,
+                    };
+                    if let Some(machine) = &machine {
+                        // Skipping the request would leave this replica's state behind the others';
+                        // stop here and retry it on the next commit
+                        if let Err(e) = machine.apply(&committed).await {
+                            tracing::error!("Failed to apply request {}, halting execution: {}", sequence, e);
+                            break;
+                        }
+                    }
+                    state.executed.insert(key.clone(), committed);
+                }
+                state.pending.retain(|(pending, _)| *pending != key);
+            }
+            state.last_executed = sequence;
+            executed_any = true;
+
+            if sequence.is_multiple_of(self.config.checkpoint_interval) {
+                self.take_checkpoint(state, sequence, machine.as_deref()).await;
+            }
+        }
+
+        if executed_any {
+            // The primary is making progress; give the next request a full timeout
+            if !state.view_changing {
+                state.timer = (!state.pending.is_empty()).then(|| Instant::now() + self.view_change_timeout);
+            }
+            self.progress.send_modify(|_| {});
+        }
+    }
+
+    async fn take_checkpoint(&self, state: &mut PbftState, sequence: u64, machine: Option<&dyn StateMachine>) {
+        let data = match machine {
+            Some(machine) => match machine.snapshot().await {
+                Ok(data) => data,
+                Err(e) => {
+                    tracing::warn!("Failed to snapshot at checkpoint {}: {}", sequence, e);
+                    return;
+                }
+            },
+            None => Vec::new(),
+        };
+        let state_digest = Sha256::digest(&data).into();
+        state.snapshots.insert(sequence, data);
+        self.broadcast(PbftPayload::Checkpoint { sequence, state_digest }).await;
+    }
+
+    async fn handle_checkpoint(&self, state: &mut PbftState, message: &PbftMessage, sequence: u64) {
+        if sequence <= state.stable_checkpoint || !sequence.is_multiple_of(self.config.checkpoint_interval) {
+            return;
+        }
+        state.checkpoints.entry(sequence).or_default().insert(message.from.clone(), message.clone());
+        self.reach_checkpoint(state, sequence).await;
+    }
+
+    /// A quorum of matching checkpoint messages for `sequence`, with the digest they agree on
+    fn checkpoint_certificate<'a>(&self, messages: impl IntoIterator<Item = &'a PbftMessage>, sequence: u64) -> Option<(Digest, Vec<PbftMessage>)> {
+        let mut by_digest: HashMap<Digest, HashMap<&NodeId, &PbftMessage>> = HashMap::new();
+        for message in messages {
+            if let PbftPayload::Checkpoint { sequence: checkpoint, state_digest } = &message.payload {
+                if *checkpoint == sequence && self.verify(message) {
+                    by_digest.entry(*state_digest).or_default().insert(&message.from, message);
+                }
+            }
+        }
+        by_digest.into_iter()
+            .find(|(_, signers)| signers.len() >= self.quorum())
+            .map(|(digest, signers)| (digest, signers.into_values().cloned().collect()))
+    }
+
+    /// Make `sequence` the stable checkpoint once a quorum agrees on it, fetching the state
+    /// first if this replica has not executed that far
+    async fn reach_checkpoint(&self, state: &mut PbftState, sequence: u64) {
+        let Some(messages) = state.checkpoints.get(&sequence) else {
+            return;
+        };
+        let Some((_, proof)) = self.checkpoint_certificate(messages.values(), sequence) else {
+            return;
+        };
+
+        if state.last_executed >= sequence {
+            self.stabilize(state, sequence, proof).await;
+        } else if state.fetching.is_none_or(|fetching| fetching < sequence) {
+            // The messages this replica missed may already be discarded everywhere else
+            let Some(source) = proof.iter().map(|message| &message.from).find(|from| **from != self.node_id) else {
+                return;
+            };
+            tracing::info!("{} is behind checkpoint {}; fetching it from {}", self.node_id, sequence, source);
+            state.fetching = Some(sequence);
+            state.timer = None;
+            let request = self.sign(PbftPayload::FetchState { sequence });
+            self.send(source, request).await;
+        }
+    }
+
+    /// Discard everything up to the stable checkpoint `sequence`
+    async fn stabilize(&self, state: &mut PbftState, sequence: u64, proof: Vec<PbftMessage>) {
+        state.stable_checkpoint = sequence;
+        state.stable_proof = proof;
+        state.slots = state.slots.split_off(&(sequence + 1));
+        state.prepared = state.prepared.split_off(&(sequence + 1));
+        state.checkpoints = state.checkpoints.split_off(&(sequence + 1));
+        state.snapshots = state.snapshots.split_off(&sequence);
+        if state.fetching.is_some_and(|fetching| fetching <= sequence) {
+            state.fetching = None;
+        }
+        state.last_assigned = state.last_assigned.max(sequence);
+        tracing::debug!("{} checkpoint {} is stable", self.node_id, sequence);
+        // The window has moved on
+        self.assign_pending(state).await;
+    }
+
+    /// Install the state at a stable checkpoint fetched from another replica
+    async fn handle_state(&self, state: &mut PbftState, sequence: u64, data: &[u8]) {
+        if sequence <= state.last_executed {
+            return;
+        }
+        let Some(messages) = state.checkpoints.get(&sequence) else {
+            return;
+        };
+        let Some((digest, proof)) = self.checkpoint_certificate(messages.values(), sequence) else {
+            return;
+        };
+        if <[u8; 32]>::from(Sha256::digest(data)) != digest {
+            tracing::warn!("{} received state for checkpoint {} that does not match its digest", self.node_id, sequence);
+            return;
+        }
+
+        if let Some(machine) = self.state_machine.read().await.clone() {
+            if let Err(e) = machine.restore(data).await {
+                tracing::warn!("Failed to restore checkpoint {}: {}", sequence, e);
+                return;
+            }
+        }
+        tracing::info!("{} caught up to checkpoint {}", self.node_id, sequence);
+        state.last_executed = sequence;
+        state.snapshots.insert(sequence, data.to_vec());
+        // Requests executed while this replica was behind are indistinguishable from new ones;
+        // their origins still hold them if they are outstanding
+        state.pending.clear();
+        self.stabilize(state, sequence, proof).await;
+        self.try_execute(state).await;
+        self.progress.send_modify(|_| {});
+    }
+
+    /// Stop taking part in `view`'s predecessor and vote for `view`
+    async fn start_view_change(&self, state: &mut PbftState, view: u64) {
+        if view <= state.view {
+            return;
+        }
+        state.failed_view_changes = match state.view_changing {
+            true => state.failed_view_changes + 1,
+            false => 0,
+        };
+        state.view = view;
+        state.view_changing = true;
+        state.timer = Some(Instant::now() + self.view_change_timeout * 2u32.pow(state.failed_view_changes.min(6)));
+        tracing::info!("{} voting to move to view {} with primary {}", self.node_id, view, self.primary(view));
+
+        self.broadcast(PbftPayload::ViewChange {
+            view,
+            checkpoint: state.stable_checkpoint,
+            checkpoint_proof: state.stable_proof.clone(),
+            prepared: state.prepared.values().cloned().collect(),
+        }).await;
+    }
+
+    /// Whether a prepared certificate from before `view` is signed by a quorum and falls in
+    /// the window after `checkpoint`
+    fn valid_prepared_proof(&self, proof: &PreparedProof, view: u64, checkpoint: u64) -> bool {
+        let PbftPayload::PrePrepare { view: prepared_view, sequence, digest, request } = &proof.pre_prepare.payload else {
+            return false;
+        };
+        let primary = self.primary(*prepared_view);
+        if *prepared_view >= view || proof.pre_prepare.from != *primary || !self.verify(&proof.pre_prepare) {
+            return false;
+        }
+        if *sequence <= checkpoint || *sequence > checkpoint + 2 * self.config.checkpoint_interval {
+            return false;
+        }
+        let request_matches = match request {
+            Some(request) => self.valid_request(request).is_some_and(|request| request.digest() == *digest),
+            None => *digest == NULL_DIGEST,
+        };
+        if !request_matches {
+            return false;
+        }
+
+        let expected = Some((*prepared_view, *sequence, *digest));
+        let signers: HashSet<&NodeId> = proof.prepares.iter()
+            .filter(|prepare| matches!(prepare.payload, PbftPayload::Prepare { .. }))
+            .filter(|prepare| prepare.from != *primary && phase_vote(prepare) == expected && self.verify(prepare))
+            .map(|prepare| &prepare.from)
+            .collect();
+        signers.len() + 1 >= self.quorum()
+    }
+
+    /// Whether a view-change message for `view` carries valid proofs
+    fn valid_view_change(&self, message: &PbftMessage, view: u64) -> bool {
+        let PbftPayload::ViewChange { view: target, checkpoint, checkpoint_proof, prepared } = &message.payload else {
+            return false;
+        };
+        if *target != view {
+            return false;
+        }
+        if *checkpoint > 0 && self.checkpoint_certificate(checkpoint_proof, *checkpoint).is_none() {
+            return false;
+        }
+        prepared.iter().all(|proof| self.valid_prepared_proof(proof, view, *checkpoint))
+    }
+
+    /// The latest stable checkpoint among `view_changes`, and what the new primary must
+    /// assign after it: each request prepared in the highest view it was, or a null request
+    fn new_view_pre_prepares(&self, view_changes: &[PbftMessage]) -> (u64, Vec<Reassignment>) {
+        let mut checkpoint = 0;
+        let mut chosen: BTreeMap<u64, (u64, Digest, Option<Box<PbftMessage>>)> = BTreeMap::new();
+        for message in view_changes {
+            let PbftPayload::ViewChange { checkpoint: stable, prepared, .. } = &message.payload else {
+                continue;
+            };
+            checkpoint = checkpoint.max(*stable);
+            for proof in prepared {
+                let PbftPayload::PrePrepare { view, sequence, digest, request } = &proof.pre_prepare.payload else {
+                    continue;
+                };
+                if chosen.get(sequence).is_none_or(|(chosen_view, ..)| chosen_view < view) {
+                    chosen.insert(*sequence, (*view, *digest, request.clone()));
+                }
+            }
+        }
+
+        let last = chosen.keys().next_back().copied().unwrap_or(checkpoint).max(checkpoint);
+        let pre_prepares = (checkpoint + 1..=last)
+            .map(|sequence| match chosen.remove(&sequence) {
+                Some((_, digest, request)) => (sequence, digest, request),
+                None => (sequence, NULL_DIGEST, None),
+            })
+            .collect();
+        (checkpoint, pre_prepares)
+    }
+
+    async fn handle_view_change(&self, state: &mut PbftState, message: &PbftMessage, view: u64) {
+        if view < state.view || (view == state.view && !state.view_changing) {
+            return;
+        }
+        if !self.valid_view_change(message, view) {
+            tracing::warn!("{} ignored an invalid view change from {}", self.node_id, message.from);
+            return;
+        }
+        state.view_changes.entry(view).or_default().insert(message.from.clone(), message.clone());
+
+        // f + 1 replicas cannot all be faulty, so join them rather than wait for a timeout
+        let supporters: HashSet<&NodeId> = state.view_changes.range(state.view + 1..)
+            .flat_map(|(_, messages)| messages.keys())
+            .collect();
+        if supporters.len() > self.max_faulty {
+            if let Some(lowest) = state.view_changes.range(state.view + 1..).next().map(|(view, _)| *view) {
+                self.start_view_change(state, lowest).await;
+            }
+        }
+
+        self.try_new_view(state).await;
+    }
+
+    /// As primary of the view being changed to, announce it once a quorum has voted for it
+    async fn try_new_view(&self, state: &mut PbftState) {
+        let view = state.view;
+        if !state.view_changing || *self.primary(view) != self.node_id || state.new_view_sent == Some(view) {
+            return;
+        }
+        let Some(messages) = state.view_changes.get(&view).filter(|messages| messages.len() >= self.quorum()) else {
+            return;
+        };
+
+        let view_changes: Vec<PbftMessage> = messages.values().take(self.quorum()).cloned().collect();
+        let (_, assignments) = self.new_view_pre_prepares(&view_changes);
+        let pre_prepares = assignments.into_iter()
+            .map(|(sequence, digest, request)| self.sign(PbftPayload::PrePrepare { view, sequence, digest, request }))
+            .collect();
+        state.new_view_sent = Some(view);
+        tracing::info!("{} starting view {}", self.node_id, view);
+        self.broadcast(PbftPayload::NewView { view, view_changes, pre_prepares }).await;
+    }
+
+    async fn handle_new_view(&self, state: &mut PbftState, from: &NodeId, view: u64, view_changes: &[PbftMessage], pre_prepares: &[PbftMessage]) {
+        if view < state.view || (view == state.view && !state.view_changing) || *from != *self.primary(view) {
+            return;
+        }
+
+        // Check the new primary's work: a quorum voted for this view, and the pre-prepares are
+        // exactly those the votes call for
+        let senders: HashSet<&NodeId> = view_changes.iter().map(|message| &message.from).collect();
+        if senders.len() < self.quorum() || senders.len() != view_changes.len() {
+            return;
+        }
+        if !view_changes.iter().all(|message| self.verify(message) && self.valid_view_change(message, view)) {
+            return;
+        }
+        let (checkpoint, expected) = self.new_view_pre_prepares(view_changes);
+        let matches = expected.len() == pre_prepares.len()
+            && expected.iter().zip(pre_prepares).all(|((sequence, digest, _), message)| {
+                matches!(&message.payload, PbftPayload::PrePrepare { view: v, sequence: s, digest: d, .. } if *v == view && s == sequence && d == digest)
+                    && message.from == *from
+                    && self.verify(message)
+            });
+        if !matches {
+            tracing::warn!("{} rejected the new view {} from {}", self.node_id, view, from);
+            return;
+        }
+
+        tracing::info!("{} entered view {} with primary {}", self.node_id, view, from);
+        state.view = view;
+        state.view_changing = false;
+        state.failed_view_changes = 0;
+        state.view_changes = state.view_changes.split_off(&(view + 1));
+        state.assigned.clear();
+        state.last_assigned = expected.last().map(|(sequence, ..)| *sequence).unwrap_or(checkpoint).max(state.stable_checkpoint);
+
+        // The view changes prove the checkpoint they start from, which may be ahead of this replica
+        if checkpoint > state.stable_checkpoint {
+            let proof = view_changes.iter().find_map(|message| match &message.payload {
+                PbftPayload::ViewChange { checkpoint: stable, checkpoint_proof, .. } if *stable == checkpoint => Some(checkpoint_proof),
+                _ => None,
+            });
+            let held = state.checkpoints.entry(checkpoint).or_default();
+            for message in proof.into_iter().flatten() {
+                held.insert(message.from.clone(), message.clone());
+            }
+            self.reach_checkpoint(state, checkpoint).await;
+        }
+
+        for message in pre_prepares {
+            if let PbftPayload::PrePrepare { request: Some(request), .. } = &message.payload {
+                if let Some(request) = self.valid_request(request) {
+                    state.assigned.insert(request.key());
+                }
+            }
+            self.accept_pre_prepare(state, message).await;
+        }
+        state.timer = (!state.pending.is_empty()).then(|| Instant::now() + self.view_change_timeout);
+
+        // Replay the pre-prepares that arrived early, dropping those for views skipped over
+        let future = std::mem::take(&mut state.future);
+        for message in future {
+            match message.payload {
+                PbftPayload::PrePrepare { view: held, .. } if held == view => {
+                    let _ = self.inbox.send(message);
+                }
+                PbftPayload::PrePrepare { view: held, .. } if held > view => state.future.push(message),
+                _ => {}
+            }
+        }
+        self.assign_pending(state).await;
+    }
+
+    /// Record `voter`'s ballot on a proposal, replacing any earlier one
+    pub async fn record_vote(&self, proposal_id: &ProposalId, voter: NodeId, vote: Vote) {
+        self.votes.write().await
+            .entry(proposal_id.0.clone())
+            .or_default()
+            .insert(voter, vote);
+    }
+}
+
+#[async_trait]
+impl ConsensusEngine for PbftNode {
+    async fn start(&mut self) -> anyhow::Result<()> {
+        if let Some(addr) = self.config.listen_addr {
+            let peers = self.config.peers.iter()
+                .filter_map(|(peer, entry)| entry.addr.map(|addr| (peer.clone(), addr)))
+                .collect();
+            let transport = TcpTransport::bind(addr, peers, self.inbox()).await?;
+            self.set_transport(Arc::new(transport)).await;
+        }
+        self.start_consensus_loop().await
+    }
+
+    /// Any replica accepts proposals. The request goes to every replica, so if the primary
+    /// does not order it the others replace the primary.
+    async fn propose(&self, value: Vec<u8>) -> anyhow::Result<ProposalId> {
+        let request = Request {
+            origin: self.node_id.clone(),
+            id: uuid::Uuid::new_v4().to_string(),
+            value,
+        };
+        let proposal_id = ProposalId(request.key());
+        self.broadcast(PbftPayload::Request(request)).await;
+        Ok(proposal_id)
+    }
+
+    async fn wait_for_commit(&self, proposal_id: &ProposalId, required_acks: usize) -> anyhow::Result<CommittedProposal> {
+        if required_acks > self.replicas.len() {
+            return Err(anyhow::anyhow!("Proposal needs {} acknowledgements but the cluster has {} replicas", required_acks, self.replicas.len()));
+        }
+
+        // Subscribe before checking so an execution between the check and the wait is not missed
+        let mut progress = self.progress.subscribe();
+        loop {
+            {
+                let state = self.state.lock().await;
+                if let Some(committed) = state.executed.get(&proposal_id.0) {
+                    // Behind a stable checkpoint only the quorum that agreed on it is known
+                    let acks = match state.slots.get(&committed.index) {
+                        Some(slot) => slot.commit_count(committed.index),
+                        None => self.quorum(),
+                    };
+                    if acks >= required_acks {
+                        return Ok(committed.clone());
+                    }
+                }
+            }
+            progress.changed().await?;
+        }
+    }
+
+    async fn vote(&self, proposal_id: ProposalId, vote: Vote) -> anyhow::Result<()> {
+        self.record_vote(&proposal_id, self.node_id.clone(), vote).await;
+        Ok(())
+    }
+
+    async fn receive_vote(&self, proposal_id: ProposalId, voter: NodeId, vote: Vote) -> anyhow::Result<()> {
+        self.record_vote(&proposal_id, voter, vote).await;
+        Ok(())
+    }
+
+    async fn tally(&self, proposal_id: &ProposalId) -> anyhow::Result<VoteTally> {
+        let mut tally = VoteTally {
+            voters: self.replicas.len(),
+            ..VoteTally::default()
+        };
+        if let Some(ballots) = self.votes.read().await.get(&proposal_id.0) {
+            for vote in ballots.values() {
+                match vote {
+                    Vote::Accept => tally.accept += 1,
+                    Vote::Reject => tally.reject += 1,
+                    Vote::Abstain => tally.abstain += 1,
+                }
+            }
+        }
+        Ok(tally)
+    }
+
+    async fn on_commit(&self, _value: Vec<u8>) -> anyhow::Result<()> {
+        // Executed requests reach the state machine as they commit
+        Ok(())
+    }
+
+    async fn set_state_machine(&self, machine: Arc<dyn StateMachine>) -> anyhow::Result<()> {
+        *self.state_machine.write().await = Some(machine);
+        Ok(())
+    }
+
+    async fn add_learner(&self, _node: NodeId, _addr: Option<SocketAddr>) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!("PBFT replicas are fixed by configuration"))
+    }
+
+    async fn add_voter(&self, _node: NodeId, _addr: Option<SocketAddr>) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!("PBFT replicas are fixed by configuration"))
+    }
+
+    async fn remove_server(&self, _node: NodeId) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!("PBFT replicas are fixed by configuration"))
+    }
+
+    async fn membership(&self) -> anyhow::Result<Membership> {
+        Ok(Membership {
+            voters: self.replicas.iter().cloned().collect(),
+            ..Membership::default()
+        })
+    }
+
+    async fn transfer_leadership(&self, _target: NodeId) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!("The PBFT primary only changes through a view change"))
+    }
+
+    async fn read_index(&self, consistency: ReadConsistency) -> anyhow::Result<u64> {
+        match consistency {
+            ReadConsistency::Stale => Ok(self.state.lock().await.last_executed),
+            // A faulty primary could claim a lease it no longer holds, so every consistent
+            // read is ordered like a write and reflects everything executed before it
+            ReadConsistency::Linearizable | ReadConsistency::Lease => {
+                let proposal_id = self.propose(Vec::new()).await?;
+                Ok(self.wait_for_commit(&proposal_id, 0).await?.index)
+            }
+        }
+    }
+}
@@ -3,17 +3,29 @@
  * Basic Raft implementation with leader election and log replication
  */
 
-use crate::{ConsensusEngine, ProposalId, Vote, Message, NodeId};
+use crate::{ConsensusEngine, CommittedProposal, ProposalId, RaftConfig, ReadConsistency, StateMachine, Vote, VoteTally, NodeId};
+use crate::raft_storage::{HardState, RaftStorage};
+use crate::transport::{ConsensusTransport, TcpTransport};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::sync::{RwLock, mpsc, Mutex};
+use tokio::sync::{RwLock, mpsc, oneshot, watch, Mutex};
+use tokio::task::JoinHandle;
 use tokio::time::{Duration, Instant, sleep, timeout};
 
+/// Most entries sent in one AppendEntries; a lagging follower catches up over several rounds
+const MAX_APPEND_ENTRIES: usize = 256;
+
+/// Shortest election timeout; each node picks its own between this and twice this
+pub const ELECTION_TIMEOUT_MIN: Duration = Duration::from_millis(150);
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum NodeState {
     Follower,
+    /// Canvassing for a pre-vote before starting a real election
+    PreCandidate,
     Candidate,
     Leader,
 }
@@ -23,6 +35,140 @@ pub struct LogEntry {
     pub term: u64,
     pub index: u64,
     pub value: Vec<u8>,
+    /// Set on configuration-change entries, which carry no value
+    pub membership: Option<Membership>,
+}
+
+/// Cluster configuration. While a joint change is in progress, elections and commits need a
+/// majority of both `voters` and `incoming`. Learners receive the log but never vote.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Membership {
+    /// Index of the entry that set this configuration; 0 for the bootstrap configuration
+    pub index: u64,
+    pub voters: BTreeSet<NodeId>,
+    /// Voters being moved to during a joint change
+    pub incoming: Option<BTreeSet<NodeId>>,
+    pub learners: BTreeSet<NodeId>,
+    /// Raft addresses of members added at runtime
+    pub addresses: BTreeMap<NodeId, SocketAddr>,
+}
+
+impl Membership {
+    pub fn is_joint(&self) -> bool {
+        self.incoming.is_some()
+    }
+    
+    /// Whether `node` votes in either half of the configuration
+    pub fn is_voter(&self, node: &NodeId) -> bool {
+        self.voters.contains(node) || self.incoming.as_ref().is_some_and(|incoming| incoming.contains(node))
+    }
+    
+    /// Voters in either half, counted once
+    pub fn voter_count(&self) -> usize {
+        match &self.incoming {
+            Some(incoming) => self.voters.union(incoming).count(),
+            None => self.voters.len(),
+        }
+    }
+    
+    /// Every node that receives the log
+    pub fn members(&self) -> BTreeSet<&NodeId> {
+        self.voters.iter()
+            .chain(self.incoming.iter().flatten())
+            .chain(&self.learners)
+            .collect()
+    }
+    
+    /// Whether `granted` holds a majority of every voter set
+    pub fn has_quorum(&self, granted: &HashSet<NodeId>) -> bool {
+        let majority = |voters: &BTreeSet<NodeId>| voters.iter().filter(|v| granted.contains(*v)).count() > voters.len() / 2;
+        majority(&self.voters) && self.incoming.as_ref().is_none_or(majority)
+    }
+    
+    /// Highest index stored on a majority of every voter set, given each voter's match index
+    pub fn quorum_index(&self, matched: impl Fn(&NodeId) -> u64) -> u64 {
+        let majority_index = |voters: &BTreeSet<NodeId>| {
+            let mut indices: Vec<u64> = voters.iter().map(&matched).collect();
+            indices.sort_unstable_by(|a, b| b.cmp(a));
+            // The value at position n/2 in descending order is held by at least n/2 + 1 voters
+            indices.get(indices.len() / 2).copied().unwrap_or(0)
+        };
+        let index = majority_index(&self.voters);
+        match &self.incoming {
+            Some(incoming) => index.min(majority_index(incoming)),
+            None => index,
+        }
+    }
+}
+
+/// The log after the last compaction. Entries up to `compacted_index` are covered by a snapshot.
+#[derive(Debug, Clone, Default)]
+pub struct RaftLog {
+    /// Index and term of the last discarded entry; zero before the first compaction
+    pub compacted_index: u64,
+    pub compacted_term: u64,
+    pub entries: Vec<LogEntry>,
+}
+
+impl RaftLog {
+    pub fn last_index(&self) -> u64 {
+        self.compacted_index + self.entries.len() as u64
+    }
+    
+    pub fn last_term(&self) -> u64 {
+        self.entries.last().map(|e| e.term).unwrap_or(self.compacted_term)
+    }
+    
+    /// The entry at `index`, unless it was compacted away or not yet written
+    pub fn get(&self, index: u64) -> Option<&LogEntry> {
+        if index <= self.compacted_index {
+            return None;
+        }
+        self.entries.get((index - self.compacted_index - 1) as usize)
+    }
+    
+    /// Term of the entry at `index`, which is still known at the compaction point itself
+    pub fn term_at(&self, index: u64) -> Option<u64> {
+        if index == self.compacted_index {
+            return Some(self.compacted_term);
+        }
+        self.get(index).map(|e| e.term)
+    }
+    
+    /// Entries from `index` (which must be after the compaction point) to the end
+    pub fn entries_from(&self, index: u64) -> &[LogEntry] {
+        let start = (index.saturating_sub(self.compacted_index + 1) as usize).min(self.entries.len());
+        &self.entries[start..]
+    }
+    
+    pub fn push(&mut self, entry: LogEntry) {
+        self.entries.push(entry);
+    }
+    
+    /// Drop the entry at `index` and everything after it
+    pub fn truncate_from(&mut self, index: u64) {
+        self.entries.truncate(index.saturating_sub(self.compacted_index + 1) as usize);
+    }
+    
+    /// Discard entries up to and including `index`
+    pub fn compact(&mut self, index: u64) {
+        let Some(term) = self.term_at(index).filter(|_| index > self.compacted_index) else {
+            return;
+        };
+        self.entries.drain(..(index - self.compacted_index) as usize);
+        self.compacted_index = index;
+        self.compacted_term = term;
+    }
+}
+
+/// State-machine contents as of `last_index`, standing in for every entry up to it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub last_index: u64,
+    pub last_term: u64,
+    /// Configuration as of `last_index`
+    pub membership: Membership,
+    pub data: Vec<u8>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,35 +188,123 @@ pub enum RaftMessageType {
     RequestVoteResponse {
         vote_granted: bool,
     },
+    /// Asks whether a candidate could win an election in the message's term, without anyone
+    /// moving to that term
+    PreVote {
+        candidate_id: NodeId,
+        last_log_index: u64,
+        last_log_term: u64,
+    },
+    /// Carries the term asked about when granted, and the voter's own term when refused
+    PreVoteResponse {
+        vote_granted: bool,
+    },
+    /// Tells the target of a leadership transfer to start an election straight away
+    TimeoutNow,
+    /// `round` is the leader's heartbeat round when sent, echoed back in the response
     AppendEntries {
         leader_id: NodeId,
         prev_log_index: u64,
         prev_log_term: u64,
         entries: Vec<LogEntry>,
         leader_commit: u64,
+        round: u64,
     },
+    /// On success `match_index` is the last entry the follower now shares with the leader.
+    /// On failure it is a hint: the last index the follower might still share.
     AppendEntriesResponse {
         success: bool,
         match_index: u64,
+        round: u64,
+    },
+    /// Sent instead of AppendEntries when a follower needs entries the leader has compacted.
+    /// Answered with an AppendEntriesResponse matching the snapshot's last index.
+    InstallSnapshot {
+        leader_id: NodeId,
+        snapshot: Snapshot,
+        round: u64,
+    },
+    /// A follower asks the leader for the index a linearizable read must wait for
+    ReadIndex {
+        request_id: u64,
+        lease: bool,
+    },
+    /// `read_index` is `None` when the leader could not confirm its leadership
+    ReadIndexResponse {
+        request_id: u64,
+        read_index: Option<u64>,
     },
     Heartbeat,
 }
 
+/// A leader's heartbeat rounds, which confirm it still leads when a read is served
+#[derive(Debug, Default)]
+struct ReadState {
+    /// Incremented whenever the leader sends to every peer
+    round: u64,
+    /// Latest round each peer has answered
+    acked: HashMap<NodeId, u64>,
+    /// When recent rounds were sent, for rounds that could still extend the lease
+    sent_at: BTreeMap<u64, Instant>,
+    lease_expires: Option<Instant>,
+    /// Set once this leader has sent TimeoutNow, after which another node can win an election
+    /// while this one's lease is still running
+    lease_revoked: bool,
+}
+
+/// Reads forwarded to the leader, waiting for its answer
+#[derive(Debug, Default)]
+struct PendingReads {
+    next_id: u64,
+    waiting: HashMap<u64, oneshot::Sender<Option<u64>>>,
+}
+
+#[derive(Clone)]
 pub struct RaftNode {
     pub node_id: NodeId,
     pub state: Arc<RwLock<NodeState>>,
     pub current_term: Arc<RwLock<u64>>,
     pub voted_for: Arc<RwLock<Option<NodeId>>>,
-    pub log: Arc<RwLock<Vec<LogEntry>>>,
+    pub log: Arc<RwLock<RaftLog>>,
+    // Latest snapshot, taken locally or installed by the leader
+    pub snapshot: Arc<RwLock<Option<Snapshot>>>,
     pub commit_index: Arc<RwLock<u64>>,
     pub last_applied: Arc<RwLock<u64>>,
+    // Signalled whenever the commit index or a follower's match index moves
+    pub progress: Arc<watch::Sender<u64>>,
+    // Application-level votes from `<?>`, one per voter and proposal
+    pub votes: Arc<RwLock<HashMap<String, HashMap<NodeId, Vote>>>>,
+    // Receives committed entries from the apply loop
+    pub state_machine: Arc<RwLock<Option<Arc<dyn StateMachine>>>>,
+    // Held while entries are applied or a snapshot is taken or installed
+    pub applying: Arc<Mutex<()>>,
     
     // Leader state
     pub next_index: Arc<RwLock<HashMap<NodeId, u64>>>,
     pub match_index: Arc<RwLock<HashMap<NodeId, u64>>>,
     
+    // Latest configuration in the log, which takes effect as soon as it is appended
+    pub membership: Arc<RwLock<Membership>>,
+    // Configuration before the first change entry, from `config.peers` and `add_peer`
+    pub bootstrap: Arc<RwLock<Membership>>,
+    
     // Network
-    pub peers: Arc<RwLock<Vec<NodeId>>>,
+    pub config: RaftConfig,
+    pub transport: Arc<RwLock<Option<Arc<dyn ConsensusTransport<RaftMessage>>>>>,
+    // Peers that granted this node their vote in the current election or pre-vote, including itself
+    pub election_votes: Arc<RwLock<HashSet<NodeId>>>,
+    // Leader of the current term, once known
+    pub leader_id: Arc<RwLock<Option<NodeId>>>,
+    // When a leader last heard from each peer, for check-quorum
+    pub last_contact: Arc<RwLock<HashMap<NodeId, Instant>>>,
+    // Voter a leader is handing over to; proposals are refused meanwhile
+    pub transfer_target: Arc<RwLock<Option<NodeId>>>,
+    reads: Arc<RwLock<ReadState>>,
+    pending_reads: Arc<Mutex<PendingReads>>,
+    // Term, vote and log are flushed here before the node acts on them; `None` keeps them in memory
+    pub storage: Option<Arc<RaftStorage>>,
+    // Background loops, aborted by `stop`
+    pub tasks: Arc<Mutex<Vec<JoinHandle<()>>>>,
     pub message_tx: Arc<Mutex<Option<mpsc::UnboundedSender<RaftMessage>>>>,
     pub message_rx: Arc<Mutex<Option<mpsc::UnboundedReceiver<RaftMessage>>>>,
     
@@ -82,57 +316,214 @@ pub struct RaftNode {
 
 impl RaftNode {
     pub fn new(node_id: NodeId) -> Self {
+        Self::from_parts(node_id, RaftConfig::default(), None, HardState::default(), None, RaftLog::default())
+    }
+    
+    /// Create a node, replaying its term, vote and log from `config.data_dir` if one is set
+    pub fn with_config(node_id: NodeId, config: RaftConfig) -> anyhow::Result<Self> {
+        // Voters keep refusing pre-votes for an election timeout after hearing from the leader,
+        // which is what keeps a lease valid
+        if Duration::from_millis(config.read_lease_ms) >= ELECTION_TIMEOUT_MIN {
+            return Err(anyhow::anyhow!("read_lease_ms must be below the {}ms minimum election timeout", ELECTION_TIMEOUT_MIN.as_millis()));
+        }
+        let Some(data_dir) = config.data_dir.clone() else {
+            return Ok(Self::from_parts(node_id, config, None, HardState::default(), None, RaftLog::default()));
+        };
+        
+        let storage = RaftStorage::open(&data_dir)?;
+        let (hard_state, snapshot, log) = storage.load()?;
+        tracing::info!(
+            "Recovered Raft state for {}: term {}, log {}..={}",
+            node_id, hard_state.current_term, log.compacted_index + 1, log.last_index()
+        );
+        Ok(Self::from_parts(node_id, config, Some(Arc::new(storage)), hard_state, snapshot, log))
+    }
+    
+    fn from_parts(node_id: NodeId, config: RaftConfig, storage: Option<Arc<RaftStorage>>, hard_state: HardState, snapshot: Option<Snapshot>, log: RaftLog) -> Self {
         let (message_tx, message_rx) = mpsc::unbounded_channel();
+        let mut bootstrap = Membership::default();
+        // A joining node waits for a leader to send it the cluster's configuration
+        if !config.join_existing {
+            bootstrap.voters = config.peers.keys().cloned().collect();
+            bootstrap.voters.insert(node_id.clone());
+        }
+        let membership = membership_at(&log, snapshot.as_ref(), &bootstrap, log.last_index());
+        // A snapshot only ever holds committed entries
+        let snapshot_index = snapshot.as_ref().map(|s| s.last_index).unwrap_or(0);
         
         Self {
             node_id,
             state: Arc::new(RwLock::new(NodeState::Follower)),
-            current_term: Arc::new(RwLock::new(0)),
-            voted_for: Arc::new(RwLock::new(None)),
-            log: Arc::new(RwLock::new(Vec::new())),
-            commit_index: Arc::new(RwLock::new(0)),
-            last_applied: Arc::new(RwLock::new(0)),
+            current_term: Arc::new(RwLock::new(hard_state.current_term)),
+            voted_for: Arc::new(RwLock::new(hard_state.voted_for)),
+            log: Arc::new(RwLock::new(log)),
+            snapshot: Arc::new(RwLock::new(snapshot)),
+            commit_index: Arc::new(RwLock::new(snapshot_index)),
+            last_applied: Arc::new(RwLock::new(snapshot_index)),
+            progress: Arc::new(watch::channel(0).0),
+            votes: Arc::new(RwLock::new(HashMap::new())),
+            state_machine: Arc::new(RwLock::new(None)),
+            applying: Arc::new(Mutex::new(())),
             next_index: Arc::new(RwLock::new(HashMap::new())),
             match_index: Arc::new(RwLock::new(HashMap::new())),
-            peers: Arc::new(RwLock::new(Vec::new())),
+            membership: Arc::new(RwLock::new(membership)),
+            bootstrap: Arc::new(RwLock::new(bootstrap)),
+            config,
+            transport: Arc::new(RwLock::new(None)),
+            election_votes: Arc::new(RwLock::new(HashSet::new())),
+            leader_id: Arc::new(RwLock::new(None)),
+            last_contact: Arc::new(RwLock::new(HashMap::new())),
+            transfer_target: Arc::new(RwLock::new(None)),
+            reads: Arc::new(RwLock::new(ReadState::default())),
+            pending_reads: Arc::new(Mutex::new(PendingReads::default())),
+            storage,
+            tasks: Arc::new(Mutex::new(Vec::new())),
             message_tx: Arc::new(Mutex::new(Some(message_tx))),
             message_rx: Arc::new(Mutex::new(Some(message_rx))),
-            election_timeout: Duration::from_millis(150 + (rand::random::<u64>() % 150)),
+            election_timeout: ELECTION_TIMEOUT_MIN.mul_f64(1.0 + rand::random::<f64>()),
             heartbeat_interval: Duration::from_millis(50),
             last_heartbeat: Arc::new(RwLock::new(Instant::now())),
         }
     }
     
+    /// Add a voter to the bootstrap configuration, which every founding member must agree on.
+    /// Running clusters change membership through `add_voter` and `remove_server` instead.
     pub async fn add_peer(&self, peer_id: NodeId) {
-        self.peers.write().await.push(peer_id);
+        self.bootstrap.write().await.voters.insert(peer_id);
+        self.refresh_membership().await;
+    }
+    
+    /// Route outgoing messages through `transport`
+    pub async fn set_transport(&self, transport: Arc<dyn ConsensusTransport<RaftMessage>>) {
+        for (peer, addr) in &self.membership.read().await.addresses {
+            transport.add_peer_address(peer, *addr).await;
+        }
+        *self.transport.write().await = Some(transport);
+    }
+    
+    /// Every other member that receives the log
+    async fn peers(&self) -> Vec<NodeId> {
+        self.membership.read().await.members().into_iter()
+            .filter(|member| **member != self.node_id)
+            .cloned()
+            .collect()
+    }
+    
+    /// Adopt the latest configuration in the log, committed or not, as Raft requires.
+    /// Called whenever entries that may change it are appended, truncated or compacted.
+    async fn refresh_membership(&self) {
+        let membership = {
+            let log = self.log.read().await;
+            let snapshot = self.snapshot.read().await;
+            membership_at(&log, snapshot.as_ref(), &*self.bootstrap.read().await, log.last_index())
+        };
+        if *self.membership.read().await == membership {
+            return;
+        }
+        
+        if let Some(transport) = self.transport.read().await.clone() {
+            for (peer, addr) in &membership.addresses {
+                transport.add_peer_address(peer, *addr).await;
+            }
+        }
+        {
+            // Stop tracking removed members
+            let members = membership.members();
+            self.next_index.write().await.retain(|peer, _| members.contains(peer));
+            self.match_index.write().await.retain(|peer, _| members.contains(peer));
+        }
+        tracing::info!(
+            "{} using configuration from index {}: voters {:?}, incoming {:?}, learners {:?}",
+            self.node_id, membership.index, membership.voters, membership.incoming, membership.learners
+        );
+        *self.membership.write().await = membership;
+    }
+    
+    /// Sender for messages addressed to this node; transports deliver into it
+    pub async fn inbox(&self) -> Option<mpsc::UnboundedSender<RaftMessage>> {
+        self.message_tx.lock().await.clone()
+    }
+    
+    /// Hand a message to the transport without waiting for delivery. Lost messages are
+    /// covered by the election and heartbeat timers.
+    async fn send(&self, peer: &NodeId, message: RaftMessage) {
+        let Some(transport) = self.transport.read().await.clone() else {
+            return;
+        };
+        let peer = peer.clone();
+        tokio::spawn(async move {
+            if let Err(e) = transport.send(&peer, message).await {
+                tracing::debug!("Failed to send Raft message to {}: {}", peer, e);
+            }
+        });
     }
     
     pub async fn start_consensus_loop(&self) {
-        let self_arc = Arc::new(self);
+        // Every field is shared, so each task works on its own handle to the same node
+        let mut tasks = self.tasks.lock().await;
         
         // Start election timer
         {
-            let raft = Arc::clone(&self_arc);
-            tokio::spawn(async move {
+            let raft = self.clone();
+            tasks.push(tokio::spawn(async move {
                 raft.election_timer_loop().await;
-            });
+            }));
         }
         
         // Start heartbeat timer (for leaders)
         {
-            let raft = Arc::clone(&self_arc);
-            tokio::spawn(async move {
+            let raft = self.clone();
+            tasks.push(tokio::spawn(async move {
                 raft.heartbeat_timer_loop().await;
-            });
+            }));
         }
         
         // Start message handler
         {
-            let raft = Arc::clone(&self_arc);
-            tokio::spawn(async move {
+            let raft = self.clone();
+            tasks.push(tokio::spawn(async move {
                 raft.message_handler_loop().await;
-            });
+            }));
+        }
+        
+        // Start apply loop
+        {
+            let raft = self.clone();
+            tasks.push(tokio::spawn(async move {
+                raft.apply_loop().await;
+            }));
+        }
+    }
+    
+    /// Stop every background loop, as if the process had died. Whatever was not flushed
+    /// to storage is lost; a new node built from the same `data_dir` picks up from there.
+    pub async fn stop(&self) {
+        let tasks = std::mem::take(&mut *self.tasks.lock().await);
+        for task in tasks {
+            task.abort();
+            let _ = task.await;
         }
+        *self.transport.write().await = None;
+    }
+    
+    /// Flush the current term and vote; a node must not reply to an RPC before this succeeds
+    async fn persist_hard_state(&self) -> anyhow::Result<()> {
+        let Some(storage) = &self.storage else {
+            return Ok(());
+        };
+        let hard_state = HardState {
+            current_term: *self.current_term.read().await,
+            voted_for: self.voted_for.read().await.clone(),
+        };
+        storage.save_hard_state(&hard_state).await
+    }
+    
+    /// Flush the log from `from_index` on, replacing whatever was stored there
+    async fn persist_entries(&self, log: &RaftLog, from_index: u64) -> anyhow::Result<()> {
+        let Some(storage) = &self.storage else {
+            return Ok(());
+        };
+        storage.write_entries(from_index, log.entries_from(from_index)).await
     }
     
     async fn election_timer_loop(&self) {
@@ -141,20 +532,47 @@ impl RaftNode {
             
             let last_heartbeat = *self.last_heartbeat.read().await;
             let state = *self.state.read().await;
+            // Learners and removed nodes wait to hear from a leader
+            let is_voter = self.membership.read().await.is_voter(&self.node_id);
             
-            if state != NodeState::Leader && last_heartbeat.elapsed() >= self.election_timeout {
-                self.start_election().await;
+            if state == NodeState::Leader {
+                if self.config.check_quorum {
+                    self.check_quorum().await;
+                }
+            } else if is_voter && last_heartbeat.elapsed() >= self.election_timeout {
+                if self.config.pre_vote {
+                    self.start_pre_vote().await;
+                } else {
+                    self.start_election().await;
+                }
             }
         }
     }
     
+    /// Step down if a quorum has not answered within an election timeout. A leader cut off from
+    /// the majority would otherwise go on accepting proposals that can never commit.
+    async fn check_quorum(&self) {
+        let mut active: HashSet<NodeId> = self.last_contact.read().await.iter()
+            .filter(|(_, contact)| contact.elapsed() < self.election_timeout)
+            .map(|(peer, _)| peer.clone())
+            .collect();
+        active.insert(self.node_id.clone());
+        
+        if !self.membership.read().await.has_quorum(&active) {
+            tracing::info!("{} lost contact with a quorum; stepping down", self.node_id);
+            *self.state.write().await = NodeState::Follower;
+            *self.leader_id.write().await = None;
+            self.progress.send_modify(|_| {});
+        }
+    }
+    
     async fn heartbeat_timer_loop(&self) {
         loop {
             sleep(self.heartbeat_interval).await;
             
             let state = *self.state.read().await;
             if state == NodeState::Leader {
-                self.send_heartbeats().await;
+                self.replicate_to_peers().await;
             }
         }
     }
@@ -167,121 +585,351 @@ impl RaftNode {
         }
     }
     
+    /// Apply entries as they commit. `last_applied` only moves while `applying` is held, here or
+    /// when a snapshot is installed, which keeps application in log order and exactly once per index.
+    async fn apply_loop(&self) {
+        let mut progress = self.progress.subscribe();
+        loop {
+            self.apply_committed().await;
+            if progress.changed().await.is_err() {
+                break;
+            }
+        }
+    }
+    
+    async fn apply_committed(&self) {
+        let _applying = self.applying.lock().await;
+        let commit_index = *self.commit_index.read().await;
+        loop {
+            let index = *self.last_applied.read().await + 1;
+            if index > commit_index {
+                break;
+            }
+            let Some(entry) = self.log.read().await.get(index).cloned() else {
+                break;
+            };
+            
+            // Configuration entries only concern Raft itself
+            let machine = self.state_machine.read().await.clone().filter(|_| entry.membership.is_none());
+            if let Some(machine) = machine {
+                let committed = CommittedProposal {
+                    term: entry.term,
+                    index: entry.index,
+                    value: entry.value,
+                };
+                // Skipping the entry would leave this replica's state behind the others';
+                // stop here and retry it on the next commit
+                if let Err(e) = machine.apply(&committed).await {
+                    tracing::error!("Failed to apply entry {}, halting application: {}", index, e);
+                    break;
+                }
+            }
+            
+            *self.last_applied.write().await = index;
+            // Wake proposers waiting for this entry
+            self.progress.send_modify(|_| {});
+        }
+        
+        let last_applied = *self.last_applied.read().await;
+        let snapshot_index = self.snapshot.read().await.as_ref().map(|s| s.last_index).unwrap_or(0);
+        if self.config.snapshot_threshold > 0 && last_applied - snapshot_index >= self.config.snapshot_threshold {
+            if let Err(e) = self.take_snapshot(last_applied).await {
+                tracing::warn!("Failed to snapshot at index {}: {}", last_applied, e);
+            }
+        }
+    }
+    
+    /// Snapshot the state machine at `last_applied` and compact the log behind it, keeping
+    /// `snapshot_retain` entries for followers that are only slightly behind. Called with
+    /// `applying` held, so the state machine matches `last_applied`.
+    async fn take_snapshot(&self, last_applied: u64) -> anyhow::Result<()> {
+        let machine = self.state_machine.read().await.clone();
+        let data = match machine {
+            Some(machine) => machine.snapshot().await?,
+            None => Vec::new(),
+        };
+        
+        let mut log = self.log.write().await;
+        let last_term = log.term_at(last_applied)
+            .ok_or_else(|| anyhow::anyhow!("Applied entry {} is missing from the log", last_applied))?;
+        let membership = membership_at(&log, self.snapshot.read().await.as_ref(), &*self.bootstrap.read().await, last_applied);
+        let snapshot = Snapshot {
+            last_index: last_applied,
+            last_term,
+            membership,
+            data,
+        };
+        log.compact(last_applied.saturating_sub(self.config.snapshot_retain));
+        
+        if let Some(storage) = &self.storage {
+            storage.save_snapshot(&snapshot, &log).await?;
+        }
+        tracing::debug!("Snapshot at index {}; log now starts after {}", last_applied, log.compacted_index);
+        *self.snapshot.write().await = Some(snapshot);
+        Ok(())
+    }
+    
     async fn start_election(&self) {
-        {
+        // One critical section, so no vote request can be granted in the new term before this
+        // node has voted for itself in it
+        let current_term = {
+            let mut current_term = self.current_term.write().await;
             let mut state = self.state.write().await;
+            let mut voted_for = self.voted_for.write().await;
+            *current_term += 1;
             *state = NodeState::Candidate;
-        }
+            *voted_for = Some(self.node_id.clone());
+            *current_term
+        };
         
         {
-            let mut current_term = self.current_term.write().await;
-            *current_term += 1;
+            let mut election_votes = self.election_votes.write().await;
+            election_votes.clear();
+            election_votes.insert(self.node_id.clone());
         }
         
         {
-            let mut voted_for = self.voted_for.write().await;
-            *voted_for = Some(self.node_id.clone());
+            let mut last_heartbeat = self.last_heartbeat.write().await;
+            *last_heartbeat = Instant::now();
+        }
+        
+        if let Err(e) = self.persist_hard_state().await {
+            tracing::error!("Failed to persist Raft state, abandoning election: {}", e);
+            *self.state.write().await = NodeState::Follower;
+            return;
         }
         
+        *self.leader_id.write().await = None;
+        self.request_votes(current_term, false).await;
+        
+        // A single-node cluster wins on its own vote
+        self.check_election_won(current_term).await;
+    }
+    
+    /// Canvass the voters for the next term without moving to it. Only a node that could win
+    /// goes on to a real election, so one that was cut off cannot raise everyone's term.
+    async fn start_pre_vote(&self) {
+        {
+            let mut state = self.state.write().await;
+            *state = NodeState::PreCandidate;
+        }
+        {
+            let mut election_votes = self.election_votes.write().await;
+            election_votes.clear();
+            election_votes.insert(self.node_id.clone());
+        }
         {
             let mut last_heartbeat = self.last_heartbeat.write().await;
             *last_heartbeat = Instant::now();
         }
+        // Having given up on the leader, grant other pre-candidates theirs
+        *self.leader_id.write().await = None;
         
-        let current_term = *self.current_term.read().await;
-        let log = self.log.read().await;
-        let last_log_index = log.len() as u64;
-        let last_log_term = log.last().map(|e| e.term).unwrap_or(0);
-        
-        // Send RequestVote to all peers
-        let peers = self.peers.read().await.clone();
-        let mut vote_count = 1; // Vote for self
+        let term = *self.current_term.read().await + 1;
+        self.request_votes(term, true).await;
+        self.check_pre_vote_won(term).await;
+    }
+    
+    /// Ask every other voter for its vote, or its pre-vote, in `term`
+    async fn request_votes(&self, term: u64, pre_vote: bool) {
+        let (last_log_index, last_log_term) = {
+            let log = self.log.read().await;
+            (log.last_index(), log.last_term())
+        };
         
-        for peer in &peers {
+        let membership = self.membership.read().await.clone();
+        let voters = membership.voters.iter().chain(membership.incoming.iter().flatten()).collect::<BTreeSet<_>>();
+        for peer in voters.into_iter().filter(|voter| **voter != self.node_id) {
+            let candidate_id = self.node_id.clone();
+            let message_type = match pre_vote {
+                true => RaftMessageType::PreVote { candidate_id, last_log_index, last_log_term },
+                false => RaftMessageType::RequestVote { candidate_id, last_log_index, last_log_term },
+            };
             let request = RaftMessage {
-                term: current_term,
+                term,
                 from: self.node_id.clone(),
-                message_type: RaftMessageType::RequestVote {
-                    candidate_id: self.node_id.clone(),
-                    last_log_index,
-                    last_log_term,
-                },
+                message_type,
             };
-            
-            // TODO: Send message to peer
-            // For MVP, we'll simulate responses
+            self.send(peer, request).await;
+        }
+    }
+    
+    /// Become leader once a majority has granted its vote for `term`
+    async fn check_election_won(&self, term: u64) {
+        if *self.state.read().await != NodeState::Candidate || *self.current_term.read().await != term {
+            return;
         }
         
-        // For MVP, assume we become leader if we have majority
-        let majority = (peers.len() + 1) / 2 + 1;
-        if vote_count >= majority {
+        let won = self.membership.read().await.has_quorum(&*self.election_votes.read().await);
+        if won {
             self.become_leader().await;
         }
     }
     
+    /// Start a real election once a majority has granted its pre-vote for `term`
+    async fn check_pre_vote_won(&self, term: u64) {
+        if *self.state.read().await != NodeState::PreCandidate || *self.current_term.read().await + 1 != term {
+            return;
+        }
+        
+        let won = self.membership.read().await.has_quorum(&*self.election_votes.read().await);
+        if won {
+            self.start_election().await;
+        }
+    }
+    
     async fn become_leader(&self) {
         {
             let mut state = self.state.write().await;
             *state = NodeState::Leader;
         }
         
-        let log_len = self.log.read().await.len() as u64;
-        let peers = self.peers.read().await.clone();
+        let last_index = self.log.read().await.last_index();
+        let peers = self.peers().await;
         
         // Initialize leader state
         {
             let mut next_index = self.next_index.write().await;
             let mut match_index = self.match_index.write().await;
+            let mut last_contact = self.last_contact.write().await;
+            next_index.clear();
+            match_index.clear();
+            last_contact.clear();
             
+            // Every peer gets an election timeout to answer before check-quorum counts it as lost
+            let now = Instant::now();
             for peer in &peers {
-                next_index.insert(peer.clone(), log_len + 1);
+                next_index.insert(peer.clone(), last_index + 1);
                 match_index.insert(peer.clone(), 0);
+                last_contact.insert(peer.clone(), now);
+            }
+        }
+        *self.leader_id.write().await = Some(self.node_id.clone());
+        {
+            let mut reads = self.reads.write().await;
+            reads.acked.clear();
+            reads.sent_at.clear();
+            reads.lease_expires = None;
+            reads.lease_revoked = false;
+        }
+        
+        tracing::info!("{} became leader for term {}", self.node_id, *self.current_term.read().await);
+        
+        // A joint change left by the previous leader commits only with an entry from this term,
+        // so restate it; committing that moves the cluster on to the new configuration
+        let membership = self.membership.read().await.clone();
+        if membership.is_joint() {
+            if let Err(e) = self.append_entry(Vec::new(), Some(membership)).await {
+                tracing::error!("Failed to resume membership change: {}", e);
             }
+            self.advance_commit_index().await;
         }
         
         // Send initial heartbeats
-        self.send_heartbeats().await;
+        self.replicate_to_peers().await;
+    }
+    
+    /// Send every peer the entries it is missing, or an empty heartbeat if it is up to date.
+    /// Returns the heartbeat round the messages carry.
+    async fn replicate_to_peers(&self) -> u64 {
+        let round = {
+            let mut reads = self.reads.write().await;
+            reads.round += 1;
+            let round = reads.round;
+            let lease = Duration::from_millis(self.config.read_lease_ms);
+            reads.sent_at.retain(|_, sent| sent.elapsed() < lease);
+            if !lease.is_zero() {
+                reads.sent_at.insert(round, Instant::now());
+            }
+            round
+        };
+        
+        let peers = self.peers().await;
+        for peer in &peers {
+            self.send_append_entries(peer).await;
+        }
+        round
     }
     
-    async fn send_heartbeats(&self) {
-        let peers = self.peers.read().await.clone();
+    async fn send_append_entries(&self, peer: &NodeId) {
         let current_term = *self.current_term.read().await;
         let commit_index = *self.commit_index.read().await;
+        let round = self.reads.read().await.round;
         
-        for peer in &peers {
-            let heartbeat = RaftMessage {
-                term: current_term,
-                from: self.node_id.clone(),
-                message_type: RaftMessageType::AppendEntries {
+        let message_type = {
+            let log = self.log.read().await;
+            let next = self.next_index.read().await.get(peer).copied()
+                .unwrap_or(log.last_index() + 1)
+                .clamp(1, log.last_index() + 1);
+            let prev_log_index = next - 1;
+            
+            match log.term_at(prev_log_index) {
+                Some(prev_log_term) => RaftMessageType::AppendEntries {
                     leader_id: self.node_id.clone(),
-                    prev_log_index: 0,
-                    prev_log_term: 0,
-                    entries: Vec::new(), // Empty for heartbeat
+                    prev_log_index,
+                    prev_log_term,
+                    entries: log.entries_from(next).iter().take(MAX_APPEND_ENTRIES).cloned().collect(),
                     leader_commit: commit_index,
+                    round,
                 },
-            };
-            
-            // TODO: Send heartbeat to peer
+                // The follower needs entries that only the snapshot still covers
+                None => {
+                    let Some(snapshot) = self.snapshot.read().await.clone() else {
+                        return;
+                    };
+                    RaftMessageType::InstallSnapshot {
+                        leader_id: self.node_id.clone(),
+                        snapshot,
+                        round,
+                    }
+                }
+            }
+        };
+        
+        let message = RaftMessage {
+            term: current_term,
+            from: self.node_id.clone(),
+            message_type,
+        };
+        self.send(peer, message).await;
+    }
+    
+    /// Adopt a newer term seen in any message, reverting to follower
+    async fn step_down(&self, term: u64) {
+        {
+            let mut current_term = self.current_term.write().await;
+            // An election may have reached the term since the caller looked; its vote stands
+            if term <= *current_term {
+                return;
+            }
+            let mut state = self.state.write().await;
+            let mut voted_for = self.voted_for.write().await;
+            *current_term = term;
+            *state = NodeState::Follower;
+            *voted_for = None;
+        }
+        *self.leader_id.write().await = None;
+        // Wake anyone waiting on this node's leadership
+        self.progress.send_modify(|_| {});
+        if let Err(e) = self.persist_hard_state().await {
+            tracing::error!("Failed to persist Raft state for term {}: {}", term, e);
         }
     }
     
     async fn handle_message(&self, message: RaftMessage) {
         let current_term = *self.current_term.read().await;
         
+        // Pre-votes ask about a later term without moving anyone to it; only a refusal carries
+        // a term the sender has actually reached
+        let adopts_term = match &message.message_type {
+            RaftMessageType::PreVote { .. } => false,
+            RaftMessageType::PreVoteResponse { vote_granted } => !vote_granted,
+            _ => true,
+        };
+        
         // If message term is higher, become follower
-        if message.term > current_term {
-            {
-                let mut term = self.current_term.write().await;
-                *term = message.term;
-            }
-            {
-                let mut state = self.state.write().await;
-                *state = NodeState::Follower;
-            }
-            {
-                let mut voted_for = self.voted_for.write().await;
-                *voted_for = None;
-            }
+        if adopts_term && message.term > current_term {
+            self.step_down(message.term).await;
         }
         
         match message.message_type {
@@ -292,12 +940,29 @@ impl RaftNode {
             } => {
                 self.handle_request_vote(message.term, candidate_id, last_log_index, last_log_term).await;
             }
+            RaftMessageType::RequestVoteResponse { vote_granted } => {
+                self.handle_request_vote_response(message.term, message.from, vote_granted).await;
+            }
+            RaftMessageType::PreVote {
+                candidate_id,
+                last_log_index,
+                last_log_term,
+            } => {
+                self.handle_pre_vote(message.term, candidate_id, last_log_index, last_log_term).await;
+            }
+            RaftMessageType::PreVoteResponse { vote_granted } => {
+                self.handle_pre_vote_response(message.term, message.from, vote_granted).await;
+            }
+            RaftMessageType::TimeoutNow => {
+                self.handle_timeout_now(message.term, message.from).await;
+            }
             RaftMessageType::AppendEntries {
                 leader_id,
                 prev_log_index,
                 prev_log_term,
                 entries,
                 leader_commit,
+                round,
             } => {
                 self.handle_append_entries(
                     message.term,
@@ -306,10 +971,25 @@ impl RaftNode {
                     prev_log_term,
                     entries,
                     leader_commit,
+                    round,
                 ).await;
             }
-            _ => {
-                // Handle other message types
+            RaftMessageType::AppendEntriesResponse { success, match_index, round } => {
+                self.handle_append_entries_response(message.term, message.from, success, match_index, round).await;
+            }
+            RaftMessageType::InstallSnapshot { leader_id, snapshot, round } => {
+                self.handle_install_snapshot(message.term, leader_id, snapshot, round).await;
+            }
+            RaftMessageType::ReadIndex { request_id, lease } => {
+                self.handle_read_index(message.term, message.from, request_id, lease).await;
+            }
+            RaftMessageType::ReadIndexResponse { request_id, read_index } => {
+                if let Some(waiting) = self.pending_reads.lock().await.waiting.remove(&request_id) {
+                    let _ = waiting.send(read_index);
+                }
+            }
+            RaftMessageType::Heartbeat => {
+                // Leaders heartbeat with empty AppendEntries
             }
         }
     }
@@ -321,80 +1001,390 @@ impl RaftNode {
         last_log_index: u64,
         last_log_term: u64,
     ) {
-        let current_term = *self.current_term.read().await;
-        let voted_for = self.voted_for.read().await.clone();
+        let log_up_to_date = self.is_log_up_to_date(last_log_index, last_log_term).await;
         
-        let vote_granted = term >= current_term
-            && (voted_for.is_none() || voted_for.as_ref() == Some(&candidate_id))
-            && self.is_log_up_to_date(last_log_index, last_log_term).await;
+        // Checked and cast under the term lock, which an election holds while voting for itself.
+        // Any newer term has already been adopted, so a current request carries our term.
+        let (current_term, vote_granted) = {
+            let current_term = self.current_term.read().await;
+            let mut voted_for = self.voted_for.write().await;
+            let vote_granted = term == *current_term
+                && (voted_for.is_none() || voted_for.as_ref() == Some(&candidate_id))
+                && log_up_to_date;
+            if vote_granted {
+                *voted_for = Some(candidate_id.clone());
+            }
+            (*current_term, vote_granted)
+        };
         
         if vote_granted {
-            let mut voted = self.voted_for.write().await;
-            *voted = Some(candidate_id.clone());
+            // A vote that could be forgotten in a crash could be cast twice in the same term
+            if let Err(e) = self.persist_hard_state().await {
+                tracing::error!("Failed to persist vote for {}: {}", candidate_id, e);
+                return;
+            }
             
             let mut last_heartbeat = self.last_heartbeat.write().await;
             *last_heartbeat = Instant::now();
         }
         
-        // TODO: Send vote response
+        let response = RaftMessage {
+            term: current_term,
+            from: self.node_id.clone(),
+            message_type: RaftMessageType::RequestVoteResponse { vote_granted },
+        };
+        self.send(&candidate_id, response).await;
     }
     
-    async fn handle_append_entries(
+    async fn handle_request_vote_response(&self, term: u64, from: NodeId, vote_granted: bool) {
+        // Responses from earlier elections no longer count
+        if !vote_granted || term != *self.current_term.read().await {
+            return;
+        }
+        
+        self.election_votes.write().await.insert(from);
+        self.check_election_won(term).await;
+    }
+    
+    async fn handle_pre_vote(
         &self,
         term: u64,
-        leader_id: NodeId,
-        prev_log_index: u64,
-        prev_log_term: u64,
-        entries: Vec<LogEntry>,
+        candidate_id: NodeId,
+        last_log_index: u64,
+        last_log_term: u64,
+    ) {
+        let current_term = *self.current_term.read().await;
+        // While a leader is still heard from, nobody needs a new one
+        let state = *self.state.read().await;
+        let leader_alive = match state {
+            NodeState::Leader => true,
+            _ => self.leader_id.read().await.is_some() && self.last_heartbeat.read().await.elapsed() < self.election_timeout,
+        };
+        
+        let vote_granted = term > current_term
+            && !leader_alive
+            && self.is_log_up_to_date(last_log_index, last_log_term).await;
+        
+        let response = RaftMessage {
+            term: if vote_granted { term } else { current_term },
+            from: self.node_id.clone(),
+            message_type: RaftMessageType::PreVoteResponse { vote_granted },
+        };
+        self.send(&candidate_id, response).await;
+    }
+    
+    async fn handle_pre_vote_response(&self, term: u64, from: NodeId, vote_granted: bool) {
+        // Grants for an earlier pre-vote no longer count
+        if !vote_granted || term != *self.current_term.read().await + 1 {
+            return;
+        }
+        
+        self.election_votes.write().await.insert(from);
+        self.check_pre_vote_won(term).await;
+    }
+    
+    async fn handle_timeout_now(&self, term: u64, from: NodeId) {
+        // Only the current leader can hand over, and only to a voter
+        if term != *self.current_term.read().await || *self.state.read().await == NodeState::Leader {
+            return;
+        }
+        if !self.membership.read().await.is_voter(&self.node_id) {
+            return;
+        }
+        
+        // The leader has stepped aside for us, so there is no point canvassing first
+        tracing::info!("{} taking over leadership from {}", self.node_id, from);
+        self.start_election().await;
+    }
+    
+    #[allow(clippy::too_many_arguments)]
+    async fn handle_append_entries(
+        &self,
+        term: u64,
+        leader_id: NodeId,
+        prev_log_index: u64,
+        prev_log_term: u64,
+        entries: Vec<LogEntry>,
         leader_commit: u64,
+        round: u64,
     ) {
         let current_term = *self.current_term.read().await;
         
+        // A leader from an earlier term learns of the new term from the response
+        if term < current_term {
+            self.respond_append_entries(&leader_id, current_term, false, 0, round).await;
+            return;
+        }
+        
         // Update heartbeat timer
         {
             let mut last_heartbeat = self.last_heartbeat.write().await;
             *last_heartbeat = Instant::now();
         }
         
-        // Become follower if not already
-        if term >= current_term {
+        // A candidate in this term has lost the election
+        {
             let mut state = self.state.write().await;
             *state = NodeState::Follower;
         }
+        self.follow(&leader_id).await;
         
         // Check if we can append entries
-        let log_consistent = self.check_log_consistency(prev_log_index, prev_log_term).await;
+        if !self.check_log_consistency(prev_log_index, prev_log_term).await {
+            let hint = self.log.read().await.last_index().min(prev_log_index.saturating_sub(1));
+            self.respond_append_entries(&leader_id, current_term, false, hint, round).await;
+            return;
+        }
         
-        if log_consistent {
-            // Append new entries
-            {
-                let mut log = self.log.write().await;
-                let start_index = prev_log_index as usize + 1;
-                
-                // Remove conflicting entries
-                if log.len() > start_index {
-                    log.truncate(start_index);
+        let last_new_index = prev_log_index + entries.len() as u64;
+        let mut membership_changed = false;
+        {
+            let mut log = self.log.write().await;
+            let mut first_changed = None;
+            for entry in entries {
+                // Compacted entries are committed, so they already match the leader's
+                if entry.index <= log.compacted_index {
+                    continue;
+                }
+                match log.get(entry.index) {
+                    // Already stored; a delayed or repeated message must not truncate the log
+                    Some(existing) if existing.term == entry.term => continue,
+                    // Conflicting entries were never committed, so they can be replaced
+                    Some(_) => {
+                        log.truncate_from(entry.index);
+                        membership_changed = true;
+                    }
+                    None => {}
+                }
+                first_changed.get_or_insert(entry.index);
+                membership_changed |= entry.membership.is_some();
+                log.push(entry);
+            }
+            
+            // The leader counts this node as storing the entries once it succeeds
+            if let Some(from_index) = first_changed {
+                if let Err(e) = self.persist_entries(&log, from_index).await {
+                    tracing::error!("Failed to persist log entries from {}: {}", from_index, e);
+                    return;
                 }
-                
-                // Append new entries
-                log.extend(entries);
             }
+        }
+        if membership_changed {
+            self.refresh_membership().await;
+        }
+        
+        // Update commit index
+        let commit_index = leader_commit.min(last_new_index);
+        if commit_index > *self.commit_index.read().await {
+            self.set_commit_index(commit_index).await;
+        }
+        
+        self.respond_append_entries(&leader_id, current_term, true, last_new_index, round).await;
+    }
+    
+    /// Record the leader of the current term
+    async fn follow(&self, leader_id: &NodeId) {
+        let mut current = self.leader_id.write().await;
+        if current.as_ref() != Some(leader_id) {
+            *current = Some(leader_id.clone());
+            // Wake a leadership transfer waiting for its target to take over
+            self.progress.send_modify(|_| {});
+        }
+    }
+    
+    async fn respond_append_entries(&self, leader_id: &NodeId, term: u64, success: bool, match_index: u64, round: u64) {
+        let response = RaftMessage {
+            term,
+            from: self.node_id.clone(),
+            message_type: RaftMessageType::AppendEntriesResponse { success, match_index, round },
+        };
+        self.send(leader_id, response).await;
+    }
+    
+    async fn handle_install_snapshot(&self, term: u64, leader_id: NodeId, snapshot: Snapshot, round: u64) {
+        let current_term = *self.current_term.read().await;
+        if term < current_term {
+            self.respond_append_entries(&leader_id, current_term, false, 0, round).await;
+            return;
+        }
+        
+        {
+            let mut last_heartbeat = self.last_heartbeat.write().await;
+            *last_heartbeat = Instant::now();
+        }
+        {
+            let mut state = self.state.write().await;
+            *state = NodeState::Follower;
+        }
+        self.follow(&leader_id).await;
+        
+        let last_index = snapshot.last_index;
+        if let Err(e) = self.install_snapshot(snapshot).await {
+            tracing::error!("Failed to install snapshot at index {}: {}", last_index, e);
+            return;
+        }
+        self.respond_append_entries(&leader_id, current_term, true, last_index, round).await;
+    }
+    
+    /// Replace the state machine and the log prefix with a leader's snapshot
+    async fn install_snapshot(&self, snapshot: Snapshot) -> anyhow::Result<()> {
+        let _applying = self.applying.lock().await;
+        if snapshot.last_index <= *self.last_applied.read().await {
+            // Already past this point; the leader will continue from the snapshot's index
+            return Ok(());
+        }
+        
+        if let Some(machine) = self.state_machine.read().await.clone() {
+            machine.restore(&snapshot.data).await?;
+        }
+        
+        {
+            let mut log = self.log.write().await;
+            if log.term_at(snapshot.last_index) == Some(snapshot.last_term) {
+                // Entries after the snapshot still agree with the leader's log
+                log.compact(snapshot.last_index);
+            } else {
+                *log = RaftLog {
+                    compacted_index: snapshot.last_index,
+                    compacted_term: snapshot.last_term,
+                    entries: Vec::new(),
+                };
+            }
+            if let Some(storage) = &self.storage {
+                storage.save_snapshot(&snapshot, &log).await?;
+                storage.write_entries(log.last_index() + 1, &[]).await?;
+            }
+        }
+        
+        tracing::info!("Installed snapshot at index {}", snapshot.last_index);
+        *self.last_applied.write().await = snapshot.last_index;
+        {
+            let mut commit_index = self.commit_index.write().await;
+            *commit_index = (*commit_index).max(snapshot.last_index);
+        }
+        *self.snapshot.write().await = Some(snapshot);
+        self.refresh_membership().await;
+        self.progress.send_modify(|_| {});
+        Ok(())
+    }
+    
+    async fn handle_append_entries_response(&self, term: u64, from: NodeId, success: bool, match_index: u64, round: u64) {
+        if *self.state.read().await != NodeState::Leader || term != *self.current_term.read().await {
+            return;
+        }
+        self.last_contact.write().await.insert(from.clone(), Instant::now());
+        // Any answer in this term, successful or not, acknowledges this node as leader
+        self.record_round_ack(&from, round).await;
+        
+        if success {
+            {
+                let mut matched = self.match_index.write().await;
+                let entry = matched.entry(from.clone()).or_insert(0);
+                *entry = (*entry).max(match_index);
+                let mut next_index = self.next_index.write().await;
+                let next = next_index.entry(from.clone()).or_insert(1);
+                *next = (*next).max(*entry + 1);
+            }
+            self.advance_commit_index().await;
+            self.progress.send_modify(|_| {});
             
-            // Update commit index
-            if leader_commit > *self.commit_index.read().await {
-                let log_len = self.log.read().await.len() as u64;
-                let mut commit_index = self.commit_index.write().await;
-                *commit_index = leader_commit.min(log_len);
+            // Keep sending until the follower has caught up
+            if match_index < self.log.read().await.last_index() {
+                self.send_append_entries(&from).await;
             }
+        } else {
+            {
+                // Back off by at least one entry, or straight to the follower's hint
+                let mut next_index = self.next_index.write().await;
+                let next = next_index.entry(from.clone()).or_insert(1);
+                *next = next.saturating_sub(1).min(match_index + 1).max(1);
+            }
+            self.send_append_entries(&from).await;
         }
+    }
+    
+    async fn advance_commit_index(&self) {
+        // Committing a joint configuration appends the final one, which may commit straight away
+        while self.try_advance_commit_index().await && self.advance_membership().await {}
+    }
+    
+    /// Commit the highest index stored on a majority, counting only entries from the current term.
+    /// Returns whether the commit index moved.
+    async fn try_advance_commit_index(&self) -> bool {
+        let current_term = *self.current_term.read().await;
+        let membership = self.membership.read().await.clone();
         
-        // TODO: Send append entries response
+        let candidate = {
+            let log = self.log.read().await;
+            let matched = self.match_index.read().await;
+            let index = membership.quorum_index(|peer| match *peer == self.node_id {
+                true => log.last_index(),
+                false => matched.get(peer).copied().unwrap_or(0),
+            });
+            let from_current_term = index > 0 && log.term_at(index) == Some(current_term);
+            from_current_term.then_some(index)
+        };
+        
+        match candidate {
+            Some(index) if index > *self.commit_index.read().await => {
+                self.set_commit_index(index).await;
+                true
+            }
+            _ => false,
+        }
+    }
+    
+    /// Act on a newly committed configuration: a joint one is followed by the configuration it
+    /// moves to, and a leader outside the committed configuration steps down. Returns whether
+    /// an entry was appended.
+    async fn advance_membership(&self) -> bool {
+        let membership = self.membership.read().await.clone();
+        if *self.state.read().await != NodeState::Leader || membership.index > *self.commit_index.read().await {
+            return false;
+        }
+        
+        match membership.incoming {
+            Some(incoming) => {
+                let next = Membership {
+                    voters: incoming,
+                    incoming: None,
+                    ..membership
+                };
+                if let Err(e) = self.append_entry(Vec::new(), Some(next)).await {
+                    tracing::error!("Failed to complete membership change: {}", e);
+                    return false;
+                }
+                self.replicate_to_peers().await;
+                true
+            }
+            None => {
+                if !membership.is_voter(&self.node_id) {
+                    // The remaining voters elect a leader once heartbeats stop
+                    tracing::info!("{} is no longer a voter; stepping down", self.node_id);
+                    *self.state.write().await = NodeState::Follower;
+                    *self.leader_id.write().await = None;
+                }
+                false
+            }
+        }
+    }
+    
+    async fn set_commit_index(&self, index: u64) {
+        *self.commit_index.write().await = index;
+        self.progress.send_replace(index);
+    }
+    
+    /// Nodes known to store the entry at `index`, including this one
+    async fn replication_count(&self, index: u64) -> usize {
+        let stored_locally = self.log.read().await.last_index() >= index;
+        let followers = self.match_index.read().await.values().filter(|&&matched| matched >= index).count();
+        followers + stored_locally as usize
     }
     
     async fn is_log_up_to_date(&self, last_log_index: u64, last_log_term: u64) -> bool {
         let log = self.log.read().await;
-        let our_last_index = log.len() as u64;
-        let our_last_term = log.last().map(|e| e.term).unwrap_or(0);
+        let our_last_index = log.last_index();
+        let our_last_term = log.last_term();
         
         last_log_term > our_last_term || (last_log_term == our_last_term && last_log_index >= our_last_index)
     }
@@ -402,37 +1392,320 @@ impl RaftNode {
     async fn check_log_consistency(&self, prev_log_index: u64, prev_log_term: u64) -> bool {
         let log = self.log.read().await;
         
-        if prev_log_index == 0 {
-            return true; // No previous entry to check
+        if prev_log_index < log.compacted_index {
+            return true; // Compacted entries are committed, so they match
         }
         
-        if prev_log_index > log.len() as u64 {
-            return false; // We don't have the previous entry
+        // Unknown if we don't have the previous entry
+        log.term_at(prev_log_index) == Some(prev_log_term)
+    }
+    
+    /// Record `voter`'s ballot on a proposal, replacing any earlier one
+    pub async fn record_vote(&self, proposal_id: &ProposalId, voter: NodeId, vote: Vote) {
+        self.votes.write().await
+            .entry(proposal_id.0.clone())
+            .or_default()
+            .insert(voter, vote);
+    }
+    
+    /// Append a value in the current term, returning its (term, index)
+    pub async fn append_log_entry(&self, value: Vec<u8>) -> anyhow::Result<(u64, u64)> {
+        self.append_entry(value, None).await
+    }
+    
+    /// Append an entry in the current term; a configuration takes effect once it is in the log
+    async fn append_entry(&self, value: Vec<u8>, membership: Option<Membership>) -> anyhow::Result<(u64, u64)> {
+        let current_term = *self.current_term.read().await;
+        let changes_membership = membership.is_some();
+        let index = {
+            let mut log = self.log.write().await;
+            let index = log.last_index() + 1;
+            
+            let entry = LogEntry {
+                term: current_term,
+                index,
+                value,
+                membership: membership.map(|membership| Membership { index, ..membership }),
+            };
+            
+            log.push(entry);
+            if let Err(e) = self.persist_entries(&log, index).await {
+                log.truncate_from(index);
+                return Err(e);
+            }
+            index
+        };
+        
+        if changes_membership {
+            self.refresh_membership().await;
+        }
+        Ok((current_term, index))
+    }
+    
+    /// The configuration to change from. Fails unless this node leads and the current
+    /// configuration has committed, so only one change is ever in flight.
+    async fn begin_membership_change(&self) -> anyhow::Result<Membership> {
+        if *self.state.read().await != NodeState::Leader {
+            return Err(anyhow::anyhow!("Not the leader"));
         }
+        if self.transfer_target.read().await.is_some() {
+            return Err(anyhow::anyhow!("Leadership transfer in progress"));
+        }
+        let membership = self.membership.read().await.clone();
+        if membership.is_joint() || membership.index > *self.commit_index.read().await {
+            return Err(anyhow::anyhow!("A membership change is already in progress"));
+        }
+        Ok(membership)
+    }
+    
+    /// Append a configuration and wait until it, or the one a joint change moves to, has committed
+    async fn commit_membership(&self, membership: Membership) -> anyhow::Result<()> {
+        let (term, index) = self.append_entry(Vec::new(), Some(membership)).await?;
+        self.advance_commit_index().await;
+        self.replicate_to_peers().await;
         
-        let prev_entry = &log[prev_log_index as usize - 1];
-        prev_entry.term == prev_log_term
+        let mut progress = self.progress.subscribe();
+        loop {
+            if self.log.read().await.term_at(index).is_some_and(|t| t != term) {
+                return Err(anyhow::anyhow!("Membership change at index {} was replaced by a new leader", index));
+            }
+            let current = self.membership.read().await.clone();
+            if current.index >= index && !current.is_joint() && current.index <= *self.commit_index.read().await {
+                return Ok(());
+            }
+            progress.changed().await?;
+        }
     }
     
-    pub async fn append_log_entry(&self, value: Vec<u8>) -> u64 {
+    /// Bring `target` up to date, tell it to start an election, and wait until it leads
+    async fn hand_over(&self, target: &NodeId) -> anyhow::Result<()> {
+        self.wait_for_catch_up(target).await?;
+        // The target skips pre-vote, so voters that still hear from this node may elect it
+        self.reads.write().await.lease_revoked = true;
+        let message = RaftMessage {
+            term: *self.current_term.read().await,
+            from: self.node_id.clone(),
+            message_type: RaftMessageType::TimeoutNow,
+        };
+        self.send(target, message).await;
+        
+        let mut progress = self.progress.subscribe();
+        while self.leader_id.read().await.as_ref() != Some(target) {
+            progress.changed().await?;
+        }
+        Ok(())
+    }
+    
+    /// Note that `peer` answered heartbeat `round`, extending the lease once a quorum has
+    async fn record_round_ack(&self, peer: &NodeId, round: u64) {
+        let membership = self.membership.read().await.clone();
+        {
+            let mut reads = self.reads.write().await;
+            let acked = reads.acked.entry(peer.clone()).or_insert(0);
+            *acked = (*acked).max(round);
+            
+            let own_round = reads.round;
+            let quorum_round = membership.quorum_index(|member| match *member == self.node_id {
+                true => own_round,
+                false => reads.acked.get(member).copied().unwrap_or(0),
+            });
+            // Voters that answered a round refuse pre-votes for an election timeout after it was sent
+            let lease = Duration::from_millis(self.config.read_lease_ms);
+            if let Some(sent) = reads.sent_at.get(&quorum_round).copied() {
+                reads.lease_expires = reads.lease_expires.max(Some(sent + lease));
+            }
+            reads.sent_at.retain(|sent_round, _| *sent_round >= quorum_round);
+        }
+        // Wake reads waiting for this round
+        self.progress.send_modify(|_| {});
+    }
+    
+    /// Whether a quorum's recent heartbeats still rule out another leader. Without pre-vote,
+    /// voters grant votes at any time, so there is no lease.
+    async fn has_lease(&self) -> bool {
+        let reads = self.reads.read().await;
+        self.config.pre_vote
+            && !reads.lease_revoked
+            && self.transfer_target.read().await.is_none()
+            && reads.lease_expires.is_some_and(|expires| Instant::now() < expires)
+    }
+    
+    /// Index a read on this leader must wait for. Without `lease`, or once the lease has
+    /// lapsed, leadership is confirmed by a heartbeat round first.
+    async fn leader_read_index(&self, lease: bool) -> anyhow::Result<u64> {
+        if *self.state.read().await != NodeState::Leader {
+            return Err(anyhow::anyhow!("Not the leader"));
+        }
+        // Until this leader commits an entry of its own term, its commit index may be behind
+        // what earlier leaders committed
+        self.commit_current_term().await?;
+        let read_index = *self.commit_index.read().await;
+        
+        if lease && self.has_lease().await {
+            return Ok(read_index);
+        }
+        self.confirm_leadership().await?;
+        Ok(read_index)
+    }
+    
+    async fn commit_current_term(&self) -> anyhow::Result<()> {
         let current_term = *self.current_term.read().await;
-        let mut log = self.log.write().await;
-        let index = log.len() as u64 + 1;
+        let committed_term = {
+            let log = self.log.read().await;
+            log.term_at(*self.commit_index.read().await)
+        };
+        if committed_term == Some(current_term) {
+            return Ok(());
+        }
         
-        let entry = LogEntry {
-            term: current_term,
-            index,
-            value,
+        // An empty entry, like a read barrier's, commits everything before it
+        if self.log.read().await.last_term() != current_term {
+            self.append_log_entry(Vec::new()).await?;
+            self.advance_commit_index().await;
+            self.replicate_to_peers().await;
+        }
+        let mut progress = self.progress.subscribe();
+        loop {
+            if *self.state.read().await != NodeState::Leader || *self.current_term.read().await != current_term {
+                return Err(anyhow::anyhow!("Lost leadership before the read was confirmed"));
+            }
+            let committed_term = {
+                let log = self.log.read().await;
+                log.term_at(*self.commit_index.read().await)
+            };
+            if committed_term == Some(current_term) {
+                return Ok(());
+            }
+            progress.changed().await?;
+        }
+    }
+    
+    /// Send a heartbeat round and wait until a quorum has answered it, which proves no other
+    /// leader had been elected when the round was sent
+    async fn confirm_leadership(&self) -> anyhow::Result<()> {
+        let term = *self.current_term.read().await;
+        let round = self.replicate_to_peers().await;
+        
+        let mut progress = self.progress.subscribe();
+        loop {
+            if *self.state.read().await != NodeState::Leader || *self.current_term.read().await != term {
+                return Err(anyhow::anyhow!("Lost leadership before the read was confirmed"));
+            }
+            let mut acked: HashSet<NodeId> = self.reads.read().await.acked.iter()
+                .filter(|(_, acked)| **acked >= round)
+                .map(|(peer, _)| peer.clone())
+                .collect();
+            acked.insert(self.node_id.clone());
+            if self.membership.read().await.has_quorum(&acked) {
+                return Ok(());
+            }
+            progress.changed().await?;
+        }
+    }
+    
+    /// Answer a follower's read on a separate task, since confirming leadership needs the
+    /// message loop to keep handling responses
+    async fn handle_read_index(&self, term: u64, from: NodeId, request_id: u64, lease: bool) {
+        if term != *self.current_term.read().await {
+            return;
+        }
+        let raft = self.clone();
+        tokio::spawn(async move {
+            let read_index = timeout(raft.election_timeout, raft.leader_read_index(lease)).await
+                .ok()
+                .and_then(|result| result.ok());
+            let response = RaftMessage {
+                term,
+                from: raft.node_id.clone(),
+                message_type: RaftMessageType::ReadIndexResponse { request_id, read_index },
+            };
+            raft.send(&from, response).await;
+        });
+    }
+    
+    /// Ask the leader for a read index on behalf of a read on this node
+    async fn forward_read_index(&self, lease: bool) -> anyhow::Result<u64> {
+        let leader = self.leader_id.read().await.clone()
+            .ok_or_else(|| anyhow::anyhow!("No known leader to confirm the read"))?;
+        let (tx, rx) = oneshot::channel();
+        let request_id = {
+            let mut pending = self.pending_reads.lock().await;
+            pending.next_id += 1;
+            let request_id = pending.next_id;
+            pending.waiting.insert(request_id, tx);
+            request_id
+        };
+        
+        let request = RaftMessage {
+            term: *self.current_term.read().await,
+            from: self.node_id.clone(),
+            message_type: RaftMessageType::ReadIndex { request_id, lease },
         };
+        self.send(&leader, request).await;
         
-        log.push(entry);
-        index
+        // The leader gives up after its own election timeout; allow for the round trip too
+        let answer = timeout(self.election_timeout * 2, rx).await;
+        self.pending_reads.lock().await.waiting.remove(&request_id);
+        match answer {
+            Ok(Ok(Some(read_index))) => Ok(read_index),
+            Ok(Ok(None)) => Err(anyhow::anyhow!("Leader {} could not confirm the read", leader)),
+            _ => Err(anyhow::anyhow!("Leader {} did not answer the read in time", leader)),
+        }
+    }
+    
+    async fn wait_for_applied(&self, index: u64) -> anyhow::Result<()> {
+        let mut progress = self.progress.subscribe();
+        while *self.last_applied.read().await < index {
+            progress.changed().await?;
+        }
+        Ok(())
+    }
+    
+    /// Wait until `node` stores everything in this leader's log as of now
+    async fn wait_for_catch_up(&self, node: &NodeId) -> anyhow::Result<()> {
+        let target = self.log.read().await.last_index();
+        let mut progress = self.progress.subscribe();
+        while self.match_index.read().await.get(node).copied().unwrap_or(0) < target {
+            if *self.state.read().await != NodeState::Leader {
+                return Err(anyhow::anyhow!("Lost leadership while {} was catching up", node));
+            }
+            progress.changed().await?;
+        }
+        Ok(())
+    }
+}
+
+/// Configuration in effect at `index`: the last change entry up to it, else the snapshot's,
+/// else the bootstrap configuration
+fn membership_at(log: &RaftLog, snapshot: Option<&Snapshot>, bootstrap: &Membership, index: u64) -> Membership {
+    let end = index.min(log.last_index()).saturating_sub(log.compacted_index) as usize;
+    log.entries[..end].iter().rev()
+        .find_map(|entry| entry.membership.clone())
+        .or_else(|| snapshot.map(|snapshot| snapshot.membership.clone()))
+        .unwrap_or_else(|| bootstrap.clone())
+}
+
+/// Proposal ids are `node:term:index`, which is enough to find the entry again and spot an overwrite
+fn parse_proposal_id(proposal_id: &ProposalId) -> anyhow::Result<(u64, u64)> {
+    let mut parts = proposal_id.0.rsplitn(3, ':');
+    let index = parts.next().and_then(|part| part.parse().ok());
+    let term = parts.next().and_then(|part| part.parse().ok());
+    match (term, index) {
+        (Some(term), Some(index)) => Ok((term, index)),
+        _ => Err(anyhow::anyhow!("Malformed Raft proposal id: {}", proposal_id.0)),
     }
 }
 
 #[async_trait]
 impl ConsensusEngine for RaftNode {
     async fn start(&mut self) -> anyhow::Result<()> {
+        if let Some(addr) = self.config.listen_addr {
+            let inbox = self.inbox().await
+                .ok_or_else(|| anyhow::anyhow!("Raft node {} has no inbox", self.node_id))?;
+            let transport = TcpTransport::bind(addr, self.config.peers.clone(), inbox).await?;
+            // `set_transport` adds the members that joined after bootstrap
+            self.set_transport(Arc::new(transport)).await;
+        }
         self.start_consensus_loop().await;
         Ok(())
     }
@@ -443,21 +1716,249 @@ impl ConsensusEngine for RaftNode {
         if state != NodeState::Leader {
             return Err(anyhow::anyhow!("Not the leader"));
         }
+        // The transfer target must catch up with a log that has stopped growing
+        if self.transfer_target.read().await.is_some() {
+            return Err(anyhow::anyhow!("Leadership transfer in progress"));
+        }
         
-        let index = self.append_log_entry(value).await;
-        Ok(ProposalId(format!("{}:{}", self.node_id, index)))
+        let (term, index) = self.append_log_entry(value).await?;
+        self.advance_commit_index().await;
+        self.replicate_to_peers().await;
+        Ok(ProposalId(format!("{}:{}:{}", self.node_id, term, index)))
+    }
+    
+    async fn wait_for_commit(&self, proposal_id: &ProposalId, required_acks: usize) -> anyhow::Result<CommittedProposal> {
+        let (term, index) = parse_proposal_id(proposal_id)?;
+        let voters = self.membership.read().await.voter_count();
+        if required_acks > voters {
+            return Err(anyhow::anyhow!("Proposal needs {} acknowledgements but the cluster has {} voters", required_acks, voters));
+        }
+        
+        // Subscribe before checking so an apply between the check and the wait is not missed
+        let mut progress = self.progress.subscribe();
+        loop {
+            if index <= *self.last_applied.read().await {
+                let entry = self.log.read().await.get(index).cloned()
+                    .ok_or_else(|| anyhow::anyhow!("Committed entry {} was compacted before its commit was observed", index))?;
+                if entry.term != term {
+                    return Err(anyhow::anyhow!("Proposal {} was replaced by an entry from term {}", proposal_id.0, entry.term));
+                }
+                if self.replication_count(index).await >= required_acks {
+                    return Ok(CommittedProposal {
+                        term: entry.term,
+                        index: entry.index,
+                        value: entry.value,
+                    });
+                }
+            }
+            progress.changed().await?;
+        }
     }
     
     async fn vote(&self, proposal_id: ProposalId, vote: Vote) -> anyhow::Result<()> {
-        // In Raft, voting is handled internally through RequestVote messages
-        // This method is used for external voting on proposals
+        // Leader election uses RequestVote internally; this records application-level votes
+        self.record_vote(&proposal_id, self.node_id.clone(), vote).await;
         Ok(())
     }
     
+    async fn receive_vote(&self, proposal_id: ProposalId, voter: NodeId, vote: Vote) -> anyhow::Result<()> {
+        self.record_vote(&proposal_id, voter, vote).await;
+        Ok(())
+    }
+    
+    async fn tally(&self, proposal_id: &ProposalId) -> anyhow::Result<VoteTally> {
+        let mut tally = VoteTally {
+            voters: self.membership.read().await.voter_count(),
+            ..VoteTally::default()
+        };
+        if let Some(ballots) = self.votes.read().await.get(&proposal_id.0) {
+            for vote in ballots.values() {
+                match vote {
+                    Vote::Accept => tally.accept += 1,
+                    Vote::Reject => tally.reject += 1,
+                    Vote::Abstain => tally.abstain += 1,
+                }
+            }
+        }
+        Ok(tally)
+    }
+    
     async fn on_commit(&self, value: Vec<u8>) -> anyhow::Result<()> {
-        // Apply committed value to state machine
-        // In MVP, we just log that the value was committed
-        println!("Committed value: {} bytes", value.len());
+        // Committed entries reach the state machine through the apply loop
+        tracing::debug!("Committed value: {} bytes", value.len());
+        Ok(())
+    }
+    
+    async fn set_state_machine(&self, machine: Arc<dyn StateMachine>) -> anyhow::Result<()> {
+        let _applying = self.applying.lock().await;
+        // Entries up to a recovered snapshot are never replayed, so the machine starts from it
+        if let Some(snapshot) = self.snapshot.read().await.as_ref() {
+            machine.restore(&snapshot.data).await?;
+        }
+        *self.state_machine.write().await = Some(machine);
         Ok(())
     }
-}
\ No newline at end of file
+    
+    async fn add_learner(&self, node: NodeId, addr: Option<SocketAddr>) -> anyhow::Result<()> {
+        let current = self.begin_membership_change().await?;
+        if current.members().contains(&node) {
+            return Ok(());
+        }
+        
+        let mut next = current;
+        if let Some(addr) = addr {
+            next.addresses.insert(node.clone(), addr);
+        }
+        next.learners.insert(node);
+        self.commit_membership(next).await
+    }
+    
+    async fn add_voter(&self, node: NodeId, addr: Option<SocketAddr>) -> anyhow::Result<()> {
+        if !self.membership.read().await.members().contains(&node) {
+            self.add_learner(node.clone(), addr).await?;
+        }
+        if self.membership.read().await.is_voter(&node) {
+            return Ok(());
+        }
+        
+        // Promoting a learner that is far behind would stall commits until it caught up
+        self.wait_for_catch_up(&node).await?;
+        let current = self.begin_membership_change().await?;
+        let mut incoming = current.voters.clone();
+        incoming.insert(node.clone());
+        let mut next = current;
+        next.learners.remove(&node);
+        next.incoming = Some(incoming);
+        self.commit_membership(next).await
+    }
+    
+    async fn remove_server(&self, node: NodeId) -> anyhow::Result<()> {
+        let current = self.begin_membership_change().await?;
+        let mut next = current.clone();
+        next.addresses.remove(&node);
+        if next.learners.remove(&node) {
+            // Learners never count towards a quorum, so they leave in one step
+            return self.commit_membership(next).await;
+        }
+        if !current.voters.contains(&node) {
+            return Ok(());
+        }
+        
+        let mut incoming = current.voters;
+        incoming.remove(&node);
+        if incoming.is_empty() {
+            return Err(anyhow::anyhow!("Cannot remove {}, the last voter", node));
+        }
+        next.incoming = Some(incoming);
+        self.commit_membership(next).await
+    }
+    
+    async fn membership(&self) -> anyhow::Result<Membership> {
+        Ok(self.membership.read().await.clone())
+    }
+    
+    async fn read_index(&self, consistency: ReadConsistency) -> anyhow::Result<u64> {
+        let lease = match consistency {
+            ReadConsistency::Stale => return Ok(*self.last_applied.read().await),
+            ReadConsistency::Lease => true,
+            ReadConsistency::Linearizable => false,
+        };
+        let state = *self.state.read().await;
+        let read_index = match state {
+            NodeState::Leader => self.leader_read_index(lease).await?,
+            _ => self.forward_read_index(lease).await?,
+        };
+        self.wait_for_applied(read_index).await?;
+        Ok(read_index)
+    }
+    
+    async fn transfer_leadership(&self, target: NodeId) -> anyhow::Result<()> {
+        if !self.config.leadership_transfer {
+            return Err(anyhow::anyhow!("Leadership transfer is disabled"));
+        }
+        if *self.state.read().await != NodeState::Leader {
+            return Err(anyhow::anyhow!("Not the leader"));
+        }
+        if target == self.node_id {
+            return Ok(());
+        }
+        if !self.membership.read().await.is_voter(&target) {
+            return Err(anyhow::anyhow!("{} is not a voter", target));
+        }
+        
+        {
+            let mut transfer_target = self.transfer_target.write().await;
+            if transfer_target.is_some() {
+                return Err(anyhow::anyhow!("Leadership transfer in progress"));
+            }
+            *transfer_target = Some(target.clone());
+        }
+        // Give up after an election timeout, as the target has most likely failed
+        let result = timeout(self.election_timeout, self.hand_over(&target)).await;
+        *self.transfer_target.write().await = None;
+        result.map_err(|_| anyhow::anyhow!("Leadership transfer to {} timed out", target))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// Keeps every message instead of delivering it
+    #[derive(Default)]
+    struct Outbox {
+        sent: Mutex<Vec<RaftMessage>>,
+    }
+    
+    #[async_trait]
+    impl ConsensusTransport<RaftMessage> for Outbox {
+        async fn send(&self, _peer: &NodeId, message: RaftMessage) -> anyhow::Result<()> {
+            self.sent.lock().await.push(message);
+            Ok(())
+        }
+    }
+    
+    fn request_vote(term: u64, candidate: &str) -> RaftMessage {
+        RaftMessage {
+            term,
+            from: candidate.to_string(),
+            message_type: RaftMessageType::RequestVote {
+                candidate_id: candidate.to_string(),
+                last_log_index: 0,
+                last_log_term: 0,
+            },
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_vote_requested_during_an_election_does_not_override_the_self_vote() {
+        let node = RaftNode::with_config("node1".to_string(), RaftConfig::default()).unwrap();
+        node.add_peer("node2".to_string()).await;
+        node.add_peer("node3".to_string()).await;
+        let outbox = Arc::new(Outbox::default());
+        node.set_transport(outbox.clone()).await;
+        
+        // Holding the log parks both tasks where they read it: the vote request while checking
+        // node2's log for term 1, the election, which has moved on to term 2, while asking for votes
+        let log = node.log.write().await;
+        let voter = node.clone();
+        let vote = tokio::spawn(async move { voter.handle_message(request_vote(1, "node2")).await });
+        tokio::task::yield_now().await;
+        let candidate = node.clone();
+        let election = tokio::spawn(async move { candidate.start_election().await });
+        tokio::task::yield_now().await;
+        drop(log);
+        vote.await.unwrap();
+        election.await.unwrap();
+        
+        // Having voted for itself in term 2, node1 has nothing left to grant in it
+        node.handle_message(request_vote(2, "node2")).await;
+        
+        assert_eq!(*node.current_term.read().await, 2);
+        assert_eq!(node.voted_for.read().await.as_deref(), Some("node1"));
+        let granted = outbox.sent.lock().await.iter().any(|message| {
+            matches!(message.message_type, RaftMessageType::RequestVoteResponse { vote_granted: true })
+        });
+        assert!(!granted, "node1 granted node2 a vote while standing for election");
+    }
+}
@@ -0,0 +1,105 @@
+/*!
+ * Durable Raft state for OMNIX
+ * Term, vote, log entries and snapshot in sled, flushed to disk before a node acts on them
+ */
+
+use crate::raft::{LogEntry, RaftLog, Snapshot};
+use crate::NodeId;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const HARD_STATE_KEY: &[u8] = b"hard_state";
+const SNAPSHOT_KEY: &[u8] = b"snapshot";
+/// Index and term of the last compacted entry
+const COMPACTED_KEY: &[u8] = b"compacted";
+
+/// The state Raft must not forget across a restart, besides the log
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HardState {
+    pub current_term: u64,
+    pub voted_for: Option<NodeId>,
+}
+
+/// Write-ahead storage for one Raft node. Entries are keyed by their big-endian index,
+/// so sled's key order is log order.
+pub struct RaftStorage {
+    db: sled::Db,
+    entries: sled::Tree,
+}
+
+impl RaftStorage {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let db = sled::open(path)?;
+        let entries = db.open_tree("entries")?;
+        Ok(Self { db, entries })
+    }
+
+    /// Hard state, snapshot and log as last flushed; empty for a new node
+    pub fn load(&self) -> anyhow::Result<(HardState, Option<Snapshot>, RaftLog)> {
+        let hard_state = match self.db.get(HARD_STATE_KEY)? {
+            Some(bytes) => bincode::deserialize(&bytes)?,
+            None => HardState::default(),
+        };
+        let snapshot = match self.db.get(SNAPSHOT_KEY)? {
+            Some(bytes) => Some(bincode::deserialize(&bytes)?),
+            None => None,
+        };
+        let (compacted_index, compacted_term) = match self.db.get(COMPACTED_KEY)? {
+            Some(bytes) => bincode::deserialize(&bytes)?,
+            None => (0, 0),
+        };
+
+        let mut log = RaftLog {
+            compacted_index,
+            compacted_term,
+            entries: Vec::new(),
+        };
+        for value in self.entries.range((compacted_index + 1).to_be_bytes()..).values() {
+            let entry: LogEntry = bincode::deserialize(&value?)?;
+            if entry.index != log.last_index() + 1 {
+                return Err(anyhow::anyhow!("Raft log is missing entry {}", log.last_index() + 1));
+            }
+            log.push(entry);
+        }
+        Ok((hard_state, snapshot, log))
+    }
+
+    pub async fn save_hard_state(&self, hard_state: &HardState) -> anyhow::Result<()> {
+        self.db.insert(HARD_STATE_KEY, bincode::serialize(hard_state)?)?;
+        self.db.flush_async().await?;
+        Ok(())
+    }
+
+    /// Store `snapshot` and drop the entries `log` has compacted
+    pub async fn save_snapshot(&self, snapshot: &Snapshot, log: &RaftLog) -> anyhow::Result<()> {
+        // Record the compaction point before dropping entries, so a crash in between leaves
+        // only stale entries that `load` skips
+        let mut batch = sled::Batch::default();
+        batch.insert(SNAPSHOT_KEY, bincode::serialize(snapshot)?);
+        batch.insert(COMPACTED_KEY, bincode::serialize(&(log.compacted_index, log.compacted_term))?);
+        self.db.apply_batch(batch)?;
+
+        let mut batch = sled::Batch::default();
+        for key in self.entries.range(..=log.compacted_index.to_be_bytes()).keys() {
+            batch.remove(key?);
+        }
+        self.entries.apply_batch(batch)?;
+        self.db.flush_async().await?;
+        Ok(())
+    }
+
+    /// Replace the log from `from_index` on with `entries`, which must start at that index
+    pub async fn write_entries(&self, from_index: u64, entries: &[LogEntry]) -> anyhow::Result<()> {
+        // One batch, so a crash never leaves a truncated log without its replacement
+        let mut batch = sled::Batch::default();
+        for key in self.entries.range(from_index.to_be_bytes()..).keys() {
+            batch.remove(key?);
+        }
+        for entry in entries {
+            batch.insert(&entry.index.to_be_bytes(), bincode::serialize(entry)?);
+        }
+        self.entries.apply_batch(batch)?;
+        self.db.flush_async().await?;
+        Ok(())
+    }
+}
@@ -3,20 +3,71 @@
  * Executes parsed OMNIX programs
  */
 
-use crate::{Runtime, RuntimeConfig, ConsensusConfig, NetworkConfig, StateConfig, ConsensusAlgorithm, DiscoveryMethod, ConsistencyLevel, NodeId};
+use crate::{Runtime, RuntimeConfig, ConsensusConfig, RaftConfig, PbftConfig, TendermintConfig, HotStuffConfig, NetworkConfig, StateConfig, ConsensusAlgorithm, DiscoveryMethod, ConsistencyLevel, NodeId, ProposalId, ReadConsistency, Vote, Message};
 use omnix_compiler::ast::*;
-use std::collections::HashMap;
+use omnix_compiler::pratt::LValue;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Duration;
+use tokio::sync::{Mutex, RwLock};
+
+pub mod events;
+mod replication;
+pub mod stdlib;
+
+use events::{Event, EventPayload, EventSequencer, MessageDispatcher};
+use replication::{Command, ReplicatedState};
+use stdlib::NativeRegistry;
+
+/// Deepest call nesting before execution is aborted. Unoptimized, every level takes close to
+/// 50KB of stack and 40 levels already overflow the 2MB of a tokio worker thread, so this
+/// leaves room for the expressions evaluated inside each call.
+const MAX_CALL_DEPTH: usize = 24;
 
 /// MVP runtime executor for OMNIX programs
 pub struct Executor {
     runtime: Runtime,
     node_id: NodeId,
     state_vars: Arc<RwLock<HashMap<String, RuntimeValue>>>,
+    // `@replicated` state variables, written only by applying committed entries
+    replicated: HashSet<String>,
+    machine: Arc<ReplicatedState>,
+    // Keyed by (owning node or cluster, name); top-level functions have no owner
+    functions: HashMap<(Option<String>, String), Arc<Callable>>,
+    frames: Vec<Frame>,
+    natives: NativeRegistry,
+    // Used by `<!>` when the proposal sets no timeout
+    consensus_timeout: Duration,
+    // Latest phase entered; shared with the HTTP API
+    phase: Arc<RwLock<Option<PhaseStatus>>>,
+    // `on` handlers by event name
+    handlers: HashMap<String, Vec<Arc<Callable>>>,
+    // Identifies this run's event stream; `last_sequence` numbers the events broadcast in it,
+    // shared with forks so their events join the same stream
+    incarnation: String,
+    last_sequence: Arc<Mutex<u64>>,
+    events: EventSequencer,
 }
 
-#[derive(Debug, Clone)]
+/// A node function, cluster service, top-level function or event handler
+#[derive(Debug)]
+struct Callable {
+    name: String,
+    owner: Option<String>,
+    params: Vec<Parameter>,
+    body: Block,
+}
+
+/// Locals of one active call, innermost block scope last
+#[derive(Debug)]
+struct Frame {
+    owner: Option<String>,
+    scopes: Vec<HashMap<String, RuntimeValue>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum RuntimeValue {
     Integer(i64),
     UInteger(u64),
@@ -24,25 +75,320 @@ pub enum RuntimeValue {
     String(String),
     Boolean(bool),
     Bytes(Vec<u8>),
+    List(Vec<RuntimeValue>),
+    /// Distinct elements in insertion order
+    Set(Vec<RuntimeValue>),
+    Map(BTreeMap<String, RuntimeValue>),
+    Duration(Duration),
+    Option(Option<Box<RuntimeValue>>),
+    Result(Result<Box<RuntimeValue>, Box<RuntimeValue>>),
+    /// Keys to insert (`Some`) or remove (`None`), produced by `Map::insert`/`Map::remove` for `<#>`
+    MapPatch(BTreeMap<String, Option<RuntimeValue>>),
+    Proposal(Box<ProposalResult>),
+    Unit,
+}
+
+/// Structural equality; signed and unsigned integers are equal when they hold the same number
+impl PartialEq for RuntimeValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (RuntimeValue::Integer(a), RuntimeValue::Integer(b)) => a == b,
+            (RuntimeValue::UInteger(a), RuntimeValue::UInteger(b)) => a == b,
+            (RuntimeValue::Integer(signed), RuntimeValue::UInteger(unsigned))
+            | (RuntimeValue::UInteger(unsigned), RuntimeValue::Integer(signed)) => u64::try_from(*signed) == Ok(*unsigned),
+            (RuntimeValue::Float(a), RuntimeValue::Float(b)) => a == b,
+            (RuntimeValue::String(a), RuntimeValue::String(b)) => a == b,
+            (RuntimeValue::Boolean(a), RuntimeValue::Boolean(b)) => a == b,
+            (RuntimeValue::Bytes(a), RuntimeValue::Bytes(b)) => a == b,
+            (RuntimeValue::List(a), RuntimeValue::List(b)) => a == b,
+            (RuntimeValue::Set(a), RuntimeValue::Set(b)) => a.len() == b.len() && a.iter().all(|item| b.contains(item)),
+            (RuntimeValue::Map(a), RuntimeValue::Map(b)) => a == b,
+            (RuntimeValue::Duration(a), RuntimeValue::Duration(b)) => a == b,
+            (RuntimeValue::Option(a), RuntimeValue::Option(b)) => a == b,
+            (RuntimeValue::Result(a), RuntimeValue::Result(b)) => a == b,
+            (RuntimeValue::MapPatch(a), RuntimeValue::MapPatch(b)) => a == b,
+            (RuntimeValue::Proposal(a), RuntimeValue::Proposal(b)) => a == b,
+            (RuntimeValue::Unit, RuntimeValue::Unit) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Outcome of a `<!>` proposal: `.accepted()`, `.value`, `.term` and `.index` in OMNIX code
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProposalResult {
+    /// Engine identifier, which `<?>` votes refer to; absent if the proposal never reached the engine
+    pub id: Option<String>,
+    pub accepted: bool,
+    pub value: RuntimeValue,
+    /// Log position of the committed entry; zero when rejected
+    pub term: u64,
+    pub index: u64,
+    /// Why the proposal was not accepted
+    pub error: Option<String>,
+}
+
+impl ProposalResult {
+    fn field(&self, name: &str) -> anyhow::Result<RuntimeValue> {
+        match name {
+            "id" => Ok(RuntimeValue::Option(self.id.clone().map(|id| Box::new(RuntimeValue::String(id))))),
+            "accepted" => Ok(RuntimeValue::Boolean(self.accepted)),
+            "value" => Ok(self.value.clone()),
+            "term" => Ok(RuntimeValue::UInteger(self.term)),
+            "index" => Ok(RuntimeValue::UInteger(self.index)),
+            "error" => Ok(RuntimeValue::Option(self.error.clone().map(|e| Box::new(RuntimeValue::String(e))))),
+            other => Err(anyhow::anyhow!("Proposal result has no field '{}'", other)),
+        }
+    }
+}
+
+/// Progress of the most recent `phase` block, reported by `/status`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhaseStatus {
+    pub name: String,
+    /// Phases entered by this node so far, counting this one
+    pub round: u64,
+    pub state: PhaseState,
+    /// Why the phase aborted
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PhaseState {
+    /// Executing the phase body
+    Running,
+    /// Body finished; waiting for the other participants at the barrier
+    Waiting,
+    Completed,
+    Aborted,
+}
+
+/// Reads and writes of a node's state from outside the program, e.g. by the HTTP API
+#[derive(Clone)]
+pub struct StateHandle {
+    runtime: Runtime,
+    machine: Arc<ReplicatedState>,
+    consensus_timeout: Duration,
+}
+
+impl StateHandle {
+    /// What this node holds for a state variable right now, with no consistency guarantee
+    pub async fn get(&self, name: &str) -> Option<RuntimeValue> {
+        self.machine.state_vars.read().await.get(name).cloned()
+    }
+    
+    /// A `@replicated` variable as applied from the log, at `consistency`.
+    /// `None` if the program declares no such variable.
+    pub async fn read(&self, name: &str, consistency: ReadConsistency, deadline: tokio::time::Instant) -> anyhow::Result<Option<RuntimeValue>> {
+        match self.runtime.get(name, consistency, deadline).await? {
+            Some(bytes) => Ok(Some(bincode::deserialize(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+    
+    /// Write a `@replicated` variable through the log, as `name op value` would in the program.
+    /// Returns the variable once this node has applied the write.
+    pub async fn update(&self, name: &str, op: AssignmentOp, value: RuntimeValue) -> anyhow::Result<RuntimeValue> {
+        if !self.machine.is_declared(name).await {
+            return Err(anyhow::anyhow!("{} is not a @replicated state variable", name));
+        }
+        // Check the update locally first so an invalid write never reaches the log
+        Executor::update_value(name, self.get(name).await, &[], &op, value.clone())?;
+        self.commit_update(name, Vec::new(), &op, value).await?;
+        self.get(name).await.ok_or_else(|| anyhow::anyhow!("Undefined variable: {}", name))
+    }
+    
+    /// Propose an update and wait until it is applied locally
+    async fn commit_update(&self, name: &str, path: Vec<PathSegment>, op: &AssignmentOp, value: RuntimeValue) -> anyhow::Result<()> {
+        let deadline = tokio::time::Instant::now() + self.consensus_timeout;
+        let command = Command::Update {
+            name: name.to_string(),
+            path,
+            op: op.clone(),
+            value,
+        };
+        
+        let committed = async {
+            let proposal_id = self.runtime.propose_until(bincode::serialize(&command)?, deadline).await?;
+            self.runtime.wait_for_commit(&proposal_id, 0, deadline).await
+        }.await.map_err(|e| anyhow::anyhow!("Replicated write to {} failed: {}", name, e))?;
+        
+        tracing::debug!("Replicated write: {} at index {}", name, committed.index);
+        Ok(())
+    }
+}
+
+impl fmt::Display for RuntimeValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeValue::Integer(n) => write!(f, "{}", n),
+            RuntimeValue::UInteger(n) => write!(f, "{}", n),
+            RuntimeValue::Float(x) => write!(f, "{}", x),
+            RuntimeValue::String(s) => write!(f, "{}", s),
+            RuntimeValue::Boolean(b) => write!(f, "{}", b),
+            RuntimeValue::Bytes(bytes) => {
+                write!(f, "0x")?;
+                bytes.iter().try_for_each(|b| write!(f, "{:02x}", b))
+            }
+            RuntimeValue::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            RuntimeValue::Set(items) => {
+                write!(f, "{{")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "}}")
+            }
+            RuntimeValue::Map(map) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in map.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", key, value)?;
+                }
+                write!(f, "}}")
+            }
+            RuntimeValue::Duration(d) => write!(f, "{}ms", d.as_millis()),
+            RuntimeValue::Option(Some(value)) => write!(f, "Some({})", value),
+            RuntimeValue::Option(None) => write!(f, "None"),
+            RuntimeValue::Result(Ok(value)) => write!(f, "Ok({})", value),
+            RuntimeValue::Result(Err(error)) => write!(f, "Err({})", error),
+            RuntimeValue::MapPatch(patch) => write!(f, "{:?}", patch),
+            RuntimeValue::Proposal(result) if result.accepted => {
+                write!(f, "accepted({}) at {}:{}", result.value, result.term, result.index)
+            }
+            RuntimeValue::Proposal(result) => {
+                write!(f, "rejected({})", result.error.as_deref().unwrap_or("unknown reason"))
+            }
+            RuntimeValue::Unit => write!(f, "()"),
+        }
+    }
+}
+
+/// How a statement finished, so loops and calls can honor `break`/`continue`/`return`
+#[derive(Debug, Clone)]
+enum ControlFlow {
+    Normal,
+    Break,
+    Continue,
+    Return(RuntimeValue),
+}
+
+/// One step below the root variable of an assignment target
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum PathSegment {
+    Field(String),
+    Index(RuntimeValue),
 }
 
 impl Executor {
     pub async fn new(node_id: NodeId, config: RuntimeConfig) -> anyhow::Result<Self> {
+        let consensus_timeout = Duration::from_millis(config.consensus.timeout_ms);
         let runtime = Runtime::new(node_id.clone(), config).await?;
+        Self::with_runtime(node_id, runtime, consensus_timeout).await
+    }
+    
+    /// An executor over a runtime that has not been started yet
+    pub async fn with_runtime(node_id: NodeId, runtime: Runtime, consensus_timeout: Duration) -> anyhow::Result<Self> {
+        let state_vars = Arc::new(RwLock::new(HashMap::new()));
+        let machine = Arc::new(ReplicatedState::new(state_vars.clone()));
+        runtime.set_state_machine(machine.clone()).await?;
         
         Ok(Self {
             runtime,
             node_id,
-            state_vars: Arc::new(RwLock::new(HashMap::new())),
+            state_vars,
+            replicated: HashSet::new(),
+            machine,
+            functions: HashMap::new(),
+            frames: Vec::new(),
+            natives: NativeRegistry::with_stdlib(),
+            consensus_timeout,
+            phase: Arc::new(RwLock::new(None)),
+            handlers: HashMap::new(),
+            incarnation: uuid::Uuid::new_v4().to_string(),
+            last_sequence: Arc::new(Mutex::new(0)),
+            events: EventSequencer::default(),
         })
     }
     
+    /// Take delivery of messages from peers; `None` if already taken
+    pub async fn message_dispatcher(&self) -> Option<MessageDispatcher> {
+        let incoming = self.runtime.take_incoming().await?;
+        Some(MessageDispatcher {
+            runtime: self.runtime.clone(),
+            incoming,
+        })
+    }
+    
+    /// Another executor over the same program, state and runtime, with its own call stack.
+    /// Call after `start`, so the program's functions and handlers are registered.
+    pub fn fork(&self) -> Executor {
+        Executor {
+            runtime: self.runtime.clone(),
+            node_id: self.node_id.clone(),
+            state_vars: self.state_vars.clone(),
+            replicated: self.replicated.clone(),
+            machine: self.machine.clone(),
+            functions: self.functions.clone(),
+            frames: Vec::new(),
+            natives: self.natives.clone(),
+            consensus_timeout: self.consensus_timeout,
+            phase: self.phase.clone(),
+            handlers: self.handlers.clone(),
+            incarnation: self.incarnation.clone(),
+            last_sequence: self.last_sequence.clone(),
+            events: EventSequencer::default(),
+        }
+    }
+    
+    /// Handle on the current phase, readable while the program runs
+    pub fn phase_status(&self) -> Arc<RwLock<Option<PhaseStatus>>> {
+        self.phase.clone()
+    }
+    
+    /// Handle on the runtime, usable while the program runs
+    pub fn runtime(&self) -> Runtime {
+        self.runtime.clone()
+    }
+    
+    /// Handle on the program's state, usable while the program runs
+    pub fn state(&self) -> StateHandle {
+        StateHandle {
+            runtime: self.runtime.clone(),
+            machine: self.machine.clone(),
+            consensus_timeout: self.consensus_timeout,
+        }
+    }
+    
     pub async fn execute(&mut self, program: Program) -> anyhow::Result<()> {
-        // Start the runtime
-        self.runtime.start().await?;
+        self.start(&program).await?;
+        self.run_main().await
+    }
+    
+    /// Register the program and initialize its state, then start the runtime. State comes
+    /// first: committed entries are applied from the moment the runtime starts, and each one
+    /// must find the variable it updates, holding the same initial value as on every replica.
+    pub async fn start(&mut self, program: &Program) -> anyhow::Result<()> {
+        // Make every function callable before anything runs
+        for item in &program.items {
+            self.register_item(item);
+        }
         
-        // Execute each top-level item
-        for item in program.items {
+        for item in &program.items {
             match item {
                 Item::Node(node) => {
                     self.execute_node(node).await?;
@@ -50,18 +396,68 @@ impl Executor {
                 Item::Cluster(cluster) => {
                     self.execute_cluster(cluster).await?;
                 }
-                Item::Function(func) => {
-                    if func.name == "main" {
-                        self.execute_function(func).await?;
-                    }
-                }
+                Item::Function(_) => {}
             }
         }
         
+        self.runtime.start().await
+    }
+    
+    /// Run the program's `main` function, if it has one
+    pub async fn run_main(&mut self) -> anyhow::Result<()> {
+        if !self.functions.contains_key(&(None, "main".to_string())) {
+            return Ok(());
+        }
+        println!("Executing function: main");
+        let result = self.call_function(None, "main", Vec::new()).await?;
+        println!("Function main returned {:?}", result);
         Ok(())
     }
     
-    async fn execute_node(&mut self, node: NodeDefinition) -> anyhow::Result<()> {
+    fn register_item(&mut self, item: &Item) {
+        match item {
+            Item::Node(node) => {
+                for node_item in &node.items {
+                    match node_item {
+                        NodeItem::Function(func) => {
+                            self.register_callable(Some(&node.name), &func.name, &func.params, &func.body);
+                        }
+                        NodeItem::EventHandler(handler) => {
+                            let callable = Callable {
+                                name: handler.event_name.clone(),
+                                owner: Some(node.name.clone()),
+                                params: handler.params.clone(),
+                                body: handler.body.clone(),
+                            };
+                            self.handlers.entry(handler.event_name.clone()).or_default().push(Arc::new(callable));
+                        }
+                        NodeItem::State(_) => {}
+                    }
+                }
+            }
+            Item::Cluster(cluster) => {
+                for cluster_item in &cluster.items {
+                    if let ClusterItem::Service(service) = cluster_item {
+                        self.register_callable(Some(&cluster.name), &service.name, &service.params, &service.body);
+                    }
+                }
+            }
+            Item::Function(func) => self.register_callable(None, &func.name, &func.params, &func.body),
+        }
+    }
+    
+    fn register_callable(&mut self, owner: Option<&str>, name: &str, params: &[Parameter], body: &Block) {
+        let owner = owner.map(str::to_string);
+        let callable = Callable {
+            name: name.to_string(),
+            owner: owner.clone(),
+            params: params.to_vec(),
+            body: body.clone(),
+        };
+        self.functions.insert((owner, name.to_string()), Arc::new(callable));
+    }
+    
+    async fn execute_node(&mut self, node: &NodeDefinition) -> anyhow::Result<()> {
         println!("Executing node: {}", node.name);
         
         // Initialize state variables
@@ -90,7 +486,7 @@ impl Executor {
         Ok(())
     }
     
-    async fn execute_cluster(&mut self, cluster: ConsensusCluster) -> anyhow::Result<()> {
+    async fn execute_cluster(&mut self, cluster: &ConsensusCluster) -> anyhow::Result<()> {
         println!("Executing consensus cluster: {}", cluster.name);
         println!("Replicas: {}, Consensus: {:?}", cluster.replicas, cluster.consensus);
         
@@ -109,13 +505,106 @@ impl Executor {
         Ok(())
     }
     
-    async fn execute_function(&mut self, func: Function) -> anyhow::Result<()> {
-        println!("Executing function: {}", func.name);
+    /// Call a function by name: the caller's own node or cluster is searched first, then top-level
+    /// functions, then the native standard library
+    async fn call_function(&mut self, owner: Option<&str>, name: &str, args: Vec<RuntimeValue>) -> anyhow::Result<RuntimeValue> {
+        let callable = match owner {
+            Some(owner) => self.functions.get(&(Some(owner.to_string()), name.to_string())),
+            None => {
+                let current_owner = self.frames.last().and_then(|frame| frame.owner.clone());
+                self.functions.get(&(current_owner, name.to_string()))
+                    .or_else(|| self.functions.get(&(None, name.to_string())))
+            }
+        }
+        .cloned();
         
-        // Execute function body
-        self.execute_block(&func.body).await?;
+        match callable {
+            Some(callable) => self.invoke(&callable, args).await,
+            None => match (owner, self.natives.function(name)) {
+                (None, Some(native)) => native.call(&args),
+                _ => Err(anyhow::anyhow!("Undefined function: {}", name)),
+            },
+        }
+    }
+    
+    /// Run a callable in a new frame with its parameters bound to `args`
+    async fn invoke(&mut self, callable: &Callable, args: Vec<RuntimeValue>) -> anyhow::Result<RuntimeValue> {
+        if self.frames.len() >= MAX_CALL_DEPTH {
+            return Err(anyhow::anyhow!("Maximum call depth of {} exceeded calling {}", MAX_CALL_DEPTH, callable.name));
+        }
+        if args.len() != callable.params.len() {
+            return Err(anyhow::anyhow!(
+                "{} expects {} arguments, got {}", callable.name, callable.params.len(), args.len()
+            ));
+        }
         
-        Ok(())
+        let locals = callable.params.iter()
+            .map(|param| param.name.clone())
+            .zip(args)
+            .collect();
+        self.frames.push(Frame {
+            owner: callable.owner.clone(),
+            scopes: vec![locals],
+        });
+        let result = self.execute_block(&callable.body).await;
+        self.frames.pop();
+        
+        match result? {
+            ControlFlow::Return(value) => Ok(value),
+            _ => Ok(RuntimeValue::Unit),
+        }
+    }
+    
+    /// Look a name up in the current call's scopes, then in state
+    async fn read_variable(&self, name: &str) -> Option<RuntimeValue> {
+        if let Some(frame) = self.frames.last() {
+            if let Some(value) = frame.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+                return Some(value.clone());
+            }
+        }
+        if let Some(value) = self.state_vars.read().await.get(name) {
+            return Some(value.clone());
+        }
+        match name {
+            "node_id" => Some(RuntimeValue::String(self.node_id.clone())),
+            "None" => Some(RuntimeValue::Option(None)),
+            _ => None,
+        }
+    }
+    
+    /// Overwrite a local if one is in scope, otherwise write state
+    async fn write_variable(&mut self, name: &str, value: RuntimeValue) {
+        if let Some(frame) = self.frames.last_mut() {
+            if let Some(slot) = frame.scopes.iter_mut().rev().find_map(|scope| scope.get_mut(name)) {
+                *slot = value;
+                return;
+            }
+        }
+        self.state_vars.write().await.insert(name.to_string(), value);
+    }
+    
+    /// Bind a new local in the innermost scope
+    async fn declare_local(&mut self, name: &str, value: RuntimeValue) {
+        match self.frames.last_mut().and_then(|frame| frame.scopes.last_mut()) {
+            Some(scope) => {
+                scope.insert(name.to_string(), value);
+            }
+            None => {
+                self.state_vars.write().await.insert(name.to_string(), value);
+            }
+        }
+    }
+    
+    fn push_scope(&mut self, scope: HashMap<String, RuntimeValue>) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.scopes.push(scope);
+        }
+    }
+    
+    fn pop_scope(&mut self) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.scopes.pop();
+        }
     }
     
     async fn initialize_state_var(&mut self, state_var: &StateVariable) -> anyhow::Result<()> {
@@ -130,141 +619,817 @@ impl Executor {
                 Type::Bool => RuntimeValue::Boolean(false),
                 Type::String => RuntimeValue::String(String::new()),
                 Type::Bytes => RuntimeValue::Bytes(Vec::new()),
+                Type::Duration => RuntimeValue::Duration(Duration::ZERO),
+                Type::Vec(_) => RuntimeValue::List(Vec::new()),
+                Type::Set(_) => RuntimeValue::Set(Vec::new()),
+                Type::Map(..) => RuntimeValue::Map(BTreeMap::new()),
                 _ => RuntimeValue::Integer(0), // Default fallback
             }
         };
         
         let mut state_vars = self.state_vars.write().await;
-        state_vars.insert(state_var.name.clone(), value);
+        if state_var.annotations.iter().any(|a| a.name == "replicated") {
+            self.replicated.insert(state_var.name.clone());
+            self.machine.declare(&state_var.name).await;
+            // Keep a value already restored from a snapshot or applied from the log
+            state_vars.entry(state_var.name.clone()).or_insert(value);
+        } else {
+            state_vars.insert(state_var.name.clone(), value);
+        }
         
         println!("Initialized state variable: {} = {:?}", state_var.name, state_vars.get(&state_var.name));
         
         Ok(())
     }
     
-    async fn execute_block(&mut self, block: &Block) -> anyhow::Result<()> {
-        for statement in &block.statements {
-            self.execute_statement(statement).await?;
+    async fn execute_block(&mut self, block: &Block) -> anyhow::Result<ControlFlow> {
+        self.push_scope(HashMap::new());
+        let result = self.execute_statements(&block.statements).await;
+        self.pop_scope();
+        result
+    }
+    
+    async fn execute_statements(&mut self, statements: &[Statement]) -> anyhow::Result<ControlFlow> {
+        for statement in statements {
+            let flow = Box::pin(self.execute_statement(statement)).await?;
+            if !matches!(flow, ControlFlow::Normal) {
+                return Ok(flow);
+            }
         }
-        Ok(())
+        Ok(ControlFlow::Normal)
     }
     
-    async fn execute_statement(&mut self, statement: &Statement) -> anyhow::Result<()> {
+    async fn execute_statement(&mut self, statement: &Statement) -> anyhow::Result<ControlFlow> {
         match statement {
             Statement::Let(let_stmt) => {
                 let value = self.evaluate_expression(&let_stmt.value).await?;
-                let mut state_vars = self.state_vars.write().await;
-                state_vars.insert(let_stmt.name.clone(), value);
-                println!("Let binding: {} = {:?}", let_stmt.name, state_vars.get(&let_stmt.name));
+                println!("Let binding: {} = {:?}", let_stmt.name, value);
+                self.declare_local(&let_stmt.name, value).await;
             }
             Statement::Assignment(assignment) => {
-                let value = self.evaluate_expression(&assignment.value).await?;
-                match assignment.op {
-                    AssignmentOp::Assign => {
-                        let mut state_vars = self.state_vars.write().await;
-                        state_vars.insert(assignment.target.clone(), value);
-                        println!("Assignment: {} = {:?}", assignment.target, state_vars.get(&assignment.target));
-                    }
-                    AssignmentOp::Merge => {
-                        // For MVP, merge is the same as assign
-                        let mut state_vars = self.state_vars.write().await;
-                        state_vars.insert(assignment.target.clone(), value);
-                        println!("Merge: {} <#> {:?}", assignment.target, state_vars.get(&assignment.target));
-                    }
-                }
+                self.execute_assignment(&assignment.target, &assignment.op, &assignment.value).await?;
             }
             Statement::Expression(expr) => {
                 let _result = self.evaluate_expression(expr).await?;
                 // Expression statements don't store their result
             }
             Statement::When(when_stmt) => {
-                let condition_result = self.evaluate_expression(&when_stmt.condition).await?;
-                if let RuntimeValue::Boolean(true) = condition_result {
-                    self.execute_block(&when_stmt.body).await?;
+                if self.evaluate_condition(&when_stmt.condition).await? {
+                    return self.execute_block(&when_stmt.body).await;
+                } else if let Some(else_body) = &when_stmt.else_body {
+                    return self.execute_block(else_body).await;
+                }
+            }
+            Statement::If(if_stmt) => {
+                if self.evaluate_condition(&if_stmt.condition).await? {
+                    return self.execute_block(&if_stmt.then_body).await;
+                } else if let Some(else_body) = &if_stmt.else_body {
+                    return self.execute_block(else_body).await;
+                }
+            }
+            Statement::While(while_stmt) => {
+                while self.evaluate_condition(&while_stmt.condition).await? {
+                    // Let timers fire, so phase timeouts can interrupt busy loops
+                    tokio::task::yield_now().await;
+                    match self.execute_block(&while_stmt.body).await? {
+                        ControlFlow::Break => break,
+                        flow @ ControlFlow::Return(_) => return Ok(flow),
+                        ControlFlow::Normal | ControlFlow::Continue => {}
+                    }
+                }
+            }
+            Statement::For(for_stmt) => {
+                let iterable = self.evaluate_expression(&for_stmt.iterable).await?;
+                for values in Self::iteration_values(iterable, for_stmt.bindings.len())? {
+                    tokio::task::yield_now().await;
+                    let bindings = for_stmt.bindings.iter()
+                        .map(|binding| binding.name.clone())
+                        .zip(values)
+                        .collect();
+                    self.push_scope(bindings);
+                    let result = self.execute_block(&for_stmt.body).await;
+                    self.pop_scope();
+                    
+                    match result? {
+                        ControlFlow::Break => break,
+                        flow @ ControlFlow::Return(_) => return Ok(flow),
+                        ControlFlow::Normal | ControlFlow::Continue => {}
+                    }
+                }
+            }
+            Statement::Break(_) => return Ok(ControlFlow::Break),
+            Statement::Continue(_) => return Ok(ControlFlow::Continue),
+            Statement::Return(expr_opt, _) => {
+                let value = match expr_opt {
+                    Some(expr) => self.evaluate_expression(expr).await?,
+                    None => RuntimeValue::Unit,
+                };
+                return Ok(ControlFlow::Return(value));
+            }
+            Statement::Broadcast(broadcast) => self.emit_event(broadcast).await?,
+            Statement::Phase(phase) => return self.execute_phase(phase).await,
+        }
+        Ok(ControlFlow::Normal)
+    }
+    
+    /// Send a typed event to every peer, numbered so receivers handle this node's events in order
+    async fn emit_event(&mut self, broadcast: &BroadcastStatement) -> anyhow::Result<()> {
+        let payload = match &broadcast.args {
+            EventArgs::Positional(args) => EventPayload::Positional(self.evaluate_arguments(args).await?),
+            EventArgs::Named(fields) => {
+                let mut values = BTreeMap::new();
+                for field in fields {
+                    values.insert(field.name.clone(), self.evaluate_expression(&field.value).await?);
+                }
+                EventPayload::Named(values)
+            }
+        };
+        println!("Broadcasting event {}: {:?}", broadcast.event, payload);
+        
+        // Only a sent event consumes a sequence number, so receivers never wait on a gap we caused.
+        // The lock is held across the send so forks' events go out in sequence order.
+        let mut last_sequence = self.last_sequence.lock().await;
+        let sequence = *last_sequence + 1;
+        self.runtime.broadcast(Message::Event {
+            origin: self.node_id.clone(),
+            incarnation: self.incarnation.clone(),
+            sequence,
+            name: broadcast.event.clone(),
+            payload: bincode::serialize(&payload)?,
+        }).await?;
+        *last_sequence = sequence;
+        Ok(())
+    }
+    
+    /// Act on an event or state sync from a peer
+    async fn handle_message(&mut self, message: Message) -> anyhow::Result<()> {
+        match message {
+            Message::Event { origin, incarnation, sequence, name, payload } => {
+                if origin == self.node_id {
+                    return Ok(());
+                }
+                let payload = bincode::deserialize(&payload)?;
+                for event in self.events.receive(incarnation, sequence, Event { origin, name, payload }) {
+                    // One failing handler must not hold up the events behind it
+                    if let Err(e) = self.deliver_event(&event).await {
+                        tracing::warn!("Handler for {} from {} failed: {}", event.name, event.origin, e);
+                    }
+                }
+            }
+            Message::StateSync { name, value } => {
+                // Replicated state changes only by applying the log; a sync could be stale
+                if self.replicated.contains(&name) {
+                    tracing::warn!("Ignoring sync of @replicated state {}", name);
+                    return Ok(());
+                }
+                let value: RuntimeValue = bincode::deserialize(&value)?;
+                match self.state_vars.write().await.get_mut(&name) {
+                    Some(current) => *current = value,
+                    None => tracing::debug!("Ignoring sync of unknown state {}", name),
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+    
+    /// Run every `on` handler for the event
+    async fn deliver_event(&mut self, event: &Event) -> anyhow::Result<()> {
+        let handlers = self.handlers.get(&event.name).cloned().unwrap_or_default();
+        if handlers.is_empty() {
+            tracing::debug!("No handler for event {}", event.name);
+        }
+        for handler in handlers {
+            println!("Handling event {} from {}", event.name, event.origin);
+            let args = event.payload.bind(&event.name, &handler.params)?;
+            self.invoke(&handler, args).await?;
+        }
+        Ok(())
+    }
+    
+    /// Run one round of a multi-round protocol. The phase completes only once every participant
+    /// has finished its body, so no replica enters the next phase early. A timeout, an error or a
+    /// rejection from any participant aborts the phase on all of them.
+    async fn execute_phase(&mut self, phase: &PhaseStatement) -> anyhow::Result<ControlFlow> {
+        let deadline = tokio::time::Instant::now() + self.option_timeout(&phase.options).await?;
+        let participants = self.option_count(&phase.options, &["participants", "nodes"]).await?;
+        let round = {
+            let mut status = self.phase.write().await;
+            let round = status.as_ref().map_or(0, |status| status.round) + 1;
+            *status = Some(PhaseStatus { name: phase.name.clone(), round, state: PhaseState::Running, reason: None });
+            round
+        };
+        // Every replica runs the same program, so round and name identify the same barrier everywhere
+        let barrier = ProposalId(format!("phase:{}:{}", round, phase.name));
+        println!("Entering phase {} (round {})", phase.name, round);
+        
+        // A timed out body is dropped mid-statement, leaving its frames and scopes behind
+        let depth = (self.frames.len(), self.frames.last().map(|frame| frame.scopes.len()));
+        let outcome = tokio::time::timeout_at(deadline, Box::pin(self.execute_block(&phase.body))).await;
+        self.frames.truncate(depth.0);
+        if let (Some(scopes), Some(frame)) = (depth.1, self.frames.last_mut()) {
+            frame.scopes.truncate(scopes);
+        }
+        
+        let reason = match outcome {
+            Ok(Ok(ControlFlow::Normal)) => {
+                self.set_phase_state(PhaseState::Waiting, None).await;
+                match self.runtime.phase_barrier(barrier.clone(), participants, deadline).await {
+                    Ok(_) => {
+                        self.set_phase_state(PhaseState::Completed, None).await;
+                        return Ok(ControlFlow::Normal);
+                    }
+                    Err(e) => e.to_string(),
                 }
             }
-            Statement::Return(expr_opt) => {
-                if let Some(expr) = expr_opt {
-                    let value = self.evaluate_expression(expr).await?;
-                    println!("Return: {:?}", value);
-                } else {
-                    println!("Return (void)");
+            Ok(Ok(flow)) => {
+                // Leaving early is not a failure: abstaining tells peers waiting at the barrier
+                // not to wait for a participant that will never arrive
+                if let Err(e) = self.runtime.cast_vote(barrier, Vote::Abstain).await {
+                    tracing::warn!("Failed to announce early exit from phase {}: {}", phase.name, e);
                 }
+                self.set_phase_state(PhaseState::Completed, None).await;
+                return Ok(flow);
             }
-            Statement::Broadcast(expr) => {
-                let value = self.evaluate_expression(expr).await?;
-                println!("Broadcast: {:?}", value);
-                // TODO: Actually broadcast the message
+            Ok(Err(e)) => e.to_string(),
+            Err(_) => "timed out".to_string(),
+        };
+        
+        println!("Phase {} aborted: {}", phase.name, reason);
+        self.set_phase_state(PhaseState::Aborted, Some(reason.clone())).await;
+        if let Err(e) = self.runtime.cast_vote(barrier, Vote::Reject).await {
+            tracing::warn!("Failed to announce abort of phase {}: {}", phase.name, e);
+        }
+        match &phase.abort {
+            Some(abort) => self.execute_block(abort).await,
+            None => Err(anyhow::anyhow!("Phase '{}' aborted: {}", phase.name, reason)),
+        }
+    }
+    
+    async fn set_phase_state(&self, state: PhaseState, reason: Option<String>) {
+        if let Some(status) = self.phase.write().await.as_mut() {
+            status.state = state;
+            status.reason = reason;
+        }
+    }
+    
+    async fn evaluate_condition(&mut self, condition: &Expression) -> anyhow::Result<bool> {
+        match self.evaluate_expression(condition).await? {
+            RuntimeValue::Boolean(b) => Ok(b),
+            other => Err(anyhow::anyhow!("Condition must be a boolean, found {:?}", other)),
+        }
+    }
+    
+    /// Values bound per iteration: elements (or index and element) of a list, elements of a set, keys (or key and value) of a map
+    fn iteration_values(iterable: RuntimeValue, bindings: usize) -> anyhow::Result<Vec<Vec<RuntimeValue>>> {
+        match iterable {
+            RuntimeValue::Set(items) if bindings == 1 => Ok(items.into_iter().map(|item| vec![item]).collect()),
+            RuntimeValue::List(items) => Ok(items.into_iter().enumerate()
+                .map(|(i, item)| if bindings == 1 { vec![item] } else { vec![RuntimeValue::UInteger(i as u64), item] })
+                .collect()),
+            RuntimeValue::Map(map) => Ok(map.into_iter()
+                .map(|(key, value)| vec![RuntimeValue::String(key), value])
+                .collect()),
+            other => Err(anyhow::anyhow!("Cannot iterate over {:?}", other)),
+        }
+    }
+    
+    /// Perform an assignment and return the value stored at the target
+    async fn execute_assignment(&mut self, target: &LValue, op: &AssignmentOp, value: &Expression) -> anyhow::Result<RuntimeValue> {
+        let value = self.evaluate_expression(value).await?;
+        self.assign(target, op, value).await
+    }
+    
+    /// Store an already evaluated value at the target
+    async fn assign(&mut self, target: &LValue, op: &AssignmentOp, value: RuntimeValue) -> anyhow::Result<RuntimeValue> {
+        let root = target.root().to_string();
+        let mut path = Vec::new();
+        self.resolve_path(target, &mut path).await?;
+        
+        let current = self.read_variable(&root).await;
+        if self.is_replicated(&root) {
+            // Check the update locally first so an invalid write never reaches the log
+            let (_, assigned) = Self::update_value(&root, current, &path, op, value.clone())?;
+            self.replicate_update(&root, path, op, value).await?;
+            return Ok(assigned);
+        }
+        
+        // Update a copy of the root variable, then store it back wherever it lives
+        let (new_root, assigned) = Self::update_value(&root, current, &path, op, value)?;
+        
+        match op {
+            AssignmentOp::Merge => println!("Merge: {} <#> {:?}", root, new_root),
+            _ => println!("Assignment: {} = {:?}", root, new_root),
+        }
+        self.write_variable(&root, new_root).await;
+        
+        Ok(assigned)
+    }
+    
+    /// Apply an assignment below `root`, returning the new root value and the value assigned
+    fn update_value(root: &str, current: Option<RuntimeValue>, path: &[PathSegment], op: &AssignmentOp, value: RuntimeValue) -> anyhow::Result<(RuntimeValue, RuntimeValue)> {
+        let updated = match path.split_last() {
+            None => {
+                let new_value = Self::apply_assignment_op(op, current, value)?;
+                (new_value.clone(), new_value)
+            }
+            Some((last, parents)) => {
+                let mut root_value = current
+                    .ok_or_else(|| anyhow::anyhow!("Undefined variable: {}", root))?;
+                let mut container = &mut root_value;
+                for segment in parents {
+                    container = Self::element_mut(container, segment)?;
+                }
+                
+                let assigned = match container {
+                    RuntimeValue::Map(map) => {
+                        let key = Self::map_key(last)?;
+                        let new_value = Self::apply_assignment_op(op, map.get(&key).cloned(), value)?;
+                        map.insert(key, new_value.clone());
+                        new_value
+                    }
+                    RuntimeValue::List(_) => {
+                        let slot = Self::element_mut(container, last)?;
+                        let new_value = Self::apply_assignment_op(op, Some(slot.clone()), value)?;
+                        *slot = new_value.clone();
+                        new_value
+                    }
+                    other => {
+                        return Err(anyhow::anyhow!("Cannot assign into {:?}", other));
+                    }
+                };
+                (root_value, assigned)
+            }
+        };
+        Ok(updated)
+    }
+    
+    /// Whether `name` refers to a `@replicated` state variable rather than a local shadowing it
+    fn is_replicated(&self, name: &str) -> bool {
+        self.replicated.contains(name)
+            && !self.frames.last().is_some_and(|frame| frame.scopes.iter().any(|scope| scope.contains_key(name)))
+    }
+    
+    /// Commit a write to `@replicated` state; the variable changes when the entry is applied
+    async fn replicate_update(&mut self, name: &str, path: Vec<PathSegment>, op: &AssignmentOp, value: RuntimeValue) -> anyhow::Result<()> {
+        self.state().commit_update(name, path, op, value).await
+    }
+    
+    /// Flatten an assignment target into the segments below its root variable
+    async fn resolve_path(&mut self, target: &LValue, path: &mut Vec<PathSegment>) -> anyhow::Result<()> {
+        match target {
+            LValue::Identifier(..) => {}
+            LValue::Member { object, field, .. } => {
+                Box::pin(self.resolve_path(object, path)).await?;
+                path.push(PathSegment::Field(field.clone()));
             }
-            Statement::Phase(_phase) => {
-                println!("Phase execution not implemented in MVP");
+            LValue::Index { array, index, .. } => {
+                Box::pin(self.resolve_path(array, path)).await?;
+                let index = self.evaluate_expression(index).await?;
+                path.push(PathSegment::Index(index));
             }
         }
         Ok(())
     }
     
+    fn apply_assignment_op(op: &AssignmentOp, current: Option<RuntimeValue>, value: RuntimeValue) -> anyhow::Result<RuntimeValue> {
+        let binary_op = match op {
+            AssignmentOp::Assign => return Ok(value),
+            AssignmentOp::Merge => return Ok(Self::merge(current, value)),
+            AssignmentOp::AddAssign => BinaryOp::Add,
+            AssignmentOp::SubAssign => BinaryOp::Sub,
+        };
+        
+        let current = current.ok_or_else(|| anyhow::anyhow!("Compound assignment to an unset value"))?;
+        Self::evaluate_binary_op(&binary_op, current, value)
+    }
+    
+    /// Merge maps key by key and apply map patches; any other value replaces the current one
+    fn merge(current: Option<RuntimeValue>, value: RuntimeValue) -> RuntimeValue {
+        match (current, value) {
+            (Some(RuntimeValue::Map(mut map)), RuntimeValue::Map(update)) => {
+                map.extend(update);
+                RuntimeValue::Map(map)
+            }
+            (current, RuntimeValue::MapPatch(patch)) => {
+                let mut map = match current {
+                    Some(RuntimeValue::Map(map)) => map,
+                    _ => BTreeMap::new(),
+                };
+                for (key, value) in patch {
+                    match value {
+                        Some(value) => map.insert(key, value),
+                        None => map.remove(&key),
+                    };
+                }
+                RuntimeValue::Map(map)
+            }
+            (_, value) => value,
+        }
+    }
+    
+    fn element_mut<'a>(container: &'a mut RuntimeValue, segment: &PathSegment) -> anyhow::Result<&'a mut RuntimeValue> {
+        match container {
+            RuntimeValue::Map(map) => {
+                let key = Self::map_key(segment)?;
+                map.get_mut(&key).ok_or_else(|| anyhow::anyhow!("Missing key: {}", key))
+            }
+            RuntimeValue::List(items) => {
+                let index = Self::list_index(segment)?;
+                let len = items.len();
+                items.get_mut(index)
+                    .ok_or_else(|| anyhow::anyhow!("Index {} out of bounds for list of length {}", index, len))
+            }
+            other => Err(anyhow::anyhow!("Cannot index into {:?}", other)),
+        }
+    }
+    
+    fn element(container: &RuntimeValue, segment: &PathSegment) -> anyhow::Result<RuntimeValue> {
+        match container {
+            RuntimeValue::Map(map) => {
+                let key = Self::map_key(segment)?;
+                map.get(&key).cloned().ok_or_else(|| anyhow::anyhow!("Missing key: {}", key))
+            }
+            RuntimeValue::List(items) => {
+                let index = Self::list_index(segment)?;
+                items.get(index).cloned()
+                    .ok_or_else(|| anyhow::anyhow!("Index {} out of bounds for list of length {}", index, items.len()))
+            }
+            RuntimeValue::Proposal(result) => result.field(&Self::map_key(segment)?),
+            other => Err(anyhow::anyhow!("Cannot index into {:?}", other)),
+        }
+    }
+    
+    fn map_key(segment: &PathSegment) -> anyhow::Result<String> {
+        match segment {
+            PathSegment::Field(name) => Ok(name.clone()),
+            PathSegment::Index(key) => stdlib::map_key(key),
+        }
+    }
+    
+    fn list_index(segment: &PathSegment) -> anyhow::Result<usize> {
+        match segment {
+            PathSegment::Index(RuntimeValue::Integer(n)) if *n >= 0 => Ok(*n as usize),
+            PathSegment::Index(RuntimeValue::UInteger(n)) => Ok(*n as usize),
+            other => Err(anyhow::anyhow!("Invalid list index: {:?}", other)),
+        }
+    }
+    
     async fn evaluate_expression(&mut self, expr: &Expression) -> anyhow::Result<RuntimeValue> {
         match expr {
-            Expression::Literal(literal) => {
+            Expression::Literal(literal, _) => {
                 Ok(match literal {
                     Literal::Integer(n) => RuntimeValue::Integer(*n),
                     Literal::UInteger(n) => RuntimeValue::UInteger(*n),
                     Literal::Float(f) => RuntimeValue::Float(*f),
                     Literal::String(s) => RuntimeValue::String(s.clone()),
                     Literal::Boolean(b) => RuntimeValue::Boolean(*b),
+                    Literal::Duration(ms) => RuntimeValue::Duration(Duration::from_millis(*ms)),
                 })
             }
-            Expression::Identifier(name) => {
-                let state_vars = self.state_vars.read().await;
-                state_vars.get(name)
-                    .cloned()
+            Expression::Identifier(name, _) => {
+                self.read_variable(name).await
                     .ok_or_else(|| anyhow::anyhow!("Undefined variable: {}", name))
             }
             Expression::Binary(bin_expr) => {
-                let left = self.evaluate_expression(&bin_expr.left).await?;
-                let right = self.evaluate_expression(&bin_expr.right).await?;
-                self.evaluate_binary_op(&bin_expr.op, left, right)
+                let left = Box::pin(self.evaluate_expression(&bin_expr.left)).await?;
+                // `&&` and `||` leave the right operand unevaluated once the left one decides
+                if matches!((&bin_expr.op, &left), (BinaryOp::And, RuntimeValue::Boolean(false)) | (BinaryOp::Or, RuntimeValue::Boolean(true))) {
+                    return Ok(left);
+                }
+                let right = Box::pin(self.evaluate_expression(&bin_expr.right)).await?;
+                Self::evaluate_binary_op(&bin_expr.op, left, right)
+            }
+            Expression::Member(member) => {
+                let object = Box::pin(self.evaluate_expression(&member.object)).await?;
+                Self::element(&object, &PathSegment::Field(member.field.clone()))
+            }
+            Expression::Index(index_expr) => {
+                let array = Box::pin(self.evaluate_expression(&index_expr.array)).await?;
+                let index = Box::pin(self.evaluate_expression(&index_expr.index)).await?;
+                Self::element(&array, &PathSegment::Index(index))
+            }
+            Expression::Unary(unary_expr) => {
+                let operand = Box::pin(self.evaluate_expression(&unary_expr.operand)).await?;
+                self.evaluate_unary_op(&unary_expr.op, operand)
             }
             Expression::Call(call_expr) => {
-                println!("Function call: {}()", call_expr.function);
-                // For MVP, just return a default value
-                Ok(RuntimeValue::Boolean(true))
+                let args = self.evaluate_arguments(&call_expr.args).await?;
+                Box::pin(self.call_function(None, &call_expr.function, args)).await
+            }
+            Expression::MethodCall(call_expr) => {
+                // `Node.function(..)` / `Cluster.service(..)` call into that node or cluster
+                match call_expr.receiver.as_ref() {
+                    Expression::Identifier(owner, _) if self.is_owner(owner) => {
+                        let args = self.evaluate_arguments(&call_expr.args).await?;
+                        Box::pin(self.call_function(Some(owner), &call_expr.method, args)).await
+                    }
+                    receiver => {
+                        let native = *self.natives.method(&call_expr.method)
+                            .ok_or_else(|| anyhow::anyhow!("Unknown method: {}", call_expr.method))?;
+                        let args = self.evaluate_arguments(&call_expr.args).await?;
+                        let mut value = Box::pin(self.evaluate_expression(receiver)).await?;
+                        let result = native.call(&mut value, &args)?;
+                        
+                        // Mutating methods store the updated receiver back into its variable
+                        if native.mutates {
+                            let target = LValue::from_expression(receiver)
+                                .ok_or_else(|| anyhow::anyhow!("{}() needs a variable as its receiver", native.name))?;
+                            Box::pin(self.assign(&target, &AssignmentOp::Assign, value)).await?;
+                        }
+                        Ok(result)
+                    }
+                }
+            }
+            Expression::Assignment(assignment) => {
+                let target = LValue::from_expression(&assignment.target)
+                    .ok_or_else(|| anyhow::anyhow!("Invalid assignment target"))?;
+                Box::pin(self.execute_assignment(&target, &assignment.op, &assignment.value)).await
             }
             Expression::Proposal(proposal) => {
-                let value = self.evaluate_expression(&proposal.value).await?;
-                println!("Consensus proposal: {:?} with config {:?}", value, proposal.config);
-                
-                // For MVP, simulate consensus by just accepting the proposal
-                // In a real implementation, this would go through the consensus protocol
-                let value_bytes = self.serialize_value(&value)?;
-                let proposal_id = self.runtime.propose(value_bytes).await?;
-                println!("Proposal accepted: {:?}", proposal_id);
-                
-                Ok(RuntimeValue::Boolean(true)) // Simulate accepted result
+                let value = Box::pin(self.evaluate_expression(&proposal.value)).await?;
+                let config = self.consensus_config(&proposal.config, proposal.config_value.as_deref()).await?;
+                println!("Consensus proposal: {:?} with config {:?}", value, config);
+                self.execute_proposal(value, &config).await
+            }
+            Expression::Vote(vote_expr) => {
+                let target = Box::pin(self.evaluate_expression(&vote_expr.value)).await?;
+                let config = self.consensus_config(&vote_expr.config, vote_expr.config_value.as_deref()).await?;
+                self.execute_vote(target, &config).await
+            }
+            Expression::Broadcast(distributed) => Box::pin(self.execute_broadcast(distributed)).await,
+            Expression::Gossip(distributed) => Box::pin(self.execute_gossip(distributed)).await,
+            Expression::Sync(distributed) => Box::pin(self.execute_sync(distributed)).await,
+            Expression::Partition(distributed) => Box::pin(self.execute_partition(distributed)).await,
+            Expression::Quorum(distributed) => Box::pin(self.execute_quorum_read(distributed)).await,
+            Expression::Array(elements, _) => {
+                let mut values = Vec::with_capacity(elements.len());
+                for element in elements {
+                    values.push(Box::pin(self.evaluate_expression(element)).await?);
+                }
+                Ok(RuntimeValue::List(values))
+            }
+            Expression::Object(fields, _) => {
+                let mut map = BTreeMap::new();
+                for field in fields {
+                    let value = Box::pin(self.evaluate_expression(&field.value)).await?;
+                    map.insert(field.name.clone(), value);
+                }
+                Ok(RuntimeValue::Map(map))
+            }
+        }
+    }
+    
+    /// Submit a value through consensus and wait for it to commit or time out
+    async fn execute_proposal(&mut self, value: RuntimeValue, config: &omnix_compiler::ast::ConsensusConfig) -> anyhow::Result<RuntimeValue> {
+        let deadline = tokio::time::Instant::now() + self.timeout_for(config);
+        // `validators` and `quorum` both ask for a minimum number of replicas holding the entry
+        let required_acks = config.validators.max(config.quorum).unwrap_or(0) as usize;
+        
+        let value_bytes = bincode::serialize(&Command::Propose(value.clone()))?;
+        let mut result = ProposalResult {
+            id: None,
+            accepted: false,
+            value,
+            term: 0,
+            index: 0,
+            error: None,
+        };
+        
+        let committed = match self.runtime.propose_until(value_bytes, deadline).await {
+            Ok(proposal_id) => {
+                result.id = Some(proposal_id.0.clone());
+                self.runtime.wait_for_commit(&proposal_id, required_acks, deadline).await
+            }
+            Err(e) => Err(e),
+        };
+        match committed {
+            Ok(committed) => {
+                println!("Proposal committed at term {}, index {}", committed.term, committed.index);
+                result.accepted = true;
+                result.term = committed.term;
+                result.index = committed.index;
             }
-            Expression::Vote(_vote_expr) => {
-                println!("Vote expression not implemented in MVP");
-                Ok(RuntimeValue::Boolean(true))
+            Err(e) => {
+                println!("Proposal rejected: {}", e);
+                result.error = Some(e.to_string());
             }
-            Expression::Array(_) => {
-                println!("Array expressions not implemented in MVP");
-                Ok(RuntimeValue::Integer(0))
+        }
+        
+        Ok(RuntimeValue::Proposal(Box::new(result)))
+    }
+    
+    /// Vote on a proposal (a `<!>` result or its id) and return the tally
+    async fn execute_vote(&mut self, target: RuntimeValue, config: &omnix_compiler::ast::ConsensusConfig) -> anyhow::Result<RuntimeValue> {
+        let proposal_id = match target {
+            RuntimeValue::Proposal(result) => result.id
+                .ok_or_else(|| anyhow::anyhow!("Cannot vote on a proposal that never reached consensus"))?,
+            RuntimeValue::String(id) => id,
+            other => return Err(anyhow::anyhow!("Can only vote on a proposal result or id, found {:?}", other)),
+        };
+        let vote = match config.vote.unwrap_or(VoteChoice::Accept) {
+            VoteChoice::Accept => Vote::Accept,
+            VoteChoice::Reject => Vote::Reject,
+            VoteChoice::Abstain => Vote::Abstain,
+        };
+        let required = config.quorum.or(config.validators).map(|n| n as usize);
+        
+        println!("Voting {:?} on proposal {}", vote, proposal_id);
+        let tally = self.runtime
+            .vote_and_tally(ProposalId(proposal_id), vote, required, self.timeout_for(config))
+            .await?;
+        
+        let mut fields = BTreeMap::new();
+        fields.insert("accept".to_string(), RuntimeValue::UInteger(tally.accept as u64));
+        fields.insert("reject".to_string(), RuntimeValue::UInteger(tally.reject as u64));
+        fields.insert("abstain".to_string(), RuntimeValue::UInteger(tally.abstain as u64));
+        fields.insert("quorum_reached".to_string(), RuntimeValue::Boolean(tally.has_quorum(required)));
+        Ok(RuntimeValue::Map(fields))
+    }
+    
+    /// Config of a `<!>` or `<?>`: the `{ .. }` block, or the map its operand evaluates to
+    async fn consensus_config(&mut self, config: &omnix_compiler::ast::ConsensusConfig, config_value: Option<&Expression>) -> anyhow::Result<omnix_compiler::ast::ConsensusConfig> {
+        let mut config = config.clone();
+        let Some(expr) = config_value else {
+            return Ok(config);
+        };
+        let fields = match Box::pin(self.evaluate_expression(expr)).await? {
+            RuntimeValue::Map(fields) => fields,
+            other => return Err(anyhow::anyhow!("Consensus config must be a map, found {:?}", other)),
+        };
+        for (key, value) in fields {
+            match (key.as_str(), value) {
+                ("validators", RuntimeValue::UInteger(n)) => config.validators = Some(u32::try_from(n)?),
+                ("validators", RuntimeValue::Integer(n)) => config.validators = Some(u32::try_from(n)?),
+                ("quorum", RuntimeValue::UInteger(n)) => config.quorum = Some(u32::try_from(n)?),
+                ("quorum", RuntimeValue::Integer(n)) => config.quorum = Some(u32::try_from(n)?),
+                ("timeout", RuntimeValue::Duration(d)) => config.timeout = Some(d.as_millis() as u64),
+                ("timeout", RuntimeValue::UInteger(ms)) => config.timeout = Some(ms),
+                ("timeout", RuntimeValue::Integer(ms)) => config.timeout = Some(u64::try_from(ms)?),
+                ("vote", RuntimeValue::String(choice)) => {
+                    config.vote = Some(match choice.as_str() {
+                        "Accept" => VoteChoice::Accept,
+                        "Reject" => VoteChoice::Reject,
+                        "Abstain" => VoteChoice::Abstain,
+                        other => return Err(anyhow::anyhow!("Unknown vote choice: {}", other)),
+                    });
+                }
+                // Engine selection is static configuration
+                ("algorithm", _) => {}
+                (key, value) => return Err(anyhow::anyhow!("Invalid consensus config field {}: {:?}", key, value)),
             }
-            Expression::Object(_) => {
-                println!("Object expressions not implemented in MVP");
-                Ok(RuntimeValue::Integer(0))
+        }
+        Ok(config)
+    }
+    
+    fn timeout_for(&self, config: &omnix_compiler::ast::ConsensusConfig) -> Duration {
+        config.timeout.map(Duration::from_millis).unwrap_or(self.consensus_timeout)
+    }
+    
+    /// `<~>`: push a state variable to every peer as it stands here, without catching up first
+    async fn execute_broadcast(&mut self, distributed: &DistributedExpression) -> anyhow::Result<RuntimeValue> {
+        let name = Self::synced_name(distributed, "<~>")?;
+        self.push_state(name).await
+    }
+    
+    /// `<@>`: gossip the value to `fanout` (or `nodes`) peers, reading it after a quorum barrier
+    /// when `consistency` is `Quorum` or `Strong`
+    async fn execute_gossip(&mut self, distributed: &DistributedExpression) -> anyhow::Result<RuntimeValue> {
+        let fanout = self.option_count(&distributed.options, &["fanout", "nodes"]).await?.unwrap_or(3);
+        let consistency = self.evaluate_option(&distributed.options, "consistency").await?;
+        if matches!(consistency, Some(RuntimeValue::String(ref level)) if level == "Quorum" || level == "Strong") {
+            let deadline = tokio::time::Instant::now() + self.option_timeout(&distributed.options).await?;
+            self.runtime.read_barrier(0, deadline).await
+                .map_err(|e| anyhow::anyhow!("Consistent read failed: {}", e))?;
+        }
+        
+        let value = self.evaluate_expression(&distributed.value).await?;
+        println!("Gossiping {:?} to {} peers", value, fanout);
+        self.runtime.gossip(bincode::serialize(&value)?, fanout as u32).await?;
+        Ok(value)
+    }
+    
+    /// `<=>`: catch up with the committed log, then push a state variable to every peer
+    async fn execute_sync(&mut self, distributed: &DistributedExpression) -> anyhow::Result<RuntimeValue> {
+        let name = Self::synced_name(distributed, "<=>")?;
+        let deadline = tokio::time::Instant::now() + self.option_timeout(&distributed.options).await?;
+        self.runtime.read_barrier(0, deadline).await
+            .map_err(|e| anyhow::anyhow!("State sync of {} failed: {}", name, e))?;
+        self.push_state(name).await
+    }
+    
+    fn synced_name(distributed: &DistributedExpression, operator: &str) -> anyhow::Result<String> {
+        match distributed.value.as_ref() {
+            Expression::Identifier(name, _) => Ok(name.clone()),
+            _ => Err(anyhow::anyhow!("{} expects the name of a state variable", operator)),
+        }
+    }
+    
+    /// Send a variable's value to every peer, which adopts it unless it is `@replicated` there
+    async fn push_state(&mut self, name: String) -> anyhow::Result<RuntimeValue> {
+        let value = self.read_variable(&name).await
+            .ok_or_else(|| anyhow::anyhow!("Undefined variable: {}", name))?;
+        println!("Syncing {} = {:?}", name, value);
+        self.runtime.broadcast(Message::StateSync { name, value: bincode::serialize(&value)? }).await?;
+        Ok(value)
+    }
+    
+    /// `<|>`: the value when a majority is reachable, otherwise `fallback` (`None` by default)
+    async fn execute_partition(&mut self, distributed: &DistributedExpression) -> anyhow::Result<RuntimeValue> {
+        let deadline = tokio::time::Instant::now() + self.option_timeout(&distributed.options).await?;
+        match self.runtime.read_barrier(0, deadline).await {
+            Ok(_) => self.evaluate_expression(&distributed.value).await,
+            Err(e) => {
+                println!("Partition detected: {}", e);
+                Ok(self.evaluate_option(&distributed.options, "fallback").await?
+                    .unwrap_or(RuntimeValue::Option(None)))
+            }
+        }
+    }
+    
+    /// `<*>`: read the value once `nodes` (or `quorum`) replicas confirm the committed log
+    async fn execute_quorum_read(&mut self, distributed: &DistributedExpression) -> anyhow::Result<RuntimeValue> {
+        let required_acks = self.option_count(&distributed.options, &["nodes", "quorum"]).await?.unwrap_or(0);
+        let deadline = tokio::time::Instant::now() + self.option_timeout(&distributed.options).await?;
+        self.runtime.read_barrier(required_acks, deadline).await
+            .map_err(|e| anyhow::anyhow!("Quorum read failed: {}", e))?;
+        self.evaluate_expression(&distributed.value).await
+    }
+    
+    /// Evaluate an operator option; unbound capitalized names such as `Quorum` evaluate to their own name
+    async fn evaluate_option(&mut self, options: &[ObjectField], name: &str) -> anyhow::Result<Option<RuntimeValue>> {
+        let Some(expr) = options.iter().find(|field| field.name == name).map(|field| &field.value) else {
+            return Ok(None);
+        };
+        if let Expression::Identifier(symbol, _) = expr {
+            if symbol.starts_with(|c: char| c.is_ascii_uppercase()) && self.read_variable(symbol).await.is_none() {
+                return Ok(Some(RuntimeValue::String(symbol.clone())));
             }
         }
+        Ok(Some(Box::pin(self.evaluate_expression(expr)).await?))
     }
     
-    fn evaluate_binary_op(&self, op: &BinaryOp, left: RuntimeValue, right: RuntimeValue) -> anyhow::Result<RuntimeValue> {
+    /// First of `names` that is set, as a count
+    async fn option_count(&mut self, options: &[ObjectField], names: &[&str]) -> anyhow::Result<Option<usize>> {
+        for name in names {
+            match self.evaluate_option(options, name).await? {
+                Some(RuntimeValue::UInteger(n)) => return Ok(Some(n as usize)),
+                Some(RuntimeValue::Integer(n)) if n >= 0 => return Ok(Some(n as usize)),
+                Some(other) => return Err(anyhow::anyhow!("Option '{}' must be a count, found {:?}", name, other)),
+                None => {}
+            }
+        }
+        Ok(None)
+    }
+    
+    /// `timeout` as a duration or milliseconds, defaulting to the consensus timeout
+    async fn option_timeout(&mut self, options: &[ObjectField]) -> anyhow::Result<Duration> {
+        match self.evaluate_option(options, "timeout").await? {
+            None => Ok(self.consensus_timeout),
+            Some(RuntimeValue::Duration(d)) => Ok(d),
+            Some(RuntimeValue::UInteger(ms)) => Ok(Duration::from_millis(ms)),
+            Some(RuntimeValue::Integer(ms)) if ms >= 0 => Ok(Duration::from_millis(ms as u64)),
+            Some(other) => Err(anyhow::anyhow!("Option 'timeout' must be a duration, found {:?}", other)),
+        }
+    }
+    
+    async fn evaluate_arguments(&mut self, args: &[Expression]) -> anyhow::Result<Vec<RuntimeValue>> {
+        let mut values = Vec::with_capacity(args.len());
+        for arg in args {
+            values.push(Box::pin(self.evaluate_expression(arg)).await?);
+        }
+        Ok(values)
+    }
+    
+    /// Whether `name` is a node or cluster that owns callable functions
+    fn is_owner(&self, name: &str) -> bool {
+        self.functions.keys().any(|(owner, _)| owner.as_deref() == Some(name))
+    }
+    
+    fn evaluate_binary_op(op: &BinaryOp, left: RuntimeValue, right: RuntimeValue) -> anyhow::Result<RuntimeValue> {
         match (left, right) {
+            (RuntimeValue::String(a), b) if matches!(op, BinaryOp::Add) => {
+                Ok(RuntimeValue::String(format!("{}{}", a, b)))
+            }
+            (RuntimeValue::String(a), RuntimeValue::String(b)) => {
+                Ok(match op {
+                    BinaryOp::Eq => RuntimeValue::Boolean(a == b),
+                    BinaryOp::Ne => RuntimeValue::Boolean(a != b),
+                    BinaryOp::Lt => RuntimeValue::Boolean(a < b),
+                    BinaryOp::Le => RuntimeValue::Boolean(a <= b),
+                    BinaryOp::Gt => RuntimeValue::Boolean(a > b),
+                    BinaryOp::Ge => RuntimeValue::Boolean(a >= b),
+                    _ => return Err(anyhow::anyhow!("Invalid binary operation for strings")),
+                })
+            }
             (RuntimeValue::Integer(a), RuntimeValue::Integer(b)) => {
+                let checked = |result: Option<i64>| result.map(RuntimeValue::Integer).ok_or_else(|| Self::arithmetic_error(op, b == 0));
                 Ok(match op {
-                    BinaryOp::Add => RuntimeValue::Integer(a + b),
-                    BinaryOp::Sub => RuntimeValue::Integer(a - b),
-                    BinaryOp::Mul => RuntimeValue::Integer(a * b),
-                    BinaryOp::Div => RuntimeValue::Integer(a / b),
+                    BinaryOp::Add => checked(a.checked_add(b))?,
+                    BinaryOp::Sub => checked(a.checked_sub(b))?,
+                    BinaryOp::Mul => checked(a.checked_mul(b))?,
+                    BinaryOp::Div => checked(a.checked_div(b))?,
+                    BinaryOp::Mod => checked(a.checked_rem(b))?,
                     BinaryOp::Eq => RuntimeValue::Boolean(a == b),
                     BinaryOp::Ne => RuntimeValue::Boolean(a != b),
                     BinaryOp::Lt => RuntimeValue::Boolean(a < b),
@@ -275,11 +1440,13 @@ impl Executor {
                 })
             }
             (RuntimeValue::UInteger(a), RuntimeValue::UInteger(b)) => {
+                let checked = |result: Option<u64>| result.map(RuntimeValue::UInteger).ok_or_else(|| Self::arithmetic_error(op, b == 0));
                 Ok(match op {
-                    BinaryOp::Add => RuntimeValue::UInteger(a + b),
-                    BinaryOp::Sub => RuntimeValue::UInteger(a.saturating_sub(b)),
-                    BinaryOp::Mul => RuntimeValue::UInteger(a * b),
-                    BinaryOp::Div => RuntimeValue::UInteger(a / b),
+                    BinaryOp::Add => checked(a.checked_add(b))?,
+                    BinaryOp::Sub => checked(a.checked_sub(b))?,
+                    BinaryOp::Mul => checked(a.checked_mul(b))?,
+                    BinaryOp::Div => checked(a.checked_div(b))?,
+                    BinaryOp::Mod => checked(a.checked_rem(b))?,
                     BinaryOp::Eq => RuntimeValue::Boolean(a == b),
                     BinaryOp::Ne => RuntimeValue::Boolean(a != b),
                     BinaryOp::Lt => RuntimeValue::Boolean(a < b),
@@ -289,6 +1456,38 @@ impl Executor {
                     _ => return Err(anyhow::anyhow!("Invalid binary operation for unsigned integers")),
                 })
             }
+            // Integer literals are signed, while `len()`, `now()` and unset `u64` state are unsigned
+            (RuntimeValue::Integer(a), RuntimeValue::UInteger(b)) => Self::evaluate_mixed_op(op, a, b, true),
+            (RuntimeValue::UInteger(a), RuntimeValue::Integer(b)) => Self::evaluate_mixed_op(op, b, a, false),
+            (RuntimeValue::Float(a), RuntimeValue::Float(b)) => {
+                Ok(match op {
+                    BinaryOp::Add => RuntimeValue::Float(a + b),
+                    BinaryOp::Sub => RuntimeValue::Float(a - b),
+                    BinaryOp::Mul => RuntimeValue::Float(a * b),
+                    BinaryOp::Div => RuntimeValue::Float(a / b),
+                    BinaryOp::Mod => RuntimeValue::Float(a % b),
+                    BinaryOp::Eq => RuntimeValue::Boolean(a == b),
+                    BinaryOp::Ne => RuntimeValue::Boolean(a != b),
+                    BinaryOp::Lt => RuntimeValue::Boolean(a < b),
+                    BinaryOp::Le => RuntimeValue::Boolean(a <= b),
+                    BinaryOp::Gt => RuntimeValue::Boolean(a > b),
+                    BinaryOp::Ge => RuntimeValue::Boolean(a >= b),
+                    _ => return Err(anyhow::anyhow!("Invalid binary operation for floats")),
+                })
+            }
+            (RuntimeValue::Duration(a), RuntimeValue::Duration(b)) => {
+                Ok(match op {
+                    BinaryOp::Add => RuntimeValue::Duration(a + b),
+                    BinaryOp::Sub => RuntimeValue::Duration(a.saturating_sub(b)),
+                    BinaryOp::Eq => RuntimeValue::Boolean(a == b),
+                    BinaryOp::Ne => RuntimeValue::Boolean(a != b),
+                    BinaryOp::Lt => RuntimeValue::Boolean(a < b),
+                    BinaryOp::Le => RuntimeValue::Boolean(a <= b),
+                    BinaryOp::Gt => RuntimeValue::Boolean(a > b),
+                    BinaryOp::Ge => RuntimeValue::Boolean(a >= b),
+                    _ => return Err(anyhow::anyhow!("Invalid binary operation for durations")),
+                })
+            }
             (RuntimeValue::Boolean(a), RuntimeValue::Boolean(b)) => {
                 Ok(match op {
                     BinaryOp::And => RuntimeValue::Boolean(a && b),
@@ -302,14 +1501,52 @@ impl Executor {
         }
     }
     
-    fn serialize_value(&self, value: &RuntimeValue) -> anyhow::Result<Vec<u8>> {
-        match value {
-            RuntimeValue::Integer(n) => Ok(n.to_be_bytes().to_vec()),
-            RuntimeValue::UInteger(n) => Ok(n.to_be_bytes().to_vec()),
-            RuntimeValue::Float(f) => Ok(f.to_be_bytes().to_vec()),
-            RuntimeValue::String(s) => Ok(s.as_bytes().to_vec()),
-            RuntimeValue::Boolean(b) => Ok(vec![if *b { 1 } else { 0 }]),
-            RuntimeValue::Bytes(bytes) => Ok(bytes.clone()),
+    /// A signed and an unsigned operand. Comparisons are exact; arithmetic is done in u64, as
+    /// the type checker assumes, so a negative operand is an error.
+    fn evaluate_mixed_op(op: &BinaryOp, signed: i64, unsigned: u64, signed_left: bool) -> anyhow::Result<RuntimeValue> {
+        let (a, b) = if signed_left {
+            (i128::from(signed), i128::from(unsigned))
+        } else {
+            (i128::from(unsigned), i128::from(signed))
+        };
+        match op {
+            BinaryOp::Eq => Ok(RuntimeValue::Boolean(a == b)),
+            BinaryOp::Ne => Ok(RuntimeValue::Boolean(a != b)),
+            BinaryOp::Lt => Ok(RuntimeValue::Boolean(a < b)),
+            BinaryOp::Le => Ok(RuntimeValue::Boolean(a <= b)),
+            BinaryOp::Gt => Ok(RuntimeValue::Boolean(a > b)),
+            BinaryOp::Ge => Ok(RuntimeValue::Boolean(a >= b)),
+            _ => {
+                let converted = u64::try_from(signed)
+                    .map_err(|_| anyhow::anyhow!("Negative value {} in unsigned arithmetic", signed))?;
+                let (left, right) = if signed_left { (converted, unsigned) } else { (unsigned, converted) };
+                Self::evaluate_binary_op(op, RuntimeValue::UInteger(left), RuntimeValue::UInteger(right))
+            }
+        }
+    }
+    
+    /// Error for integer arithmetic that has no result: division by zero or overflow
+    fn arithmetic_error(op: &BinaryOp, zero_divisor: bool) -> anyhow::Error {
+        if zero_divisor && matches!(op, BinaryOp::Div | BinaryOp::Mod) {
+            anyhow::anyhow!("Division by zero")
+        } else {
+            anyhow::anyhow!("Integer overflow in {:?}", op)
+        }
+    }
+    
+    fn evaluate_unary_op(&self, op: &UnaryOp, operand: RuntimeValue) -> anyhow::Result<RuntimeValue> {
+        match (op, operand) {
+            (UnaryOp::Not, RuntimeValue::Boolean(b)) => Ok(RuntimeValue::Boolean(!b)),
+            (UnaryOp::Neg, RuntimeValue::Integer(n)) => n.checked_neg()
+                .map(RuntimeValue::Integer)
+                .ok_or_else(|| anyhow::anyhow!("Integer overflow negating {}", n)),
+            (UnaryOp::Neg, RuntimeValue::UInteger(n)) => i64::try_from(n).ok()
+                .and_then(i64::checked_neg)
+                .map(RuntimeValue::Integer)
+                .ok_or_else(|| anyhow::anyhow!("Integer overflow negating {}", n)),
+            (UnaryOp::Neg, RuntimeValue::Float(f)) => Ok(RuntimeValue::Float(-f)),
+            (UnaryOp::Plus, value @ (RuntimeValue::Integer(_) | RuntimeValue::UInteger(_) | RuntimeValue::Float(_))) => Ok(value),
+            (op, value) => Err(anyhow::anyhow!("Invalid unary operation {:?} for {:?}", op, value)),
         }
     }
 }
@@ -321,6 +1558,10 @@ pub fn create_mvp_config(port: u16) -> RuntimeConfig {
             algorithm: ConsensusAlgorithm::Raft,
             timeout_ms: 2000,
             max_faulty: 1,
+            raft: RaftConfig::default(),
+            pbft: PbftConfig::default(),
+            tendermint: TendermintConfig::default(),
+            hotstuff: HotStuffConfig::default(),
         },
         network: NetworkConfig {
             port,
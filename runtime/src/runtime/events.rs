@@ -0,0 +1,146 @@
+/*!
+ * OMNIX Events
+ * Typed `broadcast` events and their delivery to `on` handlers
+ */
+
+use super::{Executor, RuntimeValue};
+use crate::{Message, NodeId, Runtime};
+use omnix_compiler::ast::Parameter;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// How long a missing event may hold back later events from the same sender before it is skipped
+const GAP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Arguments of a broadcast event, as sent over the network
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum EventPayload {
+    Positional(Vec<RuntimeValue>),
+    Named(BTreeMap<String, RuntimeValue>),
+}
+
+impl EventPayload {
+    /// Arguments for a handler's parameters. Positional payloads must match the parameter count;
+    /// named payloads must supply every parameter, and fields the handler does not declare are ignored.
+    pub fn bind(&self, event: &str, params: &[Parameter]) -> anyhow::Result<Vec<RuntimeValue>> {
+        match self {
+            EventPayload::Positional(args) => {
+                if args.len() != params.len() {
+                    return Err(anyhow::anyhow!(
+                        "Handler for {} expects {} arguments, got {}", event, params.len(), args.len()
+                    ));
+                }
+                Ok(args.clone())
+            }
+            EventPayload::Named(fields) => params.iter()
+                .map(|param| fields.get(&param.name).cloned()
+                    .ok_or_else(|| anyhow::anyhow!("Event {} has no field '{}'", event, param.name)))
+                .collect(),
+        }
+    }
+}
+
+/// An event received from a peer
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub origin: NodeId,
+    pub name: String,
+    pub payload: EventPayload,
+}
+
+/// Releases each sender's events in the order it broadcast them, whatever order they arrive in
+#[derive(Debug, Default)]
+pub struct EventSequencer {
+    // Keyed by sender and incarnation, so a restarted sender starts a fresh stream
+    streams: HashMap<(NodeId, String), Stream>,
+}
+
+#[derive(Debug)]
+struct Stream {
+    next: u64,
+    pending: BTreeMap<u64, Event>,
+    blocked_since: Option<Instant>,
+}
+
+impl EventSequencer {
+    /// Accept an event and return every event of its stream that is now ready, in order.
+    /// Duplicates are dropped. A gap that stays open for `GAP_TIMEOUT` is skipped the next time
+    /// the stream receives an event, so a receiver that joins late or misses a message does not
+    /// stall forever.
+    pub fn receive(&mut self, incarnation: String, sequence: u64, event: Event) -> Vec<Event> {
+        let stream = self.streams.entry((event.origin.clone(), incarnation)).or_insert_with(|| Stream {
+            next: 1,
+            pending: BTreeMap::new(),
+            blocked_since: None,
+        });
+        if sequence < stream.next {
+            return Vec::new();
+        }
+        stream.pending.insert(sequence, event);
+
+        let mut ready = Vec::new();
+        loop {
+            let delivered = ready.len();
+            while let Some(event) = stream.pending.remove(&stream.next) {
+                ready.push(event);
+                stream.next += 1;
+            }
+            if ready.len() > delivered {
+                stream.blocked_since = None;
+            }
+
+            let Some(&first) = stream.pending.keys().next() else {
+                break;
+            };
+            let blocked_since = *stream.blocked_since.get_or_insert_with(Instant::now);
+            if blocked_since.elapsed() < GAP_TIMEOUT {
+                break;
+            }
+            tracing::warn!("Skipping events {}..{} that never arrived", stream.next, first);
+            stream.next = first;
+            stream.blocked_since = None;
+        }
+        ready
+    }
+}
+
+/// Routes messages from peers. Votes go straight to the consensus engine, so they are counted
+/// even while a phase waits on them; events and state syncs are handled in arrival order by
+/// the executor the dispatcher runs with, usually a fork of the one running the program.
+pub struct MessageDispatcher {
+    pub(super) runtime: Runtime,
+    pub(super) incoming: mpsc::UnboundedReceiver<Message>,
+}
+
+impl MessageDispatcher {
+    pub async fn run(mut self, mut executor: Executor) {
+        // A single consumer keeps each sender's messages in arrival order
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let delivery = tokio::spawn(async move {
+            while let Some(message) = rx.recv().await {
+                if let Err(e) = executor.handle_message(message).await {
+                    tracing::warn!("Failed to handle message: {}", e);
+                }
+            }
+        });
+
+        while let Some(message) = self.incoming.recv().await {
+            match message {
+                Message::Vote { id, voter, vote } => {
+                    if let Err(e) = self.runtime.receive_vote(id, voter, vote).await {
+                        tracing::warn!("Failed to record vote: {}", e);
+                    }
+                }
+                Message::Event { .. } | Message::StateSync { .. } => {
+                    let _ = tx.send(message);
+                }
+                other => tracing::debug!("Ignoring message: {:?}", other),
+            }
+        }
+
+        drop(tx);
+        let _ = delivery.await;
+    }
+}
@@ -0,0 +1,126 @@
+/*!
+ * OMNIX Replication
+ * Commands carried in the consensus log and their application to `@replicated` state
+ */
+
+use super::{Executor, PathSegment, RuntimeValue};
+use crate::{CommittedProposal, StateMachine};
+use async_trait::async_trait;
+use omnix_compiler::ast::AssignmentOp;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Payload of a log entry written by the executor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) enum Command {
+    /// A value submitted with `<!>`; agreed on but not applied to any state
+    Propose(RuntimeValue),
+    /// An assignment to a `@replicated` state variable
+    Update {
+        name: String,
+        path: Vec<PathSegment>,
+        op: AssignmentOp,
+        value: RuntimeValue,
+    },
+}
+
+/// Applies committed updates to the executor's `@replicated` state variables, in log order
+pub(super) struct ReplicatedState {
+    pub(super) state_vars: Arc<RwLock<HashMap<String, RuntimeValue>>>,
+    // `@replicated` variables; the log cannot write any other state
+    declared: RwLock<BTreeSet<String>>,
+    // Variables written through the log; the rest hold the initial values the program gave them
+    written: RwLock<BTreeSet<String>>,
+}
+
+impl ReplicatedState {
+    pub(super) fn new(state_vars: Arc<RwLock<HashMap<String, RuntimeValue>>>) -> Self {
+        Self {
+            state_vars,
+            declared: RwLock::new(BTreeSet::new()),
+            written: RwLock::new(BTreeSet::new()),
+        }
+    }
+
+    /// Make `name` writable through the log
+    pub(super) async fn declare(&self, name: &str) {
+        self.declared.write().await.insert(name.to_string());
+    }
+
+    pub(super) async fn is_declared(&self, name: &str) -> bool {
+        self.declared.read().await.contains(name)
+    }
+}
+
+#[async_trait]
+impl StateMachine for ReplicatedState {
+    /// Never fails: an entry that cannot be applied is a no-op. Whether it applies depends only
+    /// on the entry and the state before it, so every replica skips the same entries.
+    async fn apply(&self, entry: &CommittedProposal) -> anyhow::Result<()> {
+        // Read barriers commit empty entries that carry no command
+        if entry.value.is_empty() {
+            return Ok(());
+        }
+
+        let command = match bincode::deserialize(&entry.value) {
+            Ok(command) => command,
+            Err(e) => {
+                tracing::warn!("Entry {} is not a command, ignoring it: {}", entry.index, e);
+                return Ok(());
+            }
+        };
+        match command {
+            Command::Propose(_) => {}
+            Command::Update { name, path, op, value } => {
+                if !self.is_declared(&name).await {
+                    tracing::warn!("Entry {} writes {}, which is not replicated; ignoring it", entry.index, name);
+                    return Ok(());
+                }
+                let mut state_vars = self.state_vars.write().await;
+                let current = state_vars.get(&name).cloned();
+                match Executor::update_value(&name, current, &path, &op, value) {
+                    Ok((new_value, _)) => {
+                        println!("Applied entry {}: {} = {:?}", entry.index, name, new_value);
+                        self.written.write().await.insert(name.clone());
+                        state_vars.insert(name, new_value);
+                    }
+                    Err(e) => tracing::warn!("Entry {} cannot update {}, ignoring it: {}", entry.index, name, e),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// A `@replicated` variable's applied value, serialized as a `RuntimeValue`
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        if !self.is_declared(key).await {
+            return Ok(None);
+        }
+        match self.state_vars.read().await.get(key) {
+            Some(value) => Ok(Some(bincode::serialize(value)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn snapshot(&self) -> anyhow::Result<Vec<u8>> {
+        let state_vars = self.state_vars.read().await;
+        let values: BTreeMap<&String, &RuntimeValue> = self.written.read().await.iter()
+            .filter_map(|name| state_vars.get_key_value(name))
+            .collect();
+        Ok(bincode::serialize(&values)?)
+    }
+
+    async fn restore(&self, snapshot: &[u8]) -> anyhow::Result<()> {
+        let values: BTreeMap<String, RuntimeValue> = bincode::deserialize(snapshot)?;
+        let mut state_vars = self.state_vars.write().await;
+        let mut written = self.written.write().await;
+        for (name, value) in values {
+            println!("Restored {} = {:?}", name, value);
+            written.insert(name.clone());
+            state_vars.insert(name, value);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,361 @@
+/*!
+ * OMNIX Standard Library
+ * Native functions and methods callable from OMNIX programs
+ */
+
+use super::RuntimeValue;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub type NativeFn = fn(&[RuntimeValue]) -> anyhow::Result<RuntimeValue>;
+pub type NativeMethodFn = fn(&mut RuntimeValue, &[RuntimeValue]) -> anyhow::Result<RuntimeValue>;
+
+/// Number of arguments a native accepts
+#[derive(Debug, Clone, Copy)]
+pub enum Arity {
+    Exact(usize),
+    Range(usize, usize),
+    Variadic,
+}
+
+impl Arity {
+    fn check(&self, name: &str, given: usize) -> anyhow::Result<()> {
+        let ok = match *self {
+            Arity::Exact(n) => given == n,
+            Arity::Range(min, max) => (min..=max).contains(&given),
+            Arity::Variadic => true,
+        };
+        if ok {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!("{} expects {:?} arguments, got {}", name, self, given))
+        }
+    }
+}
+
+/// A free function such as `now()` or `Map::new()`
+#[derive(Clone, Copy)]
+pub struct NativeFunction {
+    pub name: &'static str,
+    pub arity: Arity,
+    pub func: NativeFn,
+}
+
+impl NativeFunction {
+    pub fn call(&self, args: &[RuntimeValue]) -> anyhow::Result<RuntimeValue> {
+        self.arity.check(self.name, args.len())?;
+        (self.func)(args)
+    }
+}
+
+/// A method such as `.len()`, dispatched on the receiver's runtime type
+#[derive(Clone, Copy)]
+pub struct NativeMethod {
+    pub name: &'static str,
+    pub arity: Arity,
+    /// Whether the updated receiver must be written back to its variable
+    pub mutates: bool,
+    pub func: NativeMethodFn,
+}
+
+impl NativeMethod {
+    pub fn call(&self, receiver: &mut RuntimeValue, args: &[RuntimeValue]) -> anyhow::Result<RuntimeValue> {
+        self.arity.check(self.name, args.len())?;
+        (self.func)(receiver, args)
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct NativeRegistry {
+    functions: HashMap<&'static str, NativeFunction>,
+    methods: HashMap<&'static str, NativeMethod>,
+}
+
+impl NativeRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registry holding the full standard library
+    pub fn with_stdlib() -> Self {
+        let mut registry = Self::new();
+
+        // Output and formatting
+        registry.register_function("print", Arity::Variadic, native_print);
+        registry.register_function("println", Arity::Variadic, native_println);
+        registry.register_function("format", Arity::Variadic, |args| Ok(RuntimeValue::String(format_args_list(args)?)));
+        registry.register_function("to_string", Arity::Exact(1), |args| Ok(RuntimeValue::String(args[0].to_string())));
+
+        // Time, identifiers and hashing
+        registry.register_function("now", Arity::Exact(0), native_now);
+        registry.register_function("generate_id", Arity::Exact(0), |_| Ok(RuntimeValue::String(uuid::Uuid::new_v4().to_string())));
+        registry.register_function("hash", Arity::Exact(1), native_hash);
+
+        // Constructors
+        registry.register_function("Map::new", Arity::Exact(0), |_| Ok(RuntimeValue::Map(BTreeMap::new())));
+        registry.register_function("Vec::new", Arity::Exact(0), |_| Ok(RuntimeValue::List(Vec::new())));
+        registry.register_function("Set::new", Arity::Exact(0), |_| Ok(RuntimeValue::Set(Vec::new())));
+        registry.register_function("Some", Arity::Exact(1), |args| Ok(RuntimeValue::Option(Some(Box::new(args[0].clone())))));
+        registry.register_function("Ok", Arity::Exact(1), |args| Ok(RuntimeValue::Result(Ok(Box::new(args[0].clone())))));
+        registry.register_function("Err", Arity::Exact(1), |args| Ok(RuntimeValue::Result(Err(Box::new(args[0].clone())))));
+
+        // Map deltas, applied to replicated state with `<#>`
+        registry.register_function("Map::insert", Arity::Exact(2), |args| {
+            let patch = BTreeMap::from([(map_key(&args[0])?, Some(args[1].clone()))]);
+            Ok(RuntimeValue::MapPatch(patch))
+        });
+        registry.register_function("Map::remove", Arity::Exact(1), |args| {
+            let patch = BTreeMap::from([(map_key(&args[0])?, None)]);
+            Ok(RuntimeValue::MapPatch(patch))
+        });
+
+        // Collection methods
+        registry.register_method("len", Arity::Exact(0), false, |receiver, _| {
+            Ok(RuntimeValue::UInteger(collection_len(receiver)? as u64))
+        });
+        registry.register_method("is_empty", Arity::Exact(0), false, |receiver, _| {
+            Ok(RuntimeValue::Boolean(collection_len(receiver)? == 0))
+        });
+        registry.register_method("get", Arity::Exact(1), false, method_get);
+        registry.register_method("contains_key", Arity::Exact(1), false, |receiver, args| match receiver {
+            RuntimeValue::Map(map) => Ok(RuntimeValue::Boolean(map.contains_key(&map_key(&args[0])?))),
+            other => Err(type_error("contains_key", "Map", other)),
+        });
+        registry.register_method("contains", Arity::Exact(1), false, |receiver, args| match receiver {
+            RuntimeValue::List(items) | RuntimeValue::Set(items) => Ok(RuntimeValue::Boolean(items.contains(&args[0]))),
+            RuntimeValue::String(s) => match &args[0] {
+                RuntimeValue::String(needle) => Ok(RuntimeValue::Boolean(s.contains(needle.as_str()))),
+                other => Err(anyhow::anyhow!("contains() on a String expects a String, found {:?}", other)),
+            },
+            other => Err(type_error("contains", "List, Set or String", other)),
+        });
+        registry.register_method("keys", Arity::Exact(0), false, |receiver, _| match receiver {
+            RuntimeValue::Map(map) => Ok(RuntimeValue::List(map.keys().cloned().map(RuntimeValue::String).collect())),
+            other => Err(type_error("keys", "Map", other)),
+        });
+        registry.register_method("values", Arity::Exact(0), false, |receiver, _| match receiver {
+            RuntimeValue::Map(map) => Ok(RuntimeValue::List(map.values().cloned().collect())),
+            other => Err(type_error("values", "Map", other)),
+        });
+        registry.register_method("first", Arity::Exact(0), false, |receiver, _| match receiver {
+            RuntimeValue::List(items) => Ok(option_of(items.first().cloned())),
+            other => Err(type_error("first", "List", other)),
+        });
+        registry.register_method("last", Arity::Exact(0), false, |receiver, _| match receiver {
+            RuntimeValue::List(items) => Ok(option_of(items.last().cloned())),
+            other => Err(type_error("last", "List", other)),
+        });
+        registry.register_method("push", Arity::Exact(1), true, |receiver, args| match receiver {
+            RuntimeValue::List(items) => {
+                items.push(args[0].clone());
+                Ok(RuntimeValue::Unit)
+            }
+            other => Err(type_error("push", "List", other)),
+        });
+        registry.register_method("insert", Arity::Range(1, 2), true, method_insert);
+        registry.register_method("remove", Arity::Exact(1), true, |receiver, args| match receiver {
+            RuntimeValue::Map(map) => Ok(option_of(map.remove(&map_key(&args[0])?))),
+            RuntimeValue::Set(items) => {
+                let before = items.len();
+                items.retain(|item| *item != args[0]);
+                Ok(RuntimeValue::Boolean(items.len() < before))
+            }
+            other => Err(type_error("remove", "Map or Set", other)),
+        });
+
+        // Option and Result methods
+        registry.register_method("is_some", Arity::Exact(0), false, |receiver, _| match receiver {
+            RuntimeValue::Option(value) => Ok(RuntimeValue::Boolean(value.is_some())),
+            other => Err(type_error("is_some", "Option", other)),
+        });
+        registry.register_method("is_none", Arity::Exact(0), false, |receiver, _| match receiver {
+            RuntimeValue::Option(value) => Ok(RuntimeValue::Boolean(value.is_none())),
+            other => Err(type_error("is_none", "Option", other)),
+        });
+        registry.register_method("is_ok", Arity::Exact(0), false, |receiver, _| match receiver {
+            RuntimeValue::Result(result) => Ok(RuntimeValue::Boolean(result.is_ok())),
+            other => Err(type_error("is_ok", "Result", other)),
+        });
+        registry.register_method("is_err", Arity::Exact(0), false, |receiver, _| match receiver {
+            RuntimeValue::Result(result) => Ok(RuntimeValue::Boolean(result.is_err())),
+            other => Err(type_error("is_err", "Result", other)),
+        });
+        registry.register_method("unwrap", Arity::Exact(0), false, |receiver, _| match receiver {
+            RuntimeValue::Option(Some(value)) | RuntimeValue::Result(Ok(value)) => Ok(value.as_ref().clone()),
+            RuntimeValue::Option(None) => Err(anyhow::anyhow!("Called unwrap() on None")),
+            RuntimeValue::Result(Err(error)) => Err(anyhow::anyhow!("Called unwrap() on Err({})", error)),
+            other => Err(type_error("unwrap", "Option or Result", other)),
+        });
+        registry.register_method("unwrap_or", Arity::Exact(1), false, |receiver, args| match receiver {
+            RuntimeValue::Option(Some(value)) | RuntimeValue::Result(Ok(value)) => Ok(value.as_ref().clone()),
+            RuntimeValue::Option(None) | RuntimeValue::Result(Err(_)) => Ok(args[0].clone()),
+            other => Err(type_error("unwrap_or", "Option or Result", other)),
+        });
+
+        // Consensus results
+        registry.register_method("accepted", Arity::Exact(0), false, |receiver, _| match receiver {
+            RuntimeValue::Proposal(result) => Ok(RuntimeValue::Boolean(result.accepted)),
+            other => Err(type_error("accepted", "proposal result", other)),
+        });
+
+        // String methods
+        registry.register_method("to_string", Arity::Exact(0), false, |receiver, _| Ok(RuntimeValue::String(receiver.to_string())));
+        registry.register_method("to_uppercase", Arity::Exact(0), false, |receiver, _| match receiver {
+            RuntimeValue::String(s) => Ok(RuntimeValue::String(s.to_uppercase())),
+            other => Err(type_error("to_uppercase", "String", other)),
+        });
+        registry.register_method("to_lowercase", Arity::Exact(0), false, |receiver, _| match receiver {
+            RuntimeValue::String(s) => Ok(RuntimeValue::String(s.to_lowercase())),
+            other => Err(type_error("to_lowercase", "String", other)),
+        });
+
+        registry
+    }
+
+    pub fn register_function(&mut self, name: &'static str, arity: Arity, func: NativeFn) {
+        self.functions.insert(name, NativeFunction { name, arity, func });
+    }
+
+    pub fn register_method(&mut self, name: &'static str, arity: Arity, mutates: bool, func: NativeMethodFn) {
+        self.methods.insert(name, NativeMethod { name, arity, mutates, func });
+    }
+
+    pub fn function(&self, name: &str) -> Option<&NativeFunction> {
+        self.functions.get(name)
+    }
+
+    pub fn method(&self, name: &str) -> Option<&NativeMethod> {
+        self.methods.get(name)
+    }
+
+    /// Names of every registered free function
+    pub fn function_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.functions.keys().copied()
+    }
+}
+
+/// Map keys are strings; scalar values are converted to their text form
+pub fn map_key(value: &RuntimeValue) -> anyhow::Result<String> {
+    match value {
+        RuntimeValue::String(s) => Ok(s.clone()),
+        RuntimeValue::Integer(n) => Ok(n.to_string()),
+        RuntimeValue::UInteger(n) => Ok(n.to_string()),
+        RuntimeValue::Boolean(b) => Ok(b.to_string()),
+        other => Err(anyhow::anyhow!("Invalid map key: {:?}", other)),
+    }
+}
+
+/// Render `args[0]` with each `{}` / `{:?}` replaced by the following arguments,
+/// or join all arguments with spaces when the first is not a template
+fn format_args_list(args: &[RuntimeValue]) -> anyhow::Result<String> {
+    let template = match args.first() {
+        Some(RuntimeValue::String(s)) if s.contains("{}") || s.contains("{:?}") => s,
+        _ => {
+            let parts: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            return Ok(parts.join(" "));
+        }
+    };
+
+    let mut output = String::new();
+    let mut rest = template.as_str();
+    let mut values = args[1..].iter();
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        let (placeholder, debug) = if rest[start..].starts_with("{:?}") {
+            ("{:?}", true)
+        } else if rest[start..].starts_with("{}") {
+            ("{}", false)
+        } else {
+            output.push('{');
+            rest = &rest[start + 1..];
+            continue;
+        };
+        let value = values.next()
+            .ok_or_else(|| anyhow::anyhow!("Missing argument for placeholder in \"{}\"", template))?;
+        match value {
+            RuntimeValue::String(s) if debug => output.push_str(&format!("{:?}", s)),
+            value => output.push_str(&value.to_string()),
+        }
+        rest = &rest[start + placeholder.len()..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+fn native_print(args: &[RuntimeValue]) -> anyhow::Result<RuntimeValue> {
+    print!("{}", format_args_list(args)?);
+    Ok(RuntimeValue::Unit)
+}
+
+fn native_println(args: &[RuntimeValue]) -> anyhow::Result<RuntimeValue> {
+    println!("{}", format_args_list(args)?);
+    Ok(RuntimeValue::Unit)
+}
+
+/// Milliseconds since the Unix epoch
+fn native_now(_: &[RuntimeValue]) -> anyhow::Result<RuntimeValue> {
+    let elapsed = SystemTime::now().duration_since(UNIX_EPOCH)?;
+    Ok(RuntimeValue::UInteger(elapsed.as_millis() as u64))
+}
+
+/// Hex-encoded SHA-256 of a value; strings and bytes hash their raw contents
+fn native_hash(args: &[RuntimeValue]) -> anyhow::Result<RuntimeValue> {
+    let bytes = match &args[0] {
+        RuntimeValue::String(s) => s.as_bytes().to_vec(),
+        RuntimeValue::Bytes(bytes) => bytes.clone(),
+        other => bincode::serialize(other)?,
+    };
+    let digest = Sha256::digest(&bytes);
+    Ok(RuntimeValue::String(digest.iter().map(|b| format!("{:02x}", b)).collect()))
+}
+
+fn method_get(receiver: &mut RuntimeValue, args: &[RuntimeValue]) -> anyhow::Result<RuntimeValue> {
+    match receiver {
+        RuntimeValue::Map(map) => Ok(option_of(map.get(&map_key(&args[0])?).cloned())),
+        RuntimeValue::List(items) => {
+            let index = match &args[0] {
+                RuntimeValue::UInteger(n) => *n as usize,
+                RuntimeValue::Integer(n) if *n >= 0 => *n as usize,
+                other => return Err(anyhow::anyhow!("get() expects a non-negative index, found {:?}", other)),
+            };
+            Ok(option_of(items.get(index).cloned()))
+        }
+        other => Err(type_error("get", "Map or List", other)),
+    }
+}
+
+/// `map.insert(key, value)` returns the previous value; `set.insert(value)` returns whether it was new
+fn method_insert(receiver: &mut RuntimeValue, args: &[RuntimeValue]) -> anyhow::Result<RuntimeValue> {
+    match (receiver, args) {
+        (RuntimeValue::Map(map), [key, value]) => Ok(option_of(map.insert(map_key(key)?, value.clone()))),
+        (RuntimeValue::Set(items), [value]) => {
+            let inserted = !items.contains(value);
+            if inserted {
+                items.push(value.clone());
+            }
+            Ok(RuntimeValue::Boolean(inserted))
+        }
+        (RuntimeValue::Map(_), _) => Err(anyhow::anyhow!("insert() on a Map expects a key and a value")),
+        (RuntimeValue::Set(_), _) => Err(anyhow::anyhow!("insert() on a Set expects one value")),
+        (other, _) => Err(type_error("insert", "Map or Set", other)),
+    }
+}
+
+fn collection_len(value: &RuntimeValue) -> anyhow::Result<usize> {
+    match value {
+        RuntimeValue::List(items) | RuntimeValue::Set(items) => Ok(items.len()),
+        RuntimeValue::Map(map) => Ok(map.len()),
+        RuntimeValue::String(s) => Ok(s.chars().count()),
+        RuntimeValue::Bytes(bytes) => Ok(bytes.len()),
+        other => Err(type_error("len", "List, Set, Map, String or Bytes", other)),
+    }
+}
+
+fn option_of(value: Option<RuntimeValue>) -> RuntimeValue {
+    RuntimeValue::Option(value.map(Box::new))
+}
+
+fn type_error(method: &str, expected: &str, found: &RuntimeValue) -> anyhow::Error {
+    anyhow::anyhow!("{}() expects a {} receiver, found {:?}", method, expected, found)
+}
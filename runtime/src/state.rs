@@ -2,6 +2,7 @@
  * Distributed state management for OMNIX
  */
 
+use crate::ReadConsistency;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use tokio::sync::RwLock;
@@ -33,6 +34,19 @@ pub enum ConsistencyLevel {
     Quorum(f32), // Percentage of replicas must agree
 }
 
+impl From<ConsistencyLevel> for ReadConsistency {
+    /// Reads that must see every agreed write confirm the leader first; causal reads accept
+    /// the leader's lease, trading the heartbeat round for a clock assumption; eventual
+    /// reads stay local
+    fn from(level: ConsistencyLevel) -> Self {
+        match level {
+            ConsistencyLevel::Strong | ConsistencyLevel::Quorum(_) => ReadConsistency::Linearizable,
+            ConsistencyLevel::Causal => ReadConsistency::Lease,
+            ConsistencyLevel::Eventual => ReadConsistency::Stale,
+        }
+    }
+}
+
 impl DistributedState {
     pub fn new(consistency_level: ConsistencyLevel) -> Self {
         Self {
@@ -54,9 +68,9 @@ impl DistributedState {
                 self.replicate_strong(key.clone(), value.clone()).await?;
             }
             ConsistencyLevel::Eventual => {
-                // Update locally and replicate asynchronously
+                // Update locally; replication is fire and forget
                 self.update_local(key.clone(), value.clone()).await;
-                tokio::spawn(self.replicate_eventual(key, value));
+                self.replicate_eventual(key, value).await;
             }
             ConsistencyLevel::Causal => {
                 // Ensure causal ordering
@@ -79,28 +93,28 @@ impl DistributedState {
     async fn replicate_strong(&self, key: String, value: StateValue) -> anyhow::Result<()> {
         // Two-phase commit protocol
         // Phase 1: Prepare
-        for replica in &self.replicas {
+        for _replica in &self.replicas {
             // Send prepare message
         }
         
         // Phase 2: Commit
         self.update_local(key, value).await;
         
-        for replica in &self.replicas {
+        for _replica in &self.replicas {
             // Send commit message
         }
         
         Ok(())
     }
     
-    async fn replicate_eventual(&self, key: String, value: StateValue) {
+    async fn replicate_eventual(&self, _key: String, _value: StateValue) {
         // Fire and forget replication
-        for replica in &self.replicas {
+        for _replica in &self.replicas {
             // Send update asynchronously
         }
     }
     
-    async fn replicate_causal(&self, key: String, value: StateValue) -> anyhow::Result<()> {
+    async fn replicate_causal(&self, _key: String, _value: StateValue) -> anyhow::Result<()> {
         // Implement vector clocks for causal consistency
         Ok(())
     }
@@ -109,7 +123,7 @@ impl DistributedState {
         let required_acks = (self.replicas.len() as f32 * threshold).ceil() as usize;
         let mut acks = 0;
         
-        for replica in &self.replicas {
+        for _replica in &self.replicas {
             // Send update and wait for acknowledgment
             acks += 1;
             
@@ -0,0 +1,932 @@
+/*!
+ * Tendermint consensus for OMNIX
+ * Height and round based BFT agreement with locking, escalating round timeouts and proposer
+ * rotation weighted by voting power
+ */
+
+use crate::{ConsensusEngine, CommittedProposal, NodeId, ProposalId, ReadConsistency, StateMachine, TendermintConfig, Vote, VoteTally};
+use crate::pbft::Request;
+use crate::raft::Membership;
+use crate::transport::{ConsensusTransport, TcpTransport};
+use async_trait::async_trait;
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch, Mutex, RwLock};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration};
+
+/// SHA-256 of a block
+pub type BlockId = [u8; 32];
+
+/// Most requests a proposer puts in one block
+const MAX_BLOCK_REQUESTS: usize = 1024;
+
+/// Most messages held for heights this validator has not reached yet
+const MAX_FUTURE_MESSAGES: usize = 1024;
+
+/// The requests decided at one height
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Block {
+    pub height: u64,
+    /// Signed `Request` messages, in execution order
+    pub requests: Vec<TendermintMessage>,
+}
+
+impl Block {
+    pub fn id(&self) -> BlockId {
+        Sha256::digest(bincode::serialize(self).expect("Tendermint blocks always serialize")).into()
+    }
+}
+
+/// A signed message. Requests inside blocks and precommits inside decisions keep their
+/// original signatures, so any validator can check them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TendermintMessage {
+    pub from: NodeId,
+    pub payload: TendermintPayload,
+    pub signature: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum TendermintPayload {
+    /// Sent by the origin to every validator, so whichever proposes next includes it
+    Request(Request),
+    /// The round's proposer puts a block forward. `valid_round` is set when it re-proposes a
+    /// block a quorum prevoted for in that earlier round.
+    Proposal {
+        height: u64,
+        round: u32,
+        block: Block,
+        valid_round: Option<u32>,
+    },
+    /// `None` votes for no block
+    Prevote {
+        height: u64,
+        round: u32,
+        block_id: Option<BlockId>,
+    },
+    Precommit {
+        height: u64,
+        round: u32,
+        block_id: Option<BlockId>,
+    },
+    /// Asks for the block decided at `height`, from a validator that has fallen behind
+    FetchDecision {
+        height: u64,
+    },
+    /// A decided block with the quorum of precommits that decided it
+    Decision {
+        block: Block,
+        precommits: Vec<TendermintMessage>,
+    },
+}
+
+/// Bytes a message's signature covers
+fn signed_bytes(from: &NodeId, payload: &TendermintPayload) -> Vec<u8> {
+    bincode::serialize(&(from, payload)).expect("Tendermint payloads always serialize")
+}
+
+/// Greatest common divisor of two voting powers
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// One full cycle of proposers. Each step every validator's priority grows by its power and
+/// the highest proposes, paying back the total, so over the cycle each validator proposes in
+/// proportion to its power and as evenly spread as possible.
+fn proposer_schedule(validators: &BTreeMap<NodeId, u64>) -> Vec<NodeId> {
+    let divisor = validators.values().copied().fold(0, gcd);
+    let powers: Vec<(&NodeId, i128)> = validators.iter()
+        .map(|(validator, power)| (validator, (power / divisor) as i128))
+        .collect();
+    let total: i128 = powers.iter().map(|(_, power)| power).sum();
+
+    let mut priorities = vec![0i128; powers.len()];
+    (0..total)
+        .map(|_| {
+            for (priority, (_, power)) in priorities.iter_mut().zip(&powers) {
+                *priority += power;
+            }
+            // Ties go to the validator first by id
+            let chosen = (0..powers.len()).max_by_key(|&i| (priorities[i], Reverse(i))).unwrap();
+            priorities[chosen] -= total;
+            powers[chosen].0.clone()
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Step {
+    #[default]
+    Propose,
+    Prevote,
+    Precommit,
+}
+
+/// A proposal received for the current height
+#[derive(Debug)]
+struct Proposed {
+    block: Block,
+    id: BlockId,
+    valid_round: Option<u32>,
+    /// Whether every request in it is signed by its origin
+    valid: bool,
+}
+
+/// The first prevote and precommit of each validator in one round, with the block each is for
+#[derive(Debug, Default)]
+struct RoundVotes {
+    prevotes: HashMap<NodeId, (Option<BlockId>, TendermintMessage)>,
+    precommits: HashMap<NodeId, (Option<BlockId>, TendermintMessage)>,
+}
+
+/// Rules that act only the first time their condition holds in a round
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Trigger {
+    PrevoteTimeout,
+    PrecommitTimeout,
+    ValidBlock,
+}
+
+#[derive(Debug, Clone)]
+enum Timeout {
+    Propose,
+    Prevote,
+    Precommit,
+    /// Still behind after a round's wait; ask this validator for the decided block
+    Fetch(NodeId),
+}
+
+/// A block decided here, kept so validators that fall behind can catch up from it
+#[derive(Debug)]
+struct Decided {
+    block: Block,
+    /// The quorum that decided it, all from one round
+    precommits: Vec<TendermintMessage>,
+    /// Every validator known to have precommitted it, in any round
+    acks: HashSet<NodeId>,
+}
+
+#[derive(Debug, Default)]
+struct TendermintState {
+    height: u64,
+    round: u32,
+    step: Step,
+    /// The block this validator precommitted most recently at this height, and in which round.
+    /// It prevotes for nothing else until a quorum prevotes for another block in a later round.
+    locked: Option<(u32, Block)>,
+    /// The block a quorum prevoted for most recently at this height; proposed again in
+    /// later rounds
+    valid: Option<(u32, Block)>,
+    proposals: HashMap<u32, Proposed>,
+    votes: BTreeMap<u32, RoundVotes>,
+    fired: HashSet<(Trigger, u32)>,
+    /// Height and round this validator has proposed in, or started waiting for the proposer of
+    proposal_sent: Option<(u64, u32)>,
+    propose_timer: Option<(u64, u32)>,
+    /// Requests received but not executed yet, in arrival order
+    pending: Vec<(String, TendermintMessage)>,
+    executed: HashMap<String, CommittedProposal>,
+    last_executed: u64,
+    decisions: BTreeMap<u64, Decided>,
+    /// Messages for heights this validator has not reached yet
+    future: Vec<TendermintMessage>,
+    /// Height a decided block has been asked for at
+    fetching: Option<u64>,
+}
+
+/// One Tendermint validator. Every validator accepts proposals and broadcasts them; each
+/// height decides a block of them through rounds of propose, prevote and precommit.
+#[derive(Clone)]
+pub struct TendermintNode {
+    pub node_id: NodeId,
+    /// Every validator, this one included, with its voting power
+    pub validators: BTreeMap<NodeId, u64>,
+    pub config: TendermintConfig,
+    signing_key: Arc<SigningKey>,
+    public_keys: Arc<HashMap<NodeId, VerifyingKey>>,
+    /// Validators in the order they propose, repeating
+    schedule: Arc<Vec<NodeId>>,
+    // Changed as a whole by each message, so a single lock keeps it consistent
+    state: Arc<Mutex<TendermintState>>,
+    // Signalled whenever a block is decided or a late precommit arrives
+    pub progress: Arc<watch::Sender<u64>>,
+    // Application-level votes from `<?>`, one per validator and proposal
+    pub votes: Arc<RwLock<HashMap<String, HashMap<NodeId, Vote>>>>,
+    pub state_machine: Arc<RwLock<Option<Arc<dyn StateMachine>>>>,
+    pub transport: Arc<RwLock<Option<Arc<dyn ConsensusTransport<TendermintMessage>>>>>,
+    pub tasks: Arc<Mutex<Vec<JoinHandle<()>>>>,
+    inbox: mpsc::UnboundedSender<TendermintMessage>,
+    message_rx: Arc<Mutex<Option<mpsc::UnboundedReceiver<TendermintMessage>>>>,
+    /// How long round 0 waits at each step
+    pub base_timeout: Duration,
+}
+
+impl TendermintNode {
+    pub fn with_config(node_id: NodeId, timeout_ms: u64, config: TendermintConfig) -> anyhow::Result<Self> {
+        let signing_key = SigningKey::from_bytes(&config.secret_key.unwrap_or_else(rand::random));
+        let mut public_keys = HashMap::new();
+        let mut validators = BTreeMap::new();
+        for (peer, entry) in &config.peers {
+            let key = VerifyingKey::from_bytes(&entry.public_key)
+                .map_err(|e| anyhow::anyhow!("Invalid public key for Tendermint validator {}: {}", peer, e))?;
+            public_keys.insert(peer.clone(), key);
+            validators.insert(peer.clone(), entry.power);
+        }
+        public_keys.insert(node_id.clone(), signing_key.verifying_key());
+        validators.insert(node_id.clone(), config.power);
+
+        if let Some((validator, _)) = validators.iter().find(|(_, power)| **power == 0) {
+            return Err(anyhow::anyhow!("Tendermint validator {} has no voting power", validator));
+        }
+        if timeout_ms == 0 {
+            return Err(anyhow::anyhow!("Tendermint needs a timeout of at least 1ms"));
+        }
+
+        let (inbox, message_rx) = mpsc::unbounded_channel();
+        Ok(Self {
+            node_id,
+            schedule: Arc::new(proposer_schedule(&validators)),
+            validators,
+            signing_key: Arc::new(signing_key),
+            public_keys: Arc::new(public_keys),
+            state: Arc::new(Mutex::new(TendermintState {
+                height: 1,
+                ..TendermintState::default()
+            })),
+            progress: Arc::new(watch::channel(0).0),
+            votes: Arc::new(RwLock::new(HashMap::new())),
+            state_machine: Arc::new(RwLock::new(None)),
+            transport: Arc::new(RwLock::new(None)),
+            tasks: Arc::new(Mutex::new(Vec::new())),
+            inbox,
+            message_rx: Arc::new(Mutex::new(Some(message_rx))),
+            base_timeout: Duration::from_millis(timeout_ms),
+            config,
+        })
+    }
+
+    /// Sender for messages addressed to this validator; transports deliver into it
+    pub fn inbox(&self) -> mpsc::UnboundedSender<TendermintMessage> {
+        self.inbox.clone()
+    }
+
+    /// Route outgoing messages through `transport`
+    pub async fn set_transport(&self, transport: Arc<dyn ConsensusTransport<TendermintMessage>>) {
+        *self.transport.write().await = Some(transport);
+    }
+
+    /// The validator that proposes in `round` of `height`. Moving on a height or a round
+    /// both advance the schedule one step.
+    pub fn proposer(&self, height: u64, round: u32) -> &NodeId {
+        &self.schedule[((height + round as u64) % self.schedule.len() as u64) as usize]
+    }
+
+    /// How long each step of `round` waits; every round waits half the base timeout longer
+    /// than the one before, so validators whose clocks or links are slow eventually overlap
+    pub fn round_timeout(&self, round: u32) -> Duration {
+        self.base_timeout + self.base_timeout * round / 2
+    }
+
+    fn total_power(&self) -> u128 {
+        self.validators.values().map(|power| *power as u128).sum()
+    }
+
+    fn power<'a>(&self, voters: impl IntoIterator<Item = &'a NodeId>) -> u128 {
+        voters.into_iter()
+            .filter_map(|voter| self.validators.get(voter))
+            .map(|power| *power as u128)
+            .sum()
+    }
+
+    /// More than two thirds of the voting power, so any two such sets share a correct validator
+    fn is_quorum(&self, power: u128) -> bool {
+        power * 3 > self.total_power() * 2
+    }
+
+    /// More than a third of the voting power, so at least one correct validator is included
+    fn is_one_third(&self, power: u128) -> bool {
+        power * 3 > self.total_power()
+    }
+
+    pub async fn height(&self) -> u64 {
+        self.state.lock().await.height
+    }
+
+    pub async fn round(&self) -> u32 {
+        self.state.lock().await.round
+    }
+
+    /// Index of the last request executed here
+    pub async fn last_executed(&self) -> u64 {
+        self.state.lock().await.last_executed
+    }
+
+    pub async fn start_consensus_loop(&self) -> anyhow::Result<()> {
+        let rx = self.message_rx.lock().await.take()
+            .ok_or_else(|| anyhow::anyhow!("Tendermint validator {} already started", self.node_id))?;
+        let tendermint = self.clone();
+        self.tasks.lock().await.push(tokio::spawn(async move {
+            tendermint.message_handler_loop(rx).await;
+        }));
+        Ok(())
+    }
+
+    fn sign(&self, payload: TendermintPayload) -> TendermintMessage {
+        let signature = self.signing_key.sign(&signed_bytes(&self.node_id, &payload));
+        TendermintMessage {
+            from: self.node_id.clone(),
+            payload,
+            signature: signature.to_bytes().to_vec(),
+        }
+    }
+
+    /// Whether `message` was signed by the validator it claims to come from
+    fn verify(&self, message: &TendermintMessage) -> bool {
+        let Some(key) = self.public_keys.get(&message.from) else {
+            return false;
+        };
+        let Ok(signature) = Signature::from_slice(&message.signature) else {
+            return false;
+        };
+        key.verify_strict(&signed_bytes(&message.from, &message.payload), &signature).is_ok()
+    }
+
+    /// Sign `payload` and send it to every validator, this one included
+    async fn broadcast(&self, payload: TendermintPayload) {
+        let message = self.sign(payload);
+        for peer in self.validators.keys().filter(|validator| **validator != self.node_id) {
+            self.send(peer, message.clone()).await;
+        }
+        let _ = self.inbox.send(message);
+    }
+
+    /// Hand a message to the transport without waiting for delivery
+    async fn send(&self, peer: &NodeId, message: TendermintMessage) {
+        let Some(transport) = self.transport.read().await.clone() else {
+            return;
+        };
+        let peer = peer.clone();
+        tokio::spawn(async move {
+            if let Err(e) = transport.send(&peer, message).await {
+                tracing::debug!("Failed to send Tendermint message to {}: {}", peer, e);
+            }
+        });
+    }
+
+    async fn message_handler_loop(&self, mut rx: mpsc::UnboundedReceiver<TendermintMessage>) {
+
+        while let Some(message) = rx.recv().await {
+            self.handle_message(message).await;
+        }
+    }
+
+    async fn handle_message(&self, message: TendermintMessage) {
+        if !self.verify(&message) {
+            tracing::warn!("{} dropped a Tendermint message with a bad signature claiming to be from {}", self.node_id, message.from);
+            return;
+        }
+
+        let mut state = self.state.lock().await;
+        match &message.payload {
+            TendermintPayload::Request(request) => self.handle_request(&mut state, &message, request).await,
+            TendermintPayload::FetchDecision { height } => {
+                if let Some(decided) = state.decisions.get(height) {
+                    let reply = self.sign(TendermintPayload::Decision {
+                        block: decided.block.clone(),
+                        precommits: decided.precommits.clone(),
+                    });
+                    self.send(&message.from, reply).await;
+                }
+            }
+            TendermintPayload::Decision { block, precommits } => {
+                self.handle_decision(&mut state, &message.from, block, precommits).await;
+            }
+            TendermintPayload::Proposal { height, .. }
+            | TendermintPayload::Prevote { height, .. }
+            | TendermintPayload::Precommit { height, .. } => {
+                let height = *height;
+                if height > state.height {
+                    self.hold_future(&mut state, message);
+                } else if height < state.height {
+                    self.record_late_precommit(&mut state, &message);
+                } else {
+                    self.record(&mut state, &message);
+                    self.wake(&mut state).await;
+                    self.apply_rules(&mut state).await;
+                }
+            }
+        }
+    }
+
+    /// The request inside a signed request message, if its origin really sent it
+    fn valid_request(&self, message: &TendermintMessage) -> Option<Request> {
+        match &message.payload {
+            TendermintPayload::Request(request) if request.origin == message.from && self.verify(message) => Some(request.clone()),
+            _ => None,
+        }
+    }
+
+    fn valid_block(&self, block: &Block, height: u64) -> bool {
+        block.height == height
+            && block.requests.len() <= MAX_BLOCK_REQUESTS
+            && block.requests.iter().all(|request| self.valid_request(request).is_some())
+    }
+
+    async fn handle_request(&self, state: &mut TendermintState, message: &TendermintMessage, request: &Request) {
+        if request.origin != message.from {
+            return;
+        }
+        let key = request.key();
+        if state.executed.contains_key(&key) || state.pending.iter().any(|(pending, _)| *pending == key) {
+            return;
+        }
+        state.pending.push((key, message.clone()));
+        self.wake(state).await;
+    }
+
+    /// Keep a message for a later height, and ask its sender for the block this validator is
+    /// missing if it has not caught up by itself within a round's wait
+    fn hold_future(&self, state: &mut TendermintState, message: TendermintMessage) {
+        if state.fetching != Some(state.height) {
+            state.fetching = Some(state.height);
+            self.schedule_timeout(Timeout::Fetch(message.from.clone()), state.height, 0);
+        }
+        if state.future.len() < MAX_FUTURE_MESSAGES {
+            state.future.push(message);
+        }
+    }
+
+    /// Count a precommit that arrived after its block was decided towards the block's acks
+    fn record_late_precommit(&self, state: &mut TendermintState, message: &TendermintMessage) {
+        let TendermintPayload::Precommit { height, block_id: Some(block_id), .. } = &message.payload else {
+            return;
+        };
+        if let Some(decided) = state.decisions.get_mut(height) {
+            if decided.block.id() == *block_id && decided.acks.insert(message.from.clone()) {
+                self.progress.send_modify(|_| {});
+            }
+        }
+    }
+
+    /// Store a proposal or vote for the current height. Only the round's proposer may
+    /// propose, and only a validator's first vote of each kind in a round counts.
+    fn record(&self, state: &mut TendermintState, message: &TendermintMessage) {
+        match &message.payload {
+            TendermintPayload::Proposal { height, round, block, valid_round } => {
+                if message.from != *self.proposer(*height, *round) || state.proposals.contains_key(round) {
+                    return;
+                }
+                let valid = self.valid_block(block, *height) && valid_round.is_none_or(|valid_round| valid_round < *round);
+                state.proposals.insert(*round, Proposed {
+                    block: block.clone(),
+                    id: block.id(),
+                    valid_round: *valid_round,
+                    valid,
+                });
+            }
+            TendermintPayload::Prevote { round, block_id, .. } => {
+                state.votes.entry(*round).or_default().prevotes
+                    .entry(message.from.clone())
+                    .or_insert_with(|| (*block_id, message.clone()));
+            }
+            TendermintPayload::Precommit { round, block_id, .. } => {
+                state.votes.entry(*round).or_default().precommits
+                    .entry(message.from.clone())
+                    .or_insert_with(|| (*block_id, message.clone()));
+            }
+            _ => {}
+        }
+    }
+
+    /// Propose, or start waiting for the proposer, once there is something to agree on. An
+    /// idle cluster stays in round 0 rather than deciding empty blocks.
+    async fn wake(&self, state: &mut TendermintState) {
+        if state.step != Step::Propose {
+            return;
+        }
+        let current = Some((state.height, state.round));
+        if *self.proposer(state.height, state.round) == self.node_id {
+            if state.proposal_sent != current && (state.round > 0 || state.valid.is_some() || !state.pending.is_empty()) {
+                state.proposal_sent = current;
+                self.propose_block(state).await;
+            }
+        } else if state.propose_timer != current {
+            state.propose_timer = current;
+            self.schedule_timeout(Timeout::Propose, state.height, state.round);
+        }
+    }
+
+    /// Put forward the block a quorum last prevoted for at this height, or a new one from
+    /// the pending requests
+    async fn propose_block(&self, state: &mut TendermintState) {
+        let (block, valid_round) = match &state.valid {
+            Some((round, block)) => (block.clone(), Some(*round)),
+            None => {
+                let requests = state.pending.iter()
+                    .take(MAX_BLOCK_REQUESTS)
+                    .map(|(_, message)| message.clone())
+                    .collect();
+                (Block { height: state.height, requests }, None)
+            }
+        };
+        tracing::debug!("{} proposing {} requests at height {} round {}", self.node_id, block.requests.len(), state.height, state.round);
+        self.broadcast(TendermintPayload::Proposal {
+            height: state.height,
+            round: state.round,
+            block,
+            valid_round,
+        }).await;
+    }
+
+    async fn start_round(&self, state: &mut TendermintState, round: u32) {
+        state.round = round;
+        state.step = Step::Propose;
+        if round > 0 || !state.pending.is_empty() {
+            self.wake(state).await;
+        }
+    }
+
+    async fn prevote(&self, state: &mut TendermintState, block_id: Option<BlockId>) {
+        state.step = Step::Prevote;
+        self.broadcast(TendermintPayload::Prevote { height: state.height, round: state.round, block_id }).await;
+    }
+
+    async fn precommit(&self, state: &mut TendermintState, block_id: Option<BlockId>) {
+        state.step = Step::Precommit;
+        self.broadcast(TendermintPayload::Precommit { height: state.height, round: state.round, block_id }).await;
+    }
+
+    fn schedule_timeout(&self, timeout: Timeout, height: u64, round: u32) {
+        let tendermint = self.clone();
+        let delay = self.round_timeout(round);
+        tokio::spawn(async move {
+            sleep(delay).await;
+            let mut state = tendermint.state.lock().await;
+            tendermint.on_timeout(&mut state, timeout, height, round).await;
+        });
+    }
+
+    async fn on_timeout(&self, state: &mut TendermintState, timeout: Timeout, height: u64, round: u32) {
+        if height != state.height {
+            return;
+        }
+        match timeout {
+            Timeout::Propose if round == state.round && state.step == Step::Propose => {
+                self.prevote(state, None).await;
+            }
+            Timeout::Prevote if round == state.round && state.step == Step::Prevote => {
+                self.precommit(state, None).await;
+            }
+            Timeout::Precommit if round == state.round => {
+                tracing::info!("{} moving to round {} at height {}", self.node_id, round + 1, height);
+                self.start_round(state, round + 1).await;
+            }
+            Timeout::Fetch(peer) => {
+                state.fetching = None;
+                let request = self.sign(TendermintPayload::FetchDecision { height });
+                self.send(&peer, request).await;
+                return;
+            }
+            _ => return,
+        }
+        self.apply_rules(state).await;
+    }
+
+    /// Voting power behind the votes in `votes` that `matches` accepts
+    fn vote_power(&self, votes: &HashMap<NodeId, (Option<BlockId>, TendermintMessage)>, matches: impl Fn(Option<BlockId>) -> bool) -> u128 {
+        self.power(votes.iter().filter(|(_, (block_id, _))| matches(*block_id)).map(|(voter, _)| voter))
+    }
+
+    fn prevote_quorum(&self, state: &TendermintState, round: u32, block_id: Option<BlockId>) -> bool {
+        state.votes.get(&round).is_some_and(|votes| self.is_quorum(self.vote_power(&votes.prevotes, |id| id == block_id)))
+    }
+
+    /// Apply the protocol's rules until none of them has anything left to do
+    async fn apply_rules(&self, state: &mut TendermintState) {
+        while self.apply_rule(state).await {}
+    }
+
+    /// Act on the first rule whose condition holds, returning whether one did
+    async fn apply_rule(&self, state: &mut TendermintState) -> bool {
+        // A quorum precommitted a block in some round: decide it
+        let decided = state.proposals.iter().find_map(|(round, proposed)| {
+            let votes = state.votes.get(round)?;
+            let proof: Vec<TendermintMessage> = votes.precommits.values()
+                .filter(|(block_id, _)| *block_id == Some(proposed.id))
+                .map(|(_, message)| message.clone())
+                .collect();
+            (proposed.valid && self.is_quorum(self.power(proof.iter().map(|message| &message.from))))
+                .then(|| (proposed.block.clone(), proof))
+        });
+        if let Some((block, precommits)) = decided {
+            return self.decide(state, block, precommits).await;
+        }
+
+        // More than a third of the power is in a later round: at least one correct validator
+        // has moved on, so follow instead of waiting out this round
+        let ahead = state.votes.range(state.round + 1..)
+            .map(|(round, votes)| {
+                let mut senders: HashSet<&NodeId> = votes.prevotes.keys().chain(votes.precommits.keys()).collect();
+                if state.proposals.contains_key(round) {
+                    senders.insert(self.proposer(state.height, *round));
+                }
+                (*round, self.power(senders))
+            })
+            .filter(|(_, power)| self.is_one_third(*power))
+            .map(|(round, _)| round)
+            .max();
+        if let Some(round) = ahead {
+            self.start_round(state, round).await;
+            return true;
+        }
+
+        let round = state.round;
+        let proposal = state.proposals.get(&round).map(|proposed| (proposed.id, proposed.valid, proposed.valid_round, proposed.block.clone()));
+
+        if state.step == Step::Propose {
+            if let Some((id, valid, valid_round, _)) = proposal {
+                let locked = state.locked.as_ref().map(|(locked_round, block)| (*locked_round, block.id()));
+                match valid_round {
+                    // A new block: prevote for it unless locked on another
+                    None => {
+                        let acceptable = valid && locked.is_none_or(|(_, locked_id)| locked_id == id);
+                        self.prevote(state, acceptable.then_some(id)).await;
+                        return true;
+                    }
+                    // A block a quorum prevoted for in an earlier round: that unlocks anything
+                    // locked in that round or before
+                    Some(valid_round) if self.prevote_quorum(state, valid_round, Some(id)) => {
+                        let acceptable = valid && locked.is_none_or(|(locked_round, locked_id)| locked_round <= valid_round || locked_id == id);
+                        self.prevote(state, acceptable.then_some(id)).await;
+                        return true;
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+
+        let prevote_power = state.votes.get(&round).map_or(0, |votes| self.vote_power(&votes.prevotes, |_| true));
+        if state.step == Step::Prevote && self.is_quorum(prevote_power) && state.fired.insert((Trigger::PrevoteTimeout, round)) {
+            self.schedule_timeout(Timeout::Prevote, state.height, round);
+            return true;
+        }
+
+        if let Some((id, true, _, block)) = proposal {
+            if state.step >= Step::Prevote && self.prevote_quorum(state, round, Some(id)) && state.fired.insert((Trigger::ValidBlock, round)) {
+                if state.step == Step::Prevote {
+                    state.locked = Some((round, block.clone()));
+                    self.precommit(state, Some(id)).await;
+                }
+                state.valid = Some((round, block));
+                return true;
+            }
+        }
+
+        if state.step == Step::Prevote && self.prevote_quorum(state, round, None) {
+            self.precommit(state, None).await;
+            return true;
+        }
+
+        let precommit_power = state.votes.get(&round).map_or(0, |votes| self.vote_power(&votes.precommits, |_| true));
+        if self.is_quorum(precommit_power) && state.fired.insert((Trigger::PrecommitTimeout, round)) {
+            self.schedule_timeout(Timeout::Precommit, state.height, round);
+            return true;
+        }
+
+        false
+    }
+
+    /// Execute a decided block and move to the next height. Returns false, staying at this
+    /// height, if the state machine fails on one of its requests.
+    async fn decide(&self, state: &mut TendermintState, block: Block, precommits: Vec<TendermintMessage>) -> bool {
+        let height = state.height;
+        let machine = self.state_machine.read().await.clone();
+        for message in &block.requests {
+            let TendermintPayload::Request(request) = &message.payload else {
+                continue;
+            };
+            let key = request.key();
+            // A request can be in more than one block if it was pending on two proposers
+            if !state.executed.contains_key(&key) {
+                let committed = CommittedProposal {
+                    term: height,
+                    index: state.last_executed + 1,
+                    value: request.value.clone(),
+                };
+                if let Some(machine) = &machine {
+                    // Skipping the request would leave this validator's state behind the others';
+                    // stop here and retry the rest of the block when the decision is next seen
+                    if let Err(e) = machine.apply(&committed).await {
+                        tracing::error!("Failed to apply request {}, halting at height {}: {}", committed.index, height, e);
+                        return false;
+                    }
+                }
+                state.last_executed = committed.index;
+                state.executed.insert(key.clone(), committed);
+            }
+            state.pending.retain(|(pending, _)| *pending != key);
+        }
+        tracing::debug!("{} decided height {} with {} requests", self.node_id, height, block.requests.len());
+
+        let acks = precommits.iter().map(|message| message.from.clone()).collect();
+        state.decisions.insert(height, Decided { block, precommits, acks });
+        state.height = height + 1;
+        state.locked = None;
+        state.valid = None;
+        state.proposals.clear();
+        state.votes.clear();
+        state.fired.clear();
+        self.progress.send_modify(|_| {});
+
+        // Replay what arrived early for the new height
+        let future = std::mem::take(&mut state.future);
+        for message in future {
+            match &message.payload {
+                TendermintPayload::Proposal { height, .. }
+                | TendermintPayload::Prevote { height, .. }
+                | TendermintPayload::Precommit { height, .. } if *height == state.height => {
+                    let _ = self.inbox.send(message);
+                }
+                TendermintPayload::Proposal { height, .. }
+                | TendermintPayload::Prevote { height, .. }
+                | TendermintPayload::Precommit { height, .. } if *height > state.height => state.future.push(message),
+                _ => {}
+            }
+        }
+        self.start_round(state, 0).await;
+        true
+    }
+
+    /// Adopt a block another validator decided at this validator's height, once its
+    /// precommits prove a quorum decided it
+    async fn handle_decision(&self, state: &mut TendermintState, from: &NodeId, block: &Block, precommits: &[TendermintMessage]) {
+        if block.height != state.height || !self.valid_block(block, block.height) {
+            return;
+        }
+        let id = block.id();
+        let round = precommits.first().and_then(|message| match message.payload {
+            TendermintPayload::Precommit { round, .. } => Some(round),
+            _ => None,
+        });
+        let signers: HashSet<&NodeId> = precommits.iter()
+            .filter(|message| matches!(message.payload, TendermintPayload::Precommit { height, round: r, block_id } if height == block.height && Some(r) == round && block_id == Some(id)))
+            .filter(|message| self.verify(message))
+            .map(|message| &message.from)
+            .collect();
+        if signers.len() != precommits.len() || !self.is_quorum(self.power(signers)) {
+            tracing::warn!("{} rejected a decision for height {} from {}", self.node_id, block.height, from);
+            return;
+        }
+
+        tracing::info!("{} caught up on height {} from {}", self.node_id, block.height, from);
+        if !self.decide(state, block.clone(), precommits.to_vec()).await {
+            return;
+        }
+        // Still behind if messages for later heights are waiting
+        if state.future.iter().any(|message| match &message.payload {
+            TendermintPayload::Proposal { height, .. }
+            | TendermintPayload::Prevote { height, .. }
+            | TendermintPayload::Precommit { height, .. } => *height > state.height,
+            _ => false,
+        }) {
+            state.fetching = Some(state.height);
+            let request = self.sign(TendermintPayload::FetchDecision { height: state.height });
+            self.send(from, request).await;
+        }
+        self.apply_rules(state).await;
+    }
+
+    /// Record `voter`'s ballot on a proposal, replacing any earlier one
+    pub async fn record_vote(&self, proposal_id: &ProposalId, voter: NodeId, vote: Vote) {
+        self.votes.write().await
+            .entry(proposal_id.0.clone())
+            .or_default()
+            .insert(voter, vote);
+    }
+}
+
+#[async_trait]
+impl ConsensusEngine for TendermintNode {
+    async fn start(&mut self) -> anyhow::Result<()> {
+        if let Some(addr) = self.config.listen_addr {
+            let peers = self.config.peers.iter()
+                .filter_map(|(peer, entry)| entry.addr.map(|addr| (peer.clone(), addr)))
+                .collect();
+            let transport = TcpTransport::bind(addr, peers, self.inbox()).await?;
+            self.set_transport(Arc::new(transport)).await;
+        }
+        self.start_consensus_loop().await
+    }
+
+    /// Any validator accepts proposals. The request goes to every validator, so whichever
+    /// proposes next can include it.
+    async fn propose(&self, value: Vec<u8>) -> anyhow::Result<ProposalId> {
+        let request = Request {
+            origin: self.node_id.clone(),
+            id: uuid::Uuid::new_v4().to_string(),
+            value,
+        };
+        let proposal_id = ProposalId(request.key());
+        self.broadcast(TendermintPayload::Request(request)).await;
+        Ok(proposal_id)
+    }
+
+    async fn wait_for_commit(&self, proposal_id: &ProposalId, required_acks: usize) -> anyhow::Result<CommittedProposal> {
+        if required_acks > self.validators.len() {
+            return Err(anyhow::anyhow!("Proposal needs {} acknowledgements but the cluster has {} validators", required_acks, self.validators.len()));
+        }
+
+        // Subscribe before checking so a decision between the check and the wait is not missed
+        let mut progress = self.progress.subscribe();
+        loop {
+            {
+                let state = self.state.lock().await;
+                if let Some(committed) = state.executed.get(&proposal_id.0) {
+                    let acks = state.decisions.get(&committed.term).map_or(0, |decided| decided.acks.len());
+                    if acks >= required_acks {
+                        return Ok(committed.clone());
+                    }
+                }
+            }
+            progress.changed().await?;
+        }
+    }
+
+    async fn vote(&self, proposal_id: ProposalId, vote: Vote) -> anyhow::Result<()> {
+        self.record_vote(&proposal_id, self.node_id.clone(), vote).await;
+        Ok(())
+    }
+
+    async fn receive_vote(&self, proposal_id: ProposalId, voter: NodeId, vote: Vote) -> anyhow::Result<()> {
+        self.record_vote(&proposal_id, voter, vote).await;
+        Ok(())
+    }
+
+    async fn tally(&self, proposal_id: &ProposalId) -> anyhow::Result<VoteTally> {
+        let mut tally = VoteTally {
+            voters: self.validators.len(),
+            ..VoteTally::default()
+        };
+        if let Some(ballots) = self.votes.read().await.get(&proposal_id.0) {
+            for vote in ballots.values() {
+                match vote {
+                    Vote::Accept => tally.accept += 1,
+                    Vote::Reject => tally.reject += 1,
+                    Vote::Abstain => tally.abstain += 1,
+                }
+            }
+        }
+        Ok(tally)
+    }
+
+    async fn on_commit(&self, _value: Vec<u8>) -> anyhow::Result<()> {
+        // Decided blocks reach the state machine as they are decided
+        Ok(())
+    }
+
+    async fn set_state_machine(&self, machine: Arc<dyn StateMachine>) -> anyhow::Result<()> {
+        *self.state_machine.write().await = Some(machine);
+        Ok(())
+    }
+
+    async fn add_learner(&self, _node: NodeId, _addr: Option<SocketAddr>) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!("Tendermint validators are fixed by configuration"))
+    }
+
+    async fn add_voter(&self, _node: NodeId, _addr: Option<SocketAddr>) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!("Tendermint validators are fixed by configuration"))
+    }
+
+    async fn remove_server(&self, _node: NodeId) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!("Tendermint validators are fixed by configuration"))
+    }
+
+    async fn membership(&self) -> anyhow::Result<Membership> {
+        Ok(Membership {
+            voters: self.validators.keys().cloned().collect(),
+            ..Membership::default()
+        })
+    }
+
+    async fn transfer_leadership(&self, _target: NodeId) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!("Tendermint proposers rotate by voting power"))
+    }
+
+    async fn read_index(&self, consistency: ReadConsistency) -> anyhow::Result<u64> {
+        match consistency {
+            ReadConsistency::Stale => Ok(self.state.lock().await.last_executed),
+            // No validator leads for longer than a round, so every consistent read is decided
+            // like a write and reflects everything executed before it
+            ReadConsistency::Linearizable | ReadConsistency::Lease => {
+                let proposal_id = self.propose(Vec::new()).await?;
+                Ok(self.wait_for_commit(&proposal_id, 0).await?.index)
+            }
+        }
+    }
+}
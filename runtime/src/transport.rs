@@ -0,0 +1,254 @@
+/*!
+ * Consensus transports for OMNIX
+ * Carry `RaftMessage`s between `RaftNode` peers, `PbftMessage`s between `PbftNode`
+ * replicas, `TendermintMessage`s between `TendermintNode` validators and `HotStuffMessage`s
+ * between `HotStuffNode` replicas, in process or over TCP
+ */
+
+use crate::hotstuff::{HotStuffMessage, HotStuffNode};
+use crate::pbft::{PbftMessage, PbftNode};
+use crate::raft::{RaftMessage, RaftNode};
+use crate::tendermint::{TendermintMessage, TendermintNode};
+use crate::NodeId;
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::marker::PhantomData;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, Mutex, RwLock};
+use tokio::task::JoinHandle;
+use tokio::time::{timeout, Duration};
+
+/// Largest frame accepted from a peer
+const MAX_FRAME_LEN: u32 = 64 * 1024 * 1024;
+
+/// How long to wait for a connection to a peer before giving up on a message
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Delivers one consensus engine's messages to its peers. Delivery is best effort: Raft and
+/// PBFT recover lost messages through their own timers, so a transport may drop messages but
+/// must not corrupt them.
+#[async_trait]
+pub trait ConsensusTransport<M: Send + 'static>: Send + Sync {
+    async fn send(&self, peer: &NodeId, message: M) -> anyhow::Result<()>;
+    
+    /// Learn where to reach a member added at runtime. Transports that route by node id alone
+    /// can ignore it.
+    async fn add_peer_address(&self, _peer: &NodeId, _addr: SocketAddr) {}
+}
+
+/// Connects consensus nodes running in the same process. Nodes can be cut off and reconnected
+/// to simulate partitions.
+pub struct InProcessNetwork<M> {
+    inboxes: Arc<RwLock<HashMap<NodeId, mpsc::UnboundedSender<M>>>>,
+    disconnected: Arc<RwLock<HashSet<NodeId>>>,
+}
+
+// Derived impls would needlessly require `M: Clone + Default`
+impl<M> Clone for InProcessNetwork<M> {
+    fn clone(&self) -> Self {
+        Self {
+            inboxes: self.inboxes.clone(),
+            disconnected: self.disconnected.clone(),
+        }
+    }
+}
+
+impl<M> Default for InProcessNetwork<M> {
+    fn default() -> Self {
+        Self {
+            inboxes: Arc::default(),
+            disconnected: Arc::default(),
+        }
+    }
+}
+
+impl InProcessNetwork<RaftMessage> {
+    /// Attach a Raft node so it can send to and receive from every other attached node
+    pub async fn join(&self, node: &RaftNode) -> anyhow::Result<()> {
+        let inbox = node.inbox().await
+            .ok_or_else(|| anyhow::anyhow!("Raft node {} has no inbox", node.node_id))?;
+        node.set_transport(self.attach(&node.node_id, inbox).await).await;
+        Ok(())
+    }
+}
+
+impl InProcessNetwork<PbftMessage> {
+    /// Attach a PBFT replica so it can send to and receive from every other attached replica
+    pub async fn join(&self, node: &PbftNode) -> anyhow::Result<()> {
+        node.set_transport(self.attach(&node.node_id, node.inbox()).await).await;
+        Ok(())
+    }
+}
+
+impl InProcessNetwork<TendermintMessage> {
+    /// Attach a Tendermint validator so it can send to and receive from every other attached validator
+    pub async fn join(&self, node: &TendermintNode) -> anyhow::Result<()> {
+        node.set_transport(self.attach(&node.node_id, node.inbox()).await).await;
+        Ok(())
+    }
+}
+
+impl InProcessNetwork<HotStuffMessage> {
+    /// Attach a HotStuff replica so it can send to and receive from every other attached replica
+    pub async fn join(&self, node: &HotStuffNode) -> anyhow::Result<()> {
+        node.set_transport(self.attach(&node.node_id, node.inbox()).await).await;
+        Ok(())
+    }
+}
+
+impl<M: Send + 'static> InProcessNetwork<M> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Deliver messages for `node_id` to `inbox`, returning the transport it sends through
+    async fn attach(&self, node_id: &NodeId, inbox: mpsc::UnboundedSender<M>) -> Arc<dyn ConsensusTransport<M>> {
+        self.inboxes.write().await.insert(node_id.clone(), inbox);
+        Arc::new(InProcessTransport {
+            network: self.clone(),
+            node_id: node_id.clone(),
+        })
+    }
+
+    /// Drop every message to or from `node` until it is reconnected
+    pub async fn disconnect(&self, node: &NodeId) {
+        self.disconnected.write().await.insert(node.clone());
+    }
+
+    pub async fn reconnect(&self, node: &NodeId) {
+        self.disconnected.write().await.remove(node);
+    }
+}
+
+struct InProcessTransport<M> {
+    network: InProcessNetwork<M>,
+    node_id: NodeId,
+}
+
+#[async_trait]
+impl<M: Send + 'static> ConsensusTransport<M> for InProcessTransport<M> {
+    async fn send(&self, peer: &NodeId, message: M) -> anyhow::Result<()> {
+        {
+            let disconnected = self.network.disconnected.read().await;
+            if disconnected.contains(&self.node_id) || disconnected.contains(peer) {
+                return Ok(());
+            }
+        }
+
+        let inboxes = self.network.inboxes.read().await;
+        let inbox = inboxes.get(peer)
+            .ok_or_else(|| anyhow::anyhow!("Unknown peer: {}", peer))?;
+        inbox.send(message)
+            .map_err(|_| anyhow::anyhow!("Peer {} has stopped", peer))
+    }
+}
+
+/// Sends length-prefixed bincode frames over TCP, one outgoing connection per peer
+pub struct TcpTransport<M> {
+    peers: RwLock<HashMap<NodeId, SocketAddr>>,
+    connections: Mutex<HashMap<NodeId, Arc<Mutex<Option<TcpStream>>>>>,
+    listener: JoinHandle<()>,
+    messages: PhantomData<fn(M)>,
+}
+
+impl<M: Serialize + DeserializeOwned + Send + 'static> TcpTransport<M> {
+    /// Listen on `addr` and deliver every message received to `inbox`
+    pub async fn bind(addr: SocketAddr, peers: HashMap<NodeId, SocketAddr>, inbox: mpsc::UnboundedSender<M>) -> anyhow::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        tracing::info!("Consensus transport listening on {}", addr);
+        let listener = tokio::spawn(accept_loop(listener, inbox));
+
+        Ok(Self {
+            peers: RwLock::new(peers),
+            connections: Mutex::new(HashMap::new()),
+            listener,
+            messages: PhantomData,
+        })
+    }
+}
+
+impl<M> Drop for TcpTransport<M> {
+    fn drop(&mut self) {
+        // Free the port for a node restarted in the same process
+        self.listener.abort();
+    }
+}
+
+#[async_trait]
+impl<M: Serialize + DeserializeOwned + Send + 'static> ConsensusTransport<M> for TcpTransport<M> {
+    async fn send(&self, peer: &NodeId, message: M) -> anyhow::Result<()> {
+        let addr = *self.peers.read().await.get(peer)
+            .ok_or_else(|| anyhow::anyhow!("No address for peer {}", peer))?;
+        let payload = bincode::serialize(&message)?;
+
+        // Hold only this peer's connection, so a slow peer does not delay the others
+        let connection = self.connections.lock().await.entry(peer.clone()).or_default().clone();
+        let mut connection = connection.lock().await;
+        let stream = match connection.as_mut() {
+            Some(stream) => stream,
+            None => {
+                let stream = timeout(CONNECT_TIMEOUT, TcpStream::connect(addr)).await
+                    .map_err(|_| anyhow::anyhow!("Timed out connecting to peer {} at {}", peer, addr))??;
+                stream.set_nodelay(true)?;
+                connection.insert(stream)
+            }
+        };
+
+        let written = async {
+            stream.write_u32(payload.len() as u32).await?;
+            stream.write_all(&payload).await
+        }.await;
+        if let Err(e) = written {
+            // Reconnect on the next message
+            *connection = None;
+            return Err(e.into());
+        }
+        Ok(())
+    }
+    
+    async fn add_peer_address(&self, peer: &NodeId, addr: SocketAddr) {
+        let previous = self.peers.write().await.insert(peer.clone(), addr);
+        if previous.is_some_and(|previous| previous != addr) {
+            // The peer moved; reconnect on the next message
+            self.connections.lock().await.remove(peer);
+        }
+    }
+}
+
+async fn accept_loop<M: DeserializeOwned + Send + 'static>(listener: TcpListener, inbox: mpsc::UnboundedSender<M>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, remote)) => {
+                let inbox = inbox.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = read_frames(stream, inbox).await {
+                        tracing::debug!("Connection from {} closed: {}", remote, e);
+                    }
+                });
+            }
+            Err(e) => tracing::warn!("Failed to accept connection: {}", e),
+        }
+    }
+}
+
+async fn read_frames<M: DeserializeOwned>(mut stream: TcpStream, inbox: mpsc::UnboundedSender<M>) -> anyhow::Result<()> {
+    loop {
+        let len = stream.read_u32().await?;
+        if len > MAX_FRAME_LEN {
+            return Err(anyhow::anyhow!("Frame of {} bytes exceeds the limit", len));
+        }
+        let mut payload = vec![0; len as usize];
+        stream.read_exact(&mut payload).await?;
+
+        let message: M = bincode::deserialize(&payload)?;
+        if inbox.send(message).is_err() {
+            // The node has shut down
+            return Ok(());
+        }
+    }
+}
@@ -42,6 +42,23 @@ enum Commands {
         #[arg(long, default_value = "8080")]
         port: u16,
         
+        /// Address for Raft traffic between nodes; runs a single-node cluster if omitted
+        #[arg(long)]
+        raft_addr: Option<std::net::SocketAddr>,
+        
+        /// Another cluster member as `node_id=host:port` (repeatable)
+        #[arg(long = "peer", value_parser = parse_peer)]
+        peers: Vec<(String, std::net::SocketAddr)>,
+        
+        /// Directory for the Raft log, term and vote; kept in memory if omitted
+        #[arg(long)]
+        data_dir: Option<PathBuf>,
+        
+        /// Join a running cluster: wait for its leader to add this node instead of
+        /// founding a cluster with the peers
+        #[arg(long)]
+        join: bool,
+        
         /// Enable verbose output
         #[arg(short, long)]
         verbose: bool,
@@ -73,8 +90,15 @@ async fn main() -> Result<()> {
         Commands::Compile { input, output, verbose } => {
             compile_file(input, output, verbose).await
         }
-        Commands::Run { input, node_id, port, verbose } => {
-            run_file(input, node_id, port, verbose).await
+        Commands::Run { input, node_id, port, raft_addr, peers, data_dir, join, verbose } => {
+            let raft = omnix_runtime::RaftConfig {
+                listen_addr: raft_addr,
+                peers: peers.into_iter().collect(),
+                data_dir,
+                join_existing: join,
+                ..Default::default()
+            };
+            run_file(input, node_id, port, raft, verbose).await
         }
         Commands::Init { name, path } => {
             init_project(name, path).await
@@ -93,8 +117,8 @@ async fn compile_file(input: PathBuf, output: Option<PathBuf>, verbose: bool) ->
     let source = std::fs::read_to_string(&input)?;
     
     // Parse the source code
-    let tokens = omnix_compiler::lexer::tokenize(&source)?;
-    let program = omnix_compiler::parser::parse(tokens)?;
+    let tokens = omnix_compiler::lexer::tokenize(&source).map_err(omnix_compiler::diagnostics_error)?;
+    let program = omnix_compiler::parser::parse(tokens).map_err(omnix_compiler::diagnostics_error)?;
     
     if verbose {
         println!("Successfully parsed {} top-level items", program.items.len());
@@ -115,7 +139,13 @@ async fn compile_file(input: PathBuf, output: Option<PathBuf>, verbose: bool) ->
     Ok(())
 }
 
-async fn run_file(input: PathBuf, node_id: String, port: u16, verbose: bool) -> Result<()> {
+fn parse_peer(s: &str) -> Result<(String, std::net::SocketAddr), String> {
+    let (id, addr) = s.split_once('=').ok_or_else(|| format!("expected node_id=host:port, got '{}'", s))?;
+    let addr = addr.parse().map_err(|e| format!("invalid address '{}': {}", addr, e))?;
+    Ok((id.to_string(), addr))
+}
+
+async fn run_file(input: PathBuf, node_id: String, port: u16, raft: omnix_runtime::RaftConfig, verbose: bool) -> Result<()> {
     // Initialize tracing
     tracing_subscriber::fmt()
         .with_env_filter(
@@ -132,23 +162,27 @@ async fn run_file(input: PathBuf, node_id: String, port: u16, verbose: bool) ->
     let source = std::fs::read_to_string(&input)?;
     
     // Parse the source code
-    let tokens = omnix_compiler::lexer::tokenize(&source)?;
-    let program = omnix_compiler::parser::parse(tokens)?;
+    let tokens = omnix_compiler::lexer::tokenize(&source).map_err(omnix_compiler::diagnostics_error)?;
+    let program = omnix_compiler::parser::parse(tokens).map_err(omnix_compiler::diagnostics_error)?;
     
     if verbose {
         println!("Successfully parsed {} top-level items", program.items.len());
     }
     
     // Create runtime environment
-    let config = omnix_runtime::runtime::create_mvp_config(port);
-    let executor = omnix_runtime::runtime::Executor::new(node_id.clone(), config).await?;
-    let executor = std::sync::Arc::new(tokio::sync::RwLock::new(executor));
+    let mut config = omnix_runtime::runtime::create_mvp_config(port);
+    config.consensus.raft = raft;
+    let mut executor = omnix_runtime::runtime::Executor::new(node_id.clone(), config).await?;
+    let phase = executor.phase_status();
+    let state = executor.state();
+    let dispatcher = executor.message_dispatcher().await;
     
     // Create HTTP API state
     let api_state = omnix_runtime::http_api::AppState {
-        executor: executor.clone(),
+        state,
         node_id: node_id.clone(),
-        counter: std::sync::Arc::new(tokio::sync::RwLock::new(0)),
+        counter: "counter".to_string(),
+        phase,
     };
     
     // Start HTTP API server
@@ -159,8 +193,13 @@ async fn run_file(input: PathBuf, node_id: String, port: u16, verbose: bool) ->
         }
     });
     
-    // Execute the program
-    executor.write().await.execute(program).await?;
+    // Execute the program. Messages from peers are dispatched to a fork of the executor, so
+    // votes are counted and `on` handlers run while `main` is still going.
+    executor.start(&program).await?;
+    if let Some(dispatcher) = dispatcher {
+        tokio::spawn(dispatcher.run(executor.fork()));
+    }
+    executor.run_main().await?;
     
     println!("OMNIX node '{}' started on port {}", node_id, port);
     println!("HTTP API available at http://localhost:{}", port);
@@ -168,7 +207,7 @@ async fn run_file(input: PathBuf, node_id: String, port: u16, verbose: bool) ->
     println!("  GET  /value      - Current counter value");
     println!("  POST /increment  - Increment counter");
     println!("  POST /decrement  - Decrement counter");
-    println!("");
+    println!();
     println!("Press Ctrl+C to stop...");
     
     // Wait for shutdown signal
@@ -308,6 +347,27 @@ async fn check_file(input: PathBuf) -> Result<()> {
                             }
                         }
                     }
+
+                    // Name resolution
+                    let resolution = omnix_compiler::resolver::resolve(&program);
+                    for diagnostic in &resolution.diagnostics {
+                        println!("  {}", diagnostic);
+                    }
+                    if resolution.has_errors() {
+                        println!("✗ Name resolution failed");
+                        return Err(anyhow::anyhow!("Name resolution errors found"));
+                    }
+                    println!("✓ All names resolved ({} symbols)", resolution.symbols.len());
+
+                    // Type checking
+                    if let Err(errors) = omnix_compiler::typechecker::check(&program, &resolution) {
+                        println!("✗ Type errors found:");
+                        for error in errors {
+                            println!("  {}", error);
+                        }
+                        return Err(anyhow::anyhow!("Type errors found"));
+                    }
+                    println!("✓ Types are consistent");
                 }
                 Err(errors) => {
                     println!("✗ Syntax errors found:");
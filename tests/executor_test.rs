@@ -0,0 +1,577 @@
+/*!
+ * Executor tests on an in-process cluster
+ * OMNIX programs run by executors over PBFT replicas, which let any node write
+ */
+
+use omnix_compiler::ast::Program;
+use omnix_runtime::network::InProcessHub;
+use omnix_runtime::pbft::{self, PbftMessage, PbftNode};
+use omnix_runtime::transport::InProcessNetwork;
+use omnix_runtime::runtime::{Executor, PhaseState, RuntimeValue};
+use omnix_runtime::{Message, NetworkLayer, PbftConfig, PbftPeer, Runtime};
+use std::time::Duration;
+use tokio::time::{sleep, timeout, Instant};
+
+const CONSENSUS_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn parse(source: &str) -> Program {
+    let tokens = omnix_compiler::lexer::tokenize(source).expect("Failed to tokenize");
+    omnix_compiler::parser::parse(tokens).expect("Failed to parse")
+}
+
+fn node_ids(size: usize) -> Vec<String> {
+    (1..=size).map(|i| format!("node{}", i)).collect()
+}
+
+fn secret_key(id: &str) -> [u8; 32] {
+    [id.trim_start_matches("node").parse().unwrap(); 32]
+}
+
+/// Executors for `size` replicas on `hub`, none started yet. Every engine is on the network
+/// from the outset, so a replica started late still receives everything sent before.
+async fn cluster(hub: &InProcessHub, size: usize) -> Vec<Executor> {
+    let engines = InProcessNetwork::<PbftMessage>::new();
+    let mut executors = Vec::new();
+    for id in node_ids(size) {
+        let config = PbftConfig {
+            peers: node_ids(size).into_iter()
+                .filter(|peer| *peer != id)
+                .map(|peer| {
+                    let public_key = pbft::public_key(&secret_key(&peer));
+                    (peer, PbftPeer { addr: None, public_key })
+                })
+                .collect(),
+            secret_key: Some(secret_key(&id)),
+            ..PbftConfig::default()
+        };
+        let node = PbftNode::with_config(id.clone(), (size as u32 - 1) / 3, config).expect("Failed to create replica");
+        engines.join(&node).await.expect("Failed to join network");
+        let runtime = Runtime::with_parts(id.clone(), Box::new(node), Box::new(hub.join(&id).await));
+        executors.push(Executor::with_runtime(id, runtime, CONSENSUS_TIMEOUT).await.expect("Failed to create executor"));
+    }
+    executors
+}
+
+/// A single started replica whose `main` has not run yet
+async fn started(program: &Program) -> Executor {
+    let mut executor = cluster(&InProcessHub::new(), 1).await.pop().unwrap();
+    executor.start(program).await.expect("Failed to start");
+    executor
+}
+
+/// The first of four replicas, started while the others stay down, so nothing can commit
+async fn started_alone(program: &Program) -> Executor {
+    let mut executor = cluster(&InProcessHub::new(), 4).await.remove(0);
+    executor.start(program).await.expect("Failed to start");
+    executor
+}
+
+/// Run the program to completion the way `omnix run` does, with a fork of the executor
+/// handling messages from peers meanwhile
+async fn execute(mut executor: Executor, program: &Program) -> Executor {
+    let dispatcher = executor.message_dispatcher().await;
+    executor.start(program).await.expect("Failed to start");
+    if let Some(dispatcher) = dispatcher {
+        tokio::spawn(dispatcher.run(executor.fork()));
+    }
+    timeout(Duration::from_secs(10), executor.run_main()).await
+        .expect("Program never finished")
+        .expect("Program failed");
+    executor
+}
+
+/// Run the program on every executor at once
+async fn execute_all(executors: Vec<Executor>, program: &Program) -> Vec<Executor> {
+    let runs: Vec<_> = executors.into_iter()
+        .map(|executor| {
+            let program = program.clone();
+            tokio::spawn(async move { execute(executor, &program).await })
+        })
+        .collect();
+    let mut executors = Vec::new();
+    for run in runs {
+        executors.push(run.await.unwrap());
+    }
+    executors
+}
+
+/// Wait until every executor holds `expected` for the state variable `name`
+async fn wait_for_state(executors: &[Executor], name: &str, expected: &RuntimeValue) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let mut values = Vec::new();
+        for executor in executors {
+            values.push(executor.state().get(name).await);
+        }
+        if values.iter().all(|value| value.as_ref() == Some(expected)) {
+            return;
+        }
+        assert!(Instant::now() < deadline, "{} never converged on {}: {:?}", name, expected, values);
+        sleep(Duration::from_millis(50)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_replicas_apply_the_same_updates() {
+    let program = parse(r#"
+node Counter {
+    @replicated
+    state count: u64 = 0;
+    @replicated
+    state seen: Map<String, u64> = {};
+}
+
+function main() {
+    count += 1;
+    seen[node_id] = 1;
+    seen[node_id] += 1;
+}
+"#);
+    let mut executors = cluster(&InProcessHub::new(), 4).await;
+    let late = executors.pop().unwrap();
+
+    // The fourth replica starts only after the others have written, so it applies every
+    // entry from its backlog and must still end where they did
+    let mut executors = execute_all(executors, &program).await;
+    executors.push(execute(late, &program).await);
+
+    wait_for_state(&executors, "count", &RuntimeValue::Integer(4)).await;
+    let seen = node_ids(4).into_iter()
+        .map(|id| (id, RuntimeValue::Integer(2)))
+        .collect();
+    wait_for_state(&executors, "seen", &RuntimeValue::Map(seen)).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_handlers_run_while_main_is_busy() {
+    let program = parse(r#"
+node Pinger {
+    state pinged: bool = false;
+
+    on Ping(from: String) {
+        pinged = true;
+    }
+}
+
+function main() {
+    if node_id == "node1" {
+        broadcast(Ping(node_id));
+    } else {
+        while !pinged {
+        }
+    }
+}
+"#);
+    // node2's main only returns once its handler has run
+    let executors = execute_all(cluster(&InProcessHub::new(), 2).await, &program).await;
+    wait_for_state(&executors[1..], "pinged", &RuntimeValue::Boolean(true)).await;
+}
+
+#[tokio::test]
+async fn test_broadcast_pushes_state_to_peers() {
+    let program = parse(r#"
+node Syncer {
+    state latest: u64 = 0;
+}
+
+function main() {
+    if node_id == "node1" {
+        latest = 7;
+        latest <~> { };
+    }
+}
+"#);
+    let executors = execute_all(cluster(&InProcessHub::new(), 2).await, &program).await;
+    wait_for_state(&executors, "latest", &RuntimeValue::Integer(7)).await;
+}
+
+#[tokio::test]
+async fn test_sync_leaves_replicated_state_alone() {
+    let program = parse(r#"
+node Syncer {
+    state latest: u64 = 0;
+    @replicated
+    state total: u64 = 0;
+}
+"#);
+    let hub = InProcessHub::new();
+    let executors = execute_all(cluster(&hub, 1).await, &program).await;
+
+    // Syncs are handled in order, so once `latest` changes `total` has been seen too
+    let outsider = hub.join(&"node9".to_string()).await;
+    for name in ["total", "latest"] {
+        outsider.broadcast(Message::StateSync {
+            name: name.to_string(),
+            value: bincode::serialize(&RuntimeValue::Integer(9)).unwrap(),
+        }).await.unwrap();
+    }
+    wait_for_state(&executors, "latest", &RuntimeValue::Integer(9)).await;
+    assert_eq!(executors[0].state().get("total").await, Some(RuntimeValue::Integer(0)));
+}
+
+#[tokio::test]
+async fn test_leaving_a_phase_early_does_not_abort_it() {
+    let program = parse(r#"
+node Worker {
+    state done: bool = false;
+}
+
+function main() {
+    phase handshake(timeout: 3000ms) {
+        if node_id == "node1" {
+            return;
+        }
+    }
+    done = true;
+}
+"#);
+    // node1 abstains, so node2 passes the barrier alone instead of aborting
+    let executors = execute_all(cluster(&InProcessHub::new(), 2).await, &program).await;
+    assert_eq!(executors[0].state().get("done").await, Some(RuntimeValue::Boolean(false)));
+    assert_eq!(executors[1].state().get("done").await, Some(RuntimeValue::Boolean(true)));
+}
+
+#[tokio::test]
+async fn test_signed_and_unsigned_integers_mix() {
+    let program = parse(r#"
+node Checks {
+    state count: u64;
+    state nonempty: bool = false;
+    state elapsed: bool = false;
+}
+
+function main() {
+    count += 1;
+    let items = [1, 2];
+    nonempty = items.len() > 0;
+    let start = now() - 2000;
+    elapsed = now() - start > 1000;
+}
+"#);
+    let mut executor = started(&program).await;
+    executor.run_main().await.expect("Program failed");
+
+    let state = executor.state();
+    assert!(matches!(state.get("count").await, Some(RuntimeValue::UInteger(1))));
+    assert_eq!(state.get("nonempty").await, Some(RuntimeValue::Boolean(true)));
+    assert_eq!(state.get("elapsed").await, Some(RuntimeValue::Boolean(true)));
+}
+
+#[tokio::test]
+async fn test_negative_values_compare_with_unsigned_but_do_not_mix_in_arithmetic() {
+    let program = parse(r#"
+node Checks {
+    state below: bool = false;
+}
+
+function main() {
+    let items = [1];
+    below = -1 < items.len();
+}
+"#);
+    let mut executor = started(&program).await;
+    executor.run_main().await.expect("Program failed");
+    assert_eq!(executor.state().get("below").await, Some(RuntimeValue::Boolean(true)));
+
+    let program = parse(r#"
+function main() {
+    let items = [1];
+    let total = items.len() + -1;
+}
+"#);
+    let error = started(&program).await.run_main().await.unwrap_err();
+    assert!(error.to_string().contains("Negative value -1"), "{}", error);
+}
+
+#[tokio::test]
+async fn test_arithmetic_without_a_result_is_an_error() {
+    let cases = [
+        ("let x = 1 / 0;", "Division by zero"),
+        ("let x = 7 % 0;", "Division by zero"),
+        ("let x = 9223372036854775807 + 1;", "Integer overflow"),
+        ("let x = -9223372036854775807 - 2;", "Integer overflow"),
+        ("let x = now() * now();", "Integer overflow"),
+        ("let x = \"a\".len() - \"ab\".len();", "Integer overflow"),
+    ];
+    for (body, expected) in cases {
+        let program = parse(&format!("function main() {{ {} }}", body));
+        let error = started(&program).await.run_main().await.unwrap_err();
+        assert!(error.to_string().contains(expected), "{}: {}", body, error);
+    }
+}
+
+#[tokio::test]
+async fn test_logical_operators_short_circuit() {
+    let program = parse(r#"
+node Flags {
+    state conjunction: bool = true;
+    state disjunction: bool = false;
+}
+
+function main() {
+    conjunction = false && 1 / 0 == 0;
+    disjunction = true || 1 / 0 == 0;
+}
+"#);
+    let mut executor = started(&program).await;
+    executor.run_main().await.expect("Right operand was evaluated");
+
+    let state = executor.state();
+    assert_eq!(state.get("conjunction").await, Some(RuntimeValue::Boolean(false)));
+    assert_eq!(state.get("disjunction").await, Some(RuntimeValue::Boolean(true)));
+}
+
+#[tokio::test]
+async fn test_calls_bind_arguments_in_their_own_frame() {
+    let program = parse(r#"
+node Math {
+    state result: u64 = 0;
+    state outer: u64 = 0;
+
+    function factorial(n: u64) -> u64 {
+        if n == 0 {
+            return 1;
+        }
+        return n * factorial(n - 1);
+    }
+}
+
+consensus cluster Store {
+    replicas: 1,
+    consensus: PBFT,
+    state stored: u64 = 0;
+
+    service put(value: u64) -> u64 {
+        stored = value;
+        return value;
+    }
+}
+
+function shadow(n: u64) -> u64 {
+    let x = n + 1;
+    return x;
+}
+
+function main() {
+    let x = 5;
+    result = Math.factorial(x) + Store.put(3);
+    outer = x + shadow(x);
+}
+"#);
+    let mut executor = started(&program).await;
+    executor.run_main().await.expect("Program failed");
+
+    let state = executor.state();
+    assert_eq!(state.get("result").await, Some(RuntimeValue::UInteger(123)));
+    assert_eq!(state.get("stored").await, Some(RuntimeValue::UInteger(3)));
+    // `shadow`'s local `x` does not leak into the caller's
+    assert_eq!(state.get("outer").await, Some(RuntimeValue::UInteger(11)));
+}
+
+#[tokio::test]
+async fn test_unbounded_recursion_hits_the_call_depth_limit() {
+    let program = parse(r#"
+function forever(n: u64) -> u64 {
+    return forever(n + 1);
+}
+
+function main() {
+    forever(0);
+}
+"#);
+    let error = started(&program).await.run_main().await.unwrap_err();
+    assert!(error.to_string().contains("Maximum call depth of 24 exceeded calling forever"), "{}", error);
+}
+
+#[tokio::test]
+async fn test_proposal_result_reports_the_commit() {
+    let program = parse(r#"
+node Ledger {
+    state accepted: bool = false;
+    state value: u64 = 0;
+    state index: u64 = 0;
+    state refused: bool = true;
+    state refused_index: u64 = 1;
+    state refusal_explained: bool = false;
+}
+
+function main() {
+    let result = 42 <!> { timeout: 2000ms };
+    accepted = result.accepted();
+    value = result.value;
+    index = result.index;
+
+    // One replica can never hold an entry for nine validators
+    let refusal = 7 <!> { validators: 9, timeout: 500ms };
+    refused = refusal.accepted();
+    refused_index = refusal.index;
+    refusal_explained = refusal.error.is_some();
+}
+"#);
+    let mut executor = started(&program).await;
+    executor.run_main().await.expect("Program failed");
+
+    let state = executor.state();
+    assert_eq!(state.get("accepted").await, Some(RuntimeValue::Boolean(true)));
+    assert_eq!(state.get("value").await, Some(RuntimeValue::Integer(42)));
+    assert!(matches!(state.get("index").await, Some(RuntimeValue::UInteger(index)) if index > 0));
+    assert_eq!(state.get("refused").await, Some(RuntimeValue::Boolean(false)));
+    assert_eq!(state.get("refused_index").await, Some(RuntimeValue::UInteger(0)));
+    assert_eq!(state.get("refusal_explained").await, Some(RuntimeValue::Boolean(true)));
+}
+
+#[tokio::test]
+async fn test_vote_tally_counts_every_ballot() {
+    let program = parse(r#"
+node Voter {
+    state accept: u64 = 0;
+    state reject: u64 = 0;
+    state split_reached: bool = true;
+    state agreed: bool = false;
+}
+
+function main() {
+    if node_id == "node1" {
+        let tally = "split" <?> { vote: Accept, quorum: 2, timeout: 5000ms };
+        accept = tally.accept;
+        reject = tally.reject;
+        split_reached = tally.quorum_reached;
+    } else {
+        let tally = "split" <?> { vote: Reject, quorum: 2, timeout: 5000ms };
+        accept = tally.accept;
+        reject = tally.reject;
+        split_reached = tally.quorum_reached;
+    }
+    let tally = "agreed" <?> { quorum: 2, timeout: 5000ms };
+    agreed = tally.quorum_reached;
+}
+"#);
+    let executors = execute_all(cluster(&InProcessHub::new(), 2).await, &program).await;
+
+    // node1 can still reach the quorum after its own ballot, so its tally waits for node2's
+    let state = executors[0].state();
+    assert_eq!(state.get("accept").await, Some(RuntimeValue::UInteger(1)));
+    assert_eq!(state.get("reject").await, Some(RuntimeValue::UInteger(1)));
+    // node2's rejection decides the split on its own, whether or not node1's ballot is in yet
+    assert_eq!(executors[1].state().get("reject").await, Some(RuntimeValue::UInteger(1)));
+    for executor in &executors {
+        let state = executor.state();
+        assert_eq!(state.get("split_reached").await, Some(RuntimeValue::Boolean(false)));
+        assert_eq!(state.get("agreed").await, Some(RuntimeValue::Boolean(true)));
+    }
+}
+
+#[tokio::test]
+async fn test_read_operators_evaluate_to_their_operand_with_a_quorum() {
+    let program = parse(r#"
+node Reader {
+    state latest: u64 = 0;
+    state gossiped: u64 = 0;
+    state reachable: u64 = 0;
+    state confirmed: u64 = 0;
+}
+
+function main() {
+    gossiped = 4 <@> { consistency: Quorum, timeout: 3000ms };
+    reachable = 5 <|> { fallback: 0, timeout: 3000ms };
+    confirmed = 6 <*> { nodes: 2, timeout: 3000ms };
+    if node_id == "node1" {
+        latest = 3;
+        latest <=> { timeout: 3000ms };
+    }
+}
+"#);
+    let executors = execute_all(cluster(&InProcessHub::new(), 2).await, &program).await;
+
+    for executor in &executors {
+        let state = executor.state();
+        assert_eq!(state.get("gossiped").await, Some(RuntimeValue::Integer(4)));
+        assert_eq!(state.get("reachable").await, Some(RuntimeValue::Integer(5)));
+        assert_eq!(state.get("confirmed").await, Some(RuntimeValue::Integer(6)));
+    }
+    wait_for_state(&executors, "latest", &RuntimeValue::Integer(3)).await;
+}
+
+#[tokio::test]
+async fn test_read_operators_without_a_quorum() {
+    let program = parse(r#"
+node Reader {
+    state reachable: u64 = 1;
+}
+
+function main() {
+    reachable = 5 <|> { fallback: 0, timeout: 300ms };
+}
+"#);
+    let mut executor = started_alone(&program).await;
+    executor.run_main().await.expect("Program failed");
+    assert_eq!(executor.state().get("reachable").await, Some(RuntimeValue::Integer(0)));
+
+    let cases = [
+        ("let x = 4 <@> { consistency: Quorum, timeout: 300ms };", "Consistent read failed"),
+        ("let x = 6 <*> { timeout: 300ms };", "Quorum read failed"),
+        ("reachable <=> { timeout: 300ms };", "State sync of reachable failed"),
+    ];
+    for (body, expected) in cases {
+        let program = parse(&format!("node Reader {{ state reachable: u64 = 1; }} function main() {{ {} }}", body));
+        let error = started_alone(&program).await.run_main().await.unwrap_err();
+        assert!(error.to_string().contains(expected), "{}: {}", body, error);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_no_replica_leaves_a_phase_before_every_participant_finishes_it() {
+    let program = parse(r#"
+node Worker {
+    state finished: u64 = 0;
+    state left: u64 = 0;
+}
+
+function main() {
+    phase prepare(timeout: 5000ms) {
+        if node_id == "node2" {
+            let start = now();
+            while now() - start < 300 {
+            }
+        }
+        finished = now();
+    }
+    left = now();
+}
+"#);
+    let executors = execute_all(cluster(&InProcessHub::new(), 2).await, &program).await;
+
+    let slow_finished = executors[1].state().get("finished").await.unwrap();
+    for executor in &executors {
+        let left = executor.state().get("left").await.unwrap();
+        assert!(matches!((&left, &slow_finished), (RuntimeValue::UInteger(l), RuntimeValue::UInteger(f)) if l >= f),
+            "left at {} before node2 finished at {}", left, slow_finished);
+    }
+}
+
+#[tokio::test]
+async fn test_phase_aborts_when_its_barrier_times_out() {
+    let program = parse(r#"
+node Worker {
+    state aborted: bool = false;
+}
+
+function main() {
+    phase prepare(timeout: 300ms) {
+    } abort {
+        aborted = true;
+    }
+}
+"#);
+    let mut executor = started_alone(&program).await;
+    executor.run_main().await.expect("Program failed");
+    assert_eq!(executor.state().get("aborted").await, Some(RuntimeValue::Boolean(true)));
+    let status = executor.phase_status().read().await.clone().unwrap();
+    assert_eq!(status.state, PhaseState::Aborted);
+    assert!(status.reason.unwrap().contains("timed out"));
+
+    // Without an abort block, the abort is an error
+    let program = parse("function main() { phase prepare(timeout: 300ms) { } }");
+    let error = started_alone(&program).await.run_main().await.unwrap_err();
+    assert!(error.to_string().contains("Phase 'prepare' aborted"), "{}", error);
+}
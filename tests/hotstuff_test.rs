@@ -0,0 +1,247 @@
+/*!
+ * HotStuff tests over the in-process transport
+ * Ordering, view changes and block fetching between real HotStuffNode replicas
+ */
+
+use async_trait::async_trait;
+use omnix_runtime::hotstuff::{HotStuffMessage, HotStuffNode};
+use omnix_runtime::pbft;
+use omnix_runtime::transport::InProcessNetwork;
+use omnix_runtime::{CommittedProposal, ConsensusEngine, HotStuffConfig, HotStuffPeer, ReadConsistency, StateMachine};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::{sleep, Instant};
+
+const VIEW_TIMEOUT_MS: u64 = 200;
+
+fn node_ids(size: usize) -> Vec<String> {
+    (1..=size).map(|i| format!("node{}", i)).collect()
+}
+
+fn secret_key(id: &str) -> [u8; 32] {
+    [id.trim_start_matches("node").parse().unwrap(); 32]
+}
+
+/// Config for `id` among `size` replicas, with keys derived from the node names
+fn test_config(id: &str, size: usize) -> HotStuffConfig {
+    HotStuffConfig {
+        peers: node_ids(size).into_iter()
+            .filter(|peer| peer != id)
+            .map(|peer| {
+                let entry = HotStuffPeer {
+                    addr: None,
+                    public_key: pbft::public_key(&secret_key(&peer)),
+                };
+                (peer, entry)
+            })
+            .collect(),
+        secret_key: Some(secret_key(id)),
+        view_timeout_ms: VIEW_TIMEOUT_MS,
+        ..HotStuffConfig::default()
+    }
+}
+
+/// Four replicas tolerating one fault, each with a recording state machine
+async fn start_cluster_with(config: impl Fn(&str) -> HotStuffConfig) -> (InProcessNetwork<HotStuffMessage>, Vec<HotStuffNode>, Vec<Arc<Recorder>>) {
+    let network = InProcessNetwork::<HotStuffMessage>::new();
+    let mut nodes = Vec::new();
+    let mut recorders = Vec::new();
+    for id in node_ids(4) {
+        let node = HotStuffNode::with_config(id.clone(), 1, config(&id)).expect("Failed to create replica");
+        let recorder = Arc::new(Recorder::default());
+        node.set_state_machine(recorder.clone()).await.unwrap();
+        network.join(&node).await.expect("Failed to join network");
+        nodes.push(node);
+        recorders.push(recorder);
+    }
+    for node in &nodes {
+        node.start_consensus_loop().await.expect("Failed to start consensus loop");
+    }
+    (network, nodes, recorders)
+}
+
+async fn start_cluster() -> (InProcessNetwork<HotStuffMessage>, Vec<HotStuffNode>, Vec<Arc<Recorder>>) {
+    start_cluster_with(|id| test_config(id, 4)).await
+}
+
+/// Records every executed value; snapshots are the recorded list
+#[derive(Default)]
+struct Recorder {
+    values: Mutex<Vec<Vec<u8>>>,
+}
+
+#[async_trait]
+impl StateMachine for Recorder {
+    async fn apply(&self, entry: &CommittedProposal) -> anyhow::Result<()> {
+        if !entry.value.is_empty() {
+            self.values.lock().await.push(entry.value.clone());
+        }
+        Ok(())
+    }
+
+    async fn snapshot(&self) -> anyhow::Result<Vec<u8>> {
+        Ok(serde_json::to_vec(&*self.values.lock().await)?)
+    }
+
+    async fn restore(&self, snapshot: &[u8]) -> anyhow::Result<()> {
+        *self.values.lock().await = serde_json::from_slice(snapshot)?;
+        Ok(())
+    }
+}
+
+/// Propose through `node` and wait until a quorum has certified the value
+async fn commit(node: &HotStuffNode, value: &[u8]) -> CommittedProposal {
+    let proposal = node.propose(value.to_vec()).await.expect("Replica rejected proposal");
+    tokio::time::timeout(Duration::from_secs(10), node.wait_for_commit(&proposal, 3))
+        .await
+        .unwrap_or_else(|_| panic!("{} never committed {:?}", node.node_id, value))
+        .unwrap()
+}
+
+async fn wait_for_values(recorder: &Recorder, count: usize) -> Vec<Vec<u8>> {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let values = recorder.values.lock().await.clone();
+        if values.len() >= count {
+            return values;
+        }
+        assert!(Instant::now() < deadline, "Only {} of {} values executed", values.len(), count);
+        sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_replicas_execute_in_the_same_order() {
+    let (_network, nodes, recorders) = start_cluster().await;
+
+    // Proposals from every replica, not just the current leader
+    let mut expected = Vec::new();
+    for i in 0..10u8 {
+        let value = vec![i];
+        commit(&nodes[i as usize % nodes.len()], &value).await;
+        expected.push(value);
+    }
+
+    for recorder in &recorders {
+        assert_eq!(wait_for_values(recorder, expected.len()).await, expected);
+    }
+}
+
+#[tokio::test]
+async fn test_concurrent_proposals_are_batched_into_one_order() {
+    let (_network, nodes, recorders) = start_cluster().await;
+
+    let proposals: Vec<_> = (0..20u8).map(|i| {
+        let node = nodes[i as usize % nodes.len()].clone();
+        tokio::spawn(async move { commit(&node, &[i]).await })
+    }).collect();
+    for proposal in proposals {
+        proposal.await.unwrap();
+    }
+
+    let order = wait_for_values(&recorders[0], 20).await;
+    for recorder in &recorders[1..] {
+        assert_eq!(wait_for_values(recorder, 20).await, order);
+    }
+}
+
+#[tokio::test]
+async fn test_commits_with_one_replica_down() {
+    let (network, nodes, recorders) = start_cluster().await;
+    network.disconnect(&nodes[3].node_id).await;
+
+    // node4 leads every fourth view, so the pacemaker has to move past it
+    let mut expected = Vec::new();
+    for i in 0..6u8 {
+        let value = vec![i];
+        commit(&nodes[i as usize % 3], &value).await;
+        expected.push(value);
+    }
+
+    for recorder in &recorders[..3] {
+        assert_eq!(wait_for_values(recorder, expected.len()).await, expected);
+    }
+    assert!(recorders[3].values.lock().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_silent_leader_is_replaced_by_the_pacemaker() {
+    let (network, nodes, recorders) = start_cluster().await;
+    commit(&nodes[0], b"before").await;
+
+    // Silence whoever leads the view the replicas are about to enter
+    let view = nodes[0].view().await;
+    let leader = nodes[0].leader(view).clone();
+    network.disconnect(&leader).await;
+    let live: Vec<usize> = (0..nodes.len()).filter(|&i| nodes[i].node_id != leader).collect();
+
+    commit(&nodes[live[0]], b"after").await;
+    for &i in &live {
+        assert_eq!(wait_for_values(&recorders[i], 2).await, vec![b"before".to_vec(), b"after".to_vec()]);
+    }
+    assert!(nodes[live[0]].view().await > view);
+}
+
+#[tokio::test]
+async fn test_lagging_replica_fetches_missing_blocks() {
+    let (network, nodes, recorders) = start_cluster().await;
+    network.disconnect(&nodes[3].node_id).await;
+
+    let mut expected = Vec::new();
+    for i in 0..5u8 {
+        commit(&nodes[i as usize % 3], &[i]).await;
+        expected.push(vec![i]);
+    }
+
+    network.reconnect(&nodes[3].node_id).await;
+    for i in 0..3u8 {
+        let value = vec![100 + i];
+        commit(&nodes[0], &value).await;
+        expected.push(value);
+    }
+
+    assert_eq!(wait_for_values(&recorders[3], expected.len()).await, expected);
+}
+
+#[tokio::test]
+async fn test_replica_with_forged_signatures_is_ignored() {
+    // node1 signs with a key the others do not know, so they drop everything it sends
+    let (_network, nodes, recorders) = start_cluster_with(|id| {
+        let mut config = test_config(id, 4);
+        if id == "node1" {
+            config.secret_key = Some([42; 32]);
+        }
+        config
+    }).await;
+
+    for i in 0..4u8 {
+        commit(&nodes[1], &[i]).await;
+    }
+    for recorder in &recorders[1..] {
+        assert_eq!(wait_for_values(recorder, 4).await, (0..4u8).map(|i| vec![i]).collect::<Vec<_>>());
+    }
+}
+
+#[tokio::test]
+async fn test_linearizable_read_sees_committed_writes() {
+    let (_network, nodes, _recorders) = start_cluster().await;
+    let committed = commit(&nodes[1], b"a").await;
+
+    let index = nodes[2].read_index(ReadConsistency::Linearizable).await.unwrap();
+    assert!(index > committed.index);
+    assert!(nodes[2].last_executed().await >= index);
+}
+
+#[tokio::test]
+async fn test_too_few_replicas_are_rejected() {
+    assert!(HotStuffNode::with_config("node1".to_string(), 1, test_config("node1", 3)).is_err());
+    assert!(HotStuffNode::with_config("node1".to_string(), 1, test_config("node1", 4)).is_ok());
+}
+
+#[tokio::test]
+async fn test_starting_twice_is_an_error() {
+    let (_network, nodes, _recorders) = start_cluster().await;
+    let error = nodes[0].start_consensus_loop().await.unwrap_err();
+    assert!(error.to_string().contains("already started"));
+}
@@ -0,0 +1,228 @@
+/*!
+ * PBFT tests over the in-process transport
+ * Ordering, checkpoints and view changes between real PbftNode replicas
+ */
+
+use async_trait::async_trait;
+use omnix_runtime::pbft::{self, PbftMessage, PbftNode};
+use omnix_runtime::transport::InProcessNetwork;
+use omnix_runtime::{CommittedProposal, ConsensusEngine, PbftConfig, PbftPeer, ReadConsistency, StateMachine};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::{sleep, Instant};
+
+const CHECKPOINT_INTERVAL: u64 = 4;
+
+fn node_ids(size: usize) -> Vec<String> {
+    (1..=size).map(|i| format!("node{}", i)).collect()
+}
+
+fn secret_key(id: &str) -> [u8; 32] {
+    [id.trim_start_matches("node").parse().unwrap(); 32]
+}
+
+/// Config for `id` in a cluster of `size` replicas, with keys derived from the node names
+fn test_config(id: &str, size: usize) -> PbftConfig {
+    PbftConfig {
+        peers: node_ids(size).into_iter()
+            .filter(|peer| peer != id)
+            .map(|peer| {
+                let public_key = pbft::public_key(&secret_key(&peer));
+                (peer, PbftPeer { addr: None, public_key })
+            })
+            .collect(),
+        secret_key: Some(secret_key(id)),
+        checkpoint_interval: CHECKPOINT_INTERVAL,
+        view_change_timeout_ms: 300,
+        ..PbftConfig::default()
+    }
+}
+
+/// Four replicas tolerating one faulty one, each with a recording state machine
+async fn start_cluster_with(config: impl Fn(&str) -> PbftConfig) -> (InProcessNetwork<PbftMessage>, Vec<PbftNode>, Vec<Arc<Recorder>>) {
+    let network = InProcessNetwork::<PbftMessage>::new();
+    let mut nodes = Vec::new();
+    let mut recorders = Vec::new();
+    for id in node_ids(4) {
+        let node = PbftNode::with_config(id.clone(), 1, config(&id)).expect("Failed to create replica");
+        let recorder = Arc::new(Recorder::default());
+        node.set_state_machine(recorder.clone()).await.unwrap();
+        network.join(&node).await.expect("Failed to join network");
+        nodes.push(node);
+        recorders.push(recorder);
+    }
+    for node in &nodes {
+        node.start_consensus_loop().await.expect("Failed to start consensus loop");
+    }
+    (network, nodes, recorders)
+}
+
+async fn start_cluster() -> (InProcessNetwork<PbftMessage>, Vec<PbftNode>, Vec<Arc<Recorder>>) {
+    start_cluster_with(|id| test_config(id, 4)).await
+}
+
+/// Records every executed value; snapshots are the recorded list
+#[derive(Default)]
+struct Recorder {
+    values: Mutex<Vec<Vec<u8>>>,
+}
+
+#[async_trait]
+impl StateMachine for Recorder {
+    async fn apply(&self, entry: &CommittedProposal) -> anyhow::Result<()> {
+        if !entry.value.is_empty() {
+            self.values.lock().await.push(entry.value.clone());
+        }
+        Ok(())
+    }
+
+    async fn snapshot(&self) -> anyhow::Result<Vec<u8>> {
+        Ok(serde_json::to_vec(&*self.values.lock().await)?)
+    }
+
+    async fn restore(&self, snapshot: &[u8]) -> anyhow::Result<()> {
+        *self.values.lock().await = serde_json::from_slice(snapshot)?;
+        Ok(())
+    }
+}
+
+/// Propose through `node` and wait until a quorum has committed the value
+async fn commit(node: &PbftNode, value: &[u8]) -> CommittedProposal {
+    let proposal = node.propose(value.to_vec()).await.expect("Replica rejected proposal");
+    tokio::time::timeout(Duration::from_secs(10), node.wait_for_commit(&proposal, 3))
+        .await
+        .unwrap_or_else(|_| panic!("{} never committed {:?}", node.node_id, value))
+        .unwrap()
+}
+
+async fn wait_for_values(recorder: &Recorder, count: usize) -> Vec<Vec<u8>> {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let values = recorder.values.lock().await.clone();
+        if values.len() >= count {
+            return values;
+        }
+        assert!(Instant::now() < deadline, "Only {} of {} values executed", values.len(), count);
+        sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_replicas_execute_in_the_same_order() {
+    let (_network, nodes, recorders) = start_cluster().await;
+
+    // Proposals from every replica, not just the primary
+    let mut expected = Vec::new();
+    for i in 0..10u8 {
+        let value = vec![i];
+        commit(&nodes[i as usize % nodes.len()], &value).await;
+        expected.push(value);
+    }
+
+    for recorder in &recorders {
+        assert_eq!(wait_for_values(recorder, expected.len()).await, expected);
+    }
+    assert!(nodes[0].stable_checkpoint().await >= CHECKPOINT_INTERVAL);
+}
+
+#[tokio::test]
+async fn test_commits_with_one_backup_down() {
+    let (network, nodes, recorders) = start_cluster().await;
+    network.disconnect(&nodes[3].node_id).await;
+
+    let first = commit(&nodes[1], b"a").await;
+    let second = commit(&nodes[2], b"b").await;
+    assert!(second.index > first.index);
+
+    for recorder in &recorders[..3] {
+        assert_eq!(wait_for_values(recorder, 2).await, vec![b"a".to_vec(), b"b".to_vec()]);
+    }
+    assert!(recorders[3].values.lock().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_view_change_replaces_failed_primary() {
+    let (network, nodes, recorders) = start_cluster().await;
+    commit(&nodes[1], b"before").await;
+
+    // node1 is the primary of view 0
+    network.disconnect(&nodes[0].node_id).await;
+    let committed = commit(&nodes[2], b"after").await;
+    assert!(committed.term >= 1);
+
+    for (node, recorder) in nodes[1..].iter().zip(&recorders[1..]) {
+        assert!(node.view().await >= 1);
+        assert_eq!(wait_for_values(recorder, 2).await, vec![b"before".to_vec(), b"after".to_vec()]);
+    }
+}
+
+#[tokio::test]
+async fn test_lagging_replica_catches_up_from_checkpoint() {
+    let (network, nodes, recorders) = start_cluster().await;
+    network.disconnect(&nodes[3].node_id).await;
+
+    let mut expected = Vec::new();
+    for i in 0..(2 * CHECKPOINT_INTERVAL as u8) {
+        commit(&nodes[i as usize % 3], &[i]).await;
+        expected.push(vec![i]);
+    }
+
+    // The missed requests are only recoverable through a checkpoint's state
+    network.reconnect(&nodes[3].node_id).await;
+    for i in 0..CHECKPOINT_INTERVAL as u8 {
+        let value = vec![100 + i];
+        commit(&nodes[0], &value).await;
+        expected.push(value);
+    }
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while nodes[3].last_executed().await < nodes[0].stable_checkpoint().await {
+        assert!(Instant::now() < deadline, "Lagging replica never caught up");
+        sleep(Duration::from_millis(20)).await;
+    }
+    let caught_up = recorders[3].values.lock().await.clone();
+    assert_eq!(caught_up[..], expected[..caught_up.len()]);
+    assert!(caught_up.len() >= 2 * CHECKPOINT_INTERVAL as usize);
+}
+
+#[tokio::test]
+async fn test_replicas_replace_primary_with_forged_signatures() {
+    // node1 signs with a key the others do not know, so they drop everything it sends
+    let (_network, nodes, recorders) = start_cluster_with(|id| {
+        let mut config = test_config(id, 4);
+        if id == "node1" {
+            config.secret_key = Some([42; 32]);
+        }
+        config
+    }).await;
+
+    commit(&nodes[1], b"a").await;
+    assert!(nodes[1].view().await >= 1);
+    for recorder in &recorders[1..] {
+        assert_eq!(wait_for_values(recorder, 1).await, vec![b"a".to_vec()]);
+    }
+}
+
+#[tokio::test]
+async fn test_linearizable_read_sees_committed_writes() {
+    let (_network, nodes, _recorders) = start_cluster().await;
+    let committed = commit(&nodes[1], b"a").await;
+
+    let index = nodes[2].read_index(ReadConsistency::Linearizable).await.unwrap();
+    assert!(index > committed.index);
+    assert!(nodes[2].last_executed().await >= index);
+}
+
+#[tokio::test]
+async fn test_rejects_too_few_replicas() {
+    let config = test_config("node1", 3);
+    assert!(PbftNode::with_config("node1".to_string(), 1, config).is_err());
+}
+
+#[tokio::test]
+async fn test_starting_twice_is_an_error() {
+    let (_network, nodes, _recorders) = start_cluster().await;
+    let error = nodes[0].start_consensus_loop().await.unwrap_err();
+    assert!(error.to_string().contains("already started"));
+}
@@ -0,0 +1,419 @@
+/*!
+ * Raft tests over the in-process transport
+ * Elections, replication and leader changes between real RaftNode peers
+ */
+
+use async_trait::async_trait;
+use omnix_runtime::raft::{NodeState, RaftMessage, RaftNode};
+use omnix_runtime::transport::InProcessNetwork;
+use omnix_runtime::{CommittedProposal, ConsensusEngine, RaftConfig, ReadConsistency, StateMachine};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::{sleep, Instant};
+
+fn node_ids(size: usize) -> Vec<String> {
+    (1..=size).map(|i| format!("node{}", i)).collect()
+}
+
+async fn start_cluster(size: usize) -> (InProcessNetwork<RaftMessage>, Vec<RaftNode>) {
+    start_cluster_with(size, |_| RaftConfig::default()).await
+}
+
+async fn start_cluster_with(size: usize, config: impl Fn(&str) -> RaftConfig) -> (InProcessNetwork<RaftMessage>, Vec<RaftNode>) {
+    let network = InProcessNetwork::new();
+    let mut nodes = Vec::new();
+    for id in node_ids(size) {
+        let node = RaftNode::with_config(id.clone(), config(&id)).expect("Failed to create node");
+        nodes.push(join_cluster(&network, node, size).await);
+    }
+    for node in &nodes {
+        node.start_consensus_loop().await;
+    }
+    (network, nodes)
+}
+
+/// Connect a node to the other members of a cluster of `size` nodes
+async fn join_cluster(network: &InProcessNetwork<RaftMessage>, node: RaftNode, size: usize) -> RaftNode {
+    for peer in node_ids(size).into_iter().filter(|peer| *peer != node.node_id) {
+        node.add_peer(peer).await;
+    }
+    network.join(&node).await.expect("Failed to join network");
+    node
+}
+
+/// Values of the entries still in a node's log
+async fn log_values(node: &RaftNode) -> Vec<Vec<u8>> {
+    node.log.read().await.entries.iter().map(|e| e.value.clone()).collect()
+}
+
+/// Records every applied value; snapshots are the recorded list
+#[derive(Default)]
+struct Recorder {
+    values: Mutex<Vec<Vec<u8>>>,
+}
+
+#[async_trait]
+impl StateMachine for Recorder {
+    async fn apply(&self, entry: &CommittedProposal) -> anyhow::Result<()> {
+        self.values.lock().await.push(entry.value.clone());
+        Ok(())
+    }
+
+    async fn snapshot(&self) -> anyhow::Result<Vec<u8>> {
+        Ok(serde_json::to_vec(&*self.values.lock().await)?)
+    }
+
+    async fn restore(&self, snapshot: &[u8]) -> anyhow::Result<()> {
+        *self.values.lock().await = serde_json::from_slice(snapshot)?;
+        Ok(())
+    }
+}
+
+/// Index of the only leader among `nodes`, once exactly one has been elected
+async fn wait_for_leader(nodes: &[&RaftNode]) -> usize {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while Instant::now() < deadline {
+        let mut leaders = Vec::new();
+        for (i, node) in nodes.iter().enumerate() {
+            if *node.state.read().await == NodeState::Leader {
+                leaders.push(i);
+            }
+        }
+        if leaders.len() == 1 {
+            return leaders[0];
+        }
+        sleep(Duration::from_millis(20)).await;
+    }
+    panic!("No single leader was elected");
+}
+
+async fn wait_for_commit_index(node: &RaftNode, index: u64) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while *node.commit_index.read().await < index {
+        assert!(Instant::now() < deadline, "{} never committed index {}", node.node_id, index);
+        sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_three_nodes_elect_one_leader() {
+    let (_network, nodes) = start_cluster(3).await;
+    let leader = wait_for_leader(&nodes.iter().collect::<Vec<_>>()).await;
+
+    let term = *nodes[leader].current_term.read().await;
+    for node in &nodes {
+        assert_eq!(*node.current_term.read().await, term);
+    }
+}
+
+#[tokio::test]
+async fn test_leader_replicates_to_followers() {
+    let (_network, nodes) = start_cluster(3).await;
+    let leader = &nodes[wait_for_leader(&nodes.iter().collect::<Vec<_>>()).await];
+
+    let mut last = None;
+    for value in [b"a".to_vec(), b"b".to_vec(), b"c".to_vec()] {
+        last = Some(leader.propose(value).await.expect("Leader rejected proposal"));
+    }
+    let committed = tokio::time::timeout(Duration::from_secs(5), leader.wait_for_commit(&last.unwrap(), 3))
+        .await
+        .expect("Proposal never reached every node")
+        .expect("Proposal failed");
+    assert_eq!(committed.index, 3);
+
+    for node in &nodes {
+        wait_for_commit_index(node, 3).await;
+        assert_eq!(log_values(node).await, vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]);
+    }
+}
+
+#[tokio::test]
+async fn test_partitioned_leader_steps_down_and_catches_up() {
+    let (network, nodes) = start_cluster(3).await;
+    let old = wait_for_leader(&nodes.iter().collect::<Vec<_>>()).await;
+    let old_term = *nodes[old].current_term.read().await;
+
+    // The isolated leader keeps an entry that can never commit
+    network.disconnect(&nodes[old].node_id).await;
+    nodes[old].propose(b"lost".to_vec()).await.expect("Leader rejected proposal");
+
+    let rest: Vec<&RaftNode> = nodes.iter().enumerate().filter(|(i, _)| *i != old).map(|(_, n)| n).collect();
+    let new_leader = rest[wait_for_leader(&rest).await];
+    assert!(*new_leader.current_term.read().await > old_term);
+
+    let id = new_leader.propose(b"kept".to_vec()).await.expect("New leader rejected proposal");
+    tokio::time::timeout(Duration::from_secs(5), new_leader.wait_for_commit(&id, 2))
+        .await
+        .expect("Majority never committed")
+        .expect("Proposal failed");
+
+    // Once reconnected, the old leader adopts the new term and the new leader's log
+    network.reconnect(&nodes[old].node_id).await;
+    wait_for_commit_index(&nodes[old], 1).await;
+    assert_eq!(*nodes[old].state.read().await, NodeState::Follower);
+    assert_eq!(nodes[old].log.read().await.get(1).unwrap().value, b"kept".to_vec());
+}
+
+#[tokio::test]
+async fn test_restarted_node_recovers_term_vote_and_log() {
+    let dir: PathBuf = std::env::temp_dir().join(format!("omnix-raft-{}", omnix_runtime::uuid::Uuid::new_v4()));
+    let config = |id: &str| RaftConfig {
+        data_dir: Some(dir.join(id)),
+        ..RaftConfig::default()
+    };
+    let (network, mut nodes) = start_cluster_with(3, config).await;
+    let leader = wait_for_leader(&nodes.iter().collect::<Vec<_>>()).await;
+
+    let id = nodes[leader].propose(b"a".to_vec()).await.expect("Leader rejected proposal");
+    tokio::time::timeout(Duration::from_secs(5), nodes[leader].wait_for_commit(&id, 3))
+        .await
+        .expect("Proposal never reached every node")
+        .expect("Proposal failed");
+
+    // Kill the leader mid-term and note what it had acknowledged
+    let killed = nodes.remove(leader);
+    killed.stop().await;
+    let term = *killed.current_term.read().await;
+    let voted_for = killed.voted_for.read().await.clone();
+    let log = log_values(&killed).await;
+    let killed_id = killed.node_id.clone();
+    drop(killed);
+
+    // The survivors carry on without it
+    let survivors: Vec<&RaftNode> = nodes.iter().collect();
+    let new_leader = survivors[wait_for_leader(&survivors).await];
+    let id = new_leader.propose(b"b".to_vec()).await.expect("New leader rejected proposal");
+    tokio::time::timeout(Duration::from_secs(5), new_leader.wait_for_commit(&id, 2))
+        .await
+        .expect("Majority never committed")
+        .expect("Proposal failed");
+
+    // A fresh node on the same storage remembers its term, vote and log
+    let restarted = RaftNode::with_config(killed_id.clone(), config(&killed_id)).expect("Failed to reopen node");
+    assert_eq!(*restarted.current_term.read().await, term);
+    assert_eq!(*restarted.voted_for.read().await, voted_for);
+    assert_eq!(log_values(&restarted).await, log);
+
+    let restarted = join_cluster(&network, restarted, 3).await;
+    restarted.start_consensus_loop().await;
+    wait_for_commit_index(&restarted, 2).await;
+    assert_eq!(log_values(&restarted).await, vec![b"a".to_vec(), b"b".to_vec()]);
+
+    restarted.stop().await;
+    for node in &nodes {
+        node.stop().await;
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn test_lagging_follower_catches_up_from_snapshot() {
+    let config = |_: &str| RaftConfig {
+        snapshot_threshold: 4,
+        snapshot_retain: 1,
+        ..RaftConfig::default()
+    };
+    let (network, nodes) = start_cluster_with(3, config).await;
+    let mut machines = Vec::new();
+    for node in &nodes {
+        let machine = Arc::new(Recorder::default());
+        node.set_state_machine(machine.clone()).await.expect("Failed to set state machine");
+        machines.push(machine);
+    }
+    let leader = wait_for_leader(&nodes.iter().collect::<Vec<_>>()).await;
+    let lagging = (leader + 1) % nodes.len();
+
+    // The leader compacts entries the disconnected follower never received
+    network.disconnect(&nodes[lagging].node_id).await;
+    let values: Vec<Vec<u8>> = (0..10u8).map(|i| vec![i]).collect();
+    let mut last = None;
+    for value in &values {
+        last = Some(nodes[leader].propose(value.clone()).await.expect("Leader rejected proposal"));
+    }
+    tokio::time::timeout(Duration::from_secs(5), nodes[leader].wait_for_commit(&last.unwrap(), 2))
+        .await
+        .expect("Majority never committed")
+        .expect("Proposal failed");
+    assert!(nodes[leader].log.read().await.compacted_index > 0);
+
+    network.reconnect(&nodes[lagging].node_id).await;
+    wait_for_commit_index(&nodes[lagging], values.len() as u64).await;
+    assert!(nodes[lagging].snapshot.read().await.is_some());
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while *machines[lagging].values.lock().await != values {
+        assert!(Instant::now() < deadline, "Follower state never caught up");
+        sleep(Duration::from_millis(20)).await;
+    }
+}
+
+/// A node started to join `network`, which only takes part once a leader adds it
+async fn start_joining_node(network: &InProcessNetwork<RaftMessage>, id: &str) -> RaftNode {
+    let config = RaftConfig {
+        join_existing: true,
+        ..RaftConfig::default()
+    };
+    let node = RaftNode::with_config(id.to_string(), config).expect("Failed to create node");
+    network.join(&node).await.expect("Failed to join network");
+    node.start_consensus_loop().await;
+    node
+}
+
+#[tokio::test]
+async fn test_learner_receives_log_without_voting() {
+    let (network, nodes) = start_cluster(3).await;
+    let leader = &nodes[wait_for_leader(&nodes.iter().collect::<Vec<_>>()).await];
+    let id = leader.propose(b"a".to_vec()).await.expect("Leader rejected proposal");
+    
+    let learner = start_joining_node(&network, "learner").await;
+    tokio::time::timeout(Duration::from_secs(5), leader.add_learner(learner.node_id.clone(), None))
+        .await
+        .expect("Learner was never added")
+        .expect("Adding learner failed");
+    
+    tokio::time::timeout(Duration::from_secs(5), leader.wait_for_commit(&id, 3))
+        .await
+        .expect("Proposal never reached every voter")
+        .expect("Proposal failed");
+    wait_for_commit_index(&learner, 1).await;
+    assert_eq!(log_values(&learner).await[0], b"a".to_vec());
+    
+    let membership = learner.membership().await.unwrap();
+    assert!(membership.learners.contains(&learner.node_id));
+    assert_eq!(membership.voter_count(), 3);
+    assert_eq!(*learner.state.read().await, NodeState::Follower);
+}
+
+#[tokio::test]
+async fn test_voters_are_added_and_removed_through_the_log() {
+    let (network, nodes) = start_cluster(3).await;
+    let leader = wait_for_leader(&nodes.iter().collect::<Vec<_>>()).await;
+    let id = nodes[leader].propose(b"a".to_vec()).await.expect("Leader rejected proposal");
+    
+    // The new node catches up as a learner, then joins every quorum
+    let added = start_joining_node(&network, "node4").await;
+    tokio::time::timeout(Duration::from_secs(5), nodes[leader].add_voter(added.node_id.clone(), None))
+        .await
+        .expect("Voter was never added")
+        .expect("Adding voter failed");
+    tokio::time::timeout(Duration::from_secs(5), nodes[leader].wait_for_commit(&id, 4))
+        .await
+        .expect("New voter never stored the entry")
+        .expect("Proposal failed");
+    for node in nodes.iter().chain([&added]) {
+        wait_for_commit_index(node, *nodes[leader].commit_index.read().await).await;
+        let membership = node.membership().await.unwrap();
+        assert_eq!(membership.voters.len(), 4);
+        assert!(!membership.is_joint());
+    }
+    
+    // Removing the leader hands the cluster to the remaining voters
+    let removed = nodes[leader].node_id.clone();
+    tokio::time::timeout(Duration::from_secs(5), nodes[leader].remove_server(removed.clone()))
+        .await
+        .expect("Leader was never removed")
+        .expect("Removing leader failed");
+    assert_eq!(*nodes[leader].state.read().await, NodeState::Follower);
+    
+    let rest: Vec<&RaftNode> = nodes.iter().chain([&added]).filter(|n| n.node_id != removed).collect();
+    let new_leader = rest[wait_for_leader(&rest).await];
+    assert!(!new_leader.membership().await.unwrap().is_voter(&removed));
+    let id = new_leader.propose(b"b".to_vec()).await.expect("New leader rejected proposal");
+    tokio::time::timeout(Duration::from_secs(5), new_leader.wait_for_commit(&id, 3))
+        .await
+        .expect("Remaining voters never committed")
+        .expect("Proposal failed");
+}
+
+#[tokio::test]
+async fn test_rejoining_node_does_not_disrupt_leader() {
+    let (network, nodes) = start_cluster(3).await;
+    let leader = wait_for_leader(&nodes.iter().collect::<Vec<_>>()).await;
+    let term = *nodes[leader].current_term.read().await;
+    
+    // Without pre-vote the isolated node would come back several terms ahead
+    let isolated = (leader + 1) % nodes.len();
+    network.disconnect(&nodes[isolated].node_id).await;
+    sleep(Duration::from_secs(1)).await;
+    assert_eq!(*nodes[isolated].current_term.read().await, term);
+    
+    network.reconnect(&nodes[isolated].node_id).await;
+    sleep(Duration::from_millis(500)).await;
+    assert_eq!(*nodes[leader].state.read().await, NodeState::Leader);
+    assert_eq!(*nodes[leader].current_term.read().await, term);
+}
+
+#[tokio::test]
+async fn test_leader_without_quorum_steps_down() {
+    let (network, nodes) = start_cluster(3).await;
+    let leader = wait_for_leader(&nodes.iter().collect::<Vec<_>>()).await;
+    
+    network.disconnect(&nodes[leader].node_id).await;
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while *nodes[leader].state.read().await == NodeState::Leader {
+        assert!(Instant::now() < deadline, "Isolated leader never stepped down");
+        sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_leadership_transfer() {
+    let (_network, nodes) = start_cluster(3).await;
+    let leader = wait_for_leader(&nodes.iter().collect::<Vec<_>>()).await;
+    let target = (leader + 1) % nodes.len();
+    
+    nodes[leader].transfer_leadership(nodes[target].node_id.clone()).await.expect("Transfer failed");
+    assert_eq!(wait_for_leader(&nodes.iter().collect::<Vec<_>>()).await, target);
+    let id = nodes[target].propose(b"a".to_vec()).await.expect("New leader rejected proposal");
+    tokio::time::timeout(Duration::from_secs(5), nodes[target].wait_for_commit(&id, 3))
+        .await
+        .expect("Proposal never reached every node")
+        .expect("Proposal failed");
+}
+
+#[tokio::test]
+async fn test_linearizable_read_on_follower_sees_committed_write() {
+    let (_network, nodes) = start_cluster(3).await;
+    let mut machines = Vec::new();
+    for node in &nodes {
+        let machine = Arc::new(Recorder::default());
+        node.set_state_machine(machine.clone()).await.expect("Failed to set state machine");
+        machines.push(machine);
+    }
+    let leader = wait_for_leader(&nodes.iter().collect::<Vec<_>>()).await;
+    
+    let id = nodes[leader].propose(b"a".to_vec()).await.expect("Leader rejected proposal");
+    let committed = tokio::time::timeout(Duration::from_secs(5), nodes[leader].wait_for_commit(&id, 1))
+        .await
+        .expect("Proposal never committed")
+        .expect("Proposal failed");
+    
+    // Each read waits until its node has applied everything committed before it began
+    for (node, machine) in nodes.iter().zip(&machines) {
+        for consistency in [ReadConsistency::Linearizable, ReadConsistency::Lease] {
+            let read_index = tokio::time::timeout(Duration::from_secs(5), node.read_index(consistency))
+                .await
+                .expect("Read never completed")
+                .expect("Read failed");
+            assert!(read_index >= committed.index);
+            assert!(machine.values.lock().await.contains(&b"a".to_vec()));
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_partitioned_leader_refuses_linearizable_reads() {
+    let (network, nodes) = start_cluster(3).await;
+    let leader = wait_for_leader(&nodes.iter().collect::<Vec<_>>()).await;
+    nodes[leader].read_index(ReadConsistency::Linearizable).await.expect("Connected leader refused read");
+    
+    // The majority may already have a new leader, so the old one must not answer alone
+    network.disconnect(&nodes[leader].node_id).await;
+    let read = tokio::time::timeout(Duration::from_secs(2), nodes[leader].read_index(ReadConsistency::Linearizable)).await;
+    assert!(!matches!(read, Ok(Ok(_))), "Partitioned leader served a linearizable read");
+    
+    // A stale read never leaves the node
+    nodes[leader].read_index(ReadConsistency::Stale).await.expect("Stale read failed");
+}
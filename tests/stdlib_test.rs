@@ -0,0 +1,53 @@
+/*!
+ * Standard library tests
+ * Native functions and methods, and the registry against what the compiler assumes about it
+ */
+
+use omnix_compiler::resolver::BUILTIN_FUNCTIONS;
+use omnix_runtime::runtime::stdlib::NativeRegistry;
+use omnix_runtime::runtime::RuntimeValue;
+use std::collections::BTreeSet;
+
+#[test]
+fn test_resolver_builtins_match_the_stdlib() {
+    let registry = NativeRegistry::with_stdlib();
+    // Capitalized constructors such as `Map::new` never need declaring, so the resolver skips them
+    let natives: BTreeSet<&str> = registry.function_names()
+        .filter(|name| !name.starts_with(|c: char| c.is_ascii_uppercase()))
+        .collect();
+    let builtins: BTreeSet<&str> = BUILTIN_FUNCTIONS.iter().copied().collect();
+    assert_eq!(natives, builtins);
+}
+
+fn call_method(registry: &NativeRegistry, receiver: &mut RuntimeValue, name: &str, args: &[RuntimeValue]) -> RuntimeValue {
+    registry.method(name).unwrap_or_else(|| panic!("No method {}", name))
+        .call(receiver, args)
+        .unwrap_or_else(|e| panic!("{}() failed: {}", name, e))
+}
+
+#[test]
+fn test_set_keeps_one_copy_of_each_value() {
+    let registry = NativeRegistry::with_stdlib();
+    let mut set = registry.function("Set::new").unwrap().call(&[]).unwrap();
+
+    for value in [RuntimeValue::String("a".to_string()), RuntimeValue::String("b".to_string()), RuntimeValue::String("a".to_string())] {
+        call_method(&registry, &mut set, "insert", &[value]);
+    }
+    assert!(matches!(call_method(&registry, &mut set, "len", &[]), RuntimeValue::UInteger(2)));
+
+    assert!(matches!(call_method(&registry, &mut set, "remove", &[RuntimeValue::String("a".to_string())]), RuntimeValue::Boolean(true)));
+    assert!(matches!(call_method(&registry, &mut set, "contains", &[RuntimeValue::String("a".to_string())]), RuntimeValue::Boolean(false)));
+    assert!(matches!(call_method(&registry, &mut set, "len", &[]), RuntimeValue::UInteger(1)));
+}
+
+#[test]
+fn test_contains_compares_values_not_their_text() {
+    let registry = NativeRegistry::with_stdlib();
+    let mut list = RuntimeValue::List(vec![RuntimeValue::Integer(1), RuntimeValue::List(vec![RuntimeValue::Boolean(true)])]);
+
+    assert!(matches!(call_method(&registry, &mut list, "contains", &[RuntimeValue::String("1".to_string())]), RuntimeValue::Boolean(false)));
+    assert!(matches!(call_method(&registry, &mut list, "contains", &[RuntimeValue::Integer(1)]), RuntimeValue::Boolean(true)));
+    // Signed and unsigned integers holding the same number are equal
+    assert!(matches!(call_method(&registry, &mut list, "contains", &[RuntimeValue::UInteger(1)]), RuntimeValue::Boolean(true)));
+    assert!(matches!(call_method(&registry, &mut list, "contains", &[RuntimeValue::List(vec![RuntimeValue::Boolean(true)])]), RuntimeValue::Boolean(true)));
+}
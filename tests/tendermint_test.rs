@@ -0,0 +1,254 @@
+/*!
+ * Tendermint tests over the in-process transport
+ * Ordering, round changes and proposer rotation between real TendermintNode validators
+ */
+
+use async_trait::async_trait;
+use omnix_runtime::pbft;
+use omnix_runtime::transport::InProcessNetwork;
+use omnix_runtime::tendermint::{TendermintMessage, TendermintNode};
+use omnix_runtime::{CommittedProposal, ConsensusEngine, ReadConsistency, StateMachine, TendermintConfig, TendermintPeer};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::{sleep, Instant};
+
+const TIMEOUT_MS: u64 = 200;
+
+fn node_ids(size: usize) -> Vec<String> {
+    (1..=size).map(|i| format!("node{}", i)).collect()
+}
+
+fn secret_key(id: &str) -> [u8; 32] {
+    [id.trim_start_matches("node").parse().unwrap(); 32]
+}
+
+/// Config for `id` among `powers.len()` validators, with keys derived from the node names
+fn test_config(id: &str, powers: &[u64]) -> TendermintConfig {
+    let power = |validator: &str| powers[validator.trim_start_matches("node").parse::<usize>().unwrap() - 1];
+    TendermintConfig {
+        peers: node_ids(powers.len()).into_iter()
+            .filter(|peer| peer != id)
+            .map(|peer| {
+                let entry = TendermintPeer {
+                    addr: None,
+                    public_key: pbft::public_key(&secret_key(&peer)),
+                    power: power(&peer),
+                };
+                (peer, entry)
+            })
+            .collect(),
+        secret_key: Some(secret_key(id)),
+        power: power(id),
+        ..TendermintConfig::default()
+    }
+}
+
+/// Four equal validators, each with a recording state machine
+async fn start_cluster_with(config: impl Fn(&str) -> TendermintConfig) -> (InProcessNetwork<TendermintMessage>, Vec<TendermintNode>, Vec<Arc<Recorder>>) {
+    let network = InProcessNetwork::<TendermintMessage>::new();
+    let mut nodes = Vec::new();
+    let mut recorders = Vec::new();
+    for id in node_ids(4) {
+        let node = TendermintNode::with_config(id.clone(), TIMEOUT_MS, config(&id)).expect("Failed to create validator");
+        let recorder = Arc::new(Recorder::default());
+        node.set_state_machine(recorder.clone()).await.unwrap();
+        network.join(&node).await.expect("Failed to join network");
+        nodes.push(node);
+        recorders.push(recorder);
+    }
+    for node in &nodes {
+        node.start_consensus_loop().await.expect("Failed to start consensus loop");
+    }
+    (network, nodes, recorders)
+}
+
+async fn start_cluster() -> (InProcessNetwork<TendermintMessage>, Vec<TendermintNode>, Vec<Arc<Recorder>>) {
+    start_cluster_with(|id| test_config(id, &[1, 1, 1, 1])).await
+}
+
+/// Records every executed value; snapshots are the recorded list
+#[derive(Default)]
+struct Recorder {
+    values: Mutex<Vec<Vec<u8>>>,
+}
+
+#[async_trait]
+impl StateMachine for Recorder {
+    async fn apply(&self, entry: &CommittedProposal) -> anyhow::Result<()> {
+        if !entry.value.is_empty() {
+            self.values.lock().await.push(entry.value.clone());
+        }
+        Ok(())
+    }
+
+    async fn snapshot(&self) -> anyhow::Result<Vec<u8>> {
+        Ok(serde_json::to_vec(&*self.values.lock().await)?)
+    }
+
+    async fn restore(&self, snapshot: &[u8]) -> anyhow::Result<()> {
+        *self.values.lock().await = serde_json::from_slice(snapshot)?;
+        Ok(())
+    }
+}
+
+/// Propose through `node` and wait until a quorum has decided the value
+async fn commit(node: &TendermintNode, value: &[u8]) -> CommittedProposal {
+    let proposal = node.propose(value.to_vec()).await.expect("Validator rejected proposal");
+    tokio::time::timeout(Duration::from_secs(10), node.wait_for_commit(&proposal, 3))
+        .await
+        .unwrap_or_else(|_| panic!("{} never committed {:?}", node.node_id, value))
+        .unwrap()
+}
+
+async fn wait_for_values(recorder: &Recorder, count: usize) -> Vec<Vec<u8>> {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let values = recorder.values.lock().await.clone();
+        if values.len() >= count {
+            return values;
+        }
+        assert!(Instant::now() < deadline, "Only {} of {} values executed", values.len(), count);
+        sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_validators_execute_in_the_same_order() {
+    let (_network, nodes, recorders) = start_cluster().await;
+
+    // Proposals from every validator, not just the proposer
+    let mut expected = Vec::new();
+    for i in 0..10u8 {
+        let value = vec![i];
+        commit(&nodes[i as usize % nodes.len()], &value).await;
+        expected.push(value);
+    }
+
+    for recorder in &recorders {
+        assert_eq!(wait_for_values(recorder, expected.len()).await, expected);
+    }
+}
+
+#[tokio::test]
+async fn test_commits_with_one_validator_down() {
+    let (network, nodes, recorders) = start_cluster().await;
+    network.disconnect(&nodes[3].node_id).await;
+
+    // node4 proposes some of these heights, so rounds have to move past it
+    let mut expected = Vec::new();
+    for i in 0..6u8 {
+        let value = vec![i];
+        commit(&nodes[i as usize % 3], &value).await;
+        expected.push(value);
+    }
+
+    for recorder in &recorders[..3] {
+        assert_eq!(wait_for_values(recorder, expected.len()).await, expected);
+    }
+    assert!(recorders[3].values.lock().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_silent_proposer_is_skipped_with_a_new_round() {
+    let (network, nodes, recorders) = start_cluster().await;
+    commit(&nodes[0], b"before").await;
+
+    // Silence whoever proposes round 0 of the next height
+    let height = nodes[0].height().await;
+    let proposer = nodes[0].proposer(height, 0).clone();
+    network.disconnect(&proposer).await;
+    let live: Vec<usize> = (0..nodes.len()).filter(|&i| nodes[i].node_id != proposer).collect();
+
+    commit(&nodes[live[0]], b"after").await;
+    for &i in &live {
+        assert_eq!(wait_for_values(&recorders[i], 2).await, vec![b"before".to_vec(), b"after".to_vec()]);
+    }
+}
+
+#[tokio::test]
+async fn test_lagging_validator_catches_up_from_decisions() {
+    let (network, nodes, recorders) = start_cluster().await;
+    network.disconnect(&nodes[3].node_id).await;
+
+    let mut expected = Vec::new();
+    for i in 0..5u8 {
+        commit(&nodes[i as usize % 3], &[i]).await;
+        expected.push(vec![i]);
+    }
+
+    network.reconnect(&nodes[3].node_id).await;
+    for i in 0..3u8 {
+        let value = vec![100 + i];
+        commit(&nodes[0], &value).await;
+        expected.push(value);
+    }
+
+    assert_eq!(wait_for_values(&recorders[3], expected.len()).await, expected);
+    assert_eq!(nodes[3].height().await, nodes[0].height().await);
+}
+
+#[tokio::test]
+async fn test_validator_with_forged_signatures_is_ignored() {
+    // node1 signs with a key the others do not know, so they drop everything it sends
+    let (_network, nodes, recorders) = start_cluster_with(|id| {
+        let mut config = test_config(id, &[1, 1, 1, 1]);
+        if id == "node1" {
+            config.secret_key = Some([42; 32]);
+        }
+        config
+    }).await;
+
+    for i in 0..4u8 {
+        commit(&nodes[1], &[i]).await;
+    }
+    for recorder in &recorders[1..] {
+        assert_eq!(wait_for_values(recorder, 4).await, (0..4u8).map(|i| vec![i]).collect::<Vec<_>>());
+    }
+}
+
+#[tokio::test]
+async fn test_proposers_rotate_by_voting_power() {
+    let powers = [3, 1, 1, 1];
+    let node = TendermintNode::with_config("node1".to_string(), TIMEOUT_MS, test_config("node1", &powers)).unwrap();
+
+    let mut turns: HashMap<String, u64> = HashMap::new();
+    for height in 1..=600 {
+        *turns.entry(node.proposer(height, 0).clone()).or_default() += 1;
+    }
+    assert_eq!(turns["node1"], 300);
+    for id in ["node2", "node3", "node4"] {
+        assert_eq!(turns[id], 100);
+    }
+
+    // The heaviest validator never proposes twice in a row when the others could
+    assert_ne!(node.proposer(1, 0), node.proposer(2, 0));
+    // A new round hands the proposal to the next validator in the schedule
+    assert_eq!(node.proposer(5, 1), node.proposer(6, 0));
+}
+
+#[tokio::test]
+async fn test_round_timeouts_escalate() {
+    let node = TendermintNode::with_config("node1".to_string(), TIMEOUT_MS, test_config("node1", &[1])).unwrap();
+    assert_eq!(node.round_timeout(0), Duration::from_millis(TIMEOUT_MS));
+    assert!(node.round_timeout(1) > node.round_timeout(0));
+    assert!(node.round_timeout(4) > node.round_timeout(3));
+}
+
+#[tokio::test]
+async fn test_linearizable_read_sees_committed_writes() {
+    let (_network, nodes, _recorders) = start_cluster().await;
+    let committed = commit(&nodes[1], b"a").await;
+
+    let index = nodes[2].read_index(ReadConsistency::Linearizable).await.unwrap();
+    assert!(index > committed.index);
+    assert!(nodes[2].last_executed().await >= index);
+}
+
+#[tokio::test]
+async fn test_starting_twice_is_an_error() {
+    let (_network, nodes, _recorders) = start_cluster().await;
+    let error = nodes[0].start_consensus_loop().await.unwrap_err();
+    assert!(error.to_string().contains("already started"));
+}